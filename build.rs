@@ -0,0 +1,18 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+fn main() {
+    // Generate the gRPC service, with a vendored protoc so that no system install is needed.
+    // The transport helpers are not generated, as they need the prelude of edition 2021.
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/cupcake.proto");
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc");
+        std::env::set_var("PROTOC", protoc);
+        tonic_prost_build::configure()
+            .build_transport(false)
+            .compile_protos(&["proto/cupcake.proto"], &["proto"])
+            .expect("invalid proto file");
+    }
+}
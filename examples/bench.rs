@@ -0,0 +1,197 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Times the operations of the scheme under several parameter sets, and writes the results as
+//! CSV, to compare parameter choices on the hardware at hand.
+//!
+//! Usage:
+//!     cargo run --release --example bench -- list
+//!     cargo run --release --example bench -- run [--params NAME,...] [--iterations N] [--output FILE]
+use cupcake::integer_arith::scalar::Scalar;
+use cupcake::traits::*;
+use cupcake::FV;
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{self, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: bench list
+       bench run [--params NAME,...] [--iterations N] [--output FILE]";
+
+/// A parameter set: a name, the ring degree and the ciphertext modulus.
+struct ParamSet {
+    name: &'static str,
+    n: usize,
+    q: u64,
+    description: &'static str,
+}
+
+const PARAM_SETS: &[ParamSet] = &[
+    ParamSet {
+        name: "n1024",
+        n: 1024,
+        q: 18014398492704769,
+        description: "toy parameters, below 128-bit security",
+    },
+    ParamSet {
+        name: "default",
+        n: 2048,
+        q: 18014398492704769,
+        description: "the parameters of cupcake::default()",
+    },
+    ParamSet {
+        name: "n4096",
+        n: 4096,
+        q: 18014398492704769,
+        description: "a larger ring degree with the default modulus",
+    },
+];
+
+const OPERATIONS: &[&str] = &["keygen", "encrypt", "add", "rerandomize", "decrypt"];
+
+impl ParamSet {
+    fn scheme(&self) -> FV<Scalar> {
+        if self.name == "default" {
+            return FV::<Scalar>::default_2048();
+        }
+        let mut fv = FV::new(self.n, &Scalar::new_modulus(self.q));
+        fv.flooding_stdev = (1u64 << 40) as f64;
+        fv
+    }
+}
+
+/// The timings of the operations in one parameter set, in the order of `OPERATIONS`.
+fn measure(set: &ParamSet, iterations: usize) -> Vec<Vec<Duration>> {
+    let fv = set.scheme();
+    let mut timings = vec![Vec::with_capacity(iterations); OPERATIONS.len()];
+    let pt: Vec<u8> = (0..fv.n).map(|i| i as u8).collect();
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let (pk, sk) = fv.generate_keypair();
+        timings[0].push(start.elapsed());
+
+        let start = Instant::now();
+        let mut ct = fv.encrypt(&pt, &pk);
+        timings[1].push(start.elapsed());
+
+        let other = fv.encrypt(&pt, &pk);
+        let start = Instant::now();
+        fv.add_inplace(&mut ct, &other);
+        timings[2].push(start.elapsed());
+
+        let start = Instant::now();
+        fv.rerandomize(&mut ct, &pk);
+        timings[3].push(start.elapsed());
+
+        let start = Instant::now();
+        let decrypted = fv.decrypt(&ct, &sk);
+        timings[4].push(start.elapsed());
+        black_box(decrypted);
+    }
+    timings
+}
+
+fn micros(d: Duration) -> f64 {
+    d.as_secs_f64() * 1e6
+}
+
+fn write_csv<W: Write>(out: &mut W, sets: &[&ParamSet], iterations: usize) -> io::Result<()> {
+    writeln!(
+        out,
+        "params,n,q_bits,operation,iterations,mean_us,min_us,max_us"
+    )?;
+    for set in sets {
+        let q_bits = 64 - set.q.leading_zeros();
+        for (op, samples) in OPERATIONS.iter().zip(measure(set, iterations)) {
+            let total: Duration = samples.iter().sum();
+            let min = samples.iter().min().cloned().unwrap_or_default();
+            let max = samples.iter().max().cloned().unwrap_or_default();
+            writeln!(
+                out,
+                "{},{},{},{},{},{:.1},{:.1},{:.1}",
+                set.name,
+                set.n,
+                q_bits,
+                op,
+                iterations,
+                micros(total) / iterations as f64,
+                micros(min),
+                micros(max)
+            )?;
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn run(args: &[String]) -> io::Result<()> {
+    let mut sets: Vec<&ParamSet> = PARAM_SETS.iter().collect();
+    let mut iterations = 10;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .unwrap_or_else(|| fail(&format!("missing value for {}", arg)));
+        match arg.as_str() {
+            "--params" => {
+                sets = value
+                    .split(',')
+                    .map(|name| {
+                        PARAM_SETS
+                            .iter()
+                            .find(|set| set.name == name)
+                            .unwrap_or_else(|| fail(&format!("unknown parameter set {}", name)))
+                    })
+                    .collect();
+            }
+            "--iterations" => {
+                iterations = match value.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => fail(&format!("invalid iteration count {}", value)),
+                };
+            }
+            "--output" => output = Some(value.clone()),
+            _ => fail(&format!("unknown option {}", arg)),
+        }
+    }
+    match output {
+        Some(path) => write_csv(&mut File::create(path)?, &sets, iterations),
+        None => write_csv(&mut io::stdout().lock(), &sets, iterations),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("list") => {
+            for set in PARAM_SETS {
+                println!(
+                    "{:<8} n = {:<5} log q = {:<3} {}",
+                    set.name,
+                    set.n,
+                    64 - set.q.leading_zeros(),
+                    set.description
+                );
+            }
+            Ok(())
+        }
+        Some("run") => run(&args[1..]),
+        Some("help") | Some("--help") | None => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => fail(&format!("unknown command {}", other)),
+    };
+    if let Err(e) = result {
+        eprintln!("bench: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,174 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! A private survey, from parameter agreement to threshold decryption of the results.
+//!
+//! Three trustees jointly generate a public key, respondents encrypt their answers under it,
+//! an aggregator sums the encrypted answers, and the trustees decrypt only the sum. The parties
+//! run in one process, but every message crosses a simulated network as bytes, and every
+//! receiver decodes and validates what it receives.
+use cupcake::dkg::DkgParty;
+use cupcake::integer_arith::scalar::Scalar;
+use cupcake::integer_arith::ArithUtils;
+use cupcake::traits::*;
+use cupcake::{FVCiphertext, FV};
+
+const TRUSTEES: usize = 3;
+const RESPONDENTS: usize = 40;
+
+/// The questions of the survey, and their possible answers.
+const QUESTIONS: &[(&str, &[&str])] = &[
+    ("How often do you commute?", &["never", "weekly", "daily"]),
+    (
+        "Preferred transport?",
+        &["walk", "bike", "bus", "train", "car"],
+    ),
+    ("Would you use a new bike lane?", &["no", "yes"]),
+];
+
+/// The slot of each answer: the answers of all questions are laid out one after the other in the
+/// coefficients of a single plaintext.
+fn slot(question: usize, answer: usize) -> usize {
+    QUESTIONS[..question]
+        .iter()
+        .map(|(_, answers)| answers.len())
+        .sum::<usize>()
+        + answer
+}
+
+/// The parameters announced by the organizer: the ring degree, the modulus and their id.
+fn announce_parameters() -> (usize, u64, String) {
+    let fv = FV::<Scalar>::default_2048();
+    (
+        fv.n,
+        Scalar::to_u64(fv.q.clone()),
+        fv.params_id().to_string(),
+    )
+}
+
+/// A party instantiates the announced parameters, and checks their id and security level.
+fn agree_on_parameters(n: usize, q: u64, params_id: &str) -> FV<Scalar> {
+    let mut fv = FV::new(n, &Scalar::new_modulus(q));
+    fv.flooding_stdev = (1u64 << 40) as f64;
+    assert_eq!(fv.params_id().to_string(), params_id, "parameter mismatch");
+    assert_eq!(fv.describe().security_bits, Some(128));
+    fv
+}
+
+/// The trustees run distributed key generation over a broadcast channel, and each ends with the
+/// public key and a share of the secret key.
+fn generate_keys(fv: &FV<Scalar>) -> Vec<cupcake::dkg::DkgOutput<Scalar>> {
+    let (mut trustees, mut outbox): (Vec<_>, Vec<_>) =
+        (0..TRUSTEES).map(|i| DkgParty::new(i, TRUSTEES)).unzip();
+    while !outbox.is_empty() {
+        let mut next = vec![];
+        for msg in outbox.iter() {
+            let bytes = msg.to_bytes();
+            for (index, trustee) in trustees.iter_mut().enumerate() {
+                if index == msg.from() {
+                    continue;
+                }
+                let msg = fv
+                    .dkg_message_from_bytes(&bytes)
+                    .expect("malformed message");
+                if let Some(reply) = trustee.receive(fv, msg).expect("protocol error") {
+                    next.push(reply);
+                }
+            }
+        }
+        outbox = next;
+    }
+    let outputs: Vec<_> = trustees
+        .into_iter()
+        .map(|t| t.finish(fv).expect("incomplete key generation"))
+        .collect();
+    for output in outputs.iter() {
+        assert_eq!(output.transcript_hash, outputs[0].transcript_hash);
+    }
+    outputs
+}
+
+/// A respondent encrypts its answers, one per question.
+fn respond(fv: &FV<Scalar>, pk: &FVCiphertext<Scalar>, answers: &[usize]) -> Vec<u8> {
+    let mut pt = vec![0u8; fv.n];
+    for (question, &answer) in answers.iter().enumerate() {
+        pt[slot(question, answer)] = 1;
+    }
+    fv.encrypt(&pt, pk).to_bytes()
+}
+
+fn main() {
+    println!("1. Parameter agreement");
+    let (n, q, params_id) = announce_parameters();
+    let fv = agree_on_parameters(n, q, &params_id);
+    println!("   n = {}, params id {}", n, params_id);
+
+    println!("2. Key generation by {} trustees", TRUSTEES);
+    let outputs = generate_keys(&fv);
+    let pk_bytes = outputs[0].public_key.to_bytes();
+    // Respondents validate the published key, and compare its fingerprint out of band.
+    let pk = fv
+        .public_key_from_bytes(&pk_bytes)
+        .expect("invalid public key");
+    println!("   public key {}", fv.public_key_fingerprint(&pk));
+
+    println!("3. Contributions of {} respondents", RESPONDENTS);
+    let mut submissions: Vec<Vec<u8>> = (0..RESPONDENTS)
+        .map(|i| {
+            let answers = [i % 3, (i * 7) % 5, (i % 4 != 0) as usize];
+            respond(&fv, &pk, &answers)
+        })
+        .collect();
+    // A truncated submission, which the aggregator must reject.
+    let truncated = submissions[0][1..].to_vec();
+    submissions.push(truncated);
+
+    println!("4. Aggregation");
+    let mut sum: Option<FVCiphertext<Scalar>> = None;
+    let mut accepted = 0;
+    for bytes in submissions.iter() {
+        match fv.ciphertext_from_bytes(bytes) {
+            Ok(ct) => {
+                match sum.as_mut() {
+                    Some(sum) => fv.add_inplace(sum, &ct),
+                    None => sum = Some(ct),
+                }
+                accepted += 1;
+            }
+            Err(e) => println!("   rejected a submission: {}", e),
+        }
+    }
+    // Each slot counts at most one answer per respondent, below the plaintext modulus of 256.
+    assert!(accepted < 256);
+    let sum_bytes = sum.expect("no submissions").to_bytes();
+    println!("   {} submissions accepted", accepted);
+
+    println!("5. Threshold decryption");
+    let sum = fv.ciphertext_from_bytes(&sum_bytes).expect("invalid sum");
+    let partials: Vec<Vec<u8>> = outputs
+        .iter()
+        .map(|o| fv.partial_decrypt(&sum, &o.share).to_bytes())
+        .collect();
+    let partials: Vec<_> = partials
+        .iter()
+        .map(|bytes| {
+            fv.partial_decryption_from_bytes(bytes)
+                .expect("invalid partial decryption")
+        })
+        .collect();
+    let counts = fv
+        .combine_partials(&sum, &partials)
+        .expect("decryption failed");
+
+    for (question, (text, answers)) in QUESTIONS.iter().enumerate() {
+        println!("   {}", text);
+        for (answer, label) in answers.iter().enumerate() {
+            println!("     {:<6} {}", label, counts[slot(question, answer)]);
+        }
+        let total: u32 = (0..answers.len())
+            .map(|answer| counts[slot(question, answer)] as u32)
+            .sum();
+        assert_eq!(total as usize, accepted);
+    }
+}
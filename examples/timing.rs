@@ -0,0 +1,35 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Runs the timing leakage tests of decryption and Gaussian sampling under the default
+//! parameters, and exits with status 1 if an operation leaks.
+//!
+//! Usage:
+//!     cargo run --release --features timing --example timing -- [SAMPLES]
+use cupcake::integer_arith::scalar::Scalar;
+use cupcake::timing::TimingConfig;
+use cupcake::FV;
+use std::process;
+
+fn main() {
+    let mut config = TimingConfig::default();
+    if let Some(arg) = std::env::args().nth(1) {
+        config.samples = match arg.parse() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                eprintln!("invalid sample count {}", arg);
+                process::exit(2);
+            }
+        };
+    }
+    let fv = FV::<Scalar>::default_2048();
+    let mut leaks = false;
+    for report in fv.timing_suite(&config) {
+        println!("{}", report);
+        leaks |= report.leaks();
+    }
+    if leaks {
+        process::exit(1);
+    }
+}
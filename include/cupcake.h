@@ -0,0 +1,190 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#ifndef CUPCAKE_H
+#define CUPCAKE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define CUPCAKE_ABI_VERSION 1
+
+// The result of a call.
+typedef enum CupcakeStatus {
+  // The call succeeded.
+  CUPCAKE_STATUS_OK = 0,
+  // A required pointer argument is null.
+  CUPCAKE_STATUS_NULL_POINTER = 1,
+  // A plaintext or an output buffer does not have the length of the scheme.
+  CUPCAKE_STATUS_WRONG_LENGTH = 2,
+  // An encoded key or ciphertext is malformed.
+  CUPCAKE_STATUS_INVALID_ENCODING = 3,
+  // A key or ciphertext belongs to a scheme with other parameters.
+  CUPCAKE_STATUS_PARAMETER_MISMATCH = 4,
+  // The library panicked, which indicates a bug.
+  CUPCAKE_STATUS_PANIC = 5,
+} CupcakeStatus;
+
+// A ciphertext.
+typedef struct CupcakeCiphertext CupcakeCiphertext;
+
+// A public key.
+typedef struct CupcakePublicKey CupcakePublicKey;
+
+// A scheme.
+typedef struct CupcakeScheme CupcakeScheme;
+
+// A secret key.
+typedef struct CupcakeSecretKey CupcakeSecretKey;
+
+// A byte buffer owned by the library.
+typedef struct CupcakeBuffer {
+  // The bytes, or null for an empty buffer.
+  uint8_t *data;
+  // The number of bytes.
+  size_t len;
+} CupcakeBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The version of the C ABI of the library, which a program compares to the
+// `CUPCAKE_ABI_VERSION` of the header it was compiled against.
+uint32_t cupcake_abi_version(void);
+
+// The scheme with the default parameters.
+struct CupcakeScheme *cupcake_scheme_default(void);
+
+// Release a scheme.
+//
+// # Safety
+// `scheme` must be null or a scheme returned by this library, which is not used afterwards.
+void cupcake_scheme_free(struct CupcakeScheme *scheme);
+
+// The number of bytes of a plaintext of the scheme, or 0 if `scheme` is null.
+//
+// # Safety
+// `scheme` must be null or a live scheme.
+size_t cupcake_plaintext_len(const struct CupcakeScheme *scheme);
+
+// Generate a keypair.
+//
+// # Safety
+// `scheme` must be a live scheme, and `pk` and `sk` valid for writes.
+enum CupcakeStatus cupcake_generate_keypair(const struct CupcakeScheme *scheme,
+                                            struct CupcakePublicKey **pk,
+                                            struct CupcakeSecretKey **sk);
+
+// Release a public key.
+//
+// # Safety
+// `pk` must be null or a public key returned by this library, which is not used afterwards.
+void cupcake_public_key_free(struct CupcakePublicKey *pk);
+
+// Release a secret key, wiping it with the `zeroize` feature.
+//
+// # Safety
+// `sk` must be null or a secret key returned by this library, which is not used afterwards.
+void cupcake_secret_key_free(struct CupcakeSecretKey *sk);
+
+// Release a ciphertext.
+//
+// # Safety
+// `ct` must be null or a ciphertext returned by this library, which is not used afterwards.
+void cupcake_ciphertext_free(struct CupcakeCiphertext *ct);
+
+// Release a buffer returned by this library.
+//
+// # Safety
+// `buffer` must have been returned by this library, and is not used afterwards.
+void cupcake_buffer_free(struct CupcakeBuffer buffer);
+
+// Encrypt the `pt_len` bytes at `pt` under a public key.
+//
+// # Safety
+// `scheme` and `pk` must be live, `pt` valid for `pt_len` reads and `out` valid for writes.
+enum CupcakeStatus cupcake_encrypt(const struct CupcakeScheme *scheme,
+                                   const struct CupcakePublicKey *pk,
+                                   const uint8_t *pt,
+                                   size_t pt_len,
+                                   struct CupcakeCiphertext **out);
+
+// Decrypt a ciphertext into the `out_len` bytes at `out`, which must be the plaintext length.
+//
+// # Safety
+// `scheme`, `sk` and `ct` must be live, and `out` valid for `out_len` writes.
+enum CupcakeStatus cupcake_decrypt(const struct CupcakeScheme *scheme,
+                                   const struct CupcakeSecretKey *sk,
+                                   const struct CupcakeCiphertext *ct,
+                                   uint8_t *out,
+                                   size_t out_len);
+
+// Add the ciphertext `ct2` into `ct1`.
+//
+// # Safety
+// `scheme`, `ct1` and `ct2` must be live, and `ct1` not aliased.
+enum CupcakeStatus cupcake_add_inplace(const struct CupcakeScheme *scheme,
+                                       struct CupcakeCiphertext *ct1,
+                                       const struct CupcakeCiphertext *ct2);
+
+// Add the `pt_len` bytes at `pt` into a ciphertext.
+//
+// # Safety
+// `scheme` and `ct` must be live, `ct` not aliased, and `pt` valid for `pt_len` reads.
+enum CupcakeStatus cupcake_add_plain_inplace(const struct CupcakeScheme *scheme,
+                                             struct CupcakeCiphertext *ct,
+                                             const uint8_t *pt,
+                                             size_t pt_len);
+
+// Rerandomize a ciphertext under a public key.
+//
+// # Safety
+// `scheme`, `ct` and `pk` must be live, and `ct` not aliased.
+enum CupcakeStatus cupcake_rerandomize(const struct CupcakeScheme *scheme,
+                                       struct CupcakeCiphertext *ct,
+                                       const struct CupcakePublicKey *pk);
+
+// The canonical encoding of a ciphertext.
+//
+// # Safety
+// `ct` must be live, and `out` valid for writes.
+enum CupcakeStatus cupcake_ciphertext_to_bytes(const struct CupcakeCiphertext *ct,
+                                               struct CupcakeBuffer *out);
+
+// Decode and validate a ciphertext.
+//
+// # Safety
+// `scheme` must be live, `data` valid for `len` reads and `out` valid for writes.
+enum CupcakeStatus cupcake_ciphertext_from_bytes(const struct CupcakeScheme *scheme,
+                                                 const uint8_t *data,
+                                                 size_t len,
+                                                 struct CupcakeCiphertext **out);
+
+// The canonical encoding of a public key.
+//
+// # Safety
+// `pk` must be live, and `out` valid for writes.
+enum CupcakeStatus cupcake_public_key_to_bytes(const struct CupcakePublicKey *pk,
+                                               struct CupcakeBuffer *out);
+
+// Decode and validate a public key.
+//
+// # Safety
+// `scheme` must be live, `data` valid for `len` reads and `out` valid for writes.
+enum CupcakeStatus cupcake_public_key_from_bytes(const struct CupcakeScheme *scheme,
+                                                 const uint8_t *data,
+                                                 size_t len,
+                                                 struct CupcakePublicKey **out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CUPCAKE_H */
@@ -0,0 +1,141 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Circuit-privacy audit mode, for debugging applications.
+//!
+//! A server evaluating on ciphertexts of a data owner must rerandomize its results before
+//! returning them, otherwise their noise can reveal the evaluated function and the server's own
+//! inputs. An [`AuditedCiphertext`] records every operation applied to it, and
+//! [`FV::release`] panics if a ciphertext is about to be returned without having been
+//! rerandomized after its last homomorphic operation.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::*;
+use crate::{FVCiphertext, FVPlaintext, FV};
+
+/// An operation applied to an audited ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// Fresh encryption.
+    Encrypt,
+    /// Addition of another ciphertext.
+    Add,
+    /// Addition of a plaintext.
+    AddPlain,
+    /// Rerandomization with noise flooding.
+    Rerandomize,
+}
+
+/// A ciphertext together with the history of the operations applied to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditedCiphertext<T> {
+    pub ct: FVCiphertext<T>,
+    history: Vec<Operation>,
+}
+
+impl<T> AuditedCiphertext<T> {
+    /// Start auditing a ciphertext of unknown origin, with an empty history.
+    pub fn new(ct: FVCiphertext<T>) -> Self {
+        AuditedCiphertext {
+            ct,
+            history: vec![],
+        }
+    }
+
+    /// The operations applied to this ciphertext, in order.
+    pub fn history(&self) -> &[Operation] {
+        &self.history
+    }
+
+    /// Whether the ciphertext was rerandomized after its last homomorphic operation.
+    pub fn is_rerandomized(&self) -> bool {
+        self.history.last() == Some(&Operation::Rerandomize)
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Encrypt a plaintext under a public key and start auditing the result.
+    pub fn encrypt_audited(&self, pt: &FVPlaintext, pk: &FVCiphertext<T>) -> AuditedCiphertext<T> {
+        AuditedCiphertext {
+            ct: self.encrypt(pt, pk),
+            history: vec![Operation::Encrypt],
+        }
+    }
+
+    /// Add a ciphertext into another, recording the addition.
+    pub fn add_audited_inplace(&self, ct1: &mut AuditedCiphertext<T>, ct2: &AuditedCiphertext<T>) {
+        self.add_inplace(&mut ct1.ct, &ct2.ct);
+        ct1.history.push(Operation::Add);
+    }
+
+    /// Add a plaintext into a ciphertext, recording the addition.
+    pub fn add_plain_audited_inplace(&self, ct: &mut AuditedCiphertext<T>, pt: &FVPlaintext) {
+        self.add_plain_inplace(&mut ct.ct, pt);
+        ct.history.push(Operation::AddPlain);
+    }
+
+    /// Rerandomize a ciphertext, recording the rerandomization.
+    pub fn rerandomize_audited(&self, ct: &mut AuditedCiphertext<T>, pk: &FVCiphertext<T>) {
+        self.rerandomize(&mut ct.ct, pk);
+        ct.history.push(Operation::Rerandomize);
+    }
+
+    /// Stop auditing a ciphertext which is about to be serialized and returned to the data
+    /// owner.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ciphertext was not rerandomized after its last homomorphic operation.
+    pub fn release(&self, ct: AuditedCiphertext<T>) -> FVCiphertext<T> {
+        assert!(
+            ct.is_rerandomized(),
+            "ciphertext released without rerandomization, history: {:?}",
+            ct.history
+        );
+        ct.ct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_release_rerandomized() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct1 = fv.encrypt_audited(&vec![1; fv.n], &pk);
+        let ct2 = fv.encrypt_audited(&vec![2; fv.n], &pk);
+        fv.add_audited_inplace(&mut ct1, &ct2);
+        fv.add_plain_audited_inplace(&mut ct1, &vec![3; fv.n]);
+        fv.rerandomize_audited(&mut ct1, &pk);
+        assert_eq!(
+            ct1.history(),
+            &[
+                Operation::Encrypt,
+                Operation::Add,
+                Operation::AddPlain,
+                Operation::Rerandomize
+            ]
+        );
+        let ct = fv.release(ct1);
+        assert_eq!(fv.decrypt(&ct, &sk), vec![6; fv.n]);
+    }
+
+    #[test]
+    #[should_panic(expected = "without rerandomization")]
+    fn test_release_after_addition_panics() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let mut ct = fv.encrypt_audited(&vec![1; fv.n], &pk);
+        fv.rerandomize_audited(&mut ct, &pk);
+        fv.add_plain_audited_inplace(&mut ct, &vec![1; fv.n]);
+        fv.release(ct);
+    }
+}
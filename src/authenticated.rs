@@ -0,0 +1,224 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Authenticated ciphertexts.
+//!
+//! FV ciphertexts are malleable by design: anybody can add an encryption of any value to them.
+//! An [`AuthenticatedCiphertext`] carries a MAC of the encoded ciphertext under a key shared by
+//! the two endpoints of a transport, so that a ciphertext modified in transit is rejected. The
+//! MAC is SHA3-256 of the key followed by the message, which, unlike for SHA-2, is a secure MAC
+//! since SHA-3 is not subject to length extension.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::traits::Serializable;
+use crate::utils::{ct_eq, wipe};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use rand::RngCore;
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+/// A key shared by the endpoints of a transport, for authenticating ciphertexts.
+#[derive(Clone)]
+pub struct MacKey([u8; 32]);
+
+impl MacKey {
+    /// A key from its bytes, e.g. as derived by a key exchange.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        MacKey(bytes)
+    }
+
+    /// A fresh random key.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        with_rng(|rng| rng.fill_bytes(&mut bytes));
+        MacKey(bytes)
+    }
+
+    fn tag(&self, message: &[u8]) -> [u8; 32] {
+        self.tag_in(b"cupcake ciphertext mac", message)
+    }
+
+    /// The MAC of a message under a domain separation string, which distinguishes the kinds of
+    /// messages authenticated with the same key.
+    pub(crate) fn tag_in(&self, domain: &[u8], message: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(domain);
+        hasher.update(self.0);
+        hasher.update(message);
+        let mut tag = [0u8; 32];
+        tag.copy_from_slice(&hasher.finalize());
+        tag
+    }
+
+    /// Check a tag in constant time.
+    fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        ct_eq(&self.tag(message), tag)
+    }
+
+    /// Check a tag under a domain separation string in constant time.
+    pub(crate) fn verify_in(&self, domain: &[u8], message: &[u8], tag: &[u8]) -> bool {
+        ct_eq(&self.tag_in(domain, message), tag)
+    }
+}
+
+impl fmt::Debug for MacKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MacKey(..)")
+    }
+}
+
+impl PartialEq for MacKey {
+    /// Compare two keys in constant time.
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for MacKey {}
+
+impl Drop for MacKey {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+/// A ciphertext together with a MAC of its encoding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthenticatedCiphertext<T> {
+    pub ct: FVCiphertext<T>,
+    pub tag: [u8; 32],
+}
+
+impl<T> Serializable for AuthenticatedCiphertext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The encoding of the ciphertext followed by the tag.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.ct.to_bytes();
+        bytes.extend_from_slice(&self.tag);
+        bytes
+    }
+}
+
+/// The reasons for which an authenticated ciphertext can be rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthenticationError {
+    /// The ciphertext is not well-formed for the scheme.
+    Malformed(ValidationError),
+    /// The tag does not match the ciphertext.
+    TagMismatch,
+}
+
+impl fmt::Display for AuthenticationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthenticationError::Malformed(e) => write!(f, "malformed ciphertext: {}", e),
+            AuthenticationError::TagMismatch => write!(f, "ciphertext authentication failed"),
+        }
+    }
+}
+
+impl Error for AuthenticationError {}
+
+impl From<ValidationError> for AuthenticationError {
+    fn from(e: ValidationError) -> Self {
+        AuthenticationError::Malformed(e)
+    }
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// MAC a ciphertext before sending it.
+    pub fn authenticate(&self, ct: FVCiphertext<T>, key: &MacKey) -> AuthenticatedCiphertext<T> {
+        let tag = key.tag(&ct.to_bytes());
+        AuthenticatedCiphertext { ct, tag }
+    }
+
+    /// Check the MAC of a received ciphertext, and return the ciphertext if it is authentic.
+    pub fn verify_authenticated(
+        &self,
+        act: AuthenticatedCiphertext<T>,
+        key: &MacKey,
+    ) -> Result<FVCiphertext<T>, AuthenticationError> {
+        if !key.verify(&act.ct.to_bytes(), &act.tag) {
+            return Err(AuthenticationError::TagMismatch);
+        }
+        self.validate(&act.ct)?;
+        Ok(act.ct)
+    }
+
+    /// Check the MAC of the encoding of an authenticated ciphertext, then decode the ciphertext.
+    /// Nothing is parsed before the tag is verified.
+    pub fn authenticated_from_bytes(
+        &self,
+        bytes: &[u8],
+        key: &MacKey,
+    ) -> Result<FVCiphertext<T>, AuthenticationError> {
+        if bytes.len() != self.ciphertext_byte_len() + 32 {
+            return Err(ValidationError::WrongLength {
+                expected: self.ciphertext_byte_len() + 32,
+                actual: bytes.len(),
+            }
+            .into());
+        }
+        let (message, tag) = bytes.split_at(self.ciphertext_byte_len());
+        if !key.verify(message, tag) {
+            return Err(AuthenticationError::TagMismatch);
+        }
+        Ok(self.ciphertext_from_bytes(message)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_authenticated_roundtrip() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let key = MacKey::generate();
+        let act = fv.authenticate(fv.encrypt(&vec![1; fv.n], &pk), &key);
+
+        let ct = fv.authenticated_from_bytes(&act.to_bytes(), &key).unwrap();
+        assert_eq!(fv.decrypt(&ct, &sk), vec![1; fv.n]);
+        let ct = fv.verify_authenticated(act, &key).unwrap();
+        assert_eq!(fv.decrypt(&ct, &sk), vec![1; fv.n]);
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let key = MacKey::generate();
+        let mut act = fv.authenticate(fv.encrypt(&vec![1; fv.n], &pk), &key);
+
+        let mut bytes = act.to_bytes();
+        bytes[10] ^= 1;
+        assert_eq!(
+            fv.authenticated_from_bytes(&bytes, &key).unwrap_err(),
+            AuthenticationError::TagMismatch
+        );
+        assert_eq!(
+            fv.authenticated_from_bytes(&act.to_bytes(), &MacKey::generate())
+                .unwrap_err(),
+            AuthenticationError::TagMismatch
+        );
+
+        // homomorphic tampering.
+        let delta = fv.encrypt(&vec![1; fv.n], &pk);
+        fv.add_inplace(&mut act.ct, &delta);
+        assert_eq!(
+            fv.verify_authenticated(act, &key).unwrap_err(),
+            AuthenticationError::TagMismatch
+        );
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Binding of ciphertexts to the key they are encrypted under.
+//!
+//! A [`TaggedCiphertext`] carries the fingerprint of the public key it was encrypted under, and
+//! the operations below check the tags of their operands, catching ciphertexts of different keys
+//! being mixed (e.g. across tenants) at runtime instead of silently producing garbage. An
+//! untagged ciphertext is only compatible with other untagged ones: it must be bound to a key
+//! with [`FV::tag`] before it is combined with tagged ciphertexts.
+use crate::fingerprint::Fingerprint;
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::*;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// A ciphertext, optionally tagged with the fingerprint of the public key it is encrypted under.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaggedCiphertext<T> {
+    pub ct: FVCiphertext<T>,
+    pub key: Option<Fingerprint>,
+}
+
+/// The error returned when the operands of an operation belong to different keys, or when only
+/// one of them is tagged. `None` stands for an untagged operand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyMismatch {
+    pub expected: Option<Fingerprint>,
+    pub actual: Option<Fingerprint>,
+}
+
+impl fmt::Display for KeyMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let key = |fp: &Option<Fingerprint>| match fp {
+            Some(fp) => format!("key {}", fp),
+            None => "no key".to_string(),
+        };
+        write!(
+            f,
+            "key mismatch: expected {}, found {}",
+            key(&self.expected),
+            key(&self.actual)
+        )
+    }
+}
+
+impl Error for KeyMismatch {}
+
+/// Check that two optional tags are equal, and return the tag.
+fn combine(
+    expected: Option<Fingerprint>,
+    actual: Option<Fingerprint>,
+) -> Result<Option<Fingerprint>, KeyMismatch> {
+    if expected != actual {
+        return Err(KeyMismatch { expected, actual });
+    }
+    Ok(expected)
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Tag a ciphertext with the fingerprint of the public key it is encrypted under.
+    pub fn tag(&self, ct: FVCiphertext<T>, pk: &FVCiphertext<T>) -> TaggedCiphertext<T> {
+        TaggedCiphertext {
+            ct,
+            key: Some(self.public_key_fingerprint(pk)),
+        }
+    }
+
+    /// Encrypt a plaintext under a public key and tag the result with the key.
+    pub fn encrypt_tagged(&self, pt: &FVPlaintext, pk: &FVCiphertext<T>) -> TaggedCiphertext<T> {
+        self.tag(self.encrypt(pt, pk), pk)
+    }
+
+    /// Add a ciphertext into another, after checking that they belong to the same key. Two
+    /// untagged operands can be added, but an untagged operand is never added to a tagged one.
+    pub fn add_tagged_inplace(
+        &self,
+        ct1: &mut TaggedCiphertext<T>,
+        ct2: &TaggedCiphertext<T>,
+    ) -> Result<(), KeyMismatch> {
+        ct1.key = combine(ct1.key, ct2.key)?;
+        self.add_inplace(&mut ct1.ct, &ct2.ct);
+        Ok(())
+    }
+
+    /// Rerandomize a ciphertext, after checking that it is tagged with the given public key.
+    pub fn rerandomize_tagged(
+        &self,
+        ct: &mut TaggedCiphertext<T>,
+        pk: &FVCiphertext<T>,
+    ) -> Result<(), KeyMismatch> {
+        ct.key = combine(Some(self.public_key_fingerprint(pk)), ct.key)?;
+        self.rerandomize(&mut ct.ct, pk);
+        Ok(())
+    }
+
+    /// Decrypt a ciphertext, after checking that it is tagged with the public key matching the
+    /// secret key. The check is skipped if the secret key does not record its public key.
+    pub fn decrypt_tagged(
+        &self,
+        ct: &TaggedCiphertext<T>,
+        sk: &SecretKey<T>,
+    ) -> Result<FVPlaintext, KeyMismatch> {
+        if let Some(expected) = sk.public_key_fingerprint() {
+            combine(Some(expected), ct.key)?;
+        }
+        Ok(self.decrypt(&ct.ct, sk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_tagged_operations() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct1 = fv.encrypt_tagged(&vec![1; fv.n], &pk);
+        let ct2 = fv.encrypt_tagged(&vec![2; fv.n], &pk);
+        fv.add_tagged_inplace(&mut ct1, &ct2).unwrap();
+        fv.rerandomize_tagged(&mut ct1, &pk).unwrap();
+        assert_eq!(fv.decrypt_tagged(&ct1, &sk), Ok(vec![3; fv.n]));
+    }
+
+    #[test]
+    fn test_mixed_keys_are_rejected() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk1, sk1) = fv.generate_keypair();
+        let (pk2, _) = fv.generate_keypair();
+        let fp1 = fv.public_key_fingerprint(&pk1);
+        let fp2 = fv.public_key_fingerprint(&pk2);
+
+        let mut ct1 = fv.encrypt_tagged(&vec![1; fv.n], &pk1);
+        let ct2 = fv.encrypt_tagged(&vec![2; fv.n], &pk2);
+        assert_eq!(
+            fv.add_tagged_inplace(&mut ct1, &ct2),
+            Err(KeyMismatch {
+                expected: Some(fp1),
+                actual: Some(fp2)
+            })
+        );
+        assert!(fv.rerandomize_tagged(&mut ct1, &pk2).is_err());
+        assert!(fv.decrypt_tagged(&ct2, &sk1).is_err());
+    }
+
+    #[test]
+    fn test_untagged_operands_are_rejected() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let fp = fv.public_key_fingerprint(&pk);
+        let mut ct = fv.encrypt_tagged(&vec![1; fv.n], &pk);
+        let mut untagged = TaggedCiphertext {
+            ct: fv.encrypt(&vec![2; fv.n], &pk),
+            key: None,
+        };
+        let mismatch = KeyMismatch {
+            expected: Some(fp),
+            actual: None,
+        };
+        assert_eq!(fv.add_tagged_inplace(&mut ct, &untagged), Err(mismatch));
+        assert_eq!(fv.rerandomize_tagged(&mut untagged, &pk), Err(mismatch));
+        assert_eq!(fv.decrypt_tagged(&untagged, &sk), Err(mismatch));
+        assert_eq!(
+            mismatch.to_string(),
+            format!("key mismatch: expected key {}, found no key", fp)
+        );
+
+        // binding the untagged ciphertext to its key makes it usable.
+        let bound = fv.tag(untagged.ct, &pk);
+        fv.add_tagged_inplace(&mut ct, &bound).unwrap();
+        assert_eq!(fv.decrypt_tagged(&ct, &sk), Ok(vec![3; fv.n]));
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Blind evaluation of a secret affine function.
+//!
+//! A client holding an input x and a server holding a [`BlindSecret`] (a, b) compute
+//! f(x) = a x + b in Z_256[X]/(X^n + 1), without the server learning x. The client sends a
+//! [`BlindRequest`], the encryption of x with its public key; the server multiplies it by a, adds
+//! b, and rerandomizes the result under the public key of the client, which decrypts f(x).
+//!
+//! The server checks that the key and the ciphertext of a request are well-formed before
+//! evaluating, and rerandomizes the response, whose noise would otherwise depend on a. It cannot
+//! check that the ciphertext is a fresh encryption: a client submitting one with a large noise
+//! learns more of a than f(x). The function is affine, so a client learns (a, b) from two
+//! evaluations: f is not a pseudorandom function on its own, and a secret should be evaluated at
+//! most once per client, e.g. to mask a value the client commits to.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption, Serializable};
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use rand::RngCore;
+use std::fmt;
+
+/// The secret function of the server, f(x) = multiplier x + offset.
+#[derive(Clone)]
+pub struct BlindSecret {
+    multiplier: FVPlaintext,
+    offset: FVPlaintext,
+}
+
+impl fmt::Debug for BlindSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BlindSecret({} coefficients)", self.multiplier.len())
+    }
+}
+
+impl Drop for BlindSecret {
+    fn drop(&mut self) {
+        wipe(&mut self.multiplier);
+        wipe(&mut self.offset);
+    }
+}
+
+impl BlindSecret {
+    /// The function multiplying by `multiplier` and adding `offset`.
+    ///
+    /// # Panics
+    /// If the two polynomials have different lengths.
+    pub fn new(multiplier: FVPlaintext, offset: FVPlaintext) -> Self {
+        assert_eq!(
+            multiplier.len(),
+            offset.len(),
+            "polynomials of different lengths"
+        );
+        BlindSecret { multiplier, offset }
+    }
+}
+
+/// An encrypted input, with the public key under which to return the result.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlindRequest<T> {
+    /// The public key of the client.
+    pub public_key: FVCiphertext<T>,
+    /// The encryption of the input.
+    pub ciphertext: FVCiphertext<T>,
+}
+
+impl<T> Serializable for BlindRequest<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The encoding of the public key followed by that of the ciphertext.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.public_key.to_bytes();
+        bytes.extend_from_slice(&self.ciphertext.to_bytes());
+        bytes
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// A random secret function.
+    pub fn generate_blind_secret(&self) -> BlindSecret {
+        let mut multiplier = vec![0u8; self.n];
+        let mut offset = vec![0u8; self.n];
+        with_rng(|rng| {
+            rng.fill_bytes(&mut multiplier);
+            rng.fill_bytes(&mut offset);
+        });
+        BlindSecret::new(multiplier, offset)
+    }
+
+    /// The request for the evaluation of the secret function of a server at `input`.
+    ///
+    /// # Panics
+    /// If `input` is longer than n.
+    pub fn blind(&self, pk: &FVCiphertext<T>, input: &[u8]) -> BlindRequest<T> {
+        assert!(input.len() <= self.n, "input longer than n");
+        let mut pt = input.to_vec();
+        pt.resize(self.n, 0);
+        let ciphertext = self.encrypt(&pt, pk);
+        wipe(&mut pt);
+        BlindRequest {
+            public_key: pk.clone(),
+            ciphertext,
+        }
+    }
+
+    /// Decode and validate a request.
+    pub fn blind_request_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<BlindRequest<T>, ValidationError> {
+        let len = self.ciphertext_byte_len();
+        if bytes.len() != 2 * len {
+            return Err(ValidationError::WrongLength {
+                expected: 2 * len,
+                actual: bytes.len(),
+            });
+        }
+        Ok(BlindRequest {
+            public_key: self.public_key_from_bytes(&bytes[..len])?,
+            ciphertext: self.ciphertext_from_bytes(&bytes[len..])?,
+        })
+    }
+
+    /// Evaluate the secret function on the input of a request, returning the result encrypted
+    /// and rerandomized under the public key of the client.
+    ///
+    /// # Panics
+    /// If the polynomials of `secret` are longer than n.
+    pub fn evaluate_blind(
+        &self,
+        secret: &BlindSecret,
+        request: &BlindRequest<T>,
+    ) -> Result<FVCiphertext<T>, ValidationError> {
+        assert!(secret.multiplier.len() <= self.n, "secret longer than n");
+        self.validate_public_key(&request.public_key)?;
+        self.validate(&request.ciphertext)?;
+        let mut ct = self.multiply_plain(&request.ciphertext, &secret.multiplier);
+        self.add_plain_inplace(&mut ct, &secret.offset);
+        self.rerandomize(&mut ct, &request.public_key);
+        Ok(ct)
+    }
+
+    /// Decrypt the result of an evaluation.
+    pub fn unblind(&self, response: &FVCiphertext<T>, sk: &SecretKey<T>) -> FVPlaintext {
+        self.decrypt(response, sk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_blind_evaluation() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        // f(x) = 3 X x + 5, which shifts the coefficients of x by one.
+        let mut multiplier = vec![0; fv.n];
+        multiplier[1] = 3;
+        let secret = BlindSecret::new(multiplier, vec![5; fv.n]);
+
+        let request = fv.blind(&pk, &[1, 2, 3]);
+        let request = fv.blind_request_from_bytes(&request.to_bytes()).unwrap();
+        let response = fv.evaluate_blind(&secret, &request).unwrap();
+        let mut expected = vec![5; fv.n];
+        expected[1..4].copy_from_slice(&[8, 11, 14]);
+        assert_eq!(fv.unblind(&response, &sk), expected);
+
+        let random = fv.generate_blind_secret();
+        let zero = fv.evaluate_blind(&random, &fv.blind(&pk, &[])).unwrap();
+        assert_eq!(fv.unblind(&zero, &sk), random.offset);
+    }
+
+    #[test]
+    fn test_malformed_request() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair();
+        let secret = fv.generate_blind_secret();
+        let mut request = fv.blind(&pk, &[1]);
+        request.ciphertext.0.coeffs[0] = fv.q.clone();
+        assert!(fv.evaluate_blind(&secret, &request).is_err());
+        let bytes = fv.blind(&pk, &[1]).to_bytes();
+        assert!(fv.blind_request_from_bytes(&bytes[1..]).is_err());
+    }
+}
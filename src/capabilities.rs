@@ -0,0 +1,206 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! The schemes, parameter sets and serialization versions supported by this build, for a client
+//! and a server to agree on a configuration.
+//!
+//! Each side sends its [`CAPABILITIES`], serialized with serde under the `serde` feature, and
+//! calls [`Capabilities::negotiate`] with those of its peer. Both sides pick the same
+//! [`Agreement`] as long as they list their parameter sets in the same order of preference, and
+//! entries which one side does not know are ignored, so that newer and older builds interoperate.
+//!
+//! ```
+//! use cupcake::CAPABILITIES;
+//!
+//! let agreement = CAPABILITIES.negotiate(&CAPABILITIES).unwrap();
+//! assert!(agreement.parameter_set.matches(&cupcake::default()));
+//! ```
+use crate::integer_arith::ArithUtils;
+use crate::FV;
+use alloc::borrow::Cow;
+use core::error::Error;
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The version of the canonical encoding of ciphertexts and public keys.
+pub const SERIALIZATION_VERSION: u32 = 1;
+
+/// The capabilities of this build.
+pub const CAPABILITIES: Capabilities = Capabilities {
+    crate_version: Cow::Borrowed(env!("CARGO_PKG_VERSION")),
+    schemes: Cow::Borrowed(&[Cow::Borrowed("fv")]),
+    parameter_sets: Cow::Borrowed(&[ParameterSet {
+        name: Cow::Borrowed("fv-2048"),
+        scheme: Cow::Borrowed("fv"),
+        n: 2048,
+        q: 18014398492704769,
+        plaintext_modulus: 256,
+        security_bits: Some(128),
+    }]),
+    serialization_versions: Cow::Borrowed(&[SERIALIZATION_VERSION]),
+};
+
+/// A parameter set, identified by its scheme, degree and moduli. The name is only a label.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParameterSet {
+    /// A human-readable name.
+    pub name: Cow<'static, str>,
+    /// The scheme the parameters are for.
+    pub scheme: Cow<'static, str>,
+    /// The degree of the ring.
+    pub n: usize,
+    /// The ciphertext modulus.
+    pub q: u64,
+    /// The plaintext modulus.
+    pub plaintext_modulus: u64,
+    /// The estimated classical security level, see [`crate::info::SchemeInfo`].
+    pub security_bits: Option<u32>,
+}
+
+impl ParameterSet {
+    /// Whether the parameters describe the same configuration, whatever their names.
+    pub fn is_compatible(&self, other: &ParameterSet) -> bool {
+        self.scheme == other.scheme
+            && self.n == other.n
+            && self.q == other.q
+            && self.plaintext_modulus == other.plaintext_modulus
+    }
+
+    /// Whether an FV scheme uses these parameters.
+    pub fn matches<T: ArithUtils<T> + Clone>(&self, fv: &FV<T>) -> bool {
+        self.scheme == "fv"
+            && self.n == fv.n
+            && self.q == T::to_u64(fv.q.clone())
+            && self.plaintext_modulus == crate::info::PLAINTEXT_MODULUS
+    }
+}
+
+/// What a build supports, in decreasing order of preference.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Capabilities {
+    /// The version of the crate.
+    pub crate_version: Cow<'static, str>,
+    /// The names of the schemes.
+    pub schemes: Cow<'static, [Cow<'static, str>]>,
+    /// The parameter sets.
+    pub parameter_sets: Cow<'static, [ParameterSet]>,
+    /// The versions of the encoding of ciphertexts and public keys.
+    pub serialization_versions: Cow<'static, [u32]>,
+}
+
+/// The configuration agreed on by two peers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Agreement {
+    /// The first parameter set of this side which the peer supports.
+    pub parameter_set: ParameterSet,
+    /// The highest serialization version both sides support.
+    pub serialization_version: u32,
+}
+
+/// The reasons for which two peers cannot agree on a configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NegotiationError {
+    /// No parameter set of a scheme known to both sides is supported by both.
+    NoCommonParameterSet,
+    /// No serialization version is supported by both sides.
+    NoCommonSerializationVersion,
+}
+
+impl fmt::Display for NegotiationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NegotiationError::NoCommonParameterSet => write!(f, "no common parameter set"),
+            NegotiationError::NoCommonSerializationVersion => {
+                write!(f, "no common serialization version")
+            }
+        }
+    }
+}
+
+impl Error for NegotiationError {}
+
+impl Capabilities {
+    /// Pick the first parameter set of this side which the peer supports, and the highest common
+    /// serialization version.
+    pub fn negotiate(&self, peer: &Capabilities) -> Result<Agreement, NegotiationError> {
+        let known = |scheme: &str| {
+            self.schemes.iter().any(|s| s == scheme) && peer.schemes.iter().any(|s| s == scheme)
+        };
+        let parameter_set = self
+            .parameter_sets
+            .iter()
+            .filter(|set| known(&set.scheme))
+            .find(|set| peer.parameter_sets.iter().any(|p| p.is_compatible(set)))
+            .ok_or(NegotiationError::NoCommonParameterSet)?;
+        let serialization_version = self
+            .serialization_versions
+            .iter()
+            .filter(|v| peer.serialization_versions.contains(v))
+            .max()
+            .ok_or(NegotiationError::NoCommonSerializationVersion)?;
+        Ok(Agreement {
+            parameter_set: parameter_set.clone(),
+            serialization_version: *serialization_version,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use alloc::vec;
+
+    #[test]
+    fn test_capabilities_describe_default() {
+        let fv = FV::<Scalar>::default_2048();
+        let set = &CAPABILITIES.parameter_sets[0];
+        assert!(set.matches(&fv));
+        assert_eq!(set.security_bits, fv.describe().security_bits);
+        assert!(!set.matches(&FV::new(2048, &Scalar::new_modulus(1099511795713))));
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let toy = ParameterSet {
+            name: Cow::Borrowed("toy"),
+            scheme: Cow::Borrowed("fv"),
+            n: 16,
+            q: 65537,
+            plaintext_modulus: 256,
+            security_bits: None,
+        };
+        let mut renamed = CAPABILITIES.parameter_sets[0].clone();
+        renamed.name = Cow::Borrowed("default");
+        let peer = Capabilities {
+            crate_version: Cow::Borrowed("9.9.9"),
+            schemes: Cow::Owned(vec![Cow::Borrowed("ckks"), Cow::Borrowed("fv")]),
+            parameter_sets: Cow::Owned(vec![toy.clone(), renamed]),
+            serialization_versions: Cow::Owned(vec![1, 2]),
+        };
+        let agreement = CAPABILITIES.negotiate(&peer).unwrap();
+        assert_eq!(agreement.parameter_set, CAPABILITIES.parameter_sets[0]);
+        assert_eq!(agreement.serialization_version, 1);
+
+        let peer = Capabilities {
+            parameter_sets: Cow::Owned(vec![toy]),
+            ..peer
+        };
+        assert_eq!(
+            CAPABILITIES.negotiate(&peer),
+            Err(NegotiationError::NoCommonParameterSet)
+        );
+        let peer = Capabilities {
+            serialization_versions: Cow::Owned(vec![2]),
+            ..CAPABILITIES
+        };
+        assert_eq!(
+            CAPABILITIES.negotiate(&peer),
+            Err(NegotiationError::NoCommonSerializationVersion)
+        );
+    }
+}
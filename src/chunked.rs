@@ -0,0 +1,279 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Splitting of serialized values into size-capped chunks, for key-value stores.
+//!
+//! Several key-value stores reject values above a size limit, 1MB by default for memcached,
+//! which the ciphertexts of larger parameter sets exceed. [`split`] cuts a byte string into
+//! chunks no longer than a limit, each carrying a header with an identifier of the whole value,
+//! its index, the number of chunks and the total length. The chunks are stored under the keys
+//! returned by [`chunk_key`]; a reader fetches chunk 0, learns the number of chunks from it and
+//! fetches the others, in any order. [`reassemble`] checks that the chunks are complete and
+//! consistent, and that the value matches its identifier, a SHA3-256 based fingerprint, so that
+//! chunks of an older version of a value left in the store are detected.
+//!
+//! The header is 36 bytes: the magic `CPK1`, the 16-byte identifier, then the index, the number
+//! of chunks and the total length as little-endian 32, 32 and 64-bit integers.
+use crate::fingerprint::{self, Fingerprint};
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The length of the header of a chunk.
+pub const HEADER_LEN: usize = 36;
+
+/// A limit below the default 1MB item size of memcached, leaving room for the key and the
+/// overhead of the item.
+pub const DEFAULT_MAX_CHUNK_LEN: usize = 1_000_000;
+
+const MAGIC: &[u8; 4] = b"CPK1";
+const DOMAIN: &[u8] = b"cupcake chunked value";
+
+/// A chunk of a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    /// The identifier of the whole value.
+    pub id: Fingerprint,
+    /// The index of the chunk.
+    pub index: u32,
+    /// The number of chunks of the value.
+    pub count: u32,
+    /// The length of the whole value.
+    pub total_len: u64,
+    /// The bytes of the value in this chunk.
+    pub data: Vec<u8>,
+}
+
+/// The reasons for which chunks cannot be reassembled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChunkError {
+    /// A chunk has no valid header.
+    Malformed,
+    /// The chunk of this index is missing.
+    Missing { index: u32 },
+    /// The chunks belong to different values, or are duplicated.
+    Inconsistent,
+    /// The reassembled value does not match its identifier.
+    Corrupted,
+    /// The reassembled ciphertext is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChunkError::Malformed => write!(f, "malformed chunk"),
+            ChunkError::Missing { index } => write!(f, "chunk {} is missing", index),
+            ChunkError::Inconsistent => write!(f, "chunks of different values"),
+            ChunkError::Corrupted => write!(f, "reassembled value does not match its identifier"),
+            ChunkError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ChunkError {}
+
+impl From<ValidationError> for ChunkError {
+    fn from(e: ValidationError) -> Self {
+        ChunkError::Invalid(e)
+    }
+}
+
+impl Chunk {
+    /// The encoding of the chunk, its header followed by its data.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.data.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.id.0);
+        bytes.extend_from_slice(&self.index.to_le_bytes());
+        bytes.extend_from_slice(&self.count.to_le_bytes());
+        bytes.extend_from_slice(&self.total_len.to_le_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Decode a chunk.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChunkError> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(ChunkError::Malformed);
+        }
+        let mut id = [0u8; 16];
+        id.copy_from_slice(&bytes[4..20]);
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&bytes[20..24]);
+        let index = u32::from_le_bytes(word);
+        word.copy_from_slice(&bytes[24..28]);
+        let count = u32::from_le_bytes(word);
+        let mut long = [0u8; 8];
+        long.copy_from_slice(&bytes[28..36]);
+        if index >= count {
+            return Err(ChunkError::Malformed);
+        }
+        Ok(Chunk {
+            id: Fingerprint(id),
+            index,
+            count,
+            total_len: u64::from_le_bytes(long),
+            data: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+/// The key under which to store a chunk of the value stored at `key`.
+pub fn chunk_key(key: &str, index: u32) -> String {
+    format!("{}:{}", key, index)
+}
+
+/// Split a value into chunks whose encodings are at most `max_chunk_len` bytes long. An empty
+/// value has a single empty chunk.
+///
+/// # Panics
+/// If `max_chunk_len` does not exceed [`HEADER_LEN`].
+pub fn split(value: &[u8], max_chunk_len: usize) -> Vec<Chunk> {
+    assert!(
+        max_chunk_len > HEADER_LEN,
+        "chunks too small for their header"
+    );
+    let id = fingerprint::fingerprint_bytes(DOMAIN, value);
+    let data_len = max_chunk_len - HEADER_LEN;
+    let mut pieces: Vec<&[u8]> = value.chunks(data_len).collect();
+    if pieces.is_empty() {
+        pieces.push(&[]);
+    }
+    let count = pieces.len() as u32;
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(index, data)| Chunk {
+            id,
+            index: index as u32,
+            count,
+            total_len: value.len() as u64,
+            data: data.to_vec(),
+        })
+        .collect()
+}
+
+/// Reassemble a value from all its chunks, in any order.
+pub fn reassemble<I: IntoIterator<Item = Chunk>>(chunks: I) -> Result<Vec<u8>, ChunkError> {
+    let mut chunks: Vec<Chunk> = chunks.into_iter().collect();
+    chunks.sort_by_key(|chunk| chunk.index);
+    let first = chunks.first().ok_or(ChunkError::Missing { index: 0 })?;
+    let (id, count, total_len) = (first.id, first.count, first.total_len);
+    if chunks
+        .iter()
+        .any(|chunk| chunk.id != id || chunk.count != count || chunk.total_len != total_len)
+    {
+        return Err(ChunkError::Inconsistent);
+    }
+    for (expected, chunk) in (0..count).zip(chunks.iter()) {
+        if chunk.index != expected {
+            // The first index missing from the sorted list, unless it is a duplicate.
+            return Err(if chunk.index < expected {
+                ChunkError::Inconsistent
+            } else {
+                ChunkError::Missing { index: expected }
+            });
+        }
+    }
+    if chunks.len() < count as usize {
+        return Err(ChunkError::Missing {
+            index: chunks.len() as u32,
+        });
+    }
+    if chunks.len() > count as usize {
+        return Err(ChunkError::Inconsistent);
+    }
+    let value: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.data).collect();
+    if value.len() as u64 != total_len || fingerprint::fingerprint_bytes(DOMAIN, &value) != id {
+        return Err(ChunkError::Corrupted);
+    }
+    Ok(value)
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The encoded chunks of a ciphertext, each at most `max_chunk_len` bytes long.
+    pub fn ciphertext_to_chunks(&self, ct: &FVCiphertext<T>, max_chunk_len: usize) -> Vec<Vec<u8>> {
+        split(&ct.to_bytes(), max_chunk_len)
+            .iter()
+            .map(Chunk::to_bytes)
+            .collect()
+    }
+
+    /// Reassemble and validate a ciphertext from its encoded chunks, in any order.
+    pub fn ciphertext_from_chunks<B: AsRef<[u8]>>(
+        &self,
+        chunks: &[B],
+    ) -> Result<FVCiphertext<T>, ChunkError> {
+        let chunks = chunks
+            .iter()
+            .map(|bytes| Chunk::from_bytes(bytes.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.ciphertext_from_bytes(&reassemble(chunks)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_ciphertext_chunks() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let ct = fv.encrypt(&vec![3; fv.n], &pk);
+
+        let mut chunks = fv.ciphertext_to_chunks(&ct, 4096);
+        assert_eq!(chunks.len(), 9);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 4096));
+        chunks.reverse();
+        let decoded = fv.ciphertext_from_chunks(&chunks).unwrap();
+        assert_eq!(fv.decrypt(&decoded, &sk), vec![3; fv.n]);
+
+        let single = fv.ciphertext_to_chunks(&ct, DEFAULT_MAX_CHUNK_LEN);
+        assert_eq!(single.len(), 1);
+        assert_eq!(fv.ciphertext_from_chunks(&single), Ok(ct));
+        assert_eq!(chunk_key("ct/42", 3), "ct/42:3");
+    }
+
+    #[test]
+    fn test_reassembly_failures() {
+        let value: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let chunks = split(&value, HEADER_LEN + 100);
+        assert_eq!(reassemble(chunks.clone()), Ok(value.clone()));
+        assert_eq!(reassemble(split(&[], 64)), Ok(vec![]));
+
+        let mut missing = chunks.clone();
+        missing.remove(4);
+        assert_eq!(reassemble(missing), Err(ChunkError::Missing { index: 4 }));
+        let mut truncated = chunks.clone();
+        truncated.pop();
+        assert_eq!(reassemble(truncated), Err(ChunkError::Missing { index: 9 }));
+        let mut duplicated = chunks.clone();
+        duplicated.push(chunks[2].clone());
+        assert_eq!(reassemble(duplicated), Err(ChunkError::Inconsistent));
+
+        // A chunk of a previous version of the value.
+        let mut stale = chunks.clone();
+        let mut other = value.clone();
+        other[0] ^= 1;
+        stale[5] = split(&other, HEADER_LEN + 100).remove(5);
+        assert_eq!(reassemble(stale), Err(ChunkError::Inconsistent));
+        let mut corrupted = chunks;
+        corrupted[5].data[0] ^= 1;
+        assert_eq!(reassemble(corrupted), Err(ChunkError::Corrupted));
+
+        assert_eq!(Chunk::from_bytes(b"CPK1"), Err(ChunkError::Malformed));
+    }
+}
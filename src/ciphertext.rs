@@ -0,0 +1,335 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Ciphertexts which carry their parameters.
+//!
+//! The scheme traits operate on [`FVCiphertext`], a bare pair of polynomials, and keep doing so:
+//! implementing them a second time for [`Ciphertext`] would make every call which does not name a
+//! ciphertext, such as `generate_key`, ambiguous. A [`Ciphertext`] is instead a typed wrapper: it
+//! wraps such a pair together with the identifier of the parameters (n, q) it was produced
+//! under, so that ciphertexts of different parameter sets are not silently mixed. It converts to
+//! and from the pair with `From`/`Into`, and borrows it with `AsRef`/`AsMut`, so it can be passed
+//! to every existing method without copying.
+//!
+//! Ciphertexts of the same parameters also support `+`, `+=`, `-`, `-=` and unary `-`, which
+//! act on the underlying plaintexts modulo t. The operators panic if the operands have different
+//! parameters or NTT forms.
+use crate::fingerprint::{self, Fingerprint};
+use crate::integer_arith::ArithUtils;
+use crate::pubkey::PublicKey;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use std::fmt;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// A ciphertext, tagged with the identifier of its parameters.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(
+    feature = "diesel",
+    derive(diesel::expression::AsExpression, diesel::deserialize::FromSqlRow),
+    diesel(sql_type = diesel::sql_types::Binary)
+)]
+pub struct Ciphertext<T> {
+    polys: FVCiphertext<T>,
+    params: Fingerprint,
+}
+
+// The polynomials are summarized by a fingerprint.
+impl<T> fmt::Debug for Ciphertext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fingerprint =
+            fingerprint::fingerprint(b"cupcake ciphertext", &[&self.polys.0, &self.polys.1]);
+        f.debug_struct("Ciphertext")
+            .field("params", &format_args!("{}", self.params))
+            .field("ntt_flags", &self.ntt_flags())
+            .field("fingerprint", &format_args!("{}", fingerprint))
+            .finish()
+    }
+}
+
+#[allow(clippy::len_without_is_empty)]
+impl<T> Ciphertext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The number of polynomials of the ciphertext.
+    pub fn len(&self) -> usize {
+        2
+    }
+
+    /// The identifier of the parameters of the ciphertext, see [`FV::params_id`].
+    pub fn params_id(&self) -> Fingerprint {
+        self.params
+    }
+
+    /// Whether each polynomial is in NTT form.
+    pub fn ntt_flags(&self) -> [bool; 2] {
+        [self.polys.0.is_ntt_form, self.polys.1.is_ntt_form]
+    }
+
+    /// Whether all polynomials are in NTT form.
+    pub fn is_ntt_form(&self) -> bool {
+        self.ntt_flags().iter().all(|&flag| flag)
+    }
+
+    /// A rerandomized copy of this ciphertext, which decrypts to the same plaintext.
+    pub fn clone_rerandomized(&self, fv: &FV<T>, pk: &PublicKey<T>) -> Self
+    where
+        RqPoly<T>: FiniteRingElt,
+        T: PartialEq,
+    {
+        let mut ct = self.clone();
+        fv.rerandomize_with(&mut ct, pk);
+        ct
+    }
+}
+
+impl<T> From<FVCiphertext<T>> for Ciphertext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn from(polys: FVCiphertext<T>) -> Self {
+        let params = fingerprint::params_fingerprint(
+            polys.0.coeffs.len(),
+            T::to_u64(polys.0.modulus().clone()),
+        );
+        Ciphertext { polys, params }
+    }
+}
+
+impl<T> From<Ciphertext<T>> for FVCiphertext<T> {
+    fn from(ct: Ciphertext<T>) -> Self {
+        ct.polys
+    }
+}
+
+impl<T> AsRef<FVCiphertext<T>> for Ciphertext<T> {
+    fn as_ref(&self) -> &FVCiphertext<T> {
+        &self.polys
+    }
+}
+
+impl<T> AsMut<FVCiphertext<T>> for Ciphertext<T> {
+    fn as_mut(&mut self) -> &mut FVCiphertext<T> {
+        &mut self.polys
+    }
+}
+
+impl<T> Ciphertext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn assert_compatible(&self, other: &Self) {
+        assert_eq!(
+            self.params, other.params,
+            "ciphertexts of different parameters"
+        );
+        assert_eq!(
+            self.ntt_flags(),
+            other.ntt_flags(),
+            "ciphertexts in different NTT forms"
+        );
+    }
+}
+
+impl<T> AddAssign<&Ciphertext<T>> for Ciphertext<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: ArithUtils<T> + Clone,
+{
+    fn add_assign(&mut self, other: &Ciphertext<T>) {
+        self.assert_compatible(other);
+        self.polys.0.add_inplace(&other.polys.0);
+        self.polys.1.add_inplace(&other.polys.1);
+    }
+}
+
+impl<T> SubAssign<&Ciphertext<T>> for Ciphertext<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: ArithUtils<T> + Clone,
+{
+    fn sub_assign(&mut self, other: &Ciphertext<T>) {
+        self.assert_compatible(other);
+        self.polys.0.sub_inplace(&other.polys.0);
+        self.polys.1.sub_inplace(&other.polys.1);
+    }
+}
+
+impl<T> Add<&Ciphertext<T>> for Ciphertext<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: ArithUtils<T> + Clone,
+{
+    type Output = Ciphertext<T>;
+
+    fn add(mut self, other: &Ciphertext<T>) -> Ciphertext<T> {
+        self += other;
+        self
+    }
+}
+
+impl<T> Add<&Ciphertext<T>> for &Ciphertext<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: ArithUtils<T> + Clone,
+{
+    type Output = Ciphertext<T>;
+
+    fn add(self, other: &Ciphertext<T>) -> Ciphertext<T> {
+        self.clone() + other
+    }
+}
+
+impl<T> Sub<&Ciphertext<T>> for Ciphertext<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: ArithUtils<T> + Clone,
+{
+    type Output = Ciphertext<T>;
+
+    fn sub(mut self, other: &Ciphertext<T>) -> Ciphertext<T> {
+        self -= other;
+        self
+    }
+}
+
+impl<T> Sub<&Ciphertext<T>> for &Ciphertext<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: ArithUtils<T> + Clone,
+{
+    type Output = Ciphertext<T>;
+
+    fn sub(self, other: &Ciphertext<T>) -> Ciphertext<T> {
+        self.clone() - other
+    }
+}
+
+impl<T> Neg for Ciphertext<T>
+where
+    RqPoly<T>: FiniteRingElt,
+{
+    type Output = Ciphertext<T>;
+
+    fn neg(mut self) -> Ciphertext<T> {
+        self.polys.0.negate_inplace();
+        self.polys.1.negate_inplace();
+        self
+    }
+}
+
+impl<T> Neg for &Ciphertext<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone,
+{
+    type Output = Ciphertext<T>;
+
+    fn neg(self) -> Ciphertext<T> {
+        -self.clone()
+    }
+}
+
+impl<T> Serializable for Ciphertext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The encoding of the underlying pair; the parameters are implied by the decoding scheme.
+    fn to_bytes(&self) -> Vec<u8> {
+        self.polys.to_bytes()
+    }
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Check that a ciphertext was produced under the parameters of this scheme, and is
+    /// well-formed for them.
+    pub fn validate_ciphertext(&self, ct: &Ciphertext<T>) -> Result<(), ValidationError> {
+        if ct.params != self.params_id() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                params_id = %self.params_id(),
+                received_params_id = %ct.params,
+                "rejected a ciphertext of other parameters"
+            );
+            return Err(ValidationError::ParameterMismatch { component: 0 });
+        }
+        self.validate(&ct.polys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_ciphertext_bridge() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let ct: Ciphertext<Scalar> = fv.encrypt(&vec![3; fv.n], &pk).into();
+        assert_eq!(ct.len(), 2);
+        assert_eq!(ct.params_id(), fv.params_id());
+        assert_eq!(ct.ntt_flags(), [false, false]);
+        assert!(!ct.is_ntt_form());
+        assert!(Ciphertext::from(pk.clone()).is_ntt_form());
+        assert!(fv.validate_ciphertext(&ct).is_ok());
+
+        let fresh = ct.clone_rerandomized(&fv, &PublicKey::from_pair(pk.clone()));
+        assert_ne!(fresh.as_ref().1.coeffs, ct.as_ref().1.coeffs);
+        assert_eq!(fv.decrypt(fresh.as_ref(), &sk), vec![3; fv.n]);
+        assert_eq!(fresh.to_bytes().len(), fv.ciphertext_byte_len());
+
+        let pair: FVCiphertext<Scalar> = ct.into();
+        assert_eq!(fv.decrypt(&pair, &sk), vec![3; fv.n]);
+
+        let other = FV::new(2048, &Scalar::new_modulus(12289));
+        let (other_pk, _) = other.generate_keypair();
+        let foreign: Ciphertext<Scalar> = other.encrypt(&vec![1; other.n], &other_pk).into();
+        assert_eq!(
+            fv.validate_ciphertext(&foreign),
+            Err(ValidationError::ParameterMismatch { component: 0 })
+        );
+    }
+
+    #[test]
+    fn test_ciphertext_operators() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let encrypt = |m: u8| -> Ciphertext<Scalar> { fv.encrypt(&vec![m; fv.n], &pk).into() };
+        let (a, b) = (encrypt(10), encrypt(3));
+
+        let mut acc = &a + &b;
+        assert_eq!(fv.decrypt(acc.as_ref(), &sk), vec![13; fv.n]);
+        acc += &b;
+        assert_eq!(fv.decrypt(acc.as_ref(), &sk), vec![16; fv.n]);
+        acc -= &a;
+        assert_eq!(fv.decrypt(acc.as_ref(), &sk), vec![6; fv.n]);
+        assert_eq!(fv.decrypt((&b - &a).as_ref(), &sk), vec![249; fv.n]);
+        assert_eq!(fv.decrypt((-&b).as_ref(), &sk), vec![253; fv.n]);
+        let zero = a.clone() + &(-a);
+        assert!(fv.validate_ciphertext(&zero).is_ok());
+        assert_eq!(fv.decrypt(zero.as_ref(), &sk), vec![0; fv.n]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ciphertexts of different parameters")]
+    fn test_operators_reject_foreign_ciphertexts() {
+        let fv = FV::<Scalar>::default_2048();
+        let other = FV::new(2048, &Scalar::new_modulus(12289));
+        let (pk, _) = fv.generate_keypair();
+        let (other_pk, _) = other.generate_keypair();
+        let mut ct: Ciphertext<Scalar> = fv.encrypt(&vec![1; fv.n], &pk).into();
+        ct += &Ciphertext::from(other.encrypt(&vec![1; other.n], &other_pk));
+    }
+}
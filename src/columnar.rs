@@ -0,0 +1,265 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Storage of ciphertexts in Arrow columns and Parquet files, with the `arrow` feature.
+//!
+//! A column of ciphertexts is a nullable Arrow `Binary` column holding the canonical encoding of
+//! each ciphertext. The field of the column records n, q and the identifier of the parameters in
+//! its metadata under [`METADATA_N`], [`METADATA_Q`] and [`METADATA_PARAMS_ID`], so that the
+//! column can be stored alongside plain columns in a record batch, and survives a round trip
+//! through Parquet, which keeps the Arrow schema of the file.
+//!
+//! Reading a column checks its parameters and validates every ciphertext. Null entries stand for
+//! missing values, and are skipped by [`FV::sum_column`].
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, Serializable};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use arrow_array::{Array, ArrayRef, BinaryArray, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::file::reader::ChunkReader;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+
+/// The metadata key of the degree n of a ciphertext column.
+pub const METADATA_N: &str = "cupcake.n";
+
+/// The metadata key of the modulus q of a ciphertext column.
+pub const METADATA_Q: &str = "cupcake.q";
+
+/// The metadata key of the identifier of the parameters of a ciphertext column.
+pub const METADATA_PARAMS_ID: &str = "cupcake.params_id";
+
+/// The reasons for which a ciphertext column cannot be stored or read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ColumnarError {
+    /// Arrow or Parquet failed.
+    Storage(String),
+    /// The batch has no column of this name.
+    MissingColumn(String),
+    /// The column is not a binary column, or does not record its parameters.
+    NotCiphertexts(String),
+    /// The column was written under other parameters.
+    ParameterMismatch(String),
+    /// A ciphertext of the column is invalid.
+    Invalid { row: usize, error: ValidationError },
+}
+
+impl fmt::Display for ColumnarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColumnarError::Storage(e) => write!(f, "storage failed: {}", e),
+            ColumnarError::MissingColumn(name) => write!(f, "no column {}", name),
+            ColumnarError::NotCiphertexts(name) => {
+                write!(f, "column {} does not hold ciphertexts", name)
+            }
+            ColumnarError::ParameterMismatch(name) => {
+                write!(f, "column {} was written under other parameters", name)
+            }
+            ColumnarError::Invalid { row, error } => {
+                write!(f, "invalid ciphertext at row {}: {}", row, error)
+            }
+        }
+    }
+}
+
+impl Error for ColumnarError {}
+
+fn storage<E: fmt::Display>(e: E) -> ColumnarError {
+    ColumnarError::Storage(e.to_string())
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The field of a column of ciphertexts of this scheme.
+    pub fn ciphertext_field(&self, name: &str) -> Field {
+        let metadata: HashMap<String, String> = vec![
+            (METADATA_N.to_string(), self.n.to_string()),
+            (
+                METADATA_Q.to_string(),
+                T::to_u64(self.q.clone()).to_string(),
+            ),
+            (METADATA_PARAMS_ID.to_string(), self.params_id().to_string()),
+        ]
+        .into_iter()
+        .collect();
+        Field::new(name, DataType::Binary, true).with_metadata(metadata)
+    }
+
+    /// The encodings of ciphertexts as an Arrow array, with nulls for missing values.
+    pub fn ciphertext_array(&self, cts: &[Option<FVCiphertext<T>>]) -> BinaryArray {
+        cts.iter()
+            .map(|ct| ct.as_ref().map(|ct| ct.to_bytes()))
+            .collect()
+    }
+
+    /// A record batch with a single column of ciphertexts.
+    pub fn ciphertext_batch(&self, name: &str, cts: &[FVCiphertext<T>]) -> RecordBatch {
+        let cts: Vec<_> = cts.iter().cloned().map(Some).collect();
+        let schema = Schema::new(vec![self.ciphertext_field(name)]);
+        let column: ArrayRef = Arc::new(self.ciphertext_array(&cts));
+        RecordBatch::try_new(Arc::new(schema), vec![column]).expect("the column matches the schema")
+    }
+
+    /// Decode and validate a column of ciphertexts, with `None` for null entries.
+    pub fn ciphertexts_from_batch(
+        &self,
+        batch: &RecordBatch,
+        name: &str,
+    ) -> Result<Vec<Option<FVCiphertext<T>>>, ColumnarError> {
+        let schema = batch.schema();
+        let (index, field) = schema
+            .column_with_name(name)
+            .ok_or_else(|| ColumnarError::MissingColumn(name.to_string()))?;
+        let params_id = field
+            .metadata()
+            .get(METADATA_PARAMS_ID)
+            .ok_or_else(|| ColumnarError::NotCiphertexts(name.to_string()))?;
+        if *params_id != self.params_id().to_string() {
+            return Err(ColumnarError::ParameterMismatch(name.to_string()));
+        }
+        let column = batch
+            .column(index)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .ok_or_else(|| ColumnarError::NotCiphertexts(name.to_string()))?;
+        column
+            .iter()
+            .enumerate()
+            .map(|(row, bytes)| {
+                bytes
+                    .map(|bytes| self.ciphertext_from_bytes(bytes))
+                    .transpose()
+                    .map_err(|error| ColumnarError::Invalid { row, error })
+            })
+            .collect()
+    }
+
+    /// The homomorphic sum of the non-null ciphertexts of a column over several batches, or
+    /// `None` if there are none.
+    pub fn sum_column(
+        &self,
+        batches: &[RecordBatch],
+        name: &str,
+    ) -> Result<Option<FVCiphertext<T>>, ColumnarError> {
+        let mut sum: Option<FVCiphertext<T>> = None;
+        for batch in batches.iter() {
+            for ct in self
+                .ciphertexts_from_batch(batch, name)?
+                .into_iter()
+                .flatten()
+            {
+                match sum.as_mut() {
+                    Some(sum) => self.add_inplace(sum, &ct),
+                    None => sum = Some(ct),
+                }
+            }
+        }
+        Ok(sum)
+    }
+}
+
+/// Write record batches, which must share a schema, as a Parquet file.
+pub fn write_parquet<W: Write + Send>(
+    writer: W,
+    batches: &[RecordBatch],
+) -> Result<(), ColumnarError> {
+    let schema = match batches.first() {
+        Some(batch) => batch.schema(),
+        None => return Err(ColumnarError::Storage("no record batch".to_string())),
+    };
+    let mut writer = ArrowWriter::try_new(writer, schema, None).map_err(storage)?;
+    for batch in batches.iter() {
+        writer.write(batch).map_err(storage)?;
+    }
+    writer.close().map_err(storage)?;
+    Ok(())
+}
+
+/// Read the record batches of a Parquet file.
+pub fn read_parquet<R: ChunkReader + 'static>(
+    reader: R,
+) -> Result<Vec<RecordBatch>, ColumnarError> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(reader)
+        .map_err(storage)?
+        .build()
+        .map_err(storage)?;
+    reader.map(|batch| batch.map_err(storage)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+    use arrow_array::UInt32Array;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let cts: Vec<_> = (1..=3u8)
+            .map(|i| Some(fv.encrypt(&vec![i; fv.n], &pk)))
+            .collect();
+
+        // A ciphertext column next to a plain column, with a missing value.
+        let mut column = cts.clone();
+        column.push(None);
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::UInt32, false),
+            fv.ciphertext_field("value"),
+        ]);
+        let ids: ArrayRef = Arc::new(UInt32Array::from(vec![1, 2, 3, 4]));
+        let values: ArrayRef = Arc::new(fv.ciphertext_array(&column));
+        let batch = RecordBatch::try_new(Arc::new(schema), vec![ids, values]).unwrap();
+        let single = fv.ciphertext_batch("value", &[fv.encrypt(&vec![4; fv.n], &pk)]);
+
+        let mut file = Vec::new();
+        write_parquet(&mut file, &[batch.clone(), batch]).unwrap();
+        // The reader merges the two batches.
+        let batches = read_parquet(bytes::Bytes::from(file)).unwrap();
+        assert_eq!(batches.len(), 1);
+        let read = fv.ciphertexts_from_batch(&batches[0], "value").unwrap();
+        assert!(read == [column.clone(), column].concat());
+
+        let sum = fv.sum_column(&batches, "value").unwrap().unwrap();
+        assert_eq!(fv.decrypt(&sum, &sk), vec![12; fv.n]);
+        let sum = fv.sum_column(&[single], "value").unwrap().unwrap();
+        assert_eq!(fv.decrypt(&sum, &sk), vec![4; fv.n]);
+    }
+
+    #[test]
+    fn test_column_rejections() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair();
+        let batch = fv.ciphertext_batch("value", &[fv.encrypt(&vec![1; fv.n], &pk)]);
+
+        let toy = FV::new(16, &Scalar::new_modulus(65537));
+        assert_eq!(
+            toy.ciphertexts_from_batch(&batch, "value"),
+            Err(ColumnarError::ParameterMismatch("value".to_string()))
+        );
+        assert_eq!(
+            fv.sum_column(&[batch], "other"),
+            Err(ColumnarError::MissingColumn("other".to_string()))
+        );
+
+        let truncated: BinaryArray = vec![Some(&[0u8; 8][..])].into_iter().collect();
+        let schema = Schema::new(vec![fv.ciphertext_field("value")]);
+        let batch = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(truncated)]).unwrap();
+        assert!(matches!(
+            fv.ciphertexts_from_batch(&batch, "value"),
+            Err(ColumnarError::Invalid { row: 0, .. })
+        ));
+    }
+}
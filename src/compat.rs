@@ -0,0 +1,86 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Interoperation between the typed and the tuple-based APIs.
+//!
+//! The scheme traits keep operating on [`FVCiphertext`] pairs, so tuple-based code needs no
+//! compatibility layer, and [`Ciphertext`] already converts to and from a pair. For code moving to the typed API one call site at a time, this
+//! module also makes [`PublicKey`] convertible with `From`/`Into` and borrowable as a pair, and
+//! lets typed and tuple ciphertexts be compared, so that both kinds of values can flow through
+//! the same code while it is being migrated. Outside of a migration, the explicit
+//! [`PublicKey::from_pair`] and [`PublicKey::as_pair`] are preferred.
+use crate::ciphertext::Ciphertext;
+use crate::integer_arith::ArithUtils;
+use crate::pubkey::PublicKey;
+use crate::FVCiphertext;
+
+impl<T> From<FVCiphertext<T>> for PublicKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn from(pk: FVCiphertext<T>) -> Self {
+        PublicKey::from_pair(pk)
+    }
+}
+
+impl<T> From<PublicKey<T>> for FVCiphertext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn from(pk: PublicKey<T>) -> Self {
+        pk.into_pair()
+    }
+}
+
+impl<T> AsRef<FVCiphertext<T>> for PublicKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn as_ref(&self) -> &FVCiphertext<T> {
+        self.as_pair()
+    }
+}
+
+impl<T: PartialEq> PartialEq<FVCiphertext<T>> for Ciphertext<T> {
+    fn eq(&self, other: &FVCiphertext<T>) -> bool {
+        self.as_ref() == other
+    }
+}
+
+impl<T: PartialEq> PartialEq<Ciphertext<T>> for FVCiphertext<T> {
+    fn eq(&self, other: &Ciphertext<T>) -> bool {
+        self == other.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+    use crate::FV;
+
+    // Encrypt with a key in either representation.
+    fn encrypt_legacy<K: AsRef<FVCiphertext<Scalar>>>(
+        fv: &FV<Scalar>,
+        pk: &K,
+    ) -> FVCiphertext<Scalar> {
+        fv.encrypt(&vec![7; fv.n], pk.as_ref())
+    }
+
+    #[test]
+    fn test_mixed_representations() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pair, sk) = fv.generate_keypair();
+        let pk: PublicKey<Scalar> = pair.clone().into();
+        let ct = encrypt_legacy(&fv, &pk);
+        assert_eq!(fv.decrypt(&ct, &sk), vec![7; fv.n]);
+
+        let typed = Ciphertext::from(ct.clone());
+        assert!(typed == ct);
+        assert!(ct == typed);
+        let back: FVCiphertext<Scalar> = pk.into();
+        assert_eq!(back, pair);
+    }
+}
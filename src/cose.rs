@@ -0,0 +1,283 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! CBOR encoding of ciphertexts and public keys with COSE headers, with the `cose` feature.
+//!
+//! A ciphertext is encoded as a tagged `COSE_Encrypt0` structure (RFC 9052): the protected
+//! header holds the algorithm [`ALG_CUPCAKE_FV`] and the identifier of the parameters under the
+//! label [`HEADER_PARAMS_ID`], the unprotected header holds the fingerprint of the public key as
+//! key id, and the payload is the canonical encoding of the ciphertext. A public key is encoded
+//! as a `COSE_Key` map with the key type and algorithm [`ALG_CUPCAKE_FV`], its fingerprint as
+//! key id, and its canonical encoding and parameters under the labels [`KEY_PUBLIC`] and
+//! [`KEY_PARAMS_ID`].
+//!
+//! The algorithm and labels are taken from the private-use ranges of the COSE registries. The
+//! structures carry no authentication tag: a ciphertext is malleable by design, and must be
+//! wrapped in a `COSE_Sign1` or `COSE_Mac0` where its integrity matters.
+use crate::fingerprint::Fingerprint;
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use ciborium::value::{Integer, Value};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// The COSE algorithm and key type of the FV scheme of this crate.
+pub const ALG_CUPCAKE_FV: i64 = -65601;
+
+/// The label of the identifier of the parameters in the protected header of a ciphertext.
+pub const HEADER_PARAMS_ID: i64 = -65602;
+
+/// The label of the canonical encoding of a public key in a `COSE_Key`.
+pub const KEY_PUBLIC: i64 = -1;
+
+/// The label of the identifier of the parameters in a `COSE_Key`.
+pub const KEY_PARAMS_ID: i64 = -2;
+
+/// The CBOR tag of a `COSE_Encrypt0` structure.
+const TAG_ENCRYPT0: u64 = 16;
+
+const HEADER_ALG: i64 = 1;
+const HEADER_KID: i64 = 4;
+const KEY_KTY: i64 = 1;
+const KEY_KID: i64 = 2;
+const KEY_ALG: i64 = 3;
+
+/// The reasons for which a COSE structure is rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CoseError {
+    /// The CBOR encoding is malformed, or a required field is missing.
+    Malformed(&'static str),
+    /// The structure uses another algorithm.
+    Algorithm(i64),
+    /// The structure was produced under other parameters.
+    ParameterMismatch,
+    /// The encoded ciphertext or public key is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for CoseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoseError::Malformed(what) => write!(f, "malformed COSE structure: {}", what),
+            CoseError::Algorithm(alg) => write!(f, "unsupported COSE algorithm {}", alg),
+            CoseError::ParameterMismatch => write!(f, "COSE structure of other parameters"),
+            CoseError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for CoseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CoseError::Invalid(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ValidationError> for CoseError {
+    fn from(e: ValidationError) -> Self {
+        CoseError::Invalid(e)
+    }
+}
+
+fn label(label: i64) -> Value {
+    Value::Integer(label.into())
+}
+
+fn to_cbor(value: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes).expect("writing to a vector cannot fail");
+    bytes
+}
+
+fn from_cbor(bytes: &[u8]) -> Result<Value, CoseError> {
+    ciborium::from_reader(bytes).map_err(|_| CoseError::Malformed("invalid CBOR"))
+}
+
+// The value of an integer label in a map.
+fn lookup(map: &[(Value, Value)], key: i64) -> Option<&Value> {
+    let key = Integer::from(key);
+    map.iter()
+        .find(|(k, _)| k.as_integer() == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn bytes_field<'a>(
+    map: &'a [(Value, Value)],
+    key: i64,
+    what: &'static str,
+) -> Result<&'a [u8], CoseError> {
+    lookup(map, key)
+        .and_then(Value::as_bytes)
+        .map(Vec::as_slice)
+        .ok_or(CoseError::Malformed(what))
+}
+
+fn check_algorithm(map: &[(Value, Value)], key: i64) -> Result<(), CoseError> {
+    let alg = lookup(map, key)
+        .and_then(Value::as_integer)
+        .and_then(|alg| i64::try_from(alg).ok())
+        .ok_or(CoseError::Malformed("missing algorithm"))?;
+    if alg != ALG_CUPCAKE_FV {
+        return Err(CoseError::Algorithm(alg));
+    }
+    Ok(())
+}
+
+fn fingerprint(bytes: &[u8], what: &'static str) -> Result<Fingerprint, CoseError> {
+    let mut fp = [0u8; 16];
+    if bytes.len() != fp.len() {
+        return Err(CoseError::Malformed(what));
+    }
+    fp.copy_from_slice(bytes);
+    Ok(Fingerprint(fp))
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Encode a ciphertext as a tagged `COSE_Encrypt0`, with the fingerprint of the public key
+    /// it was encrypted under as key id.
+    pub fn ciphertext_to_cose(&self, ct: &FVCiphertext<T>, kid: &Fingerprint) -> Vec<u8> {
+        let protected = Value::Map(vec![
+            (label(HEADER_ALG), label(ALG_CUPCAKE_FV)),
+            (
+                label(HEADER_PARAMS_ID),
+                Value::Bytes(self.params_id().0.to_vec()),
+            ),
+        ]);
+        let unprotected = Value::Map(vec![(label(HEADER_KID), Value::Bytes(kid.0.to_vec()))]);
+        let structure = Value::Array(vec![
+            Value::Bytes(to_cbor(&protected)),
+            unprotected,
+            Value::Bytes(ct.to_bytes()),
+        ]);
+        to_cbor(&Value::Tag(TAG_ENCRYPT0, Box::new(structure)))
+    }
+
+    /// Decode and validate a `COSE_Encrypt0`, returning the ciphertext and the key id.
+    pub fn ciphertext_from_cose(
+        &self,
+        bytes: &[u8],
+    ) -> Result<(FVCiphertext<T>, Fingerprint), CoseError> {
+        let structure = match from_cbor(bytes)? {
+            Value::Tag(TAG_ENCRYPT0, structure) => *structure,
+            structure => structure,
+        };
+        let fields = match structure {
+            Value::Array(fields) if fields.len() == 3 => fields,
+            _ => return Err(CoseError::Malformed("not a COSE_Encrypt0")),
+        };
+        let protected = match fields[0].as_bytes().map(|bytes| from_cbor(bytes)) {
+            Some(Ok(Value::Map(map))) => map,
+            _ => return Err(CoseError::Malformed("invalid protected header")),
+        };
+        let unprotected = fields[1]
+            .as_map()
+            .ok_or(CoseError::Malformed("invalid unprotected header"))?;
+        let payload = fields[2]
+            .as_bytes()
+            .ok_or(CoseError::Malformed("missing ciphertext"))?;
+
+        check_algorithm(&protected, HEADER_ALG)?;
+        let params = bytes_field(&protected, HEADER_PARAMS_ID, "missing parameters")?;
+        if fingerprint(params, "invalid parameters")? != self.params_id() {
+            return Err(CoseError::ParameterMismatch);
+        }
+        let kid = bytes_field(unprotected, HEADER_KID, "missing key id")?;
+        let kid = fingerprint(kid, "invalid key id")?;
+        Ok((self.ciphertext_from_bytes(payload)?, kid))
+    }
+
+    /// Encode a public key as a `COSE_Key`.
+    pub fn public_key_to_cose(&self, pk: &FVCiphertext<T>) -> Vec<u8> {
+        let kid = self.public_key_fingerprint(pk);
+        to_cbor(&Value::Map(vec![
+            (label(KEY_KTY), label(ALG_CUPCAKE_FV)),
+            (label(KEY_KID), Value::Bytes(kid.0.to_vec())),
+            (label(KEY_ALG), label(ALG_CUPCAKE_FV)),
+            (label(KEY_PUBLIC), Value::Bytes(pk.to_bytes())),
+            (
+                label(KEY_PARAMS_ID),
+                Value::Bytes(self.params_id().0.to_vec()),
+            ),
+        ]))
+    }
+
+    /// Decode and validate a `COSE_Key`. The key id is not checked, as it is only a hint.
+    pub fn public_key_from_cose(&self, bytes: &[u8]) -> Result<FVCiphertext<T>, CoseError> {
+        let map = match from_cbor(bytes)? {
+            Value::Map(map) => map,
+            _ => return Err(CoseError::Malformed("not a COSE_Key")),
+        };
+        check_algorithm(&map, KEY_KTY)?;
+        check_algorithm(&map, KEY_ALG)?;
+        let params = bytes_field(&map, KEY_PARAMS_ID, "missing parameters")?;
+        if fingerprint(params, "invalid parameters")? != self.params_id() {
+            return Err(CoseError::ParameterMismatch);
+        }
+        let encoding = bytes_field(&map, KEY_PUBLIC, "missing public key")?;
+        Ok(self.public_key_from_bytes(encoding)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_cose_round_trip() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let kid = fv.public_key_fingerprint(&pk);
+        let ct = fv.encrypt(&vec![5; fv.n], &pk);
+
+        let encoded = fv.ciphertext_to_cose(&ct, &kid);
+        // A tagged array of three items.
+        assert_eq!(encoded[..2], [0xd0, 0x83]);
+        let (decoded, decoded_kid) = fv.ciphertext_from_cose(&encoded).unwrap();
+        assert_eq!(decoded_kid, kid);
+        assert_eq!(fv.decrypt(&decoded, &sk), vec![5; fv.n]);
+
+        let key = fv.public_key_to_cose(&pk);
+        assert_eq!(fv.public_key_from_cose(&key), Ok(pk));
+    }
+
+    #[test]
+    fn test_cose_rejections() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair();
+        let ct = fv.encrypt(&vec![5; fv.n], &pk);
+        let encoded = fv.ciphertext_to_cose(&ct, &fv.public_key_fingerprint(&pk));
+
+        let toy = FV::new(16, &Scalar::new_modulus(65537));
+        assert_eq!(
+            toy.ciphertext_from_cose(&encoded).err(),
+            Some(CoseError::ParameterMismatch)
+        );
+        assert_eq!(
+            fv.ciphertext_from_cose(&encoded[..100]).err(),
+            Some(CoseError::Malformed("invalid CBOR"))
+        );
+        assert_eq!(
+            fv.public_key_from_cose(&encoded),
+            Err(CoseError::Malformed("not a COSE_Key"))
+        );
+
+        let key = to_cbor(&Value::Map(vec![
+            (label(KEY_KTY), label(1)),
+            (label(KEY_ALG), label(ALG_CUPCAKE_FV)),
+        ]));
+        assert_eq!(fv.public_key_from_cose(&key), Err(CoseError::Algorithm(1)));
+    }
+}
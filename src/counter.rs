@@ -0,0 +1,332 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Named counters held in encrypted slots.
+//!
+//! An [`EncryptedCounter`] allocates each named counter a slot, slot i being coefficient i mod n
+//! of ciphertext i / n, in the order of allocation, so that devices allocating the same names in
+//! the same order agree on the layout. An increment adds the plaintext 1 in the slot of a
+//! counter, and merging adds the ciphertexts of two sets of counters, so that a collector can
+//! combine the counters of many users and only the holder of the secret key reads their sum.
+//!
+//! The plaintext modulus is 256, so every set of counters carries a bound on its largest count:
+//! an increment or merge which could take a slot to 256 fails with [`CounterError::Overflow`].
+//! The ciphertexts are rerandomized every `rerandomize_interval` updates, and before they leave
+//! the device with [`EncryptedCounter::rerandomize`]. Each rerandomization adds flooding noise,
+//! so the counters track a [`NoiseEstimate`]: periodic rerandomizations stop once another one
+//! could prevent decryption, and a merge which could fails with [`CounterError::NoiseBudget`].
+use crate::integer_arith::ArithUtils;
+use crate::noise::NoiseEstimate;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, SecretKey, FV};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// The largest count of a slot.
+pub const MAX_COUNT: u64 = 255;
+
+/// The reasons for which counters cannot be allocated, updated or merged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CounterError {
+    /// All slots are allocated.
+    Full { capacity: usize },
+    /// No counter has this name.
+    UnknownCounter(String),
+    /// A slot could exceed [`MAX_COUNT`].
+    Overflow,
+    /// The names of the other counters are not a prefix of these ones.
+    LayoutMismatch,
+    /// The merged counters could not be decrypted correctly.
+    NoiseBudget,
+    /// A ciphertext of the other counters is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for CounterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CounterError::Full { capacity } => write!(f, "all {} slots are allocated", capacity),
+            CounterError::UnknownCounter(name) => write!(f, "unknown counter {}", name),
+            CounterError::Overflow => write!(f, "a count could exceed {}", MAX_COUNT),
+            CounterError::LayoutMismatch => write!(f, "counters of another layout"),
+            CounterError::NoiseBudget => write!(f, "the merged noise could prevent decryption"),
+            CounterError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for CounterError {}
+
+impl From<ValidationError> for CounterError {
+    fn from(e: ValidationError) -> Self {
+        CounterError::Invalid(e)
+    }
+}
+
+/// A set of named counters in encrypted slots.
+pub struct EncryptedCounter<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    pk: FVCiphertext<T>,
+    names: Vec<String>,
+    slots: HashMap<String, usize>,
+    ciphertexts: Vec<FVCiphertext<T>>,
+    max_count: u64,
+    noise: NoiseEstimate,
+    rerandomize_interval: usize,
+    updates: usize,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for EncryptedCounter<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "EncryptedCounter({}/{} slots)",
+            self.names.len(),
+            self.capacity()
+        )
+    }
+}
+
+impl<'a, T: ArithUtils<T>> EncryptedCounter<'a, T> {
+    /// The number of slots.
+    pub fn capacity(&self) -> usize {
+        self.ciphertexts.len() * self.fv.n
+    }
+
+    /// The names of the counters, in the order of their slots.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The slot of a counter.
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.slots.get(name).cloned()
+    }
+
+    /// The bound on the largest count.
+    pub fn max_count(&self) -> u64 {
+        self.max_count
+    }
+
+    /// The estimate of the noise of the ciphertexts.
+    pub fn noise(&self) -> NoiseEstimate {
+        self.noise
+    }
+
+    /// The ciphertexts of the slots.
+    pub fn ciphertexts(&self) -> &[FVCiphertext<T>] {
+        &self.ciphertexts
+    }
+}
+
+impl<'a, T> EncryptedCounter<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Zero counters in `ciphertexts` ciphertexts under `pk`, rerandomized every
+    /// `rerandomize_interval` updates, or never if it is zero.
+    pub fn new(
+        fv: &'a FV<T>,
+        pk: FVCiphertext<T>,
+        ciphertexts: usize,
+        rerandomize_interval: usize,
+    ) -> Self {
+        let ciphertexts = (0..ciphertexts).map(|_| fv.encrypt_zero(&pk)).collect();
+        EncryptedCounter {
+            fv,
+            pk,
+            names: vec![],
+            slots: HashMap::new(),
+            ciphertexts,
+            max_count: 0,
+            noise: fv.noise_model().fresh_pk(),
+            rerandomize_interval,
+            updates: 0,
+        }
+    }
+
+    /// Allocate the next slot to a counter, or return its slot if it exists.
+    pub fn allocate(&mut self, name: &str) -> Result<usize, CounterError> {
+        if let Some(slot) = self.slot(name) {
+            return Ok(slot);
+        }
+        let slot = self.names.len();
+        if slot == self.capacity() {
+            return Err(CounterError::Full {
+                capacity: self.capacity(),
+            });
+        }
+        self.names.push(name.to_string());
+        self.slots.insert(name.to_string(), slot);
+        Ok(slot)
+    }
+
+    /// Add one to a counter.
+    pub fn increment(&mut self, name: &str) -> Result<(), CounterError> {
+        let slot = self
+            .slot(name)
+            .ok_or_else(|| CounterError::UnknownCounter(name.to_string()))?;
+        if self.max_count == MAX_COUNT {
+            return Err(CounterError::Overflow);
+        }
+        let mut pt = vec![0u8; self.fv.n];
+        pt[slot % self.fv.n] = 1;
+        self.fv
+            .add_plain_inplace(&mut self.ciphertexts[slot / self.fv.n], &pt);
+        self.max_count += 1;
+        self.noise = self.fv.noise_model().add_plain(&self.noise);
+        self.updated();
+        Ok(())
+    }
+
+    /// Add the counters of another set, whose names must be a prefix of these ones.
+    pub fn merge(&mut self, other: &EncryptedCounter<T>) -> Result<(), CounterError> {
+        self.merge_parts(
+            &other.names,
+            &other.ciphertexts,
+            other.max_count,
+            &other.noise,
+        )
+    }
+
+    /// Add counters received from another device: their names, ciphertexts, bound on the
+    /// largest count and noise estimate.
+    pub fn merge_parts(
+        &mut self,
+        names: &[String],
+        ciphertexts: &[FVCiphertext<T>],
+        max_count: u64,
+        noise: &NoiseEstimate,
+    ) -> Result<(), CounterError> {
+        if names.len() > self.names.len()
+            || names.iter().zip(self.names.iter()).any(|(a, b)| a != b)
+            || ciphertexts.len() != self.ciphertexts.len()
+        {
+            return Err(CounterError::LayoutMismatch);
+        }
+        if self.max_count + max_count > MAX_COUNT {
+            return Err(CounterError::Overflow);
+        }
+        let model = self.fv.noise_model();
+        let merged = model.add(&self.noise, noise);
+        if !model.is_correct(&merged) {
+            return Err(CounterError::NoiseBudget);
+        }
+        for ct in ciphertexts {
+            self.fv.validate(ct)?;
+        }
+        for (acc, ct) in self.ciphertexts.iter_mut().zip(ciphertexts.iter()) {
+            self.fv.add_inplace(acc, ct);
+        }
+        self.max_count += max_count;
+        self.noise = merged;
+        self.updated();
+        Ok(())
+    }
+
+    fn updated(&mut self) {
+        self.updates += 1;
+        if self.rerandomize_interval > 0 && self.updates >= self.rerandomize_interval {
+            let model = self.fv.noise_model();
+            if model.is_correct(&model.rerandomize(&self.noise)) {
+                self.rerandomize();
+            }
+        }
+    }
+
+    /// Rerandomize the ciphertexts, before they are sent to another party.
+    pub fn rerandomize(&mut self) {
+        for ct in self.ciphertexts.iter_mut() {
+            self.fv.rerandomize(ct, &self.pk);
+        }
+        self.noise = self.fv.noise_model().rerandomize(&self.noise);
+        self.updates = 0;
+    }
+
+    /// The counts, decrypted with the secret key, in the order of the names.
+    pub fn counts(&self, sk: &SecretKey<T>) -> Vec<(String, u64)> {
+        let pts: Vec<_> = self
+            .ciphertexts
+            .iter()
+            .map(|ct| self.fv.decrypt(ct, sk))
+            .collect();
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(slot, name)| (name.clone(), pts[slot / self.fv.n][slot % self.fv.n] as u64))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_encrypted_counters() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut device = EncryptedCounter::new(&fv, pk.clone(), 2, 4);
+        assert_eq!(device.allocate("opened"), Ok(0));
+        assert_eq!(device.allocate("clicked"), Ok(1));
+        assert_eq!(device.allocate("opened"), Ok(0));
+        for _ in 0..5 {
+            device.increment("opened").unwrap();
+        }
+        device.increment("clicked").unwrap();
+        assert_eq!(
+            device.increment("closed"),
+            Err(CounterError::UnknownCounter("closed".to_string()))
+        );
+
+        // A collector with a later slot, beyond the first ciphertext.
+        let mut collector = EncryptedCounter::new(&fv, pk, 2, 0);
+        for name in ["opened", "clicked"] {
+            collector.allocate(name).unwrap();
+        }
+        for i in 2..=fv.n {
+            collector.allocate(&format!("c{}", i)).unwrap();
+        }
+        assert_eq!(collector.slot(&format!("c{}", fv.n)), Some(fv.n));
+        collector.increment(&format!("c{}", fv.n)).unwrap();
+        collector.merge(&device).unwrap();
+        device.rerandomize();
+        collector.merge(&device).unwrap();
+        assert_eq!(collector.max_count(), 13);
+
+        let counts = collector.counts(&sk);
+        assert_eq!(counts[0], ("opened".to_string(), 10));
+        assert_eq!(counts[1], ("clicked".to_string(), 2));
+        assert_eq!(counts[fv.n], (format!("c{}", fv.n), 1));
+        assert_eq!(device.merge(&collector), Err(CounterError::LayoutMismatch));
+    }
+
+    #[test]
+    fn test_counter_limits() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let mut counters = EncryptedCounter::new(&fv, pk.clone(), 1, 0);
+        for i in 0..16 {
+            counters.allocate(&i.to_string()).unwrap();
+        }
+        assert_eq!(
+            counters.allocate("16"),
+            Err(CounterError::Full { capacity: 16 })
+        );
+        for _ in 0..MAX_COUNT {
+            counters.increment("3").unwrap();
+        }
+        assert_eq!(counters.increment("4"), Err(CounterError::Overflow));
+
+        // The noise of two ciphertexts of these toy parameters exceeds their budget.
+        let mut other = EncryptedCounter::new(&fv, pk.clone(), 1, 0);
+        let fresh = EncryptedCounter::new(&fv, pk, 1, 0);
+        assert_eq!(other.merge(&fresh), Err(CounterError::NoiseBudget));
+    }
+}
@@ -0,0 +1,405 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Count-min sketches over encrypted counters.
+//!
+//! A count-min sketch estimates the frequency of items with `depth` rows of `width` counters:
+//! an item increments one counter per row, chosen by a keyed hash, and its estimate is the
+//! minimum of its counters, which never underestimates and overestimates by at most epsilon
+//! times the total count with probability 1 - delta for `width = e / epsilon` and
+//! `depth = ln(1 / delta)`.
+//!
+//! In an [`EncryptedCountMin`], the counters are the coefficients of ciphertexts under the public
+//! key of an analyst, counter j of row i in coefficient (i width + j) mod n of ciphertext
+//! (i width + j) / n. An update adds the sparse plaintext of the counters of an item, and merging
+//! two sketches adds their ciphertexts, so that a collector can store and combine sketches it
+//! cannot read. The plaintext modulus is 256, so the counters are held in batches, each
+//! receiving a total count of at most [`MAX_BATCH_WEIGHT`]; a new batch starts when an update
+//! would exceed it, and the batches are added after decryption.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, SecretKey, FV};
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+/// The largest total count added into one batch, so that no counter wraps around the plaintext
+/// modulus.
+pub const MAX_BATCH_WEIGHT: u64 = 255;
+
+const DOMAIN: &[u8] = b"cupcake count-min column";
+
+/// The reasons for which sketches cannot be merged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CountMinError {
+    /// The sketches have different dimensions or hash keys.
+    ParameterMismatch,
+    /// A batch has a total count above [`MAX_BATCH_WEIGHT`].
+    WeightTooLarge { weight: u64 },
+    /// A batch does not have one ciphertext per n counters.
+    WrongLength { expected: usize, actual: usize },
+    /// A ciphertext of a batch is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for CountMinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CountMinError::ParameterMismatch => write!(f, "sketches of different parameters"),
+            CountMinError::WeightTooLarge { weight } => {
+                write!(f, "batch of total count {} is too large", weight)
+            }
+            CountMinError::WrongLength { expected, actual } => {
+                write!(f, "batch of {} ciphertexts, expected {}", actual, expected)
+            }
+            CountMinError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for CountMinError {}
+
+impl From<ValidationError> for CountMinError {
+    fn from(e: ValidationError) -> Self {
+        CountMinError::Invalid(e)
+    }
+}
+
+/// The dimensions and hash key of a sketch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountMinParams {
+    /// The number of counters of a row.
+    pub width: usize,
+    /// The number of rows.
+    pub depth: usize,
+    /// The key of the hash choosing the counters of an item.
+    pub key: [u8; 32],
+}
+
+impl CountMinParams {
+    /// A sketch of `depth` rows of `width` counters.
+    ///
+    /// # Panics
+    /// If `width` or `depth` is zero.
+    pub fn new(width: usize, depth: usize, key: [u8; 32]) -> Self {
+        assert!(width > 0 && depth > 0, "empty sketch");
+        CountMinParams { width, depth, key }
+    }
+
+    /// A sketch overestimating by at most `epsilon` times the total count with probability at
+    /// least `1 - delta`.
+    pub fn with_error(epsilon: f64, delta: f64, key: [u8; 32]) -> Self {
+        let width = (std::f64::consts::E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil() as usize;
+        Self::new(width, depth.max(1), key)
+    }
+
+    /// The number of counters.
+    pub fn counters(&self) -> usize {
+        self.width * self.depth
+    }
+
+    /// The index of the counter of `item` in `row`.
+    pub fn counter(&self, row: usize, item: &[u8]) -> usize {
+        let mut hasher = Sha3_256::new();
+        hasher.update(DOMAIN);
+        hasher.update(self.key);
+        hasher.update((row as u32).to_le_bytes());
+        hasher.update(item);
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&hasher.finalize()[..8]);
+        row * self.width + (u64::from_le_bytes(word) % self.width as u64) as usize
+    }
+}
+
+/// Encrypted counters receiving a total count of at most [`MAX_BATCH_WEIGHT`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountMinBatch<T> {
+    /// The ciphertexts, each holding n counters.
+    pub ciphertexts: Vec<FVCiphertext<T>>,
+    /// The total count added to the counters of each row.
+    pub weight: u64,
+}
+
+/// A count-min sketch with encrypted counters.
+pub struct EncryptedCountMin<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    pk: FVCiphertext<T>,
+    params: CountMinParams,
+    batches: Vec<CountMinBatch<T>>,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for EncryptedCountMin<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "EncryptedCountMin({}x{}, {} batches)",
+            self.params.depth,
+            self.params.width,
+            self.batches.len()
+        )
+    }
+}
+
+impl<'a, T> EncryptedCountMin<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// An empty sketch, with counters encrypted under `pk`.
+    pub fn new(fv: &'a FV<T>, pk: FVCiphertext<T>, params: CountMinParams) -> Self {
+        EncryptedCountMin {
+            fv,
+            pk,
+            params,
+            batches: vec![],
+        }
+    }
+
+    /// The parameters of the sketch.
+    pub fn params(&self) -> &CountMinParams {
+        &self.params
+    }
+
+    /// The batches of counters.
+    pub fn batches(&self) -> &[CountMinBatch<T>] {
+        &self.batches
+    }
+
+    /// The total count added to the sketch.
+    pub fn weight(&self) -> u64 {
+        self.batches.iter().map(|batch| batch.weight).sum()
+    }
+
+    fn ciphertexts(&self) -> usize {
+        self.params.counters().div_ceil(self.fv.n)
+    }
+
+    /// Add `count` occurrences of `item`.
+    pub fn update(&mut self, item: &[u8], count: u8) {
+        if count == 0 {
+            return;
+        }
+        let weight = count as u64;
+        if self
+            .batches
+            .last()
+            .is_none_or(|batch| batch.weight + weight > MAX_BATCH_WEIGHT)
+        {
+            let ciphertexts = (0..self.ciphertexts())
+                .map(|_| self.fv.encrypt_zero(&self.pk))
+                .collect();
+            self.batches.push(CountMinBatch {
+                ciphertexts,
+                weight: 0,
+            });
+        }
+
+        // The sparse plaintexts of the counters of the item, by ciphertext.
+        let mut pts: Vec<Option<Vec<u8>>> = vec![None; self.ciphertexts()];
+        for row in 0..self.params.depth {
+            let index = self.params.counter(row, item);
+            let pt = pts[index / self.fv.n].get_or_insert_with(|| vec![0; self.fv.n]);
+            pt[index % self.fv.n] = count;
+        }
+        let batch = self.batches.last_mut().expect("a batch was just ensured");
+        for (ct, pt) in batch.ciphertexts.iter_mut().zip(pts.iter()) {
+            if let Some(pt) = pt {
+                self.fv.add_plain_inplace(ct, pt);
+            }
+        }
+        batch.weight += weight;
+    }
+
+    /// Add the batches of another sketch of the same parameters.
+    pub fn merge(
+        &mut self,
+        params: &CountMinParams,
+        batches: &[CountMinBatch<T>],
+    ) -> Result<(), CountMinError> {
+        if *params != self.params {
+            return Err(CountMinError::ParameterMismatch);
+        }
+        let expected = self.ciphertexts();
+        for batch in batches {
+            if batch.weight > MAX_BATCH_WEIGHT {
+                return Err(CountMinError::WeightTooLarge {
+                    weight: batch.weight,
+                });
+            }
+            if batch.ciphertexts.len() != expected {
+                return Err(CountMinError::WrongLength {
+                    expected,
+                    actual: batch.ciphertexts.len(),
+                });
+            }
+            for ct in batch.ciphertexts.iter() {
+                self.fv.validate(ct)?;
+            }
+        }
+        for batch in batches {
+            match self.batches.last_mut() {
+                Some(last) if last.weight + batch.weight <= MAX_BATCH_WEIGHT => {
+                    for (acc, ct) in last.ciphertexts.iter_mut().zip(batch.ciphertexts.iter()) {
+                        self.fv.add_inplace(acc, ct);
+                    }
+                    last.weight += batch.weight;
+                }
+                _ => self.batches.push(batch.clone()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Rerandomize the counters, before the sketch is sent to the holder of the secret key.
+    pub fn rerandomize(&mut self) {
+        for batch in self.batches.iter_mut() {
+            for ct in batch.ciphertexts.iter_mut() {
+                self.fv.rerandomize(ct, &self.pk);
+            }
+        }
+    }
+
+    /// The counters of the sketch, decrypted with the secret key.
+    pub fn decrypt(&self, sk: &SecretKey<T>) -> CountMinCounters {
+        let mut counters = vec![0u64; self.params.counters()];
+        for batch in self.batches.iter() {
+            let pts: Vec<_> = batch
+                .ciphertexts
+                .iter()
+                .map(|ct| self.fv.decrypt(ct, sk))
+                .collect();
+            for (index, counter) in counters.iter_mut().enumerate() {
+                *counter += pts[index / self.fv.n][index % self.fv.n] as u64;
+            }
+        }
+        CountMinCounters {
+            params: self.params.clone(),
+            counters,
+        }
+    }
+}
+
+/// The decrypted counters of a sketch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountMinCounters {
+    /// The parameters of the sketch.
+    pub params: CountMinParams,
+    /// The counters, row by row.
+    pub counters: Vec<u64>,
+}
+
+impl CountMinCounters {
+    /// The estimated number of occurrences of `item`, never below the actual number.
+    pub fn estimate(&self, item: &[u8]) -> u64 {
+        (0..self.params.depth)
+            .map(|row| self.counters[self.params.counter(row, item)])
+            .min()
+            .expect("a sketch has at least one row")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_encrypted_count_min() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let params = CountMinParams::with_error(0.01, 0.01, [5; 32]);
+        assert_eq!((params.width, params.depth), (272, 5));
+
+        let mut sketch = EncryptedCountMin::new(&fv, pk.clone(), params.clone());
+        for _ in 0..100 {
+            sketch.update(b"popular", 3);
+        }
+        sketch.update(b"rare", 1);
+        assert_eq!(sketch.weight(), 301);
+        assert_eq!(sketch.batches().len(), 2);
+
+        let mut other = EncryptedCountMin::new(&fv, pk, params.clone());
+        other.update(b"rare", 4);
+        other.rerandomize();
+        sketch.merge(other.params(), other.batches()).unwrap();
+        assert_eq!(sketch.batches().len(), 2);
+
+        let counters = sketch.decrypt(&sk);
+        assert_eq!(counters.estimate(b"popular"), 300);
+        assert_eq!(counters.estimate(b"rare"), 5);
+        assert_eq!(counters.estimate(b"absent"), 0);
+    }
+
+    #[test]
+    fn test_merge_rejections() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let params = CountMinParams::new(10, 3, [0; 32]);
+        let mut sketch = EncryptedCountMin::new(&fv, pk.clone(), params.clone());
+        let mut other = EncryptedCountMin::new(&fv, pk, CountMinParams::new(10, 3, [1; 32]));
+        other.update(b"x", 1);
+        assert_eq!(
+            sketch.merge(other.params(), other.batches()),
+            Err(CountMinError::ParameterMismatch)
+        );
+        let mut batches = other.batches().to_vec();
+        batches[0].weight = 256;
+        assert_eq!(
+            sketch.merge(&params, &batches),
+            Err(CountMinError::WeightTooLarge { weight: 256 })
+        );
+        batches[0].weight = 1;
+        batches[0].ciphertexts.pop();
+        assert_eq!(
+            sketch.merge(&params, &batches),
+            Err(CountMinError::WrongLength {
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_merge_invalid_and_batch_boundaries() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let params = CountMinParams::new(10, 3, [0; 32]);
+        let mut sketch = EncryptedCountMin::new(&fv, pk.clone(), params.clone());
+        sketch.update(b"x", 0);
+        assert!(sketch.batches().is_empty());
+        sketch.update(b"x", 255);
+        assert_eq!(sketch.batches().len(), 1);
+        sketch.update(b"x", 1);
+        assert_eq!(sketch.batches().len(), 2);
+        let before = sketch.batches().to_vec();
+
+        // a malformed ciphertext in the last batch rejects the whole merge.
+        let mut other = EncryptedCountMin::new(&fv, pk, params.clone());
+        other.update(b"y", 200);
+        other.update(b"y", 200);
+        let mut batches = other.batches().to_vec();
+        batches[1].ciphertexts[0].1.coeffs.pop();
+        assert_eq!(
+            sketch.merge(&params, &batches),
+            Err(CountMinError::Invalid(ValidationError::WrongDegree {
+                expected: 16,
+                actual: 15
+            }))
+        );
+        assert_eq!(sketch.batches(), &before[..]);
+        assert_eq!(
+            CountMinError::WeightTooLarge { weight: 256 }.to_string(),
+            "batch of total count 256 is too large"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "empty sketch")]
+    fn test_empty_sketch() {
+        CountMinParams::new(0, 3, [0; 32]);
+    }
+}
@@ -0,0 +1,249 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Verifiable decryption.
+//!
+//! A decryption m of a ciphertext (c0, c1) is correct if c1 - c0 * s = Delta * m + v for the
+//! secret s of the public key and a small v. The key owner proves this together with the
+//! well-formedness of the public key (see [`crate::keyproof`]), with the same sigma protocol
+//! extended by the relation c0 * s + v = c1 - Delta * m: an additional mask y_v of v is committed
+//! as w2 = c0 * y_s + y_v, and answered by z_v = y_v + c * v.
+//!
+//! The bound on v is revealed as a power of two 2^k, i.e. the proof leaks the number of bits of
+//! noise in the ciphertext. Decryption is only provable when the response bound 2nκ 2^k is below
+//! Delta / 4, so that no other plaintext satisfies the relation; this holds for sums of many
+//! fresh ciphertexts with the default parameters, but not for rerandomized ones, whose flooding
+//! noise is too large.
+use crate::integer_arith::ArithUtils;
+use crate::keyproof::{
+    add_sparse_product, challenge_hash, dense_challenge, expand_challenge, sample_centered, within,
+    KeyProofError, ProofBounds,
+};
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::SKEncryption;
+use crate::utils::wipe;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+
+/// A non-interactive proof that a plaintext is the decryption of a ciphertext under the secret
+/// key of a public key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecryptionProof {
+    /// log2 of the bound on the noise of the ciphertext.
+    pub noise_bits: u32,
+    /// The hash from which the challenge is expanded.
+    pub challenge: [u8; 32],
+    /// The response for the secret key.
+    pub z_s: Vec<i64>,
+    /// The response for the error of the public key.
+    pub z_e: Vec<i64>,
+    /// The response for the noise of the ciphertext.
+    pub z_v: Vec<i64>,
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Decrypt a ciphertext, and prove that the plaintext is its correct decryption under the
+    /// secret key of `pk`.
+    pub fn decrypt_with_proof(
+        &self,
+        ct: &FVCiphertext<T>,
+        sk: &SecretKey<T>,
+        pk: &FVCiphertext<T>,
+    ) -> Result<(FVPlaintext, DecryptionProof), KeyProofError> {
+        self.validate_public_key(pk)?;
+        self.validate(ct)?;
+        if let Some(fp) = sk.public_key_fingerprint() {
+            if fp != self.public_key_fingerprint(pk) {
+                return Err(KeyProofError::InvalidWitness);
+            }
+        }
+        let bounds = self.proof_bounds();
+        let pt = self.decrypt(ct, sk);
+
+        // v = c1 - c0 * s - Delta * m.
+        let mut v_poly = self.shifted_phase(ct, &pt);
+        let mut c0_s = self.multiply_secret(&ct.0, sk);
+        v_poly.sub_inplace(&c0_s);
+        wipe(&mut c0_s);
+        let mut v = self.centered(&v_poly);
+        wipe(&mut v_poly);
+        let max = v.iter().map(|x| x.unsigned_abs()).max().unwrap_or(0);
+        let noise_bits = 64 - max.leading_zeros();
+        let mask_v = match self.decryption_mask(&bounds, noise_bits) {
+            Some(mask_v) => mask_v,
+            None => {
+                wipe(&mut v);
+                return Err(KeyProofError::NoiseTooLarge);
+            }
+        };
+        let response_v = mask_v - ((bounds.weight as i64) << noise_bits);
+
+        let (mut s, mut e) = self.key_witness(pk, sk, &bounds)?;
+        let proof = loop {
+            let mut y_s = sample_centered(self.n, bounds.mask_s);
+            let mut y_e = sample_centered(self.n, bounds.mask_e);
+            let mut y_v = sample_centered(self.n, mask_v);
+            let y_s_ring = self.to_ring(&y_s);
+            let mut w1 = (self.poly_multiplier)(&pk.0, &y_s_ring);
+            w1.add_inplace(&self.to_ring(&y_e));
+            let mut w2 = (self.poly_multiplier)(&ct.0, &y_s_ring);
+            w2.add_inplace(&self.to_ring(&y_v));
+            let challenge = self.decryption_challenge(pk, ct, &pt, noise_bits, &w1, &w2);
+            let c = expand_challenge(&challenge, self.n, bounds.weight);
+
+            let mut z_s = add_sparse_product(&y_s, &c, &s);
+            let mut z_e = add_sparse_product(&y_e, &c, &e);
+            let mut z_v = add_sparse_product(&y_v, &c, &v);
+            wipe(&mut y_s);
+            wipe(&mut y_e);
+            wipe(&mut y_v);
+            if within(&z_s, bounds.response_s())
+                && within(&z_e, bounds.response_e())
+                && within(&z_v, response_v)
+            {
+                break DecryptionProof {
+                    noise_bits,
+                    challenge,
+                    z_s,
+                    z_e,
+                    z_v,
+                };
+            }
+            wipe(&mut z_s);
+            wipe(&mut z_e);
+            wipe(&mut z_v);
+        };
+        wipe(&mut s);
+        wipe(&mut e);
+        wipe(&mut v);
+        Ok((pt, proof))
+    }
+
+    /// Verify a proof that `pt` is the decryption of `ct` under the secret key of `pk`.
+    pub fn verify_decryption(
+        &self,
+        pk: &FVCiphertext<T>,
+        ct: &FVCiphertext<T>,
+        pt: &FVPlaintext,
+        proof: &DecryptionProof,
+    ) -> Result<(), KeyProofError> {
+        self.validate_public_key(pk)?;
+        self.validate(ct)?;
+        let bounds = self.proof_bounds();
+        if pt.len() != self.n
+            || proof.z_s.len() != self.n
+            || proof.z_e.len() != self.n
+            || proof.z_v.len() != self.n
+        {
+            return Err(KeyProofError::WrongLength);
+        }
+        let mask_v = self
+            .decryption_mask(&bounds, proof.noise_bits)
+            .ok_or(KeyProofError::NoiseTooLarge)?;
+        let response_v = mask_v - ((bounds.weight as i64) << proof.noise_bits);
+        if !within(&proof.z_s, bounds.response_s())
+            || !within(&proof.z_e, bounds.response_e())
+            || !within(&proof.z_v, response_v)
+        {
+            return Err(KeyProofError::ResponseOutOfRange);
+        }
+
+        // w1 = a * z_s + z_e - c * b and w2 = c0 * z_s + z_v - c * (c1 - Delta * m).
+        let c = self.to_ring(&dense_challenge(&proof.challenge, self.n, bounds.weight));
+        let z_s = self.to_ring(&proof.z_s);
+        let mut w1 = (self.poly_multiplier)(&pk.0, &z_s);
+        w1.add_inplace(&self.to_ring(&proof.z_e));
+        w1.sub_inplace(&(self.poly_multiplier)(&pk.1, &c));
+        let mut w2 = (self.poly_multiplier)(&ct.0, &z_s);
+        w2.add_inplace(&self.to_ring(&proof.z_v));
+        w2.sub_inplace(&(self.poly_multiplier)(&self.shifted_phase(ct, pt), &c));
+
+        let challenge = self.decryption_challenge(pk, ct, pt, proof.noise_bits, &w1, &w2);
+        if challenge == proof.challenge {
+            Ok(())
+        } else {
+            Err(KeyProofError::ChallengeMismatch)
+        }
+    }
+
+    /// The mask bound of the noise for a given noise bound, if the proof is sound for it.
+    fn decryption_mask(&self, bounds: &ProofBounds, noise_bits: u32) -> Option<i64> {
+        let delta = T::to_u64(self.delta.clone()) as i128;
+        let mask_v = (2 * (self.n * bounds.weight) as i128).checked_shl(noise_bits)?;
+        if noise_bits < 64 && mask_v < delta / 4 {
+            Some(mask_v as i64)
+        } else {
+            None
+        }
+    }
+
+    /// c1 - Delta * m.
+    fn shifted_phase(&self, ct: &FVCiphertext<T>, pt: &FVPlaintext) -> RqPoly<T> {
+        let mut d = ct.1.clone();
+        for (x, y) in d.coeffs.iter_mut().zip(pt.iter()) {
+            let temp = T::mul(&T::from_u32_raw(*y as u32), &self.delta);
+            *x = T::sub_mod(x, &temp, &self.q);
+        }
+        d
+    }
+
+    fn decryption_challenge(
+        &self,
+        pk: &FVCiphertext<T>,
+        ct: &FVCiphertext<T>,
+        pt: &FVPlaintext,
+        noise_bits: u32,
+        w1: &RqPoly<T>,
+        w2: &RqPoly<T>,
+    ) -> [u8; 32] {
+        let mut extra = noise_bits.to_le_bytes().to_vec();
+        extra.extend_from_slice(pt);
+        challenge_hash(
+            b"cupcake decryption proof",
+            &[&pk.0, &pk.1, &ct.0, &ct.1, w1, w2],
+            &extra,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_verifiable_decryption() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct = fv.encrypt(&vec![3; fv.n], &pk);
+        fv.add_inplace(&mut ct, &fv.encrypt(&vec![4; fv.n], &pk));
+
+        let (pt, proof) = fv.decrypt_with_proof(&ct, &sk, &pk).unwrap();
+        assert_eq!(pt, vec![7; fv.n]);
+        assert_eq!(fv.verify_decryption(&pk, &ct, &pt, &proof), Ok(()));
+
+        let mut wrong = pt;
+        wrong[0] = 8;
+        assert_eq!(
+            fv.verify_decryption(&pk, &ct, &wrong, &proof),
+            Err(KeyProofError::ChallengeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_rerandomized_decryption_is_not_provable() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct = fv.encrypt(&vec![3; fv.n], &pk);
+        fv.rerandomize(&mut ct, &pk);
+        assert_eq!(
+            fv.decrypt_with_proof(&ct, &sk, &pk),
+            Err(KeyProofError::NoiseTooLarge)
+        );
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Hierarchical key derivation.
+//!
+//! A keypair is generated deterministically from a 32-byte [`KeySeed`], and seeds form a tree:
+//! the child of a seed k for a path component c is SHA3-256(tag || k || len(c) || c), and a path
+//! such as `"tenant/42/epoch/7"` is derived one component at a time. A service can thus store a
+//! single master seed instead of one key per tenant and epoch, and hand the seed of a subtree,
+//! e.g. `"tenant/42"`, to a component which only needs the keys below it.
+//!
+//! All keys below a seed are compromised with it, so seeds must be protected like secret keys.
+use crate::integer_arith::ArithUtils;
+use crate::rng::{with_rng, with_seeded_rng};
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::PKEncryption;
+use crate::utils::{ct_eq, wipe};
+use crate::{FVCiphertext, SecretKey, FV};
+use rand::RngCore;
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+/// A seed from which a keypair and child seeds are derived.
+#[derive(Clone)]
+pub struct KeySeed([u8; 32]);
+
+impl KeySeed {
+    /// A seed from its bytes.
+    pub fn new(bytes: [u8; 32]) -> Self {
+        KeySeed(bytes)
+    }
+
+    /// A fresh random seed.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        with_rng(|rng| rng.fill_bytes(&mut bytes));
+        KeySeed(bytes)
+    }
+
+    /// The bytes of the seed, e.g. for a backup.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The seed at `path` below this seed, for a path of non-empty components separated by `/`.
+    pub fn derive(&self, path: &str) -> Result<KeySeed, DerivationError> {
+        if path.is_empty() {
+            return Err(DerivationError::EmptyPath);
+        }
+        let mut seed = self.clone();
+        for (position, component) in path.split('/').enumerate() {
+            if component.is_empty() {
+                return Err(DerivationError::EmptyComponent { position });
+            }
+            seed = seed.child(component.as_bytes());
+        }
+        Ok(seed)
+    }
+
+    fn child(&self, component: &[u8]) -> KeySeed {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"cupcake key derivation");
+        hasher.update(self.0);
+        hasher.update((component.len() as u32).to_le_bytes());
+        hasher.update(component);
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&hasher.finalize());
+        KeySeed(bytes)
+    }
+}
+
+impl fmt::Debug for KeySeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KeySeed(..)")
+    }
+}
+
+impl PartialEq for KeySeed {
+    /// Compare two seeds in constant time.
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for KeySeed {}
+
+impl Drop for KeySeed {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+/// The reasons for which a derivation path is rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DerivationError {
+    /// The path has no components.
+    EmptyPath,
+    /// The component at this position is empty, e.g. in `"tenant//7"`.
+    EmptyComponent { position: usize },
+}
+
+impl fmt::Display for DerivationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DerivationError::EmptyPath => write!(f, "empty derivation path"),
+            DerivationError::EmptyComponent { position } => {
+                write!(f, "empty component {} in derivation path", position)
+            }
+        }
+    }
+}
+
+impl Error for DerivationError {}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Generate the keypair of a seed. The same seed always yields the same keypair for a fixed
+    /// version of the crate.
+    pub fn generate_keypair_from_seed(&self, seed: &KeySeed) -> (FVCiphertext<T>, SecretKey<T>) {
+        with_seeded_rng(seed.0, || self.generate_keypair())
+    }
+
+    /// Derive the keypair at `path` below a master seed.
+    pub fn derive_key(
+        &self,
+        master: &KeySeed,
+        path: &str,
+    ) -> Result<(FVCiphertext<T>, SecretKey<T>), DerivationError> {
+        let seed = master.derive(path)?;
+        Ok(self.generate_keypair_from_seed(&seed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_derive_key() {
+        let fv = FV::<Scalar>::default_2048();
+        let master = KeySeed::new([7u8; 32]);
+        let (pk1, sk1) = fv.derive_key(&master, "tenant/42/epoch/7").unwrap();
+        let (pk2, _) = fv.derive_key(&master, "tenant/42/epoch/7").unwrap();
+        let (pk3, _) = fv.derive_key(&master, "tenant/42/epoch/8").unwrap();
+        assert_eq!(pk1.1.coeffs, pk2.1.coeffs);
+        assert_ne!(pk1.1.coeffs, pk3.1.coeffs);
+
+        let tenant = master.derive("tenant/42").unwrap();
+        let (pk4, _) = fv.derive_key(&tenant, "epoch/7").unwrap();
+        assert_eq!(pk1.1.coeffs, pk4.1.coeffs);
+
+        let ct = fv.encrypt(&vec![2; fv.n], &pk2);
+        assert_eq!(fv.decrypt(&ct, &sk1), vec![2; fv.n]);
+    }
+
+    #[test]
+    fn test_derived_key_known_answer() {
+        // The key of a seed must not change with the version of the crate or of its
+        // dependencies, since only the seed is stored.
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair_from_seed(&KeySeed::new([7u8; 32]));
+        assert_eq!(
+            fv.public_key_fingerprint(&pk).to_string(),
+            "d4ce614ebfa07bd122ce522cae443e7b"
+        );
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        let master = KeySeed::generate();
+        assert_eq!(master.derive("").unwrap_err(), DerivationError::EmptyPath);
+        assert_eq!(
+            master.derive("tenant//7").unwrap_err(),
+            DerivationError::EmptyComponent { position: 1 }
+        );
+        assert_eq!(
+            master.derive("tenant/").unwrap_err(),
+            DerivationError::EmptyComponent { position: 1 }
+        );
+    }
+}
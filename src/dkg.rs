@@ -0,0 +1,639 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Distributed key generation.
+//!
+//! Parties 0..count jointly generate a public key (a, b_0 + ... + b_{count-1}) whose secret key
+//! s_0 + ... + s_{count-1} is never assembled: each party keeps its s_i as a [`SecretKeyShare`]
+//! for n-out-of-n threshold decryption. The protocol has four broadcast rounds:
+//!
+//! 1. every party commits to a random seed with H(seed_i);
+//! 2. every party reveals its seed, and the common a is expanded from all the seeds, so that no
+//!    party can bias it once the commitments are fixed;
+//! 3. every party commits to b_i = a * s_i + e_i with H(b_i);
+//! 4. every party reveals b_i, so that no party can choose its share as a function of the
+//!    others, e.g. to cancel them in the sum.
+//!
+//! After the last round, [`DkgParty::finish`] returns the public key, the secret key share and a
+//! hash of the whole transcript. The parties should compare their transcript hashes to check that
+//! they all received the same messages.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::{randutils, FiniteRingElt, RqPoly, NTT};
+use crate::threshold::SecretKeyShare;
+use crate::traits::Serializable;
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use rand::RngCore;
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+/// A message broadcast by a party during distributed key generation.
+#[derive(Clone, Debug)]
+pub enum DkgMessage<T> {
+    /// Round 1: the commitment H(seed) to the seed of a party.
+    SeedCommitment { from: usize, commitment: [u8; 32] },
+    /// Round 2: the seed of a party.
+    SeedReveal { from: usize, seed: [u8; 32] },
+    /// Round 3: the commitment H(b_i) to the public key share of a party.
+    ShareCommitment { from: usize, commitment: [u8; 32] },
+    /// Round 4: the public key share b_i = a * s_i + e_i of a party, in coefficient form.
+    PublicKeyShare { from: usize, share: RqPoly<T> },
+}
+
+impl<T> DkgMessage<T> {
+    /// The index of the party which sent this message.
+    pub fn from(&self) -> usize {
+        match self {
+            DkgMessage::SeedCommitment { from, .. } => *from,
+            DkgMessage::SeedReveal { from, .. } => *from,
+            DkgMessage::ShareCommitment { from, .. } => *from,
+            DkgMessage::PublicKeyShare { from, .. } => *from,
+        }
+    }
+
+    fn round(&self) -> DkgRound {
+        match self {
+            DkgMessage::SeedCommitment { .. } => DkgRound::Commit,
+            DkgMessage::SeedReveal { .. } => DkgRound::Reveal,
+            DkgMessage::ShareCommitment { .. } => DkgRound::ShareCommit,
+            DkgMessage::PublicKeyShare { .. } => DkgRound::KeyShare,
+        }
+    }
+}
+
+impl<T> Serializable for DkgMessage<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// A tag byte for the round, the sender as a 4-byte little-endian integer, and the payload.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.round() as u8];
+        bytes.extend_from_slice(&(self.from() as u32).to_le_bytes());
+        match self {
+            DkgMessage::SeedCommitment { commitment, .. } => bytes.extend_from_slice(commitment),
+            DkgMessage::SeedReveal { seed, .. } => bytes.extend_from_slice(seed),
+            DkgMessage::ShareCommitment { commitment, .. } => bytes.extend_from_slice(commitment),
+            DkgMessage::PublicKeyShare { share, .. } => bytes.extend_from_slice(&share.to_bytes()),
+        }
+        bytes
+    }
+}
+
+/// The rounds of the protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DkgRound {
+    Commit = 0,
+    Reveal = 1,
+    ShareCommit = 2,
+    KeyShare = 3,
+    Done = 4,
+}
+
+/// The reasons for which a party rejects a message or cannot finish the protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DkgError {
+    /// The sender is not one of the parties.
+    UnknownParty { from: usize },
+    /// The message belongs to another round than the current one.
+    UnexpectedRound {
+        expected: DkgRound,
+        actual: DkgRound,
+    },
+    /// The sender already sent a message for this round.
+    DuplicateMessage { from: usize },
+    /// The revealed seed or public key share does not match the commitment of the sender.
+    CommitmentMismatch { from: usize },
+    /// The public key share is not well-formed.
+    Malformed(ValidationError),
+    /// The protocol is not finished.
+    Incomplete { round: DkgRound },
+}
+
+impl fmt::Display for DkgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DkgError::UnknownParty { from } => write!(f, "message from unknown party {}", from),
+            DkgError::UnexpectedRound { expected, actual } => write!(
+                f,
+                "message for round {:?} during round {:?}",
+                actual, expected
+            ),
+            DkgError::DuplicateMessage { from } => {
+                write!(f, "duplicate message from party {}", from)
+            }
+            DkgError::CommitmentMismatch { from } => {
+                write!(f, "reveal of party {} does not match its commitment", from)
+            }
+            DkgError::Malformed(e) => write!(f, "malformed public key share: {}", e),
+            DkgError::Incomplete { round } => write!(f, "protocol incomplete in round {:?}", round),
+        }
+    }
+}
+
+impl Error for DkgError {}
+
+impl From<ValidationError> for DkgError {
+    fn from(e: ValidationError) -> Self {
+        DkgError::Malformed(e)
+    }
+}
+
+/// The result of distributed key generation for one party.
+#[derive(Debug)]
+pub struct DkgOutput<T: ArithUtils<T>> {
+    /// The common public key.
+    pub public_key: FVCiphertext<T>,
+    /// The share of the common secret key held by this party.
+    pub share: SecretKeyShare<T>,
+    /// The hash of all the messages of the protocol, to be compared between parties.
+    pub transcript_hash: [u8; 32],
+}
+
+/// The state of one party in distributed key generation.
+pub struct DkgParty<T: ArithUtils<T>> {
+    index: usize,
+    count: usize,
+    round: DkgRound,
+    seed: [u8; 32],
+    commitments: Vec<Option<[u8; 32]>>,
+    seeds: Vec<Option<[u8; 32]>>,
+    share_commitments: Vec<Option<[u8; 32]>>,
+    a: Option<RqPoly<T>>,
+    secret: Option<RqPoly<T>>,
+    key_shares: Vec<Option<RqPoly<T>>>,
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for DkgParty<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DkgParty {{ index: {}, count: {}, round: {:?} }}",
+            self.index, self.count, self.round
+        )
+    }
+}
+
+impl<T: ArithUtils<T>> Drop for DkgParty<T> {
+    fn drop(&mut self) {
+        wipe(&mut self.seed);
+        if let Some(secret) = self.secret.as_mut() {
+            wipe(secret);
+        }
+    }
+}
+
+fn commit(seed: &[u8; 32]) -> [u8; 32] {
+    hash(b"cupcake dkg commitment", seed)
+}
+
+fn commit_share<T>(share: &RqPoly<T>) -> [u8; 32]
+where
+    T: ArithUtils<T> + Clone,
+{
+    hash(b"cupcake dkg share commitment", &share.to_bytes())
+}
+
+fn hash(tag: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(tag);
+    hasher.update(data);
+    let mut commitment = [0u8; 32];
+    commitment.copy_from_slice(&hasher.finalize());
+    commitment
+}
+
+impl<T> DkgParty<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Create party `index` out of `count`, and its round 1 message to broadcast.
+    pub fn new(index: usize, count: usize) -> (Self, DkgMessage<T>) {
+        assert!(index < count, "party index out of range");
+        let mut seed = [0u8; 32];
+        with_rng(|rng| rng.fill_bytes(&mut seed));
+        let commitment = commit(&seed);
+        let mut commitments = vec![None; count];
+        commitments[index] = Some(commitment);
+        let party = DkgParty {
+            index,
+            count,
+            round: DkgRound::Commit,
+            seed,
+            commitments,
+            seeds: vec![None; count],
+            share_commitments: vec![None; count],
+            a: None,
+            secret: None,
+            key_shares: vec![None; count],
+        };
+        let msg = DkgMessage::SeedCommitment {
+            from: index,
+            commitment,
+        };
+        (party, msg)
+    }
+
+    /// The current round.
+    pub fn round(&self) -> DkgRound {
+        self.round
+    }
+
+    /// Process a message broadcast by another party. Once all the messages of the current round
+    /// are received, this returns the message of this party for the next round, if any.
+    pub fn receive(
+        &mut self,
+        fv: &FV<T>,
+        msg: DkgMessage<T>,
+    ) -> Result<Option<DkgMessage<T>>, DkgError> {
+        let from = msg.from();
+        if from >= self.count {
+            return Err(DkgError::UnknownParty { from });
+        }
+        if msg.round() != self.round {
+            return Err(DkgError::UnexpectedRound {
+                expected: self.round,
+                actual: msg.round(),
+            });
+        }
+        match msg {
+            DkgMessage::SeedCommitment { commitment, .. } => {
+                if self.commitments[from].is_some() {
+                    return Err(DkgError::DuplicateMessage { from });
+                }
+                self.commitments[from] = Some(commitment);
+                if self.commitments.iter().all(Option::is_some) {
+                    self.seeds[self.index] = Some(self.seed);
+                    self.round = DkgRound::Reveal;
+                    return Ok(Some(DkgMessage::SeedReveal {
+                        from: self.index,
+                        seed: self.seed,
+                    }));
+                }
+            }
+            DkgMessage::SeedReveal { seed, .. } => {
+                if self.seeds[from].is_some() {
+                    return Err(DkgError::DuplicateMessage { from });
+                }
+                if self.commitments[from] != Some(commit(&seed)) {
+                    return Err(DkgError::CommitmentMismatch { from });
+                }
+                self.seeds[from] = Some(seed);
+                if self.seeds.iter().all(Option::is_some) {
+                    let share = self.generate_share(fv);
+                    let commitment = commit_share(&share);
+                    self.share_commitments[self.index] = Some(commitment);
+                    self.key_shares[self.index] = Some(share);
+                    self.round = DkgRound::ShareCommit;
+                    return Ok(Some(DkgMessage::ShareCommitment {
+                        from: self.index,
+                        commitment,
+                    }));
+                }
+            }
+            DkgMessage::ShareCommitment { commitment, .. } => {
+                if self.share_commitments[from].is_some() {
+                    return Err(DkgError::DuplicateMessage { from });
+                }
+                self.share_commitments[from] = Some(commitment);
+                if self.share_commitments.iter().all(Option::is_some) {
+                    self.round = DkgRound::KeyShare;
+                    let share = self.key_shares[self.index].clone();
+                    return Ok(Some(DkgMessage::PublicKeyShare {
+                        from: self.index,
+                        share: share.expect("the share is generated in round 2"),
+                    }));
+                }
+            }
+            DkgMessage::PublicKeyShare { share, .. } => {
+                if self.key_shares[from].is_some() {
+                    return Err(DkgError::DuplicateMessage { from });
+                }
+                fv.validate_poly(&share, 1, false)?;
+                if self.share_commitments[from] != Some(commit_share(&share)) {
+                    return Err(DkgError::CommitmentMismatch { from });
+                }
+                self.key_shares[from] = Some(share);
+                if self.key_shares.iter().all(Option::is_some) {
+                    self.round = DkgRound::Done;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Expand the common a from the seeds, and compute b_i = a * s_i + e_i.
+    fn generate_share(&mut self, fv: &FV<T>) -> RqPoly<T> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"cupcake dkg a");
+        for seed in self.seeds.iter().flatten() {
+            hasher.update(seed);
+        }
+        let mut a_seed = [0u8; 32];
+        a_seed.copy_from_slice(&hasher.finalize());
+        let a = randutils::sample_uniform_poly_from_seed(fv.context.clone(), &a_seed);
+
+        let mut secret = with_rng(|rng| randutils::sample_ternary_poly(fv.context.clone(), rng));
+        let mut e = randutils::sample_gaussian_poly(fv.context.clone(), fv.stdev);
+        let mut secret_ntt = secret.clone();
+        if fv.context.is_ntt_enabled {
+            secret_ntt.forward_transform();
+        }
+        let mut b = (fv.poly_multiplier)(&a, &secret_ntt);
+        b.add_inplace(&e);
+        wipe(&mut e);
+        wipe(&mut secret_ntt);
+
+        self.a = Some(a);
+        if let Some(previous) = self.secret.as_mut() {
+            wipe(previous);
+        }
+        self.secret = Some(secret.clone());
+        wipe(&mut secret);
+        b
+    }
+
+    /// Finish the protocol, once all the public key shares are received.
+    pub fn finish(mut self, fv: &FV<T>) -> Result<DkgOutput<T>, DkgError> {
+        if self.round != DkgRound::Done {
+            return Err(DkgError::Incomplete { round: self.round });
+        }
+        let mut a = self.a.take().expect("a is expanded in round 2");
+        let mut b = a.clone();
+        b.coeffs.iter_mut().for_each(|x| *x = T::zero());
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"cupcake dkg transcript");
+        hasher.update((self.count as u32).to_le_bytes());
+        for commitment in self.commitments.iter().flatten() {
+            hasher.update(commitment);
+        }
+        for seed in self.seeds.iter().flatten() {
+            hasher.update(seed);
+        }
+        for commitment in self.share_commitments.iter().flatten() {
+            hasher.update(commitment);
+        }
+        for share in self.key_shares.iter().flatten() {
+            hasher.update(share.to_bytes());
+            b.add_inplace(share);
+        }
+        let mut transcript_hash = [0u8; 32];
+        transcript_hash.copy_from_slice(&hasher.finalize());
+
+        if fv.context.is_ntt_enabled {
+            a.forward_transform();
+            b.forward_transform();
+        }
+        let secret = self
+            .secret
+            .take()
+            .expect("the secret is sampled in round 2");
+        Ok(DkgOutput {
+            public_key: (a, b),
+            share: fv.new_share(self.index, self.count, secret),
+            transcript_hash,
+        })
+    }
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Decode a distributed key generation message.
+    pub fn dkg_message_from_bytes(&self, bytes: &[u8]) -> Result<DkgMessage<T>, ValidationError> {
+        let expected = match bytes.first() {
+            Some(0) | Some(1) | Some(2) => 5 + 32,
+            Some(3) => 5 + self.poly_byte_len(),
+            Some(&tag) => return Err(ValidationError::UnknownMessageTag { tag }),
+            None => 5,
+        };
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&bytes[1..5]);
+        let from = u32::from_le_bytes(word) as usize;
+        let mut payload = [0u8; 32];
+        Ok(match bytes[0] {
+            0 => {
+                payload.copy_from_slice(&bytes[5..]);
+                DkgMessage::SeedCommitment {
+                    from,
+                    commitment: payload,
+                }
+            }
+            1 => {
+                payload.copy_from_slice(&bytes[5..]);
+                DkgMessage::SeedReveal {
+                    from,
+                    seed: payload,
+                }
+            }
+            2 => {
+                payload.copy_from_slice(&bytes[5..]);
+                DkgMessage::ShareCommitment {
+                    from,
+                    commitment: payload,
+                }
+            }
+            _ => DkgMessage::PublicKeyShare {
+                from,
+                share: self.poly_from_bytes(&bytes[5..], 1)?,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    /// Run the protocol between `count` parties over a simulated broadcast channel.
+    fn run(fv: &FV<Scalar>, count: usize) -> Vec<DkgOutput<Scalar>> {
+        let (mut parties, mut outbox): (Vec<_>, Vec<_>) =
+            (0..count).map(|i| DkgParty::new(i, count)).unzip();
+        while !outbox.is_empty() {
+            let mut next = vec![];
+            for msg in outbox.iter() {
+                let bytes = msg.to_bytes();
+                for party in parties.iter_mut().filter(|p| p.index != msg.from()) {
+                    let msg = fv.dkg_message_from_bytes(&bytes).unwrap();
+                    if let Some(reply) = party.receive(fv, msg).unwrap() {
+                        next.push(reply);
+                    }
+                }
+            }
+            outbox = next;
+        }
+        parties.into_iter().map(|p| p.finish(fv).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_distributed_key_generation() {
+        let fv = FV::<Scalar>::default_2048();
+        let outputs = run(&fv, 3);
+        for output in outputs.iter() {
+            assert_eq!(output.transcript_hash, outputs[0].transcript_hash);
+            assert_eq!(output.public_key.1.coeffs, outputs[0].public_key.1.coeffs);
+        }
+        let pk = &outputs[0].public_key;
+        assert_eq!(fv.validate_public_key(pk), Ok(()));
+
+        let ct = fv.encrypt(&vec![5; fv.n], pk);
+        let partials: Vec<_> = outputs
+            .iter()
+            .map(|o| fv.partial_decrypt(&ct, &o.share))
+            .collect();
+        assert_eq!(fv.combine_partials(&ct, &partials), Ok(vec![5; fv.n]));
+    }
+
+    #[test]
+    fn test_dkg_rejects_bad_messages() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (mut p0, _) = DkgParty::<Scalar>::new(0, 2);
+        let (p1, m1) = DkgParty::<Scalar>::new(1, 2);
+        assert!(p0.receive(&fv, m1.clone()).unwrap().is_some());
+        assert_eq!(
+            p0.receive(&fv, m1).unwrap_err(),
+            DkgError::UnexpectedRound {
+                expected: DkgRound::Reveal,
+                actual: DkgRound::Commit
+            }
+        );
+        let reveal = DkgMessage::SeedReveal {
+            from: 1,
+            seed: [0u8; 32],
+        };
+        assert_eq!(
+            p0.receive(&fv, reveal).unwrap_err(),
+            DkgError::CommitmentMismatch { from: 1 }
+        );
+        assert_eq!(
+            p0.receive(
+                &fv,
+                DkgMessage::SeedReveal {
+                    from: 7,
+                    seed: [0u8; 32]
+                }
+            )
+            .unwrap_err(),
+            DkgError::UnknownParty { from: 7 }
+        );
+        assert_eq!(p1.round(), DkgRound::Commit);
+        assert_eq!(
+            p1.finish(&fv).unwrap_err(),
+            DkgError::Incomplete {
+                round: DkgRound::Commit
+            }
+        );
+    }
+
+    #[test]
+    fn test_dkg_missing_and_equivocating_parties() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (mut p0, m0) = DkgParty::<Scalar>::new(0, 3);
+        let (mut p1, m1) = DkgParty::<Scalar>::new(1, 3);
+        let (_, m2) = DkgParty::<Scalar>::new(2, 3);
+
+        // party 1 equivocates on its commitment.
+        assert!(p0.receive(&fv, m1).unwrap().is_none());
+        let (_, other) = DkgParty::<Scalar>::new(1, 3);
+        assert_eq!(
+            p0.receive(&fv, other).unwrap_err(),
+            DkgError::DuplicateMessage { from: 1 }
+        );
+        let r0 = p0.receive(&fv, m2.clone()).unwrap().unwrap();
+        assert!(p1.receive(&fv, m0).unwrap().is_none());
+        let r1 = p1.receive(&fv, m2).unwrap().unwrap();
+
+        // party 1 reveals its seed twice, and party 2 never reveals its own.
+        assert!(p0.receive(&fv, r1.clone()).unwrap().is_none());
+        assert_eq!(
+            p0.receive(&fv, r1).unwrap_err(),
+            DkgError::DuplicateMessage { from: 1 }
+        );
+        assert!(p1.receive(&fv, r0).unwrap().is_none());
+        assert_eq!(p0.round(), DkgRound::Reveal);
+        assert_eq!(
+            p0.finish(&fv).unwrap_err(),
+            DkgError::Incomplete {
+                round: DkgRound::Reveal
+            }
+        );
+
+        // party 1 sends a malformed key share, then one which does not match its commitment,
+        // and then nothing.
+        let (mut q0, c0) = DkgParty::<Scalar>::new(0, 2);
+        let (mut q1, c1) = DkgParty::<Scalar>::new(1, 2);
+        let r0 = q0.receive(&fv, c1).unwrap().unwrap();
+        let r1 = q1.receive(&fv, c0).unwrap().unwrap();
+        let s0 = q0.receive(&fv, r1).unwrap().unwrap();
+        let s1 = q1.receive(&fv, r0).unwrap().unwrap();
+        assert!(q0.receive(&fv, s1).unwrap().is_some());
+        let share = match q1.receive(&fv, s0).unwrap() {
+            Some(DkgMessage::PublicKeyShare { share, .. }) => share,
+            _ => panic!("expected a public key share"),
+        };
+        let mut short = share.clone();
+        short.coeffs.pop();
+        let malformed = DkgMessage::PublicKeyShare {
+            from: 1,
+            share: short,
+        };
+        assert_eq!(
+            q0.receive(&fv, malformed).unwrap_err(),
+            DkgError::Malformed(ValidationError::WrongDegree {
+                expected: 16,
+                actual: 15
+            })
+        );
+        let mut rogue = share;
+        rogue.coeffs[0] = Scalar::zero();
+        rogue.coeffs[1] = Scalar::one();
+        let rogue = DkgMessage::PublicKeyShare {
+            from: 1,
+            share: rogue,
+        };
+        assert_eq!(
+            q0.receive(&fv, rogue).unwrap_err(),
+            DkgError::CommitmentMismatch { from: 1 }
+        );
+        assert_eq!(
+            q0.finish(&fv).unwrap_err(),
+            DkgError::Incomplete {
+                round: DkgRound::KeyShare
+            }
+        );
+    }
+
+    #[test]
+    fn test_dkg_message_decoding_errors() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (_, m0) = DkgParty::<Scalar>::new(0, 2);
+        let bytes = m0.to_bytes();
+        assert_eq!(
+            fv.dkg_message_from_bytes(&bytes[..36]).unwrap_err(),
+            ValidationError::WrongLength {
+                expected: 37,
+                actual: 36
+            }
+        );
+        let mut tagged = bytes.clone();
+        tagged[0] = 9;
+        assert_eq!(
+            fv.dkg_message_from_bytes(&tagged).unwrap_err(),
+            ValidationError::UnknownMessageTag { tag: 9 }
+        );
+        assert!(fv.dkg_message_from_bytes(&[]).is_err());
+    }
+}
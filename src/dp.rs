@@ -0,0 +1,230 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Differentially private releases of encrypted aggregates.
+//!
+//! The party holding an encrypted aggregate adds integer noise to its slots before it is
+//! decrypted, so that the decrypted values are a differentially private release of the
+//! aggregate. [`DpNoise::laplace`] calibrates discrete Laplace noise, P(x) proportional to
+//! exp(-|x| epsilon / sensitivity), which is epsilon-DP for queries of the given L1 sensitivity;
+//! [`DpNoise::gaussian`] calibrates discrete Gaussian noise of variance sensitivity^2 / (2 rho),
+//! which is rho-zCDP for queries of the given L2 sensitivity, and (epsilon, delta)-DP for
+//! epsilon = rho + 2 sqrt(rho ln(1 / delta)).
+//!
+//! [`FV::add_dp_noise`] adds sampled noise with an add_plain: the party adding it knows the noise,
+//! and must not see the decryption. Alternatively, each of k parties encrypts a share of the
+//! noise with [`FV::encrypt_dp_noise`] and the shares are added to the aggregate, so that no
+//! party knows the noise unless all collude. Only Gaussian noise splits into shares: the sum of k
+//! discrete Gaussians of variance sigma^2 / k is close to, but not exactly, a discrete Gaussian of
+//! variance sigma^2, and its privacy loss is close to that of a single one.
+//!
+//! The noise is added modulo 256, like the plaintexts: a slot decrypts to the aggregate plus the
+//! noise modulo 256, so that the aggregate and the noise must be small enough for a reader to
+//! recover their sum, e.g. as a signed byte. Sampling uses floating point and is not hardened
+//! against timing or floating-point side channels.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption};
+use crate::utils::wipe;
+use crate::{FVCiphertext, FVPlaintext, FV};
+use rand::Rng;
+
+/// A distribution of integer noise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DpNoise {
+    /// P(x) proportional to exp(-|x| / scale).
+    DiscreteLaplace { scale: f64 },
+    /// P(x) proportional to exp(-x^2 / (2 sigma^2)).
+    DiscreteGaussian { sigma: f64 },
+}
+
+impl DpNoise {
+    /// Noise making a query of L1 sensitivity `sensitivity` epsilon-DP.
+    ///
+    /// # Panics
+    /// If `epsilon` is not positive.
+    pub fn laplace(sensitivity: u32, epsilon: f64) -> Self {
+        assert!(epsilon > 0.0, "epsilon must be positive");
+        DpNoise::DiscreteLaplace {
+            scale: sensitivity as f64 / epsilon,
+        }
+    }
+
+    /// Noise making a query of L2 sensitivity `sensitivity` rho-zCDP.
+    ///
+    /// # Panics
+    /// If `rho` is not positive.
+    pub fn gaussian(sensitivity: f64, rho: f64) -> Self {
+        assert!(rho > 0.0, "rho must be positive");
+        DpNoise::DiscreteGaussian {
+            sigma: sensitivity / (2.0 * rho).sqrt(),
+        }
+    }
+
+    /// The distribution of the shares of `parties` parties summing to this noise, if it splits.
+    ///
+    /// # Panics
+    /// If `parties` is zero.
+    pub fn share(&self, parties: usize) -> Option<DpNoise> {
+        assert!(parties > 0, "at least one party");
+        match *self {
+            DpNoise::DiscreteLaplace { .. } => None,
+            DpNoise::DiscreteGaussian { sigma } => Some(DpNoise::DiscreteGaussian {
+                sigma: sigma / (parties as f64).sqrt(),
+            }),
+        }
+    }
+
+    /// Sample the noise.
+    pub fn sample(&self) -> i64 {
+        with_rng(|rng| match *self {
+            DpNoise::DiscreteLaplace { scale } => sample_discrete_laplace(rng, scale),
+            DpNoise::DiscreteGaussian { sigma } => sample_discrete_gaussian(rng, sigma),
+        })
+    }
+
+    /// A plaintext of n coefficients, the first `slots` of which are samples modulo 256.
+    ///
+    /// # Panics
+    /// If `slots` is larger than n.
+    pub fn sample_plaintext(&self, n: usize, slots: usize) -> FVPlaintext {
+        assert!(slots <= n, "more slots than coefficients");
+        let mut pt = vec![0u8; n];
+        for x in pt.iter_mut().take(slots) {
+            *x = self.sample() as u8;
+        }
+        pt
+    }
+}
+
+/// A geometric variable with P(k) proportional to exp(-k / scale), for k >= 0.
+fn sample_geometric<R: Rng>(rng: &mut R, scale: f64) -> i64 {
+    if scale <= 0.0 {
+        return 0;
+    }
+    // 1 - u lies in (0, 1], so that its logarithm is finite.
+    let u = 1.0 - rng.gen::<f64>();
+    (-scale * u.ln()).floor() as i64
+}
+
+/// The difference of two geometric variables is a discrete Laplace variable.
+fn sample_discrete_laplace<R: Rng>(rng: &mut R, scale: f64) -> i64 {
+    sample_geometric(rng, scale) - sample_geometric(rng, scale)
+}
+
+/// Rejection sampling from the discrete Laplace distribution, of Canonne, Kamath and Steinke.
+fn sample_discrete_gaussian<R: Rng>(rng: &mut R, sigma: f64) -> i64 {
+    if sigma <= 0.0 {
+        return 0;
+    }
+    let t = sigma.floor() + 1.0;
+    let variance = sigma * sigma;
+    loop {
+        let y = sample_discrete_laplace(rng, t);
+        let d = y.abs() as f64 - variance / t;
+        if rng.gen::<f64>() < (-d * d / (2.0 * variance)).exp() {
+            return y;
+        }
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Add noise to the first `slots` coefficients of an encrypted aggregate.
+    ///
+    /// # Panics
+    /// If `slots` is larger than n.
+    pub fn add_dp_noise(&self, ct: &mut FVCiphertext<T>, noise: &DpNoise, slots: usize) {
+        let mut pt = noise.sample_plaintext(self.n, slots);
+        self.add_plain_inplace(ct, &pt);
+        wipe(&mut pt);
+    }
+
+    /// The encryption of noise in the first `slots` coefficients, e.g. of a share of the noise
+    /// of a release, to be added to the aggregate.
+    ///
+    /// # Panics
+    /// If `slots` is larger than n.
+    pub fn encrypt_dp_noise(
+        &self,
+        pk: &FVCiphertext<T>,
+        noise: &DpNoise,
+        slots: usize,
+    ) -> FVCiphertext<T> {
+        let mut pt = noise.sample_plaintext(self.n, slots);
+        let ct = self.encrypt(&pt, pk);
+        wipe(&mut pt);
+        ct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::SKEncryption;
+
+    fn moments(noise: &DpNoise, count: usize) -> (f64, f64) {
+        let samples: Vec<f64> = (0..count).map(|_| noise.sample() as f64).collect();
+        let mean = samples.iter().sum::<f64>() / count as f64;
+        let variance = samples.iter().map(|x| x * x).sum::<f64>() / count as f64;
+        (mean, variance)
+    }
+
+    #[test]
+    fn test_noise_distributions() {
+        // A discrete Laplace variable of scale b has variance 2 e^(-1/b) / (1 - e^(-1/b))^2.
+        let laplace = DpNoise::laplace(2, 0.5);
+        assert_eq!(laplace, DpNoise::DiscreteLaplace { scale: 4.0 });
+        let r = (-0.25f64).exp();
+        let expected = 2.0 * r / ((1.0 - r) * (1.0 - r));
+        let (mean, variance) = moments(&laplace, 20000);
+        assert!(mean.abs() < 0.5, "mean {}", mean);
+        assert!(
+            (variance / expected - 1.0).abs() < 0.1,
+            "variance {}",
+            variance
+        );
+        assert_eq!(laplace.share(3), None);
+
+        let gaussian = DpNoise::gaussian(3.0, 0.125);
+        assert_eq!(gaussian, DpNoise::DiscreteGaussian { sigma: 6.0 });
+        let (mean, variance) = moments(&gaussian, 20000);
+        assert!(mean.abs() < 0.5, "mean {}", mean);
+        assert!((variance / 36.0 - 1.0).abs() < 0.1, "variance {}", variance);
+        let share = gaussian.share(4).unwrap();
+        assert_eq!(share, DpNoise::DiscreteGaussian { sigma: 3.0 });
+    }
+
+    #[test]
+    fn test_noisy_release() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let counts: Vec<u8> = (0..fv.n).map(|i| (i % 50) as u8).collect();
+        let noise = DpNoise::gaussian(1.0, 0.5);
+
+        let mut ct = fv.encrypt(&counts, &pk);
+        fv.add_dp_noise(&mut ct, &noise, 100);
+        let pt = fv.decrypt(&ct, &sk);
+        assert_eq!(pt[100..], counts[100..]);
+        let errors: Vec<i8> = (0..100)
+            .map(|i| pt[i].wrapping_sub(counts[i]) as i8)
+            .collect();
+        assert!(errors.iter().all(|e| e.abs() <= 10));
+        assert!(errors.iter().any(|e| *e != 0));
+
+        // Four parties add encrypted shares, none of which knows the noise.
+        let mut ct = fv.encrypt(&counts, &pk);
+        let share = noise.share(4).unwrap();
+        for _ in 0..4 {
+            fv.add_inplace(&mut ct, &fv.encrypt_dp_noise(&pk, &share, fv.n));
+        }
+        let pt = fv.decrypt(&ct, &sk);
+        assert!((0..fv.n).all(|i| (pt[i].wrapping_sub(counts[i]) as i8).abs() <= 10));
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! A scheme interface with the scalar type erased.
+//!
+//! The traits in [`crate::traits`] are generic over the ciphertext and key types, and thus over
+//! the scalar type of the scheme. [`HomomorphicScheme`] is object-safe instead: ciphertexts and
+//! keys are passed as the opaque [`DynCiphertext`], [`DynPublicKey`] and [`DynSecretKey`], so an application can
+//! pick its parameters at runtime and hold the scheme as a `Box<dyn HomomorphicScheme>` or an
+//! `Arc<dyn HomomorphicScheme>`. Passing an object created by a scheme over another scalar type
+//! fails with [`CupcakeError::WrongScheme`], and one created under other parameters fails
+//! validation.
+use crate::error::CupcakeError;
+use crate::fingerprint::Fingerprint;
+use crate::integer_arith::scalar::Scalar;
+use crate::integer_arith::ArithUtils;
+use crate::pubkey::PublicKey;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::Serializable;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::any::Any;
+use std::fmt;
+
+trait Erased: Any + Send + Sync {
+    fn clone_box(&self) -> Box<dyn Erased>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<V: Any + Clone + Send + Sync> Erased for V {
+    fn clone_box(&self) -> Box<dyn Erased> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A ciphertext of a scheme held behind a [`HomomorphicScheme`].
+pub struct DynCiphertext(Box<dyn Erased>);
+
+impl Clone for DynCiphertext {
+    fn clone(&self) -> Self {
+        DynCiphertext(self.0.clone_box())
+    }
+}
+
+impl fmt::Debug for DynCiphertext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DynCiphertext(..)")
+    }
+}
+
+/// A public key of a scheme held behind a [`HomomorphicScheme`].
+pub struct DynPublicKey(Box<dyn Erased>);
+
+impl Clone for DynPublicKey {
+    fn clone(&self) -> Self {
+        DynPublicKey(self.0.clone_box())
+    }
+}
+
+impl fmt::Debug for DynPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DynPublicKey(..)")
+    }
+}
+
+/// A secret key of a scheme held behind a [`HomomorphicScheme`].
+pub struct DynSecretKey(Box<dyn Any + Send + Sync>);
+
+impl fmt::Debug for DynSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DynSecretKey(..)")
+    }
+}
+
+/// The operations of an additive homomorphic scheme, in an object-safe form.
+pub trait HomomorphicScheme: Send + Sync {
+    /// The identifier of the parameters of the scheme.
+    fn params_id(&self) -> Fingerprint;
+
+    /// The number of slots of a plaintext.
+    fn plaintext_len(&self) -> usize;
+
+    /// Generate a (pk, sk) keypair.
+    fn generate_keypair(&self) -> (DynPublicKey, DynSecretKey);
+
+    /// Encrypt a plaintext under a public key.
+    fn encrypt(&self, pt: &FVPlaintext, pk: &DynPublicKey) -> Result<DynCiphertext, CupcakeError>;
+
+    /// Decrypt a ciphertext.
+    fn decrypt(&self, ct: &DynCiphertext, sk: &DynSecretKey) -> Result<FVPlaintext, CupcakeError>;
+
+    /// Add a ciphertext into another.
+    fn add_inplace(&self, ct1: &mut DynCiphertext, ct2: &DynCiphertext)
+        -> Result<(), CupcakeError>;
+
+    /// Add a plaintext into a ciphertext.
+    fn add_plain_inplace(
+        &self,
+        ct: &mut DynCiphertext,
+        pt: &FVPlaintext,
+    ) -> Result<(), CupcakeError>;
+
+    /// Rerandomize a ciphertext.
+    fn rerandomize(&self, ct: &mut DynCiphertext, pk: &DynPublicKey) -> Result<(), CupcakeError>;
+
+    /// The canonical encoding of a ciphertext.
+    fn ciphertext_to_bytes(&self, ct: &DynCiphertext) -> Result<Vec<u8>, CupcakeError>;
+
+    /// Decode and validate a ciphertext.
+    fn ciphertext_from_bytes(&self, bytes: &[u8]) -> Result<DynCiphertext, CupcakeError>;
+
+    /// The canonical encoding of a public key.
+    fn public_key_to_bytes(&self, pk: &DynPublicKey) -> Result<Vec<u8>, CupcakeError>;
+
+    /// Decode and validate a public key.
+    fn public_key_from_bytes(&self, bytes: &[u8]) -> Result<DynPublicKey, CupcakeError>;
+}
+
+/// A scheme with degree n and a 64-bit modulus q, e.g. read from a configuration file.
+pub fn scheme_from_params(n: usize, q: u64) -> Box<dyn HomomorphicScheme> {
+    Box::new(FV::new(n, &Scalar::new_modulus(q)))
+}
+
+fn ciphertext<T: 'static>(ct: &DynCiphertext) -> Result<&FVCiphertext<T>, CupcakeError> {
+    ct.0.as_any()
+        .downcast_ref()
+        .ok_or(CupcakeError::WrongScheme)
+}
+
+fn public_key<T: 'static>(pk: &DynPublicKey) -> Result<&PublicKey<T>, CupcakeError> {
+    pk.0.as_any()
+        .downcast_ref()
+        .ok_or(CupcakeError::WrongScheme)
+}
+
+fn ciphertext_mut<T: 'static>(
+    ct: &mut DynCiphertext,
+) -> Result<&mut FVCiphertext<T>, CupcakeError> {
+    ct.0.as_any_mut()
+        .downcast_mut()
+        .ok_or(CupcakeError::WrongScheme)
+}
+
+impl<T> HomomorphicScheme for FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq + Send + Sync + 'static,
+{
+    fn params_id(&self) -> Fingerprint {
+        FV::params_id(self)
+    }
+
+    fn plaintext_len(&self) -> usize {
+        self.n
+    }
+
+    fn generate_keypair(&self) -> (DynPublicKey, DynSecretKey) {
+        let (pk, sk) = self.generate_typed_keypair();
+        (DynPublicKey(Box::new(pk)), DynSecretKey(Box::new(sk)))
+    }
+
+    fn encrypt(&self, pt: &FVPlaintext, pk: &DynPublicKey) -> Result<DynCiphertext, CupcakeError> {
+        let ct = self.try_encrypt(pt, public_key(pk)?.as_pair())?;
+        Ok(DynCiphertext(Box::new(ct)))
+    }
+
+    fn decrypt(&self, ct: &DynCiphertext, sk: &DynSecretKey) -> Result<FVPlaintext, CupcakeError> {
+        let sk: &SecretKey<T> = sk.0.downcast_ref().ok_or(CupcakeError::WrongScheme)?;
+        self.try_decrypt(ciphertext(ct)?, sk)
+    }
+
+    fn add_inplace(
+        &self,
+        ct1: &mut DynCiphertext,
+        ct2: &DynCiphertext,
+    ) -> Result<(), CupcakeError> {
+        self.try_add_inplace(ciphertext_mut(ct1)?, ciphertext(ct2)?)
+    }
+
+    fn add_plain_inplace(
+        &self,
+        ct: &mut DynCiphertext,
+        pt: &FVPlaintext,
+    ) -> Result<(), CupcakeError> {
+        self.try_add_plain_inplace(ciphertext_mut(ct)?, pt)
+    }
+
+    fn rerandomize(&self, ct: &mut DynCiphertext, pk: &DynPublicKey) -> Result<(), CupcakeError> {
+        self.try_rerandomize(ciphertext_mut(ct)?, public_key(pk)?.as_pair())
+    }
+
+    fn ciphertext_to_bytes(&self, ct: &DynCiphertext) -> Result<Vec<u8>, CupcakeError> {
+        Ok(ciphertext::<T>(ct)?.to_bytes())
+    }
+
+    fn ciphertext_from_bytes(&self, bytes: &[u8]) -> Result<DynCiphertext, CupcakeError> {
+        let ct = FV::ciphertext_from_bytes(self, bytes)?;
+        Ok(DynCiphertext(Box::new(ct)))
+    }
+
+    fn public_key_to_bytes(&self, pk: &DynPublicKey) -> Result<Vec<u8>, CupcakeError> {
+        Ok(public_key::<T>(pk)?.to_bytes())
+    }
+
+    fn public_key_from_bytes(&self, bytes: &[u8]) -> Result<DynPublicKey, CupcakeError> {
+        let pk = self.typed_public_key_from_bytes(bytes)?;
+        Ok(DynPublicKey(Box::new(pk)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::ValidationError;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_dyn_scheme() {
+        let scheme: Arc<dyn HomomorphicScheme> =
+            Arc::from(scheme_from_params(2048, 18014398492704769));
+        let n = scheme.plaintext_len();
+        let (pk, sk) = scheme.generate_keypair();
+        let pk = scheme
+            .public_key_from_bytes(&scheme.public_key_to_bytes(&pk).unwrap())
+            .unwrap();
+        let mut ct = scheme.encrypt(&vec![4; n], &pk).unwrap();
+        let other = ct.clone();
+        scheme.add_inplace(&mut ct, &other).unwrap();
+        scheme.add_plain_inplace(&mut ct, &vec![1; n]).unwrap();
+        scheme.rerandomize(&mut ct, &pk).unwrap();
+        let bytes = scheme.ciphertext_to_bytes(&ct).unwrap();
+        let ct = scheme.ciphertext_from_bytes(&bytes).unwrap();
+        assert_eq!(scheme.decrypt(&ct, &sk).unwrap(), vec![9; n]);
+        assert_eq!(scheme.params_id(), FV::<Scalar>::default_2048().params_id());
+    }
+
+    #[test]
+    fn test_dyn_scheme_rejects_foreign_objects() {
+        let scheme = scheme_from_params(2048, 18014398492704769);
+        let small = scheme_from_params(16, 65537);
+        let (pk, sk) = scheme.generate_keypair();
+        let ct = scheme.encrypt(&vec![1; 2048], &pk).unwrap();
+        assert_eq!(
+            small.decrypt(&ct, &sk).unwrap_err(),
+            CupcakeError::Invalid(ValidationError::WrongDegree {
+                expected: 16,
+                actual: 2048
+            })
+        );
+
+        let mut wrong = DynCiphertext(Box::new(0u32));
+        assert_eq!(
+            scheme.add_inplace(&mut wrong, &ct).unwrap_err(),
+            CupcakeError::WrongScheme
+        );
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Ciphertexts with associated data.
+//!
+//! An [`Envelope`] carries a ciphertext together with [`AssociatedData`] in the clear: the id of
+//! the client which produced it, the epoch it belongs to and the version of the schema of its
+//! plaintext. The MAC of [`crate::authenticated`] covers both, so that an envelope cannot be
+//! replayed into another epoch or attributed to another client in transit. The associated data
+//! is not part of the ciphertext: once the envelope is opened, the ciphertext is aggregated as
+//! any other.
+//!
+//! An envelope is encoded as the length of the client id as a 4-byte little-endian integer, the
+//! client id, the epoch and the schema version as 8 and 4-byte little-endian integers, the
+//! canonical encoding of the ciphertext and the 32-byte tag.
+use crate::authenticated::{AuthenticationError, MacKey};
+use crate::integer_arith::ArithUtils;
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use std::convert::TryInto;
+
+const DOMAIN: &[u8] = b"cupcake ciphertext envelope";
+
+/// Metadata travelling with a ciphertext.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AssociatedData {
+    /// The id of the client which produced the ciphertext.
+    pub client_id: Vec<u8>,
+    /// The aggregation round the ciphertext belongs to.
+    pub epoch: u64,
+    /// The version of the layout of the plaintext.
+    pub schema_version: u32,
+}
+
+impl Serializable for AssociatedData {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + self.client_id.len());
+        bytes.extend_from_slice(&(self.client_id.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.client_id);
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        bytes.extend_from_slice(&self.schema_version.to_le_bytes());
+        bytes
+    }
+}
+
+/// A ciphertext with its associated data and a MAC of both.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Envelope<T> {
+    pub aad: AssociatedData,
+    pub ct: FVCiphertext<T>,
+    pub tag: [u8; 32],
+}
+
+impl<T> Serializable for Envelope<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The associated data, the ciphertext and the tag.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.aad.to_bytes();
+        bytes.extend_from_slice(&self.ct.to_bytes());
+        bytes.extend_from_slice(&self.tag);
+        bytes
+    }
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Put a ciphertext in an envelope with its associated data, before sending it.
+    pub fn seal(&self, ct: FVCiphertext<T>, aad: AssociatedData, key: &MacKey) -> Envelope<T> {
+        let mut message = aad.to_bytes();
+        message.extend_from_slice(&ct.to_bytes());
+        let tag = key.tag_in(DOMAIN, &message);
+        Envelope { aad, ct, tag }
+    }
+
+    /// Check the MAC of a received envelope, and return its associated data and ciphertext.
+    pub fn open(
+        &self,
+        envelope: Envelope<T>,
+        key: &MacKey,
+    ) -> Result<(AssociatedData, FVCiphertext<T>), AuthenticationError> {
+        let bytes = envelope.to_bytes();
+        let (message, tag) = bytes.split_at(bytes.len() - 32);
+        if !key.verify_in(DOMAIN, message, tag) {
+            return Err(AuthenticationError::TagMismatch);
+        }
+        self.validate(&envelope.ct)?;
+        Ok((envelope.aad, envelope.ct))
+    }
+
+    /// Check the MAC of the encoding of an envelope, then decode its associated data and
+    /// ciphertext. Only the length of the client id is read before the tag is verified.
+    pub fn envelope_from_bytes(
+        &self,
+        bytes: &[u8],
+        key: &MacKey,
+    ) -> Result<(AssociatedData, FVCiphertext<T>), AuthenticationError> {
+        let id_len = match bytes.get(..4) {
+            Some(len) => u32::from_le_bytes(len.try_into().unwrap()) as usize,
+            None => 0,
+        };
+        let expected = id_len.saturating_add(16 + self.ciphertext_byte_len() + 32);
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            }
+            .into());
+        }
+        let (message, tag) = bytes.split_at(bytes.len() - 32);
+        if !key.verify_in(DOMAIN, message, tag) {
+            return Err(AuthenticationError::TagMismatch);
+        }
+        let (client_id, rest) = message[4..].split_at(id_len);
+        let (epoch, rest) = rest.split_at(8);
+        let (schema_version, ct) = rest.split_at(4);
+        let aad = AssociatedData {
+            client_id: client_id.to_vec(),
+            epoch: u64::from_le_bytes(epoch.try_into().unwrap()),
+            schema_version: u32::from_le_bytes(schema_version.try_into().unwrap()),
+        };
+        Ok((aad, self.ciphertext_from_bytes(ct)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    fn aad() -> AssociatedData {
+        AssociatedData {
+            client_id: b"client-7".to_vec(),
+            epoch: 42,
+            schema_version: 3,
+        }
+    }
+
+    #[test]
+    fn test_envelope_roundtrip() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let key = MacKey::generate();
+        let envelope = fv.seal(fv.encrypt(&vec![1; fv.n], &pk), aad(), &key);
+
+        let (decoded, ct) = fv.envelope_from_bytes(&envelope.to_bytes(), &key).unwrap();
+        assert_eq!(decoded, aad());
+        assert_eq!(ct, envelope.ct);
+        let (opened, mut ct) = fv.open(envelope, &key).unwrap();
+        assert_eq!(opened, aad());
+
+        // the payload aggregates as a bare ciphertext.
+        fv.add_inplace(&mut ct, &fv.encrypt(&vec![2; fv.n], &pk));
+        assert_eq!(fv.decrypt(&ct, &sk), vec![3; fv.n]);
+    }
+
+    #[test]
+    fn test_associated_data_is_authenticated() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let key = MacKey::generate();
+        let envelope = fv.seal(fv.encrypt(&vec![1; fv.n], &pk), aad(), &key);
+
+        let mut replayed = envelope.clone();
+        replayed.aad.epoch += 1;
+        assert_eq!(
+            fv.open(replayed.clone(), &key).unwrap_err(),
+            AuthenticationError::TagMismatch
+        );
+        assert_eq!(
+            fv.envelope_from_bytes(&replayed.to_bytes(), &key)
+                .unwrap_err(),
+            AuthenticationError::TagMismatch
+        );
+
+        // the tags of envelopes and of authenticated ciphertexts are domain separated.
+        let act = fv.authenticate(envelope.ct.clone(), &key);
+        assert_ne!(act.tag, envelope.tag);
+
+        let mut bytes = envelope.to_bytes();
+        bytes[0] = 9;
+        assert!(matches!(
+            fv.envelope_from_bytes(&bytes, &key).unwrap_err(),
+            AuthenticationError::Malformed(ValidationError::WrongLength { .. })
+        ));
+        bytes[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(fv.envelope_from_bytes(&bytes, &key).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Fallible variants of the basic operations.
+//!
+//! The trait methods of [`FV`] trust their inputs: a plaintext of the wrong length is silently
+//! truncated, and a ciphertext in the wrong NTT domain or for other ring parameters panics. The
+//! `try_` methods below check their operands first, and return a [`CupcakeError`] instead.
+use crate::integer_arith::ArithUtils;
+use crate::observer::{observe, Operation};
+use crate::rqpoly::{DomainError, FiniteRingElt, RqPoly};
+use crate::traits::*;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The reasons for which an operation rejects its operands.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CupcakeError {
+    /// A plaintext does not have n coefficients.
+    PlaintextLength { expected: usize, actual: usize },
+    /// A secret key does not have n coefficients.
+    SecretKeyLength { expected: usize, actual: usize },
+    /// A ciphertext or public key is not well-formed for the parameters.
+    Invalid(ValidationError),
+    /// A type-erased object was created by a scheme over another scalar type.
+    WrongScheme,
+}
+
+impl fmt::Display for CupcakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CupcakeError::PlaintextLength { expected, actual } => write!(
+                f,
+                "expected a plaintext of {} coefficients, found {}",
+                expected, actual
+            ),
+            CupcakeError::SecretKeyLength { expected, actual } => write!(
+                f,
+                "expected a secret key of {} coefficients, found {}",
+                expected, actual
+            ),
+            CupcakeError::Invalid(e) => write!(f, "{}", e),
+            CupcakeError::WrongScheme => write!(f, "object of another scheme type"),
+        }
+    }
+}
+
+impl Error for CupcakeError {}
+
+impl From<ValidationError> for CupcakeError {
+    fn from(e: ValidationError) -> Self {
+        CupcakeError::Invalid(e)
+    }
+}
+
+/// The validation error of a ciphertext component rejected by a ring operation.
+fn domain_error(e: DomainError, component: usize) -> CupcakeError {
+    CupcakeError::Invalid(match e {
+        DomainError::DifferentRings => ValidationError::ParameterMismatch { component },
+        DomainError::WrongForm { ntt_form } => ValidationError::WrongNttForm {
+            component,
+            expected: ntt_form,
+        },
+    })
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Encrypt a plaintext under a public key.
+    pub fn try_encrypt(
+        &self,
+        pt: &FVPlaintext,
+        pk: &FVCiphertext<T>,
+    ) -> Result<FVCiphertext<T>, CupcakeError> {
+        self.check_plaintext(pt)?;
+        self.validate_public_key(pk)?;
+        Ok(self.encrypt(pt, pk))
+    }
+
+    /// Encrypt a plaintext under a secret key.
+    pub fn try_encrypt_sk(
+        &self,
+        pt: &FVPlaintext,
+        sk: &SecretKey<T>,
+    ) -> Result<FVCiphertext<T>, CupcakeError> {
+        self.check_plaintext(pt)?;
+        self.check_secret_key(sk)?;
+        Ok(self.encrypt_sk(pt, sk))
+    }
+
+    /// Decrypt a ciphertext.
+    pub fn try_decrypt(
+        &self,
+        ct: &FVCiphertext<T>,
+        sk: &SecretKey<T>,
+    ) -> Result<FVPlaintext, CupcakeError> {
+        self.validate(ct)?;
+        self.check_secret_key(sk)?;
+        Ok(self.decrypt(ct, sk))
+    }
+
+    /// Add a ciphertext into another.
+    pub fn try_add_inplace(
+        &self,
+        ct1: &mut FVCiphertext<T>,
+        ct2: &FVCiphertext<T>,
+    ) -> Result<(), CupcakeError> {
+        self.validate(ct1)?;
+        self.validate(ct2)?;
+        observe(Operation::Addition, || {
+            ct1.0
+                .try_add_inplace(&ct2.0)
+                .map_err(|e| domain_error(e, 0))?;
+            ct1.1
+                .try_add_inplace(&ct2.1)
+                .map_err(|e| domain_error(e, 1))
+        })
+    }
+
+    /// Add a plaintext into a ciphertext.
+    pub fn try_add_plain_inplace(
+        &self,
+        ct: &mut FVCiphertext<T>,
+        pt: &FVPlaintext,
+    ) -> Result<(), CupcakeError> {
+        self.validate(ct)?;
+        self.check_plaintext(pt)?;
+        self.add_plain_inplace(ct, pt);
+        Ok(())
+    }
+
+    /// Rerandomize a ciphertext.
+    pub fn try_rerandomize(
+        &self,
+        ct: &mut FVCiphertext<T>,
+        pk: &FVCiphertext<T>,
+    ) -> Result<(), CupcakeError> {
+        self.validate(ct)?;
+        self.validate_public_key(pk)?;
+        self.rerandomize(ct, pk);
+        Ok(())
+    }
+
+    pub(crate) fn check_plaintext(&self, pt: &FVPlaintext) -> Result<(), CupcakeError> {
+        if pt.len() != self.n {
+            return Err(CupcakeError::PlaintextLength {
+                expected: self.n,
+                actual: pt.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_secret_key(&self, sk: &SecretKey<T>) -> Result<(), CupcakeError> {
+        if sk.0.degree() != self.n {
+            return Err(CupcakeError::SecretKeyLength {
+                expected: self.n,
+                actual: sk.0.degree(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_fallible_operations() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct = fv.try_encrypt(&vec![1; fv.n], &pk).unwrap();
+        fv.try_add_plain_inplace(&mut ct, &vec![2; fv.n]).unwrap();
+        let ct2 = fv.try_encrypt_sk(&vec![3; fv.n], &sk).unwrap();
+        fv.try_add_inplace(&mut ct, &ct2).unwrap();
+        fv.try_rerandomize(&mut ct, &pk).unwrap();
+        assert_eq!(fv.try_decrypt(&ct, &sk), Ok(vec![6; fv.n]));
+
+        assert_eq!(
+            fv.try_encrypt(&vec![1; 8], &pk).err(),
+            Some(CupcakeError::PlaintextLength {
+                expected: 16,
+                actual: 8
+            })
+        );
+        assert_eq!(
+            fv.try_add_inplace(&mut ct, &pk),
+            Err(CupcakeError::Invalid(ValidationError::WrongNttForm {
+                component: 0,
+                expected: false
+            }))
+        );
+
+        let other = FV::new(32, &Scalar::new_modulus(65537));
+        let (_, other_sk) = other.generate_keypair();
+        assert_eq!(
+            fv.try_decrypt(&ct, &other_sk),
+            Err(CupcakeError::SecretKeyLength {
+                expected: 16,
+                actual: 32
+            })
+        );
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Chainable homomorphic evaluation.
+//!
+//! An [`Evaluator`] borrows a scheme and starts an [`Evaluation`], which owns the ciphertext being
+//! computed and validates every operand it is given:
+//!
+//! ```ignore
+//! let ct = fv.evaluator().add(&a, &b)?.add_plain(&p)?.rerandomize(&pk)?.finish();
+//! ```
+//!
+//! The scheme itself keeps the key generation and encryption methods.
+use crate::ciphertext::Ciphertext;
+use crate::error::CupcakeError;
+use crate::integer_arith::ArithUtils;
+use crate::pubkey::PublicKey;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::AdditiveHomomorphicScheme;
+use crate::{FVPlaintext, FV};
+use std::fmt;
+
+/// A handle for homomorphic evaluation under a scheme.
+pub struct Evaluator<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+}
+
+/// A computation in progress, holding its current ciphertext.
+pub struct Evaluation<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    ct: Ciphertext<T>,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for Evaluator<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Evaluator(n = {})", self.fv.n)
+    }
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for Evaluation<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Evaluation(n = {})", self.fv.n)
+    }
+}
+
+impl<T: ArithUtils<T>> FV<T> {
+    /// An evaluator for ciphertexts of this scheme.
+    pub fn evaluator(&self) -> Evaluator<'_, T> {
+        Evaluator { fv: self }
+    }
+}
+
+impl<'a, T> Evaluator<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Start a computation from a ciphertext.
+    pub fn start(&self, ct: &Ciphertext<T>) -> Result<Evaluation<'a, T>, CupcakeError> {
+        self.fv.validate_ciphertext(ct)?;
+        Ok(Evaluation {
+            fv: self.fv,
+            ct: ct.clone(),
+        })
+    }
+
+    /// Start a computation from the sum of two ciphertexts.
+    pub fn add(
+        &self,
+        ct1: &Ciphertext<T>,
+        ct2: &Ciphertext<T>,
+    ) -> Result<Evaluation<'a, T>, CupcakeError> {
+        self.start(ct1)?.add(ct2)
+    }
+}
+
+impl<'a, T> Evaluation<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Add a ciphertext.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, ct: &Ciphertext<T>) -> Result<Self, CupcakeError> {
+        self.fv.validate_ciphertext(ct)?;
+        self.fv.add_inplace(self.ct.as_mut(), ct.as_ref());
+        Ok(self)
+    }
+
+    /// Add a plaintext.
+    pub fn add_plain(mut self, pt: &FVPlaintext) -> Result<Self, CupcakeError> {
+        self.fv.check_plaintext(pt)?;
+        self.fv.add_plain_inplace(self.ct.as_mut(), pt);
+        Ok(self)
+    }
+
+    /// Rerandomize the ciphertext.
+    pub fn rerandomize(mut self, pk: &PublicKey<T>) -> Result<Self, CupcakeError> {
+        self.fv.validate_public_key(pk.as_pair())?;
+        self.fv.rerandomize_with(&mut self.ct, pk);
+        Ok(self)
+    }
+
+    /// The ciphertext computed so far.
+    pub fn finish(self) -> Ciphertext<T> {
+        self.ct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+    use crate::validation::ValidationError;
+
+    #[test]
+    fn test_evaluation_chain() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_typed_keypair();
+        let a = fv.encrypt_to(&vec![3; fv.n], &pk);
+        let b = fv.encrypt_to(&vec![4; fv.n], &pk);
+        let eval = fv.evaluator();
+        let ct = eval
+            .add(&a, &b)
+            .and_then(|e| e.add_plain(&vec![1; fv.n]))
+            .and_then(|e| e.add(&a))
+            .and_then(|e| e.rerandomize(&pk))
+            .unwrap()
+            .finish();
+        assert_eq!(fv.decrypt(ct.as_ref(), &sk), vec![11; fv.n]);
+
+        assert_eq!(
+            eval.start(&a)
+                .and_then(|e| e.add_plain(&vec![1; 3]))
+                .unwrap_err(),
+            CupcakeError::PlaintextLength {
+                expected: fv.n,
+                actual: 3
+            }
+        );
+        let other = FV::new(2048, &Scalar::new_modulus(12289));
+        let (other_pk, _) = other.generate_typed_keypair();
+        let foreign = other.encrypt_to(&vec![1; other.n], &other_pk);
+        assert_eq!(
+            eval.add(&a, &foreign).unwrap_err(),
+            CupcakeError::Invalid(ValidationError::ParameterMismatch { component: 0 })
+        );
+    }
+}
@@ -0,0 +1,561 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! A C ABI, with the `ffi` feature.
+//!
+//! Schemes, keys and ciphertexts are opaque handles, created by the functions below and released
+//! with the matching `_free` function. Keys and ciphertexts are exchanged with other processes in
+//! their canonical encoding, returned in a [`CupcakeBuffer`] which is released with
+//! [`cupcake_buffer_free`]. Every fallible function returns a [`CupcakeStatus`], and writes its
+//! result through an output pointer only on success. A panic never unwinds into the caller: it
+//! is reported as [`CupcakeStatus::Panic`].
+//!
+//! The declarations for C are in `include/cupcake.h`, generated from this module by cbindgen
+//! with the configuration in `cbindgen.toml`. The header defines `CUPCAKE_ABI_VERSION`, which is
+//! incremented by every incompatible change; a program should check at startup that it matches
+//! [`cupcake_abi_version`] of the library it is linked against. A shared library is built with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+use crate::error::CupcakeError;
+use crate::integer_arith::scalar::Scalar;
+use crate::pubkey::PublicKey;
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, SecretKey, FV};
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+/// The result of a call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CupcakeStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer argument is null.
+    NullPointer = 1,
+    /// A plaintext or an output buffer does not have the length of the scheme.
+    WrongLength = 2,
+    /// An encoded key or ciphertext is malformed.
+    InvalidEncoding = 3,
+    /// A key or ciphertext belongs to a scheme with other parameters.
+    ParameterMismatch = 4,
+    /// The library panicked, which indicates a bug.
+    Panic = 5,
+}
+
+impl From<CupcakeError> for CupcakeStatus {
+    fn from(e: CupcakeError) -> Self {
+        match e {
+            CupcakeError::PlaintextLength { .. } | CupcakeError::SecretKeyLength { .. } => {
+                CupcakeStatus::WrongLength
+            }
+            CupcakeError::Invalid(ValidationError::ParameterMismatch { .. })
+            | CupcakeError::WrongScheme => CupcakeStatus::ParameterMismatch,
+            CupcakeError::Invalid(_) => CupcakeStatus::InvalidEncoding,
+        }
+    }
+}
+
+impl From<ValidationError> for CupcakeStatus {
+    fn from(e: ValidationError) -> Self {
+        CupcakeError::from(e).into()
+    }
+}
+
+/// The version of the C ABI, incremented by every incompatible change to the declarations of
+/// `include/cupcake.h`.
+pub const CUPCAKE_ABI_VERSION: u32 = 1;
+
+/// A scheme.
+pub struct CupcakeScheme(FV<Scalar>);
+
+/// A public key.
+pub struct CupcakePublicKey(PublicKey<Scalar>);
+
+/// A secret key.
+pub struct CupcakeSecretKey(SecretKey<Scalar>);
+
+/// A ciphertext.
+pub struct CupcakeCiphertext(FVCiphertext<Scalar>);
+
+/// A byte buffer owned by the library.
+#[repr(C)]
+#[derive(Debug)]
+pub struct CupcakeBuffer {
+    /// The bytes, or null for an empty buffer.
+    pub data: *mut u8,
+    /// The number of bytes.
+    pub len: usize,
+}
+
+impl CupcakeBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        CupcakeBuffer { data, len }
+    }
+}
+
+/// Run `f`, turning a panic into a status.
+fn guard<F>(f: F) -> CupcakeStatus
+where
+    F: FnOnce() -> Result<(), CupcakeStatus>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => CupcakeStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => CupcakeStatus::Panic,
+    }
+}
+
+unsafe fn deref<'a, V>(p: *const V) -> Result<&'a V, CupcakeStatus> {
+    p.as_ref().ok_or(CupcakeStatus::NullPointer)
+}
+
+unsafe fn deref_mut<'a, V>(p: *mut V) -> Result<&'a mut V, CupcakeStatus> {
+    p.as_mut().ok_or(CupcakeStatus::NullPointer)
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], CupcakeStatus> {
+    if data.is_null() {
+        return Err(CupcakeStatus::NullPointer);
+    }
+    Ok(slice::from_raw_parts(data, len))
+}
+
+unsafe fn write<V>(out: *mut *mut V, value: V) -> Result<(), CupcakeStatus> {
+    if out.is_null() {
+        return Err(CupcakeStatus::NullPointer);
+    }
+    *out = Box::into_raw(Box::new(value));
+    Ok(())
+}
+
+unsafe fn free<V>(p: *mut V) {
+    if !p.is_null() {
+        drop(Box::from_raw(p));
+    }
+}
+
+/// The version of the C ABI of the library, which a program compares to the
+/// `CUPCAKE_ABI_VERSION` of the header it was compiled against.
+#[no_mangle]
+pub extern "C" fn cupcake_abi_version() -> u32 {
+    CUPCAKE_ABI_VERSION
+}
+
+/// The scheme with the default parameters.
+#[no_mangle]
+pub extern "C" fn cupcake_scheme_default() -> *mut CupcakeScheme {
+    Box::into_raw(Box::new(CupcakeScheme(FV::<Scalar>::default_2048())))
+}
+
+/// Release a scheme.
+///
+/// # Safety
+/// `scheme` must be null or a scheme returned by this library, which is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_scheme_free(scheme: *mut CupcakeScheme) {
+    free(scheme);
+}
+
+/// The number of bytes of a plaintext of the scheme, or 0 if `scheme` is null.
+///
+/// # Safety
+/// `scheme` must be null or a live scheme.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_plaintext_len(scheme: *const CupcakeScheme) -> usize {
+    scheme.as_ref().map_or(0, |s| s.0.n)
+}
+
+/// Generate a keypair.
+///
+/// # Safety
+/// `scheme` must be a live scheme, and `pk` and `sk` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_generate_keypair(
+    scheme: *const CupcakeScheme,
+    pk: *mut *mut CupcakePublicKey,
+    sk: *mut *mut CupcakeSecretKey,
+) -> CupcakeStatus {
+    guard(|| {
+        let fv = &deref(scheme)?.0;
+        if pk.is_null() || sk.is_null() {
+            return Err(CupcakeStatus::NullPointer);
+        }
+        let (public, secret) = fv.generate_typed_keypair();
+        write(pk, CupcakePublicKey(public))?;
+        write(sk, CupcakeSecretKey(secret))
+    })
+}
+
+/// Release a public key.
+///
+/// # Safety
+/// `pk` must be null or a public key returned by this library, which is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_public_key_free(pk: *mut CupcakePublicKey) {
+    free(pk);
+}
+
+/// Release a secret key, wiping it with the `zeroize` feature.
+///
+/// # Safety
+/// `sk` must be null or a secret key returned by this library, which is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_secret_key_free(sk: *mut CupcakeSecretKey) {
+    free(sk);
+}
+
+/// Release a ciphertext.
+///
+/// # Safety
+/// `ct` must be null or a ciphertext returned by this library, which is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_ciphertext_free(ct: *mut CupcakeCiphertext) {
+    free(ct);
+}
+
+/// Release a buffer returned by this library.
+///
+/// # Safety
+/// `buffer` must have been returned by this library, and is not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_buffer_free(buffer: CupcakeBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Encrypt the `pt_len` bytes at `pt` under a public key.
+///
+/// # Safety
+/// `scheme` and `pk` must be live, `pt` valid for `pt_len` reads and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_encrypt(
+    scheme: *const CupcakeScheme,
+    pk: *const CupcakePublicKey,
+    pt: *const u8,
+    pt_len: usize,
+    out: *mut *mut CupcakeCiphertext,
+) -> CupcakeStatus {
+    guard(|| {
+        let fv = &deref(scheme)?.0;
+        let pt = bytes(pt, pt_len)?.to_vec();
+        let ct = fv.try_encrypt(&pt, deref(pk)?.0.as_pair())?;
+        write(out, CupcakeCiphertext(ct))
+    })
+}
+
+/// Decrypt a ciphertext into the `out_len` bytes at `out`, which must be the plaintext length.
+///
+/// # Safety
+/// `scheme`, `sk` and `ct` must be live, and `out` valid for `out_len` writes.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_decrypt(
+    scheme: *const CupcakeScheme,
+    sk: *const CupcakeSecretKey,
+    ct: *const CupcakeCiphertext,
+    out: *mut u8,
+    out_len: usize,
+) -> CupcakeStatus {
+    guard(|| {
+        let fv = &deref(scheme)?.0;
+        if out.is_null() {
+            return Err(CupcakeStatus::NullPointer);
+        }
+        if out_len != fv.n {
+            return Err(CupcakeStatus::WrongLength);
+        }
+        let pt = fv.try_decrypt(&deref(ct)?.0, &deref(sk)?.0)?;
+        slice::from_raw_parts_mut(out, out_len).copy_from_slice(&pt);
+        Ok(())
+    })
+}
+
+/// Add the ciphertext `ct2` into `ct1`.
+///
+/// # Safety
+/// `scheme`, `ct1` and `ct2` must be live, and `ct1` not aliased.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_add_inplace(
+    scheme: *const CupcakeScheme,
+    ct1: *mut CupcakeCiphertext,
+    ct2: *const CupcakeCiphertext,
+) -> CupcakeStatus {
+    guard(|| {
+        let fv = &deref(scheme)?.0;
+        let ct2 = deref(ct2)?.0.clone();
+        Ok(fv.try_add_inplace(&mut deref_mut(ct1)?.0, &ct2)?)
+    })
+}
+
+/// Add the `pt_len` bytes at `pt` into a ciphertext.
+///
+/// # Safety
+/// `scheme` and `ct` must be live, `ct` not aliased, and `pt` valid for `pt_len` reads.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_add_plain_inplace(
+    scheme: *const CupcakeScheme,
+    ct: *mut CupcakeCiphertext,
+    pt: *const u8,
+    pt_len: usize,
+) -> CupcakeStatus {
+    guard(|| {
+        let fv = &deref(scheme)?.0;
+        let pt = bytes(pt, pt_len)?.to_vec();
+        Ok(fv.try_add_plain_inplace(&mut deref_mut(ct)?.0, &pt)?)
+    })
+}
+
+/// Rerandomize a ciphertext under a public key.
+///
+/// # Safety
+/// `scheme`, `ct` and `pk` must be live, and `ct` not aliased.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_rerandomize(
+    scheme: *const CupcakeScheme,
+    ct: *mut CupcakeCiphertext,
+    pk: *const CupcakePublicKey,
+) -> CupcakeStatus {
+    guard(|| {
+        let fv = &deref(scheme)?.0;
+        let pk = deref(pk)?.0.as_pair();
+        Ok(fv.try_rerandomize(&mut deref_mut(ct)?.0, pk)?)
+    })
+}
+
+/// The canonical encoding of a ciphertext.
+///
+/// # Safety
+/// `ct` must be live, and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_ciphertext_to_bytes(
+    ct: *const CupcakeCiphertext,
+    out: *mut CupcakeBuffer,
+) -> CupcakeStatus {
+    guard(|| {
+        let encoded = deref(ct)?.0.to_bytes();
+        *deref_mut(out)? = CupcakeBuffer::from_vec(encoded);
+        Ok(())
+    })
+}
+
+/// Decode and validate a ciphertext.
+///
+/// # Safety
+/// `scheme` must be live, `data` valid for `len` reads and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_ciphertext_from_bytes(
+    scheme: *const CupcakeScheme,
+    data: *const u8,
+    len: usize,
+    out: *mut *mut CupcakeCiphertext,
+) -> CupcakeStatus {
+    guard(|| {
+        let fv = &deref(scheme)?.0;
+        let ct = fv.ciphertext_from_bytes(bytes(data, len)?)?;
+        write(out, CupcakeCiphertext(ct))
+    })
+}
+
+/// The canonical encoding of a public key.
+///
+/// # Safety
+/// `pk` must be live, and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_public_key_to_bytes(
+    pk: *const CupcakePublicKey,
+    out: *mut CupcakeBuffer,
+) -> CupcakeStatus {
+    guard(|| {
+        let encoded = deref(pk)?.0.to_bytes();
+        *deref_mut(out)? = CupcakeBuffer::from_vec(encoded);
+        Ok(())
+    })
+}
+
+/// Decode and validate a public key.
+///
+/// # Safety
+/// `scheme` must be live, `data` valid for `len` reads and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cupcake_public_key_from_bytes(
+    scheme: *const CupcakeScheme,
+    data: *const u8,
+    len: usize,
+    out: *mut *mut CupcakePublicKey,
+) -> CupcakeStatus {
+    guard(|| {
+        let fv = &deref(scheme)?.0;
+        let pk = fv.typed_public_key_from_bytes(bytes(data, len)?)?;
+        write(out, CupcakePublicKey(pk))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_round_trip() {
+        unsafe {
+            let scheme = cupcake_scheme_default();
+            let n = cupcake_plaintext_len(scheme);
+            let mut pk = ptr::null_mut();
+            let mut sk = ptr::null_mut();
+            assert_eq!(
+                cupcake_generate_keypair(scheme, &mut pk, &mut sk),
+                CupcakeStatus::Ok
+            );
+
+            let mut buffer = CupcakeBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(
+                cupcake_public_key_to_bytes(pk, &mut buffer),
+                CupcakeStatus::Ok
+            );
+            let mut decoded = ptr::null_mut();
+            assert_eq!(
+                cupcake_public_key_from_bytes(scheme, buffer.data, buffer.len, &mut decoded),
+                CupcakeStatus::Ok
+            );
+            cupcake_buffer_free(buffer);
+
+            let pt = vec![6u8; n];
+            let mut ct = ptr::null_mut();
+            assert_eq!(
+                cupcake_encrypt(scheme, decoded, pt.as_ptr(), n, &mut ct),
+                CupcakeStatus::Ok
+            );
+            let mut buffer = CupcakeBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(
+                cupcake_ciphertext_to_bytes(ct, &mut buffer),
+                CupcakeStatus::Ok
+            );
+            let mut copy = ptr::null_mut();
+            assert_eq!(
+                cupcake_ciphertext_from_bytes(scheme, buffer.data, buffer.len, &mut copy),
+                CupcakeStatus::Ok
+            );
+            cupcake_buffer_free(buffer);
+            assert_eq!(cupcake_add_inplace(scheme, ct, copy), CupcakeStatus::Ok);
+            assert_eq!(
+                cupcake_add_plain_inplace(scheme, ct, pt.as_ptr(), n),
+                CupcakeStatus::Ok
+            );
+            assert_eq!(cupcake_rerandomize(scheme, ct, pk), CupcakeStatus::Ok);
+
+            let mut out = vec![0u8; n];
+            assert_eq!(
+                cupcake_decrypt(scheme, sk, ct, out.as_mut_ptr(), n),
+                CupcakeStatus::Ok
+            );
+            assert_eq!(out, vec![18u8; n]);
+
+            assert_eq!(
+                cupcake_encrypt(scheme, pk, pt.as_ptr(), 3, &mut ct),
+                CupcakeStatus::WrongLength
+            );
+            assert_eq!(
+                cupcake_decrypt(scheme, ptr::null(), ct, out.as_mut_ptr(), n),
+                CupcakeStatus::NullPointer
+            );
+            let mut rejected = ptr::null_mut();
+            assert_eq!(
+                cupcake_ciphertext_from_bytes(scheme, pt.as_ptr(), n, &mut rejected),
+                CupcakeStatus::InvalidEncoding
+            );
+            assert!(rejected.is_null());
+
+            cupcake_ciphertext_free(copy);
+            cupcake_ciphertext_free(ct);
+            cupcake_public_key_free(decoded);
+            cupcake_public_key_free(pk);
+            cupcake_secret_key_free(sk);
+            cupcake_scheme_free(scheme);
+        }
+    }
+
+    fn manifest_path(file: &str) -> String {
+        format!("{}/{}", env!("CARGO_MANIFEST_DIR"), file)
+    }
+
+    #[test]
+    fn test_header_is_up_to_date() {
+        let config = cbindgen::Config::from_file(manifest_path("cbindgen.toml")).unwrap();
+        let mut generated = Vec::new();
+        cbindgen::Builder::new()
+            .with_crate(env!("CARGO_MANIFEST_DIR"))
+            .with_config(config)
+            .generate()
+            .unwrap()
+            .write(&mut generated);
+        let shipped = std::fs::read(manifest_path("include/cupcake.h")).unwrap();
+        assert!(
+            generated == shipped,
+            "include/cupcake.h is stale, regenerate it with cbindgen"
+        );
+    }
+
+    // A change to the declarations of the header is a change to the ABI, which must bump
+    // CUPCAKE_ABI_VERSION. The digest below records the declarations of the current version,
+    // without the comments.
+    #[test]
+    fn test_abi_version() {
+        use sha3::{Digest, Sha3_256};
+
+        let header = std::fs::read(manifest_path("include/cupcake.h")).unwrap();
+        let define = format!("#define CUPCAKE_ABI_VERSION {}\n", CUPCAKE_ABI_VERSION);
+        assert!(String::from_utf8_lossy(&header).contains(&define));
+        assert_eq!(cupcake_abi_version(), CUPCAKE_ABI_VERSION);
+
+        let declarations: String = String::from_utf8_lossy(&header)
+            .lines()
+            .filter(|line| {
+                let line = line.trim_start();
+                !(line.starts_with("//") || line.starts_with("/*") || line.starts_with('*'))
+            })
+            .collect();
+        let digest: String = Sha3_256::digest(declarations.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(
+            (CUPCAKE_ABI_VERSION, digest.as_str()),
+            (
+                1,
+                "bf9e90e6057ee0b84418bf7796a3a6b2effa1d89c8f95623207ef559937672c5"
+            ),
+            "the header changed: bump CUPCAKE_ABI_VERSION and record the new digest"
+        );
+    }
+
+    #[test]
+    fn test_abi_layout() {
+        use std::mem::{align_of, size_of};
+
+        assert_eq!(size_of::<CupcakeBuffer>(), 2 * size_of::<usize>());
+        assert_eq!(align_of::<CupcakeBuffer>(), align_of::<usize>());
+        assert_eq!(size_of::<CupcakeStatus>(), size_of::<i32>());
+        let codes = [
+            CupcakeStatus::Ok,
+            CupcakeStatus::NullPointer,
+            CupcakeStatus::WrongLength,
+            CupcakeStatus::InvalidEncoding,
+            CupcakeStatus::ParameterMismatch,
+            CupcakeStatus::Panic,
+        ];
+        for (code, status) in codes.iter().enumerate() {
+            assert_eq!(*status as i32, code as i32);
+        }
+    }
+}
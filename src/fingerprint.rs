@@ -0,0 +1,114 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Short fingerprints identifying keys without revealing them.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::RqPoly;
+use sha3::{Digest, Sha3_256};
+use core::fmt;
+
+/// A 16-byte fingerprint of a key, computed as a truncated SHA3-256 hash of its coefficients.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(pub [u8; 16]);
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fingerprint({})", self)
+    }
+}
+
+/// Hash the coefficients of the given polynomials, prefixed by a domain separation tag.
+pub(crate) fn fingerprint<T>(domain: &[u8], polys: &[&RqPoly<T>]) -> Fingerprint
+where
+    T: ArithUtils<T> + Clone,
+{
+    let mut hasher = Sha3_256::new();
+    hasher.update(domain);
+    for poly in polys.iter() {
+        hasher.update([poly.is_ntt_form as u8]);
+        for x in poly.coeffs.iter() {
+            hasher.update(T::to_u64(x.clone()).to_le_bytes());
+        }
+    }
+    let digest = hasher.finalize();
+    let mut fp = [0u8; 16];
+    fp.copy_from_slice(&digest[..16]);
+    Fingerprint(fp)
+}
+
+/// Feeds formatted text into a hash.
+struct HashWriter(Sha3_256);
+
+impl fmt::Write for HashWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.update(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Hash the debug representations of values, prefixed by a domain separation tag, to summarize
+/// them in the Debug output of a type without bounds on its coefficients.
+pub(crate) fn fingerprint_debug<D: fmt::Debug>(domain: &[u8], values: &[D]) -> Fingerprint {
+    let mut writer = HashWriter(Sha3_256::new());
+    writer.0.update(domain);
+    for x in values.iter() {
+        // Writing into a hash never fails.
+        let _ = fmt::Write::write_fmt(&mut writer, format_args!("{:?},", x));
+    }
+    let digest = writer.0.finalize();
+    let mut fp = [0u8; 16];
+    fp.copy_from_slice(&digest[..16]);
+    Fingerprint(fp)
+}
+
+/// The fingerprint of a public key.
+pub(crate) fn public_key_fingerprint<T>(pk: &(RqPoly<T>, RqPoly<T>)) -> Fingerprint
+where
+    T: ArithUtils<T> + Clone,
+{
+    fingerprint(b"cupcake public key", &[&pk.0, &pk.1])
+}
+
+/// The fingerprint of the parameters (n, q).
+pub(crate) fn params_fingerprint(n: usize, q: u64) -> Fingerprint {
+    let mut data = (n as u64).to_le_bytes().to_vec();
+    data.extend_from_slice(&q.to_le_bytes());
+    fingerprint_bytes(b"cupcake parameters", &data)
+}
+
+/// Hash a byte string, prefixed by a domain separation tag.
+pub(crate) fn fingerprint_bytes(domain: &[u8], data: &[u8]) -> Fingerprint {
+    let mut hasher = Sha3_256::new();
+    hasher.update(domain);
+    hasher.update(data);
+    let digest = hasher.finalize();
+    let mut fp = [0u8; 16];
+    fp.copy_from_slice(&digest[..16]);
+    Fingerprint(fp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn test_fingerprint_display() {
+        let mut bytes = [0u8; 16];
+        bytes[0] = 0xab;
+        bytes[15] = 0x01;
+        let fp = Fingerprint(bytes);
+        assert_eq!(format!("{}", fp), "ab000000000000000000000000000001");
+        assert_eq!(format!("{:?}", fp), "Fingerprint(ab000000000000000000000000000001)");
+    }
+}
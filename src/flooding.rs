@@ -0,0 +1,154 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Rerandomization with a chosen statistical security level.
+//!
+//! Rerandomization hides the noise E of a ciphertext by adding flooding noise whose uniform part
+//! has half-width B. The statistical distance between the flooded noise of two ciphertexts is at
+//! most n E / (2B + 1), so a security level of λ bits requires B >= n E 2^λ / 2. E is bounded
+//! by the worst case of the static noise model, given a bound on the number of additions that
+//! produced the ciphertext. With the default parameters, the flooding noise must stay below
+//! Delta / 2, which limits λ to a few tens of bits.
+use crate::integer_arith::ArithUtils;
+use crate::noise::NoiseEstimate;
+use crate::rqpoly::{self, FiniteRingElt, RqPoly};
+use crate::traits::*;
+use crate::{FVCiphertext, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The error returned when the parameters cannot support the requested statistical security.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FloodingUnsupported {
+    /// The standard deviation of the flooding noise required for the requested security.
+    pub flooding_stdev: f64,
+    /// The worst-case noise after rerandomization with this flooding noise.
+    pub noise: f64,
+    /// The largest noise for which decryption is correct.
+    pub max_noise: f64,
+}
+
+impl fmt::Display for FloodingUnsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "flooding noise of stdev 2^{:.1} leads to noise 2^{:.1}, above the maximum 2^{:.1}",
+            self.flooding_stdev.log2(),
+            self.noise.log2(),
+            self.max_noise.log2()
+        )
+    }
+}
+
+impl Error for FloodingUnsupported {}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The standard deviation of the flooding noise which hides the noise of any ciphertext
+    /// obtained by at most `ops_bound` additions of fresh ciphertexts, up to a statistical
+    /// distance of 2^-lambda_bits. Fails if the rerandomized ciphertext would not decrypt.
+    pub fn flooding_stdev_for(
+        &self,
+        lambda_bits: u32,
+        ops_bound: u64,
+    ) -> Result<f64, FloodingUnsupported> {
+        let model = self.noise_model();
+        let input = model.sum(&model.fresh_pk(), ops_bound.saturating_add(1));
+        let (bound, flooding_stdev) = self.hiding_flooding(&input, lambda_bits);
+        let max_noise = model.max_noise();
+        if bound >= max_noise {
+            return Err(FloodingUnsupported {
+                flooding_stdev,
+                noise: bound,
+                max_noise,
+            });
+        }
+        let output = model.rerandomize_with_stdev(&input, flooding_stdev);
+        if !model.is_correct(&output) {
+            return Err(FloodingUnsupported {
+                flooding_stdev,
+                noise: output.worst_case,
+                max_noise,
+            });
+        }
+        Ok(flooding_stdev)
+    }
+
+    /// The half-width B and the standard deviation of the flooding noise which hides a noise
+    /// term with estimate `input` up to 2^-lambda_bits.
+    pub(crate) fn hiding_flooding(&self, input: &NoiseEstimate, lambda_bits: u32) -> (f64, f64) {
+        let bound = self.n as f64 * input.worst_case * 2f64.powf(lambda_bits as f64) / 2f64;
+        // the sampler uses floor(sqrt(3) * stdev) as the half-width.
+        (bound, (bound.ceil() + 1f64) / 3f64.sqrt())
+    }
+
+    /// Rerandomize a ciphertext obtained by at most `ops_bound` additions of fresh ciphertexts,
+    /// so that its noise is statistically hidden up to 2^-lambda_bits.
+    pub fn rerandomize_with_security(
+        &self,
+        ct: &mut FVCiphertext<T>,
+        pk: &FVCiphertext<T>,
+        lambda_bits: u32,
+        ops_bound: u64,
+    ) -> Result<(), FloodingUnsupported> {
+        let flooding_stdev = self.flooding_stdev_for(lambda_bits, ops_bound)?;
+        self.rerandomize_with_stdev(ct, pk, flooding_stdev);
+        Ok(())
+    }
+
+    /// Rerandomize a ciphertext with flooding noise of the given standard deviation.
+    pub(crate) fn rerandomize_with_stdev(
+        &self,
+        ct: &mut FVCiphertext<T>,
+        pk: &FVCiphertext<T>,
+        flooding_stdev: f64,
+    ) {
+        // add a public key encryption of zero.
+        let c_mask = self.encrypt_zero(pk);
+        self.add_inplace(ct, &c_mask);
+
+        // add large noise poly for noise flooding.
+        let elarge = rqpoly::randutils::sample_flooding_poly(self.context.clone(), flooding_stdev);
+        ct.1.add_inplace(&elarge);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_rerandomize_with_security() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct = fv.encrypt(&vec![1; fv.n], &pk);
+        for _ in 0..10 {
+            fv.add_inplace(&mut ct, &fv.encrypt(&vec![1; fv.n], &pk));
+        }
+        fv.rerandomize_with_security(&mut ct, &pk, 8, 10).unwrap();
+        assert_eq!(fv.decrypt(&ct, &sk), vec![11; fv.n]);
+        assert!(
+            fv.noise_budget(&ct, &sk)
+                < fv.noise_model()
+                    .worst_case_budget(&fv.noise_model().fresh_pk())
+        );
+    }
+
+    #[test]
+    fn test_unsupported_security() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair();
+        let mut ct = fv.encrypt(&vec![1; fv.n], &pk);
+        assert!(fv.flooding_stdev_for(8, 0).is_ok());
+        let err = fv
+            .rerandomize_with_security(&mut ct, &pk, 128, 0)
+            .unwrap_err();
+        assert!(err.noise > err.max_noise);
+        assert!(fv.flooding_stdev_for(8, 1 << 20).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Fuzzing inputs for the decoders of ciphertexts and public keys.
+//!
+//! [`EncodedCiphertext`] and [`EncodedPublicKey`] implement [`Arbitrary`] to produce byte
+//! strings shaped like the canonical encodings for ring degree `N`: mostly two polynomials with
+//! their NTT flag and coefficients, sometimes truncated, extended or with a corrupted byte. A
+//! fuzzer thus gets past the length checks to the validation of flags and coefficients, instead
+//! of spending its time on strings of the wrong length. A cargo-fuzz target reads
+//!
+//! ```ignore
+//! fuzz_target!(|input: EncodedCiphertext| {
+//!     let _ = FV::<Scalar>::default_2048().ciphertext_from_bytes(&input.0);
+//! });
+//! ```
+//!
+//! The decoders return an error on any malformed input, and never panic.
+use arbitrary::{Arbitrary, Unstructured};
+
+/// Coefficients are drawn below 2^54, the size of the default modulus, so that most are in
+/// range for it and some are not.
+const COEFF_MASK: u64 = (1 << 54) - 1;
+
+/// An input for [`crate::FV::ciphertext_from_bytes`], for the default degree unless `N` says
+/// otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodedCiphertext<const N: usize = 2048>(pub Vec<u8>);
+
+/// An input for [`crate::FV::public_key_from_bytes`], whose polynomials are mostly flagged in
+/// NTT form.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodedPublicKey<const N: usize = 2048>(pub Vec<u8>);
+
+impl<'a, const N: usize> Arbitrary<'a> for EncodedCiphertext<N> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_pair(u, N, false).map(EncodedCiphertext)
+    }
+}
+
+impl<'a, const N: usize> Arbitrary<'a> for EncodedPublicKey<N> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_pair(u, N, true).map(EncodedPublicKey)
+    }
+}
+
+/// The encoding of two polynomials of degree n, then possibly damaged. Exhausted input yields
+/// the intact encoding of zero.
+fn arbitrary_pair(u: &mut Unstructured, n: usize, ntt_form: bool) -> arbitrary::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(2 * (1 + 8 * n));
+    for _ in 0..2 {
+        let flag = if u.ratio(7u8, 8)? {
+            ntt_form as u8
+        } else {
+            u.arbitrary()?
+        };
+        bytes.push(flag);
+        for _ in 0..n {
+            let x = u.arbitrary::<u64>()? & COEFF_MASK;
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+    }
+    match u.int_in_range(0..=7u8)? {
+        5 => {
+            let len = u.choose_index(bytes.len())?;
+            bytes.truncate(len);
+        }
+        6 => {
+            let len = u.arbitrary_len::<u8>()?;
+            bytes.extend_from_slice(u.bytes(len)?);
+        }
+        7 => {
+            let i = u.choose_index(bytes.len())?;
+            bytes[i] ^= u.arbitrary::<u8>()? | 1;
+        }
+        _ => {}
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::Serializable;
+    use crate::FV;
+
+    fn scheme() -> FV<Scalar> {
+        FV::new(16, &Scalar::new_modulus(18014398492704769))
+    }
+
+    #[test]
+    fn test_exhausted_input_is_zero() {
+        let fv = scheme();
+        let mut u = Unstructured::new(&[]);
+        let ct = EncodedCiphertext::<16>::arbitrary(&mut u).unwrap();
+        let zero = fv.ciphertext_from_bytes(&ct.0).unwrap();
+        assert_eq!(zero.to_bytes(), ct.0);
+        let pk = EncodedPublicKey::<16>::arbitrary(&mut u).unwrap();
+        assert!(fv.public_key_from_bytes(&pk.0).is_ok());
+    }
+
+    #[test]
+    fn test_decoders_reject_arbitrary_inputs() {
+        let fv = scheme();
+        let mut state = 1u64;
+        let mut data = vec![0u8; 4096];
+        let (mut accepted, mut rejected) = (0, 0);
+        for _ in 0..200 {
+            for byte in data.iter_mut() {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                *byte = (state >> 56) as u8;
+            }
+            let mut u = Unstructured::new(&data);
+            let ct = EncodedCiphertext::<16>::arbitrary(&mut u).unwrap();
+            let pk = EncodedPublicKey::<16>::arbitrary(&mut u).unwrap();
+            for result in [
+                fv.ciphertext_from_bytes(&ct.0),
+                fv.public_key_from_bytes(&pk.0),
+                fv.ciphertext_from_bytes(&pk.0),
+            ] {
+                match result {
+                    Ok(_) => accepted += 1,
+                    Err(_) => rejected += 1,
+                }
+            }
+        }
+        // Both valid and malformed encodings are generated.
+        assert!(accepted > 0 && rejected > 0);
+    }
+}
@@ -0,0 +1,492 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Aggregation of federated learning gradients.
+//!
+//! Each client clips the values of its gradient to [-clip, clip] and quantizes them to unsigned
+//! integers of `precision_bits` bits. The plaintext modulus is 256, so an integer is split into
+//! limbs of a few bits, one per coefficient, with enough headroom that the sum of a limb over
+//! `max_clients` clients stays below 256: the limbs are added without carries, and the sums of
+//! the integers are recombined after decryption. The values are packed into as many ciphertexts
+//! as needed, value i of ciphertext k taking the coefficients [i limbs, (i + 1) limbs).
+//!
+//! A [`GradientAggregator`] adds the encrypted gradients, and the sum is decrypted with the
+//! additive shares of [`crate::threshold`] or the secret key, then dequantized into the sum of
+//! the clipped gradients, from which [`AggregatedGradient::mean`] derives their average.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::threshold::{PartialDecryption, SecretKeyShare, ThresholdError};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The reasons for which gradients cannot be aggregated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GradientError {
+    /// A gradient or an encrypted gradient does not have the expected length.
+    WrongLength { expected: usize, actual: usize },
+    /// The aggregate already holds the largest number of clients of the configuration.
+    TooManyClients { max: usize },
+    /// A ciphertext of a gradient is invalid.
+    Invalid(ValidationError),
+    /// The partial decryptions of a sum cannot be combined.
+    Threshold(ThresholdError),
+}
+
+impl fmt::Display for GradientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GradientError::WrongLength { expected, actual } => {
+                write!(f, "length {}, expected {}", actual, expected)
+            }
+            GradientError::TooManyClients { max } => {
+                write!(f, "the aggregate is limited to {} clients", max)
+            }
+            GradientError::Invalid(e) => write!(f, "{}", e),
+            GradientError::Threshold(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for GradientError {}
+
+impl From<ValidationError> for GradientError {
+    fn from(e: ValidationError) -> Self {
+        GradientError::Invalid(e)
+    }
+}
+
+impl From<ThresholdError> for GradientError {
+    fn from(e: ThresholdError) -> Self {
+        GradientError::Threshold(e)
+    }
+}
+
+/// The clipping and fixed-point encoding of the values of gradients.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuantizationConfig {
+    /// The bound on the absolute value of a value, larger values being clipped.
+    pub clip: f32,
+    /// The number of bits of a quantized value.
+    pub precision_bits: u32,
+    /// The largest number of gradients in an aggregate.
+    pub max_clients: usize,
+}
+
+impl QuantizationConfig {
+    /// A configuration clipping the values to [-clip, clip] and quantizing them to
+    /// `precision_bits` bits, for aggregates of at most `max_clients` gradients.
+    ///
+    /// # Panics
+    /// If `clip` is not positive, `precision_bits` is not in [1, 32], or `max_clients` is not in
+    /// [1, 255].
+    pub fn new(clip: f32, precision_bits: u32, max_clients: usize) -> Self {
+        assert!(clip > 0.0, "clip must be positive");
+        assert!(
+            precision_bits > 0 && precision_bits <= 32,
+            "precision out of range"
+        );
+        assert!(
+            max_clients > 0 && max_clients <= 255,
+            "client count out of range"
+        );
+        QuantizationConfig {
+            clip,
+            precision_bits,
+            max_clients,
+        }
+    }
+
+    /// The number of bits of a limb, the largest b with max_clients (2^b - 1) <= 255.
+    pub fn bits_per_limb(&self) -> u32 {
+        (1..=8)
+            .rev()
+            .find(|b| self.max_clients * ((1usize << b) - 1) <= 255)
+            .expect("one-bit limbs fit 255 clients")
+    }
+
+    /// The number of limbs, hence of coefficients, of a value.
+    pub fn limbs(&self) -> usize {
+        self.precision_bits.div_ceil(self.bits_per_limb()) as usize
+    }
+
+    /// The number of values of one ciphertext of degree `n`.
+    pub fn values_per_ciphertext(&self, n: usize) -> usize {
+        n / self.limbs()
+    }
+
+    /// The number of ciphertexts of a gradient of `len` values.
+    pub fn ciphertexts(&self, n: usize, len: usize) -> usize {
+        len.div_ceil(self.values_per_ciphertext(n))
+    }
+
+    fn levels(&self) -> f64 {
+        ((1u64 << self.precision_bits) - 1) as f64
+    }
+
+    /// The quantization of a value, clipped to [-clip, clip]. NaN is quantized as zero.
+    pub fn quantize(&self, x: f32) -> u64 {
+        let clip = self.clip as f64;
+        let x = if x.is_nan() { 0.0 } else { x as f64 };
+        let x = x.clamp(-clip, clip);
+        ((x + clip) / (2.0 * clip) * self.levels()).round() as u64
+    }
+
+    /// The sum of the clipped values of `clients` clients whose quantizations sum to `sum`.
+    pub fn dequantize_sum(&self, sum: u64, clients: usize) -> f32 {
+        let clip = self.clip as f64;
+        (sum as f64 * 2.0 * clip / self.levels() - clients as f64 * clip) as f32
+    }
+}
+
+/// The encrypted gradient of one client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncryptedGradient<T> {
+    /// The ciphertexts of the limbs of the quantized values.
+    pub ciphertexts: Vec<FVCiphertext<T>>,
+}
+
+/// The dequantized sum of the gradients of an aggregate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AggregatedGradient {
+    /// The sum of the clipped gradients.
+    pub sum: Vec<f32>,
+    /// The number of gradients.
+    pub clients: usize,
+}
+
+impl AggregatedGradient {
+    /// The average of the clipped gradients.
+    pub fn mean(&self) -> Vec<f32> {
+        self.sum.iter().map(|x| x / self.clients as f32).collect()
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The plaintexts of the limbs of a quantized gradient.
+    pub fn quantize_gradient(
+        &self,
+        config: &QuantizationConfig,
+        gradient: &[f32],
+    ) -> Vec<FVPlaintext> {
+        let (limbs, bits) = (config.limbs(), config.bits_per_limb());
+        let per_ciphertext = config.values_per_ciphertext(self.n);
+        gradient
+            .chunks(per_ciphertext)
+            .map(|values| {
+                let mut pt = vec![0u8; self.n];
+                for (i, &x) in values.iter().enumerate() {
+                    let mut q = config.quantize(x);
+                    for limb in pt[i * limbs..(i + 1) * limbs].iter_mut() {
+                        *limb = (q & ((1 << bits) - 1)) as u8;
+                        q >>= bits;
+                    }
+                }
+                pt
+            })
+            .collect()
+    }
+
+    /// The encryption of a quantized gradient.
+    pub fn encrypt_gradient(
+        &self,
+        pk: &FVCiphertext<T>,
+        config: &QuantizationConfig,
+        gradient: &[f32],
+    ) -> EncryptedGradient<T> {
+        let ciphertexts = self
+            .quantize_gradient(config, gradient)
+            .iter()
+            .map(|pt| self.encrypt(pt, pk))
+            .collect();
+        EncryptedGradient { ciphertexts }
+    }
+}
+
+/// An accumulator of the encrypted gradients of many clients.
+pub struct GradientAggregator<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    config: QuantizationConfig,
+    len: usize,
+    sum: Option<Vec<FVCiphertext<T>>>,
+    clients: usize,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for GradientAggregator<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GradientAggregator({} clients, {} values)",
+            self.clients, self.len
+        )
+    }
+}
+
+impl<'a, T> GradientAggregator<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// An empty aggregate of gradients of `len` values.
+    pub fn new(fv: &'a FV<T>, config: QuantizationConfig, len: usize) -> Self {
+        GradientAggregator {
+            fv,
+            config,
+            len,
+            sum: None,
+            clients: 0,
+        }
+    }
+
+    /// The number of gradients added.
+    pub fn clients(&self) -> usize {
+        self.clients
+    }
+
+    /// The encrypted sum, if a gradient was added.
+    pub fn sum(&self) -> Option<&[FVCiphertext<T>]> {
+        self.sum.as_deref()
+    }
+
+    /// Validate and add the gradient of a client.
+    pub fn add(&mut self, gradient: &EncryptedGradient<T>) -> Result<(), GradientError> {
+        if self.clients == self.config.max_clients {
+            return Err(GradientError::TooManyClients {
+                max: self.config.max_clients,
+            });
+        }
+        let expected = self.config.ciphertexts(self.fv.n, self.len);
+        if gradient.ciphertexts.len() != expected {
+            return Err(GradientError::WrongLength {
+                expected,
+                actual: gradient.ciphertexts.len(),
+            });
+        }
+        for ct in gradient.ciphertexts.iter() {
+            self.fv.validate(ct)?;
+        }
+        match self.sum.as_mut() {
+            Some(sum) => {
+                for (acc, ct) in sum.iter_mut().zip(gradient.ciphertexts.iter()) {
+                    self.fv.add_inplace(acc, ct);
+                }
+            }
+            None => self.sum = Some(gradient.ciphertexts.clone()),
+        }
+        self.clients += 1;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            params_id = %self.fv.params_id(),
+            clients = self.clients,
+            "added a gradient"
+        );
+        Ok(())
+    }
+
+    /// The sum of the gradients, decrypted with the secret key.
+    pub fn decrypt(&self, sk: &SecretKey<T>) -> AggregatedGradient {
+        let pts: Vec<_> = self
+            .sum()
+            .unwrap_or(&[])
+            .iter()
+            .map(|ct| self.fv.decrypt(ct, sk))
+            .collect();
+        self.dequantize(&pts)
+    }
+
+    /// The partial decryptions of the sum by the holder of one share.
+    pub fn partial_decrypt(&self, share: &SecretKeyShare<T>) -> Vec<PartialDecryption<T>> {
+        self.sum()
+            .unwrap_or(&[])
+            .iter()
+            .map(|ct| self.fv.partial_decrypt(ct, share))
+            .collect()
+    }
+
+    /// The sum of the gradients, combined from the partial decryptions of every holder.
+    pub fn combine(
+        &self,
+        partials: &[Vec<PartialDecryption<T>>],
+    ) -> Result<AggregatedGradient, GradientError> {
+        let sum = self.sum().unwrap_or(&[]);
+        if let Some(p) = partials.iter().find(|p| p.len() != sum.len()) {
+            return Err(GradientError::WrongLength {
+                expected: sum.len(),
+                actual: p.len(),
+            });
+        }
+        let pts = sum
+            .iter()
+            .enumerate()
+            .map(|(i, ct)| {
+                let column: Vec<_> = partials.iter().map(|p| p[i].clone()).collect();
+                self.fv.combine_partials(ct, &column)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.dequantize(&pts))
+    }
+
+    fn dequantize(&self, pts: &[FVPlaintext]) -> AggregatedGradient {
+        let (limbs, bits) = (self.config.limbs(), self.config.bits_per_limb());
+        let per_ciphertext = self.config.values_per_ciphertext(self.fv.n);
+        let sum = (0..self.len)
+            .map(|index| {
+                let pt = pts.get(index / per_ciphertext);
+                let i = index % per_ciphertext;
+                let q = pt.map_or(0, |pt| {
+                    pt[i * limbs..(i + 1) * limbs]
+                        .iter()
+                        .rev()
+                        .fold(0u64, |acc, &limb| (acc << bits) + limb as u64)
+                });
+                self.config.dequantize_sum(q, self.clients)
+            })
+            .collect();
+        AggregatedGradient {
+            sum,
+            clients: self.clients,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_quantization() {
+        let config = QuantizationConfig::new(1.0, 16, 15);
+        assert_eq!(config.bits_per_limb(), 4);
+        assert_eq!(config.limbs(), 4);
+        assert_eq!(config.values_per_ciphertext(2048), 512);
+        assert_eq!(config.ciphertexts(2048, 1000), 2);
+        assert_eq!(config.quantize(-3.0), 0);
+        assert_eq!(config.quantize(1.0), 65535);
+        assert_eq!(config.quantize(f32::NAN), config.quantize(0.0));
+        assert_eq!(QuantizationConfig::new(1.0, 16, 255).bits_per_limb(), 1);
+    }
+
+    #[test]
+    fn test_gradient_aggregation() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let config = QuantizationConfig::new(1.0, 16, 15);
+        let len = 600;
+        let mut aggregator = GradientAggregator::new(&fv, config, len);
+        for c in 0..15 {
+            let gradient: Vec<f32> = (0..len)
+                .map(|i| ((i + c) % 7) as f32 / 4.0 - 0.75)
+                .collect();
+            aggregator
+                .add(&fv.encrypt_gradient(&pk, &config, &gradient))
+                .unwrap();
+        }
+        let overflow = fv.encrypt_gradient(&pk, &config, &vec![0.0; len]);
+        assert_eq!(
+            aggregator.add(&overflow),
+            Err(GradientError::TooManyClients { max: 15 })
+        );
+
+        let expected: Vec<f32> = (0..len)
+            .map(|i| (0..15).map(|c| ((i + c) % 7) as f32 / 4.0 - 0.75).sum())
+            .collect();
+        let check = |aggregate: AggregatedGradient| {
+            assert_eq!(aggregate.clients, 15);
+            for (x, y) in aggregate.sum.iter().zip(expected.iter()) {
+                assert!((x - y).abs() < 15.0 / 65535.0 * 2.0, "{} != {}", x, y);
+            }
+        };
+        check(aggregator.decrypt(&sk));
+
+        let shares = fv.split_secret_key(&sk, 3);
+        let partials: Vec<_> = shares
+            .iter()
+            .map(|s| aggregator.partial_decrypt(s))
+            .collect();
+        check(aggregator.combine(&partials).unwrap());
+    }
+
+    #[test]
+    fn test_clipping_and_limbs_at_max_clients() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        // one-bit limbs, each of which sums to 255 when every client sends the largest value.
+        let config = QuantizationConfig::new(0.5, 8, 255);
+        assert_eq!(config.limbs(), 8);
+        let mut aggregator = GradientAggregator::new(&fv, config, 3);
+        let gradient = fv.encrypt_gradient(&pk, &config, &[7.0, 0.5, -9.0]);
+        for _ in 0..255 {
+            aggregator.add(&gradient).unwrap();
+        }
+        assert_eq!(
+            aggregator.add(&gradient),
+            Err(GradientError::TooManyClients { max: 255 })
+        );
+        let aggregate = aggregator.decrypt(&sk);
+        assert_eq!(aggregate.sum, vec![127.5, 127.5, -127.5]);
+        assert_eq!(aggregate.mean(), vec![0.5, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_gradient_error_paths() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let config = QuantizationConfig::new(1.0, 16, 15);
+        let mut aggregator = GradientAggregator::new(&fv, config, 600);
+
+        let short = fv.encrypt_gradient(&pk, &config, &[0.0; 10]);
+        assert_eq!(
+            aggregator.add(&short),
+            Err(GradientError::WrongLength {
+                expected: 2,
+                actual: 1
+            })
+        );
+        let mut malformed = fv.encrypt_gradient(&pk, &config, &[0.0; 600]);
+        malformed.ciphertexts[1].0.coeffs.pop();
+        assert!(matches!(
+            aggregator.add(&malformed),
+            Err(GradientError::Invalid(ValidationError::WrongDegree { .. }))
+        ));
+        assert_eq!(aggregator.clients(), 0);
+
+        aggregator
+            .add(&fv.encrypt_gradient(&pk, &config, &[0.25; 600]))
+            .unwrap();
+        let shares = fv.split_secret_key(&sk, 3);
+        let mut partials: Vec<_> = shares
+            .iter()
+            .map(|s| aggregator.partial_decrypt(s))
+            .collect();
+        partials[2].pop();
+        assert_eq!(
+            aggregator.combine(&partials),
+            Err(GradientError::WrongLength {
+                expected: 2,
+                actual: 1
+            })
+        );
+        partials.pop();
+        assert_eq!(
+            aggregator.combine(&partials),
+            Err(GradientError::Threshold(ThresholdError::MissingShares {
+                expected: 3,
+                actual: 2
+            }))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "client count out of range")]
+    fn test_too_many_clients_for_limbs() {
+        QuantizationConfig::new(1.0, 16, 256);
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! The `Aggregator` gRPC service, with the `grpc` feature.
+//!
+//! The service of `proto/cupcake.proto` standardizes how ciphertexts move over the network: a
+//! client fetches the public key of the service, submits ciphertexts encrypted under it, and the
+//! holder of the secret key fetches their homomorphic sum. Keys and ciphertexts travel in the
+//! canonical encoding of the crate, and are validated on receipt.
+//!
+//! [`AggregatorService`] is a reference server keeping the running sum in memory, to be mounted
+//! on a tonic server with [`AggregatorService::into_server`]; [`Client`] is the matching client.
+//! The sum is rerandomized before it is returned, so that its noise does not reveal how it was
+//! computed. Submissions are assumed to be fresh public key encryptions, and the server refuses
+//! them once the static noise model no longer guarantees that the rerandomized sum decrypts
+//! correctly. The generated messages and stubs are in [`proto`].
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, Serializable};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Response, Status};
+
+/// The messages and stubs generated from `proto/cupcake.proto`.
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("cupcake.v1");
+}
+
+use proto::aggregator_client::AggregatorClient;
+use proto::aggregator_server::{Aggregator, AggregatorServer};
+use proto::{
+    GetAggregateRequest, GetAggregateResponse, GetPublicKeyRequest, GetPublicKeyResponse,
+    SubmitCiphertextRequest, SubmitCiphertextResponse,
+};
+
+/// The reasons for which a call of the [`Client`] fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GrpcError {
+    /// The connection to the server failed.
+    Transport(String),
+    /// The server returned an error.
+    Status { code: Code, message: String },
+    /// The server uses other parameters.
+    ParameterMismatch,
+    /// The server returned an invalid key or ciphertext.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for GrpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GrpcError::Transport(e) => write!(f, "transport failed: {}", e),
+            GrpcError::Status { code, message } => write!(f, "{}: {}", code, message),
+            GrpcError::ParameterMismatch => write!(f, "the server uses other parameters"),
+            GrpcError::Invalid(e) => write!(f, "invalid response: {}", e),
+        }
+    }
+}
+
+impl Error for GrpcError {}
+
+impl From<Status> for GrpcError {
+    fn from(status: Status) -> Self {
+        GrpcError::Status {
+            code: status.code(),
+            message: status.message().to_string(),
+        }
+    }
+}
+
+impl From<ValidationError> for GrpcError {
+    fn from(e: ValidationError) -> Self {
+        GrpcError::Invalid(e)
+    }
+}
+
+/// A reference server, summing the ciphertexts submitted under its public key.
+pub struct AggregatorService<T: ArithUtils<T>> {
+    fv: FV<T>,
+    pk: FVCiphertext<T>,
+    max_submissions: u64,
+    aggregate: Mutex<(Option<FVCiphertext<T>>, u64)>,
+}
+
+impl<T> AggregatorService<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq + Send + Sync + 'static,
+{
+    /// A server collecting ciphertexts under `pk`.
+    pub fn new(fv: FV<T>, pk: FVCiphertext<T>) -> Self {
+        let model = fv.noise_model();
+        let fresh = model.fresh_pk();
+        let fits = |count: u64| model.is_correct(&model.rerandomize(&model.sum(&fresh, count)));
+        // the largest count which fits, by binary search on [0, 2^48).
+        let (mut low, mut high) = (0u64, 1u64 << 48);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if fits(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        AggregatorService {
+            fv,
+            pk,
+            max_submissions: if fits(1) { low } else { 0 },
+            aggregate: Mutex::new((None, 0)),
+        }
+    }
+
+    /// The number of fresh ciphertexts the aggregate can hold, while its rerandomization is
+    /// still guaranteed to decrypt correctly.
+    pub fn max_submissions(&self) -> u64 {
+        self.max_submissions
+    }
+
+    /// The tonic service, to add to a `tonic::transport::Server`.
+    pub fn into_server(self) -> AggregatorServer<Self> {
+        AggregatorServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl<T> Aggregator for AggregatorService<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq + Send + Sync + 'static,
+{
+    async fn get_public_key(
+        &self,
+        _: Request<GetPublicKeyRequest>,
+    ) -> Result<Response<GetPublicKeyResponse>, Status> {
+        Ok(Response::new(GetPublicKeyResponse {
+            public_key: self.pk.to_bytes(),
+            params_id: self.fv.params_id().0.to_vec(),
+        }))
+    }
+
+    async fn submit_ciphertext(
+        &self,
+        request: Request<SubmitCiphertextRequest>,
+    ) -> Result<Response<SubmitCiphertextResponse>, Status> {
+        let ct = self
+            .fv
+            .ciphertext_from_bytes(&request.into_inner().ciphertext)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let mut aggregate = self
+            .aggregate
+            .lock()
+            .map_err(|_| Status::internal("aggregate lock poisoned"))?;
+        if aggregate.1 >= self.max_submissions {
+            return Err(Status::resource_exhausted(format!(
+                "the aggregate holds the maximum of {} ciphertexts",
+                self.max_submissions
+            )));
+        }
+        match aggregate.0.as_mut() {
+            Some(sum) => self.fv.add_inplace(sum, &ct),
+            None => aggregate.0 = Some(ct),
+        }
+        aggregate.1 += 1;
+        Ok(Response::new(SubmitCiphertextResponse {
+            count: aggregate.1,
+        }))
+    }
+
+    async fn get_aggregate(
+        &self,
+        _: Request<GetAggregateRequest>,
+    ) -> Result<Response<GetAggregateResponse>, Status> {
+        let (sum, count) = self
+            .aggregate
+            .lock()
+            .map_err(|_| Status::internal("aggregate lock poisoned"))?
+            .clone();
+        let ciphertext = match sum {
+            Some(mut sum) => {
+                self.fv.rerandomize(&mut sum, &self.pk);
+                sum.to_bytes()
+            }
+            None => Vec::new(),
+        };
+        Ok(Response::new(GetAggregateResponse { ciphertext, count }))
+    }
+}
+
+/// A client of the `Aggregator` service.
+pub struct Client<T: ArithUtils<T>> {
+    fv: FV<T>,
+    inner: AggregatorClient<Channel>,
+}
+
+impl<T> Client<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Connect to a server, for example at `http://[::1]:50051`.
+    pub async fn connect(fv: FV<T>, endpoint: String) -> Result<Self, GrpcError> {
+        let channel = Endpoint::from_shared(endpoint)
+            .map_err(|e| GrpcError::Transport(e.to_string()))?
+            .connect()
+            .await
+            .map_err(|e| GrpcError::Transport(e.to_string()))?;
+        Ok(Client {
+            fv,
+            inner: AggregatorClient::new(channel),
+        })
+    }
+
+    /// Fetch and validate the public key of the server.
+    pub async fn public_key(&mut self) -> Result<FVCiphertext<T>, GrpcError> {
+        let response = self
+            .inner
+            .get_public_key(GetPublicKeyRequest {})
+            .await?
+            .into_inner();
+        if response.params_id != self.fv.params_id().0 {
+            return Err(GrpcError::ParameterMismatch);
+        }
+        Ok(self.fv.public_key_from_bytes(&response.public_key)?)
+    }
+
+    /// Submit a ciphertext, returning the number of ciphertexts in the aggregate.
+    pub async fn submit(&mut self, ct: &FVCiphertext<T>) -> Result<u64, GrpcError> {
+        let request = SubmitCiphertextRequest {
+            ciphertext: ct.to_bytes(),
+        };
+        Ok(self
+            .inner
+            .submit_ciphertext(request)
+            .await?
+            .into_inner()
+            .count)
+    }
+
+    /// Fetch the sum of the submitted ciphertexts and their number, or `None` if nothing was
+    /// submitted.
+    pub async fn aggregate(&mut self) -> Result<Option<(FVCiphertext<T>, u64)>, GrpcError> {
+        let response = self
+            .inner
+            .get_aggregate(GetAggregateRequest {})
+            .await?
+            .into_inner();
+        if response.count == 0 {
+            return Ok(None);
+        }
+        let sum = self.fv.ciphertext_from_bytes(&response.ciphertext)?;
+        Ok(Some((sum, response.count)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+    use tonic::transport::server::TcpIncoming;
+    use tonic::transport::Server;
+
+    #[test]
+    fn test_aggregation_over_grpc() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        runtime.block_on(async {
+            let incoming = TcpIncoming::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+            let endpoint = format!("http://{}", incoming.local_addr().unwrap());
+            let service = AggregatorService::new(fv.clone(), pk.clone()).into_server();
+            tokio::spawn(
+                Server::builder()
+                    .add_service(service)
+                    .serve_with_incoming(incoming),
+            );
+
+            let mut client = Client::connect(fv.clone(), endpoint).await.unwrap();
+            assert_eq!(client.aggregate().await, Ok(None));
+            let pk = client.public_key().await.unwrap();
+            for i in 1..=3u8 {
+                let ct = fv.encrypt(&vec![i; fv.n], &pk);
+                assert_eq!(client.submit(&ct).await, Ok(i as u64));
+            }
+            let (sum, count) = client.aggregate().await.unwrap().unwrap();
+            assert_eq!(count, 3);
+            assert_eq!(fv.decrypt(&sum, &sk), vec![6; fv.n]);
+
+            // A ciphertext of other parameters is rejected.
+            let toy = FV::new(16, &Scalar::new_modulus(65537));
+            let (toy_pk, _) = toy.generate_keypair();
+            let status = client.submit(&toy.encrypt(&vec![1; 16], &toy_pk)).await;
+            assert!(matches!(
+                status,
+                Err(GrpcError::Status {
+                    code: Code::InvalidArgument,
+                    ..
+                })
+            ));
+        });
+    }
+
+    #[test]
+    fn test_submission_limits() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let fv = FV::new(16, &Scalar::new_modulus(12583937));
+        let (pk, _) = fv.generate_keypair();
+        let service = AggregatorService::new(fv.clone(), pk.clone());
+        let max = service.max_submissions();
+        assert!(max > 0 && max < 100);
+        let submit = |service: &AggregatorService<Scalar>| {
+            let ciphertext = fv.encrypt(&vec![1; fv.n], &pk).to_bytes();
+            runtime
+                .block_on(
+                    service.submit_ciphertext(Request::new(SubmitCiphertextRequest { ciphertext })),
+                )
+                .map(|response| response.into_inner().count)
+        };
+        for i in 1..=max {
+            assert_eq!(submit(&service).unwrap(), i);
+        }
+        assert_eq!(
+            submit(&service).unwrap_err().code(),
+            Code::ResourceExhausted
+        );
+
+        // a panic while holding the aggregate turns into an internal error, not a server panic.
+        let service = AggregatorService::new(fv.clone(), pk.clone());
+        std::thread::scope(|s| {
+            let holder = s.spawn(|| {
+                let _aggregate = service.aggregate.lock().unwrap();
+                panic!("poisoning the aggregate");
+            });
+            assert!(holder.join().is_err());
+        });
+        assert_eq!(submit(&service).unwrap_err().code(), Code::Internal);
+        let status = runtime
+            .block_on(service.get_aggregate(Request::new(GetAggregateRequest {})))
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Internal);
+    }
+}
@@ -0,0 +1,341 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Histograms of categorical values aggregated over many clients.
+//!
+//! Each client encodes its values as a histogram with one slot per category, category c in
+//! coefficient c mod n of ciphertext c / n, so that more categories than n take several
+//! ciphertexts. The aggregator adds the histograms of the clients slot-wise, and the holder of
+//! the secret key decrypts the counts, from which [`heavy_hitters`] selects the frequent
+//! categories.
+//!
+//! The plaintext modulus is 256, so the count of a slot must stay below it. A
+//! [`HistogramLayout`] bounds the weight a client can put on one category, and with it the number
+//! of histograms added into one sum without overflow; the [`HistogramAggregator`] starts a new
+//! sum when this budget is spent, and adds the sums after decryption.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The largest count of a slot.
+const SLOT_BUDGET: usize = 255;
+
+/// The reasons for which a histogram cannot be encoded or aggregated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HistogramError {
+    /// A value is not a category of the layout.
+    CategoryOutOfRange { category: usize },
+    /// A client puts more than the maximum weight on a category.
+    WeightExceeded { category: usize },
+    /// A histogram does not have one ciphertext per n categories.
+    WrongLength { expected: usize, actual: usize },
+    /// A ciphertext of a histogram is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for HistogramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistogramError::CategoryOutOfRange { category } => {
+                write!(f, "category {} out of range", category)
+            }
+            HistogramError::WeightExceeded { category } => {
+                write!(f, "weight of category {} exceeds the maximum", category)
+            }
+            HistogramError::WrongLength { expected, actual } => write!(
+                f,
+                "histogram of {} ciphertexts, expected {}",
+                actual, expected
+            ),
+            HistogramError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for HistogramError {}
+
+impl From<ValidationError> for HistogramError {
+    fn from(e: ValidationError) -> Self {
+        HistogramError::Invalid(e)
+    }
+}
+
+/// The assignment of categories to the slots of ciphertexts, and the budget of a slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistogramLayout {
+    /// The number of categories.
+    pub categories: usize,
+    /// The number of slots of a ciphertext.
+    pub n: usize,
+    /// The largest weight a client can put on one category.
+    pub max_weight: usize,
+}
+
+impl HistogramLayout {
+    /// The layout of `categories` categories over ciphertexts of `n` slots.
+    ///
+    /// # Panics
+    /// If there are no categories, or `max_weight` is not in [1, 255].
+    pub fn new(categories: usize, n: usize, max_weight: usize) -> Self {
+        assert!(categories > 0, "no categories");
+        assert!(
+            max_weight > 0 && max_weight <= SLOT_BUDGET,
+            "weight out of range"
+        );
+        HistogramLayout {
+            categories,
+            n,
+            max_weight,
+        }
+    }
+
+    /// The number of ciphertexts of a histogram.
+    pub fn ciphertexts(&self) -> usize {
+        self.categories.div_ceil(self.n)
+    }
+
+    /// The ciphertext and coefficient of a category.
+    pub fn slot(&self, category: usize) -> (usize, usize) {
+        (category / self.n, category % self.n)
+    }
+
+    /// The number of histograms added into one sum without overflowing a slot.
+    pub fn clients_per_sum(&self) -> usize {
+        SLOT_BUDGET / self.max_weight
+    }
+
+    /// The number of sums holding the histograms of `clients` clients.
+    pub fn sums_for(&self, clients: usize) -> usize {
+        clients.div_ceil(self.clients_per_sum())
+    }
+}
+
+/// The encrypted histogram of one client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncryptedHistogram<T> {
+    /// The ciphertexts, each covering n categories.
+    pub ciphertexts: Vec<FVCiphertext<T>>,
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The plaintexts of the histogram of `values`, each a category counted once per
+    /// occurrence.
+    ///
+    /// # Panics
+    /// If `layout` is for another degree.
+    pub fn encode_histogram(
+        &self,
+        layout: &HistogramLayout,
+        values: &[usize],
+    ) -> Result<Vec<FVPlaintext>, HistogramError> {
+        assert_eq!(layout.n, self.n, "layout for another degree");
+        let mut pts = vec![vec![0u8; self.n]; layout.ciphertexts()];
+        for &category in values {
+            if category >= layout.categories {
+                return Err(HistogramError::CategoryOutOfRange { category });
+            }
+            let (k, j) = layout.slot(category);
+            if pts[k][j] as usize >= layout.max_weight {
+                return Err(HistogramError::WeightExceeded { category });
+            }
+            pts[k][j] += 1;
+        }
+        Ok(pts)
+    }
+
+    /// The encrypted histogram of `values`.
+    pub fn encrypt_histogram(
+        &self,
+        pk: &FVCiphertext<T>,
+        layout: &HistogramLayout,
+        values: &[usize],
+    ) -> Result<EncryptedHistogram<T>, HistogramError> {
+        let ciphertexts = self
+            .encode_histogram(layout, values)?
+            .iter()
+            .map(|pt| self.encrypt(pt, pk))
+            .collect();
+        Ok(EncryptedHistogram { ciphertexts })
+    }
+}
+
+/// An accumulator of the histograms of many clients.
+pub struct HistogramAggregator<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    layout: HistogramLayout,
+    sums: Vec<Vec<FVCiphertext<T>>>,
+    clients: usize,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for HistogramAggregator<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "HistogramAggregator({} clients, {} categories)",
+            self.clients, self.layout.categories
+        )
+    }
+}
+
+impl<'a, T> HistogramAggregator<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// An empty aggregate of histograms of `layout`.
+    ///
+    /// # Panics
+    /// If `layout` is for another degree.
+    pub fn new(fv: &'a FV<T>, layout: HistogramLayout) -> Self {
+        assert_eq!(layout.n, fv.n, "layout for another degree");
+        HistogramAggregator {
+            fv,
+            layout,
+            sums: vec![],
+            clients: 0,
+        }
+    }
+
+    /// The number of histograms added.
+    pub fn clients(&self) -> usize {
+        self.clients
+    }
+
+    /// The encrypted sums, each of at most [`HistogramLayout::clients_per_sum`] histograms.
+    pub fn sums(&self) -> &[Vec<FVCiphertext<T>>] {
+        &self.sums
+    }
+
+    /// Validate and add the histogram of a client.
+    pub fn add(&mut self, histogram: &EncryptedHistogram<T>) -> Result<(), HistogramError> {
+        let expected = self.layout.ciphertexts();
+        if histogram.ciphertexts.len() != expected {
+            return Err(HistogramError::WrongLength {
+                expected,
+                actual: histogram.ciphertexts.len(),
+            });
+        }
+        for ct in histogram.ciphertexts.iter() {
+            self.fv.validate(ct)?;
+        }
+        if self.clients.is_multiple_of(self.layout.clients_per_sum()) {
+            self.sums.push(histogram.ciphertexts.clone());
+        } else {
+            let sum = self.sums.last_mut().expect("a sum per started batch");
+            for (acc, ct) in sum.iter_mut().zip(histogram.ciphertexts.iter()) {
+                self.fv.add_inplace(acc, ct);
+            }
+        }
+        self.clients += 1;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            params_id = %self.fv.params_id(),
+            clients = self.clients,
+            "added a histogram"
+        );
+        Ok(())
+    }
+
+    /// Rerandomize the sums under `pk`, before they are sent to the holder of the secret key.
+    pub fn rerandomize(&mut self, pk: &FVCiphertext<T>) {
+        for ct in self.sums.iter_mut().flatten() {
+            self.fv.rerandomize(ct, pk);
+        }
+    }
+
+    /// The count of each category, decrypted with the secret key.
+    pub fn counts(&self, sk: &SecretKey<T>) -> Vec<u64> {
+        let mut counts = vec![0u64; self.layout.categories];
+        for sum in self.sums.iter() {
+            let pts: Vec<_> = sum.iter().map(|ct| self.fv.decrypt(ct, sk)).collect();
+            for (category, count) in counts.iter_mut().enumerate() {
+                let (k, j) = self.layout.slot(category);
+                *count += pts[k][j] as u64;
+            }
+        }
+        counts
+    }
+}
+
+/// The categories counted at least `threshold` times, most frequent first.
+pub fn heavy_hitters(counts: &[u64], threshold: u64) -> Vec<(usize, u64)> {
+    let mut hitters: Vec<(usize, u64)> = counts
+        .iter()
+        .cloned()
+        .enumerate()
+        .filter(|&(_, count)| count >= threshold)
+        .collect();
+    hitters.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    hitters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_histogram_aggregation() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        // Categories over 2 ciphertexts, with 2 votes per category and client, so that a sum
+        // holds the histograms of 127 clients.
+        let layout = HistogramLayout::new(fv.n + 8, fv.n, 2);
+        let popular = fv.n + 3;
+        assert_eq!(layout.ciphertexts(), 2);
+        assert_eq!(layout.slot(popular), (1, 3));
+        assert_eq!(layout.clients_per_sum(), 127);
+        assert_eq!(layout.sums_for(200), 2);
+
+        let mut aggregator = HistogramAggregator::new(&fv, layout);
+        for i in 0..200 {
+            let values = [i % 39, popular, popular];
+            let histogram = fv.encrypt_histogram(&pk, &layout, &values).unwrap();
+            aggregator.add(&histogram).unwrap();
+        }
+        assert_eq!(aggregator.clients(), 200);
+        assert_eq!(aggregator.sums().len(), 2);
+        aggregator.rerandomize(&pk);
+        let counts = aggregator.counts(&sk);
+        assert_eq!(counts[5], 5);
+        assert_eq!(
+            heavy_hitters(&counts, 6),
+            vec![(popular, 400), (0, 6), (1, 6), (2, 6), (3, 6), (4, 6)]
+        );
+    }
+
+    #[test]
+    fn test_histogram_rejections() {
+        let fv = FV::new(16, &Scalar::new_modulus(18014398492704769));
+        let layout = HistogramLayout::new(20, fv.n, 1);
+        assert_eq!(
+            fv.encode_histogram(&layout, &[20]),
+            Err(HistogramError::CategoryOutOfRange { category: 20 })
+        );
+        assert_eq!(
+            fv.encode_histogram(&layout, &[3, 3]),
+            Err(HistogramError::WeightExceeded { category: 3 })
+        );
+        let mut aggregator = HistogramAggregator::new(&fv, layout);
+        let (pk, _) = fv.generate_keypair();
+        let other = fv
+            .encrypt_histogram(&pk, &HistogramLayout::new(40, fv.n, 1), &[0])
+            .unwrap();
+        assert_eq!(
+            aggregator.add(&other),
+            Err(HistogramError::WrongLength {
+                expected: 2,
+                actual: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Hybrid encryption of large payloads.
+//!
+//! The FV ciphertext acts as a key encapsulation mechanism: a fresh 32-byte key is encrypted in
+//! its first [`KEY_SLOTS`] coefficients, and the payload is encrypted under AES-256-GCM with a
+//! key derived from it. The remaining n - KEY_SLOTS coefficients carry metadata such as counters,
+//! which stay homomorphic: they can be updated by adding plaintexts which are zero on the key
+//! slots, and the ciphertext can be rerandomized, without touching the payload.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::*;
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use rand::RngCore;
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+/// Number of coefficients of the FV plaintext holding the wrapped key.
+pub const KEY_SLOTS: usize = 32;
+
+/// A payload encrypted under AES-GCM, together with the FV encryption of its key and metadata.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HybridCiphertext<T> {
+    /// The FV ciphertext of the key and of the metadata slots.
+    pub kem: FVCiphertext<T>,
+    /// The AES-GCM ciphertext of the payload, including the tag.
+    pub payload: Vec<u8>,
+}
+
+/// The reasons for which hybrid encryption or decryption can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HybridError {
+    /// The ring degree n leaves no metadata slot after the KEY_SLOTS key coefficients.
+    RingTooSmall { n: usize },
+    /// More metadata slots than the n - KEY_SLOTS available.
+    TooManySlots { max: usize, actual: usize },
+    /// The payload does not authenticate under the decrypted key.
+    Authentication,
+    /// The FV ciphertext is malformed.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for HybridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HybridError::RingTooSmall { n } => write!(
+                f,
+                "a ring of degree {} cannot hold a {}-byte key and metadata",
+                n, KEY_SLOTS
+            ),
+            HybridError::TooManySlots { max, actual } => write!(
+                f,
+                "{} metadata slots requested, at most {} available",
+                actual, max
+            ),
+            HybridError::Authentication => write!(f, "payload authentication failed"),
+            HybridError::Invalid(e) => write!(f, "invalid key encapsulation: {}", e),
+        }
+    }
+}
+
+impl Error for HybridError {}
+
+impl From<ValidationError> for HybridError {
+    fn from(e: ValidationError) -> Self {
+        HybridError::Invalid(e)
+    }
+}
+
+/// Derive the AES key from the wrapped key. Every wrapped key is fresh, so the nonce is fixed.
+fn payload_cipher(key: &[u8]) -> Aes256Gcm {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"cupcake hybrid key");
+    hasher.update(key);
+    let mut derived = hasher.finalize();
+    let cipher = Aes256Gcm::new(&derived);
+    wipe(&mut derived[..]);
+    cipher
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Encrypt a payload under a fresh AES-GCM key, wrapped in an FV ciphertext together with
+    /// the metadata `slots`, which occupy the coefficients after the key.
+    pub fn encrypt_hybrid(
+        &self,
+        payload: &[u8],
+        slots: &[u8],
+        pk: &FVCiphertext<T>,
+    ) -> Result<HybridCiphertext<T>, HybridError> {
+        self.check_ring_size()?;
+        let max = self.n.saturating_sub(KEY_SLOTS);
+        if slots.len() > max {
+            return Err(HybridError::TooManySlots {
+                max,
+                actual: slots.len(),
+            });
+        }
+        let mut pt: FVPlaintext = vec![0; self.n];
+        with_rng(|rng| rng.fill_bytes(&mut pt[..KEY_SLOTS]));
+        pt[KEY_SLOTS..KEY_SLOTS + slots.len()].copy_from_slice(slots);
+
+        let cipher = payload_cipher(&pt[..KEY_SLOTS]);
+        let kem = self.encrypt(&pt, pk);
+        wipe(&mut pt);
+        let payload = cipher
+            .encrypt(Nonce::from_slice(&[0u8; 12]), payload)
+            .expect("AES-GCM encryption of an in-memory payload");
+        Ok(HybridCiphertext { kem, payload })
+    }
+
+    /// Decrypt a hybrid ciphertext, returning the payload and the n - KEY_SLOTS metadata slots.
+    pub fn decrypt_hybrid(
+        &self,
+        ct: &HybridCiphertext<T>,
+        sk: &SecretKey<T>,
+    ) -> Result<(Vec<u8>, FVPlaintext), HybridError> {
+        self.check_ring_size()?;
+        self.validate(&ct.kem)?;
+        let mut pt = self.decrypt(&ct.kem, sk);
+        let cipher = payload_cipher(&pt[..KEY_SLOTS]);
+        let slots = pt[KEY_SLOTS..].to_vec();
+        wipe(&mut pt);
+        let payload = cipher
+            .decrypt(Nonce::from_slice(&[0u8; 12]), ct.payload.as_ref())
+            .map_err(|_| HybridError::Authentication)?;
+        Ok((payload, slots))
+    }
+
+    fn check_ring_size(&self) -> Result<(), HybridError> {
+        if self.n <= KEY_SLOTS {
+            return Err(HybridError::RingTooSmall { n: self.n });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_hybrid_roundtrip() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let payload = vec![42u8; 100_000];
+        let mut ct = fv.encrypt_hybrid(&payload, &[1, 2, 3], &pk).unwrap();
+
+        // increment the first counter homomorphically.
+        let mut increment = vec![0; fv.n];
+        increment[KEY_SLOTS] = 1;
+        fv.add_plain_inplace(&mut ct.kem, &increment);
+        fv.rerandomize(&mut ct.kem, &pk);
+
+        let (decrypted, slots) = fv.decrypt_hybrid(&ct, &sk).unwrap();
+        assert_eq!(decrypted, payload);
+        assert_eq!(&slots[..4], &[2, 2, 3, 0]);
+        assert_eq!(slots.len(), fv.n - KEY_SLOTS);
+    }
+
+    #[test]
+    fn test_hybrid_errors() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        assert_eq!(
+            fv.encrypt_hybrid(b"x", &vec![0; fv.n], &pk).unwrap_err(),
+            HybridError::TooManySlots {
+                max: fv.n - KEY_SLOTS,
+                actual: fv.n
+            }
+        );
+
+        let mut ct = fv.encrypt_hybrid(b"payload", &[], &pk).unwrap();
+        ct.payload[0] ^= 1;
+        assert_eq!(
+            fv.decrypt_hybrid(&ct, &sk).unwrap_err(),
+            HybridError::Authentication
+        );
+
+        let mut short = fv.encrypt_hybrid(b"payload", &[], &pk).unwrap();
+        short.kem.0.coeffs.pop();
+        assert_eq!(
+            fv.decrypt_hybrid(&short, &sk).unwrap_err(),
+            HybridError::Invalid(ValidationError::WrongDegree {
+                expected: fv.n,
+                actual: fv.n - 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_hybrid_ring_too_small() {
+        let big = FV::<Scalar>::default_2048();
+        let (pk, _) = big.generate_keypair();
+        let ct = big.encrypt_hybrid(b"payload", &[], &pk).unwrap();
+
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        assert_eq!(
+            fv.encrypt_hybrid(b"payload", &[], &pk).unwrap_err(),
+            HybridError::RingTooSmall { n: 16 }
+        );
+        assert_eq!(
+            fv.decrypt_hybrid(&ct, &sk).unwrap_err(),
+            HybridError::RingTooSmall { n: 16 }
+        );
+    }
+}
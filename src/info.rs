@@ -0,0 +1,101 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Introspection of the parameters of a scheme.
+use crate::integer_arith::ArithUtils;
+use crate::FV;
+
+/// The plaintext modulus t of the scheme: plaintext slots are bytes.
+pub const PLAINTEXT_MODULUS: u64 = 256;
+
+// The largest log2(q) for which a ternary secret of degree n reaches 128, 192 and 256 bits of
+// classical security, from the table of the Homomorphic Encryption Standard (2018).
+const SECURITY_TABLE: [(usize, [u32; 3]); 6] = [
+    (1024, [27, 19, 14]),
+    (2048, [54, 37, 29]),
+    (4096, [109, 75, 58]),
+    (8192, [218, 152, 118]),
+    (16384, [438, 305, 237]),
+    (32768, [881, 611, 476]),
+];
+
+/// A summary of the parameters of a scheme.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SchemeInfo {
+    /// The degree of the ring.
+    pub n: usize,
+    /// The number of bits of the ciphertext modulus q.
+    pub modulus_bits: u32,
+    /// The plaintext modulus t.
+    pub plaintext_modulus: u64,
+    /// The standard deviation of the encryption noise.
+    pub stdev: f64,
+    /// The standard deviation of the rerandomization noise.
+    pub flooding_stdev: f64,
+    /// Whether polynomials are multiplied with the NTT.
+    pub ntt_enabled: bool,
+    /// The estimated classical security level, 128, 192 or 256 bits, or `None` if the
+    /// parameters reach none of them or n is not a power of two between 1024 and 32768.
+    pub security_bits: Option<u32>,
+}
+
+/// The estimated security level of a ternary secret of degree n under a modulus of the given
+/// number of bits.
+fn security_bits(n: usize, modulus_bits: u32) -> Option<u32> {
+    let (_, bounds) = SECURITY_TABLE.iter().find(|(degree, _)| *degree == n)?;
+    [256, 192, 128]
+        .iter()
+        .zip(bounds.iter().rev())
+        .find(|(_, &bound)| modulus_bits <= bound)
+        .map(|(&level, _)| level)
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// A summary of the parameters of this scheme.
+    pub fn describe(&self) -> SchemeInfo {
+        let modulus_bits = 64 - T::to_u64(self.q.clone()).leading_zeros();
+        SchemeInfo {
+            n: self.n,
+            modulus_bits,
+            plaintext_modulus: PLAINTEXT_MODULUS,
+            stdev: self.stdev,
+            flooding_stdev: self.flooding_stdev,
+            ntt_enabled: self.context.is_ntt_enabled,
+            security_bits: security_bits(self.n, modulus_bits),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_describe() {
+        let info = FV::<Scalar>::default_2048().describe();
+        assert_eq!(info.n, 2048);
+        assert_eq!(info.modulus_bits, 54);
+        assert_eq!(info.plaintext_modulus, 256);
+        assert_eq!(info.stdev, 3.2);
+        assert!(info.ntt_enabled);
+        assert_eq!(info.security_bits, Some(128));
+
+        let toy = FV::new(16, &Scalar::new_modulus(65537)).describe();
+        assert_eq!(toy.modulus_bits, 17);
+        assert_eq!(toy.security_bits, None);
+    }
+
+    #[test]
+    fn test_security_bits() {
+        assert_eq!(security_bits(2048, 29), Some(256));
+        assert_eq!(security_bits(2048, 30), Some(192));
+        assert_eq!(security_bits(4096, 100), Some(128));
+        assert_eq!(security_bits(1024, 54), None);
+        assert_eq!(security_bits(3000, 20), None);
+    }
+}
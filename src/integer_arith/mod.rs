@@ -2,50 +2,93 @@
 //
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
+//! Modular arithmetic on the coefficients of ring elements.
+//!
+//! [`ArithUtils`] is implemented by [`scalar::Scalar`], a 64-bit integer. Moduli are created with
+//! [`scalar::Scalar::new_modulus`], which precomputes the Barrett constant floor(2^128 / q) so
+//! that reductions and modular multiplications need no hardware division. Unless stated
+//! otherwise, the `_mod` functions expect operands already reduced modulo q and a modulus q below
+//! 2^63, and return a result reduced modulo q.
 pub mod scalar;
 
 #[cfg(feature = "bigint")]
 pub mod bigint;
 
-use rand::StdRng;
+use rand::RngCore;
+
+/// Bound on scalar types which may hold secret values. With the `zeroize` feature, such types
+/// must be zeroizable so that secret keys and temporaries can be wiped.
+#[cfg(feature = "zeroize")]
+pub trait SecretScalar: zeroize::Zeroize {}
+#[cfg(feature = "zeroize")]
+impl<T: zeroize::Zeroize> SecretScalar for T {}
+
+/// Bound on scalar types which may hold secret values. With the `zeroize` feature, such types
+/// must be zeroizable so that secret keys and temporaries can be wiped.
+#[cfg(not(feature = "zeroize"))]
+pub trait SecretScalar {}
+#[cfg(not(feature = "zeroize"))]
+impl<T> SecretScalar for T {}
+
 /// The trait for utility functions related to scalar-like types.
-pub trait ArithUtils<T> {
+pub trait ArithUtils<T>: SecretScalar {
+    /// a mod q, for any a.
     fn modulus(a: &T, q: &T) -> T;
 
+    /// 2a, without reduction.
     fn double(a: &T) -> T;
 
-    // sample a value in [0, bound-1]
+    /// A uniform value in [0, bound - 1], from the thread-local generator.
+    #[cfg(feature = "std")]
     fn sample_blw(bound: &T) -> T;
 
-    fn sample_below_from_rng(bound: &T, rng: &mut StdRng) -> T;
+    /// A uniform value in [0, bound - 1], from the given generator.
+    fn sample_below_from_rng<R: RngCore>(bound: &T, rng: &mut R) -> T;
 
+    /// The value 1.
     fn one() -> T {
         Self::from_u32_raw(1u32)
     }
 
+    /// The value 0.
     fn zero() -> T {
         Self::from_u32_raw(0u32)
     }
 
+    /// a + b mod q.
     fn add_mod(a: &T, b: &T, q: &T) -> T;
+    /// a - b mod q.
     fn sub_mod(a: &T, b: &T, q: &T) -> T;
+    /// a * b mod q.
     fn mul_mod(a: &T, b: &T, q: &T) -> T;
+    /// The inverse of a modulo q, for a invertible modulo q.
     fn inv_mod(a: &T, q: &T) -> T;
 
+    /// a mod q.
     fn from_u32(a: u32, q: &T) -> T;
 
+    /// a^b mod c.
     fn pow_mod(a: &T, b: &T, c: &T) -> T;
 
+    /// a + b, without reduction.
     fn add(a: &T, b: &T) -> T;
 
+    /// a - b, without reduction, for a >= b.
     fn sub(a: &T, b: &T) -> T;
 
+    /// floor(a / b).
     fn div(a: &T, b: &T) -> T;
 
+    /// floor(a / q) for a modulus q, in time independent of a.
+    fn div_by_modulus(a: &T, q: &T) -> T;
+
+    /// a * b, without reduction.
     fn mul(a: &T, b: &T) -> T;
 
-    // conversion
+    /// The value a, unreduced.
     fn from_u32_raw(a: u32) -> T;
+    /// The value a, unreduced.
     fn from_u64_raw(a: u64) -> T;
+    /// The value of a as an integer.
     fn to_u64(a: T) -> u64;
 }
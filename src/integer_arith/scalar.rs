@@ -3,11 +3,11 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 use crate::integer_arith::ArithUtils;
-use modinverse::modinverse;
-use rand::rngs::StdRng;
-use rand::FromEntropy;
+#[cfg(feature = "std")]
+use crate::rng::with_rng;
+use alloc::vec;
+use alloc::vec::Vec;
 use rand::RngCore;
-pub use std::sync::Arc;
 
 /// The ScalarContext class contains useful auxilliary information for fast modular reduction against a Scalar instance.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -65,6 +65,24 @@ impl Scalar {
             bit_count: 64 - q.leading_zeros() as usize,
         }
     }
+
+    /// The value of this scalar.
+    pub fn value(&self) -> u64 {
+        self.rep
+    }
+
+    /// Whether this scalar was created by `new_modulus`, and can thus be used as the modulus of
+    /// `mul_mod` and `pow_mod`.
+    pub fn is_modulus(&self) -> bool {
+        self.context.is_some()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Scalar {
+    fn zeroize(&mut self) {
+        self.rep.zeroize();
+    }
 }
 
 impl PartialEq for Scalar {
@@ -82,29 +100,38 @@ impl ArithUtils<Scalar> for Scalar {
         Scalar::new(a.rep / b.rep)
     }
 
+    /// Constant time: the quotient is estimated with the Barrett ratio of q, and then corrected
+    /// with a masked conditional increment, so no hardware division is used.
+    fn div_by_modulus(a: &Scalar, q: &Scalar) -> Scalar {
+        let ratio = q.context.as_ref().unwrap().barrett_ratio;
+        Scalar::new(Scalar::_barret_quotient(a.rep, ratio, q.rep))
+    }
+
+    /// Constant time: the reduction is a masked conditional subtraction.
     fn add_mod(a: &Scalar, b: &Scalar, q: &Scalar) -> Scalar {
-        let mut sum = a.rep + b.rep;
-        if sum >= q.rep {
-            sum -= q.rep;
-        }
-        Scalar::new(sum)
+        let sum = a.rep + b.rep;
+        Scalar::new(Scalar::_reduce_once(sum, q.rep))
     }
 
+    /// Constant time: the reduction is a masked conditional addition.
     fn sub_mod(a: &Scalar, b: &Scalar, q: &Scalar) -> Scalar {
         Scalar::_sub_mod(a, b, q.rep)
     }
 
+    /// Constant time, using Barrett reduction. Panics unless q was created by `new_modulus`.
     fn mul_mod(a: &Scalar, b: &Scalar, q: &Scalar) -> Scalar {
         let res = Scalar::_barret_multiply(a, b, q.context.as_ref().unwrap().barrett_ratio, q.rep);
         Scalar::new(res)
     }
 
+    /// Not constant time: this uses the extended Euclidean algorithm, and is meant for public
+    /// values such as roots of unity.
     fn inv_mod(a: &Scalar, q: &Scalar) -> Scalar {
         Scalar::_inv_mod(a, q.rep)
     }
 
     fn from_u32(a: u32, q: &Scalar) -> Scalar {
-        Scalar::new((a as u64) % q.rep)
+        Self::modulus(&Scalar::new(a as u64), q)
     }
 
     fn from_u32_raw(a: u32) -> Scalar {
@@ -115,15 +142,17 @@ impl ArithUtils<Scalar> for Scalar {
         Scalar::new(a)
     }
 
+    /// Constant time: all 64 bits of the exponent are processed, and the multiplication by the
+    /// current power is always computed and then selected with a mask. Panics unless q was
+    /// created by `new_modulus`.
     fn pow_mod(base: &Scalar, b: &Scalar, q: &Scalar) -> Scalar {
-        let bits: Vec<bool> = b.get_bits();
         let mut res = Self::one();
         res = Self::modulus(&res, q);
         let mut pow = Scalar::new(base.rep);
-        for bit in bits.iter() {
-            if *bit {
-                res = Self::mul_mod(&res, &pow, q);
-            }
+        for i in 0..64 {
+            let mask = ((b.rep >> i) & 1).wrapping_neg();
+            let prod = Self::mul_mod(&res, &pow, q);
+            res = Scalar::new(Scalar::_select(mask, prod.rep, res.rep));
             pow = Self::mul_mod(&pow, &pow, q);
         }
         res
@@ -133,6 +162,7 @@ impl ArithUtils<Scalar> for Scalar {
         Scalar::new(a.rep << 1)
     }
 
+    #[cfg(feature = "std")]
     fn sample_blw(upper_bound: &Scalar) -> Scalar {
         loop {
             let n = Self::_sample(upper_bound.bit_count);
@@ -143,7 +173,7 @@ impl ArithUtils<Scalar> for Scalar {
     }
 
     // sample below using a given rng.
-    fn sample_below_from_rng(upper_bound: &Scalar, rng: &mut StdRng) -> Self {
+    fn sample_below_from_rng<R: RngCore>(upper_bound: &Scalar, rng: &mut R) -> Self {
         loop {
             let n = Self::_sample_form_rng(upper_bound.bit_count, rng);
             if n < upper_bound.rep {
@@ -152,8 +182,16 @@ impl ArithUtils<Scalar> for Scalar {
         }
     }
 
+    /// Constant time for a modulus created by `new_modulus`, using Barrett reduction; falls back
+    /// to a hardware division otherwise.
     fn modulus(a: &Scalar, q: &Scalar) -> Scalar {
-        Scalar::new(a.rep % q.rep)
+        match q.context {
+            Some(ref context) => {
+                let quotient = Scalar::_barret_quotient(a.rep, context.barrett_ratio, q.rep);
+                Scalar::new(a.rep.wrapping_sub(quotient.wrapping_mul(q.rep)))
+            }
+            None => Scalar::new(a.rep % q.rep),
+        }
     }
 
     fn mul(a: &Scalar, b: &Scalar) -> Scalar {
@@ -171,11 +209,13 @@ impl ArithUtils<Scalar> for Scalar {
 
 impl Scalar {
     /// Bit length of this scalar.
+    #[cfg(all(test, feature = "std"))]
     fn bit_length(&self) -> usize {
         64 - self.rep.leading_zeros() as usize
     }
 
     /// Return a vector of booleans representing the bits of this scalar, starting from the least significant bit.
+    #[cfg(all(test, feature = "std"))]
     fn get_bits(&self) -> Vec<bool> {
         let len = self.bit_length();
         let mut res = vec![];
@@ -187,7 +227,7 @@ impl Scalar {
         res
     }
 
-    fn _sample_form_rng(bit_size: usize, rng: &mut StdRng) -> u64 {
+    fn _sample_form_rng<R: RngCore>(bit_size: usize, rng: &mut R) -> u64 {
         let bytes = (bit_size - 1) / 8 + 1;
         let mut buf: Vec<u8> = vec![0; bytes];
         rng.fill_bytes(&mut buf);
@@ -202,19 +242,39 @@ impl Scalar {
         a
     }
 
+    #[cfg(feature = "std")]
     fn _sample(bit_size: usize) -> u64 {
-        let mut rng = StdRng::from_entropy();
-        Self::_sample_form_rng(bit_size, &mut rng)
+        with_rng(|rng| Self::_sample_form_rng(bit_size, rng))
     }
 
     fn _sub_mod(a: &Scalar, b: &Scalar, q: u64) -> Self {
-        let diff;
-        if a.rep >= b.rep {
-            diff = a.rep - b.rep;
-        } else {
-            diff = a.rep + q - b.rep;
-        }
-        Scalar::new(diff)
+        let (diff, borrow) = a.rep.overflowing_sub(b.rep);
+        let mask = (borrow as u64).wrapping_neg();
+        Scalar::new(diff.wrapping_add(q & mask))
+    }
+
+    /// Return a if mask is all ones, and b if mask is zero, without branching.
+    fn _select(mask: u64, a: u64, b: u64) -> u64 {
+        (a & mask) | (b & !mask)
+    }
+
+    /// Map a in [0, 2q) to a mod q without branching.
+    fn _reduce_once(a: u64, q: u64) -> u64 {
+        let (diff, borrow) = a.overflowing_sub(q);
+        let mask = (borrow as u64).wrapping_neg();
+        diff.wrapping_add(q & mask)
+    }
+
+    /// Compute floor(a / q) from ratio = floor(2^128 / q) without branching.
+    fn _barret_quotient(a: u64, ratio: (u64, u64), q: u64) -> u64 {
+        // w = floor(a * ratio / 2^128), which is floor(a / q) or floor(a / q) - 1.
+        let lo = Scalar::_multiply_u64(a, ratio.0);
+        let mid = Scalar::_multiply_u64(a, ratio.1);
+        let (_, carry) = Scalar::_add_u64(mid.0, lo.1);
+        let w = mid.1 + carry as u64;
+        let rem = a.wrapping_sub(w.wrapping_mul(q));
+        let (_, borrow) = rem.overflowing_sub(q);
+        w + (!borrow as u64)
     }
 
     fn _slowmul_mod(a: &Scalar, b: &Scalar, q: u64) -> Self {
@@ -222,14 +282,40 @@ impl Scalar {
         Scalar::new((res % (q as u128)) as u64)
     }
 
+    /// The full product of a and b as (low word, high word), with a native 64x64-bit
+    /// multiplication.
+    #[cfg(target_pointer_width = "64")]
     fn _multiply_u64(a: u64, b: u64) -> (u64, u64) {
         let res = (a as u128) * (b as u128);
         (res as u64, (res >> 64) as u64)
     }
 
+    /// The full product of a and b as (low word, high word). On 32-bit targets, u128 arithmetic
+    /// is emulated with library calls, so the product is computed from 32-bit limbs instead.
+    #[cfg(not(target_pointer_width = "64"))]
+    fn _multiply_u64(a: u64, b: u64) -> (u64, u64) {
+        Scalar::_multiply_u64_limbs(a, b)
+    }
+
+    /// The full product of a and b as (low word, high word), from four 32x32-bit products.
+    #[cfg_attr(target_pointer_width = "64", allow(dead_code))]
+    fn _multiply_u64_limbs(a: u64, b: u64) -> (u64, u64) {
+        let (a0, a1) = (a & 0xffff_ffff, a >> 32);
+        let (b0, b1) = (b & 0xffff_ffff, b >> 32);
+        let p00 = a0 * b0;
+        let p01 = a0 * b1;
+        let p10 = a1 * b0;
+        let p11 = a1 * b1;
+
+        // The middle column collects at most three 32-bit words, so it cannot overflow.
+        let mid = (p00 >> 32) + (p01 & 0xffff_ffff) + (p10 & 0xffff_ffff);
+        let lo = (p00 & 0xffff_ffff) | (mid << 32);
+        let hi = p11 + (p01 >> 32) + (p10 >> 32) + (mid >> 32);
+        (lo, hi)
+    }
+
     fn _add_u64(a: u64, b: u64) -> (u64, bool) {
-        let res = (a as u128 + b as u128) as u64;
-        (res, res < a)
+        a.overflowing_add(b)
     }
 
     fn _barret_reduce(a: (u64, u64), ratio: (u64, u64), q: u64) -> u64 {
@@ -261,22 +347,27 @@ impl Scalar {
         // let low = Scalar::multiply_u64(w, q).0;
         let low = w.wrapping_mul(q);
 
-        let mut res;
-        if a.0 >= low {
-            res = a.0 - low;
-        } else {
-            // res = a.0 + 2^64 - low.
-            res = a.0 + (!low) + 1;
-        }
-
-        if res >= q {
-            res -= q;
-        }
-        res
+        // res = a.0 - low mod 2^64, which lies in [0, 2q).
+        let res = a.0.wrapping_sub(low);
+        Scalar::_reduce_once(res, q)
     }
 
+    /// The inverse of a modulo q with the extended Euclidean algorithm. Panics unless a is
+    /// invertible modulo q.
     fn _inv_mod(a: &Scalar, q: u64) -> Self {
-        Scalar::new(modinverse(a.rep as i128, q as i128).unwrap() as u64)
+        let (mut r0, mut r1) = (q as i128, (a.rep % q) as i128);
+        let (mut t0, mut t1) = (0i128, 1i128);
+        while r1 != 0 {
+            let quotient = r0 / r1;
+            let r2 = r0 - quotient * r1;
+            r0 = r1;
+            r1 = r2;
+            let t2 = t0 - quotient * t1;
+            t0 = t1;
+            t1 = t2;
+        }
+        assert!(r0 == 1, "value is not invertible modulo q");
+        Scalar::new(t0.rem_euclid(q as i128) as u64)
     }
 
     fn _barret_multiply(a: &Scalar, b: &Scalar, ratio: (u64, u64), q: u64) -> u64 {
@@ -285,7 +376,7 @@ impl Scalar {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     #[test]
@@ -337,6 +428,95 @@ mod tests {
         assert_eq!(c.rep, 12288);
     }
 
+    #[test]
+    fn test_add_sub_mod() {
+        let q = Scalar::new_modulus(12289);
+        let a = Scalar::new(12000);
+        let b = Scalar::new(1000);
+        assert_eq!(Scalar::add_mod(&a, &b, &q).rep, 711);
+        assert_eq!(Scalar::add_mod(&b, &b, &q).rep, 2000);
+        assert_eq!(Scalar::sub_mod(&b, &a, &q).rep, 1289);
+        assert_eq!(Scalar::sub_mod(&a, &b, &q).rep, 11000);
+    }
+
+    #[test]
+    fn test_div_by_modulus() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let cases = [0u64, 1, q.rep - 1, q.rep, q.rep + 1, 256 * (q.rep - 1) + q.rep / 2, u64::MAX];
+        for a in cases.iter() {
+            let expected = a / q.rep;
+            assert_eq!(Scalar::div_by_modulus(&Scalar::new(*a), &q).rep, expected);
+        }
+        for _ in 0..1000 {
+            let a = Scalar::_sample(64);
+            assert_eq!(Scalar::div_by_modulus(&Scalar::new(a), &q).rep, a / q.rep);
+        }
+    }
+
+    #[test]
+    fn test_modulus_barrett() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let plain = Scalar::new(18014398492704769u64);
+        for _ in 0..1000 {
+            let a = Scalar::new(Scalar::_sample(64));
+            assert_eq!(Scalar::modulus(&a, &q), Scalar::modulus(&a, &plain));
+        }
+        assert_eq!(Scalar::from_u32(12290, &Scalar::new_modulus(12289)).rep, 1);
+    }
+
+    // The limb product is the one used on 32-bit targets; check it against u128 on the host.
+    #[test]
+    fn test_multiply_u64_limbs() {
+        let edges = [0, 1, 0xffff_ffff, 1 << 32, u64::MAX - 1, u64::MAX];
+        for &a in edges.iter() {
+            for &b in edges.iter() {
+                let res = (a as u128) * (b as u128);
+                let expected = (res as u64, (res >> 64) as u64);
+                assert_eq!(Scalar::_multiply_u64_limbs(a, b), expected);
+            }
+        }
+        for _ in 0..1000 {
+            let (a, b) = (Scalar::_sample(64), Scalar::_sample(64));
+            let res = (a as u128) * (b as u128);
+            assert_eq!(
+                Scalar::_multiply_u64_limbs(a, b),
+                (res as u64, (res >> 64) as u64)
+            );
+        }
+
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let a = Scalar::new(q.rep - 1);
+        assert_eq!(Scalar::mul_mod(&a, &a, &q), Scalar::_slowmul_mod(&a, &a, q.rep));
+    }
+
+    // Statistical timing tests in the style of dudect. They are sensitive to the load of the
+    // machine, so they are not run by default: use `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_mul_mod_timing() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let fixed = Scalar::new(1);
+        let t = crate::timing::fixed_vs_random_t(
+            || fixed.clone(),
+            || Scalar::sample_blw(&q),
+            |a| Scalar::mul_mod(&a, &a, &q),
+        );
+        assert!(t.abs() < crate::timing::LEAKAGE_THRESHOLD, "t = {}", t);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_add_sub_mod_timing() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let big = Scalar::new(q.rep - 1);
+        let t = crate::timing::fixed_vs_random_t(
+            || big.clone(),
+            || Scalar::sample_blw(&q),
+            |a| (Scalar::add_mod(&a, &big, &q), Scalar::sub_mod(&a, &big, &q)),
+        );
+        assert!(t.abs() < crate::timing::LEAKAGE_THRESHOLD, "t = {}", t);
+    }
+
     #[test]
     fn test_inverse() {
         let q = Scalar::new(11);
@@ -353,6 +533,17 @@ mod tests {
         assert_eq!(a.rep, 5);
     }
 
+    #[test]
+    fn test_public_accessors() {
+        let q = Scalar::new_modulus(65537);
+        assert!(q.is_modulus());
+        assert_eq!(q.value(), 65537);
+        let a = Scalar::new(3);
+        assert!(!a.is_modulus());
+        let inv = Scalar::inv_mod(&a, &q);
+        assert_eq!(Scalar::mul_mod(&a, &inv, &q).value(), 1);
+    }
+
     #[test]
     fn test_pow_mod() {
         let q = Scalar::new_modulus(11);
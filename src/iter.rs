@@ -0,0 +1,92 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Lazy bulk operations.
+//!
+//! The methods below take any iterator of plaintexts or ciphertexts, owned or borrowed, and
+//! process one element at a time, so that a pipeline can stream a large dataset through
+//! encryption, aggregation and decryption without collecting it into vectors.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption};
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::borrow::Borrow;
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Encrypt each plaintext under a public key, as the iterator is consumed.
+    pub fn encrypt_iter<'a, I>(
+        &'a self,
+        pts: I,
+        pk: &'a FVCiphertext<T>,
+    ) -> impl Iterator<Item = FVCiphertext<T>> + 'a
+    where
+        I: IntoIterator,
+        I::IntoIter: 'a,
+        I::Item: Borrow<FVPlaintext>,
+    {
+        pts.into_iter().map(move |pt| self.encrypt(pt.borrow(), pk))
+    }
+
+    /// Decrypt each ciphertext, as the iterator is consumed.
+    pub fn decrypt_iter<'a, I>(
+        &'a self,
+        cts: I,
+        sk: &'a SecretKey<T>,
+    ) -> impl Iterator<Item = FVPlaintext> + 'a
+    where
+        I: IntoIterator,
+        I::IntoIter: 'a,
+        I::Item: Borrow<FVCiphertext<T>>,
+    {
+        cts.into_iter().map(move |ct| self.decrypt(ct.borrow(), sk))
+    }
+
+    /// The sum of the ciphertexts, or `None` if there are none.
+    pub fn sum_ciphertexts<I>(&self, cts: I) -> Option<FVCiphertext<T>>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<FVCiphertext<T>>,
+    {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("sum_ciphertexts", params_id = %self.params_id()).entered();
+        let mut cts = cts.into_iter();
+        let mut sum = cts.next()?.borrow().clone();
+        for ct in cts {
+            self.add_inplace(&mut sum, ct.borrow());
+        }
+        Some(sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_streaming_pipeline() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let pts = (1..=10u8).map(|i| vec![i; fv.n]);
+        let sum = fv.sum_ciphertexts(fv.encrypt_iter(pts, &pk)).unwrap();
+        assert_eq!(fv.decrypt(&sum, &sk), vec![55; fv.n]);
+
+        let pts = vec![vec![1; fv.n], vec![2; fv.n]];
+        let cts: Vec<_> = fv.encrypt_iter(&pts, &pk).collect();
+        let decrypted: Vec<_> = fv.decrypt_iter(&cts, &sk).collect();
+        assert_eq!(decrypted, pts);
+        assert_eq!(
+            fv.decrypt(&fv.sum_ciphertexts(&cts).unwrap(), &sk),
+            vec![3; fv.n]
+        );
+        assert!(fv
+            .sum_ciphertexts(Vec::<FVCiphertext<Scalar>>::new())
+            .is_none());
+    }
+}
@@ -0,0 +1,280 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Known-answer test vectors, with the `kat` feature.
+//!
+//! A [`KatVector`] records, for a key seed, an encryption seed and a plaintext, the secret key
+//! and the digest of the public key generated from the key seed, and the ciphertext of the
+//! plaintext encrypted with the randomness of the encryption seed. A [`KatSuite`] is a list of
+//! vectors for one set of parameters, stored as JSON with all byte strings in hex.
+//!
+//! [`FV::verify_kat_suite`] checks that this version of the crate regenerates every key and
+//! ciphertext of a suite bit for bit, and decrypts every ciphertext to its plaintext. Another
+//! implementation of the scheme, which cannot reproduce the sampling of this crate, can still
+//! check the decryption of the recorded ciphertexts under the recorded secret keys. The secret
+//! key is encoded with two bits per coefficient, 0, 1 and 2 standing for 0, 1 and -1, the
+//! coefficient i at bits 2(i mod 4) of byte i / 4.
+//!
+//! The vectors for the default parameters are shipped in `kats/fv_default_2048.json`.
+use crate::derive::KeySeed;
+use crate::integer_arith::ArithUtils;
+use crate::packed::PackedTernary;
+use crate::rng::with_seeded_rng;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{PKEncryption, SKEncryption, Serializable};
+use crate::{FVPlaintext, SecretKey, FV};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+/// A known-answer test vector.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KatVector {
+    /// The seed of the keypair.
+    #[serde(with = "hex")]
+    pub key_seed: Vec<u8>,
+    /// The seed of the randomness of the encryption.
+    #[serde(with = "hex")]
+    pub encryption_seed: Vec<u8>,
+    /// The plaintext.
+    #[serde(with = "hex")]
+    pub plaintext: Vec<u8>,
+    /// The secret key, packed with two bits per coefficient.
+    #[serde(with = "hex")]
+    pub secret_key: Vec<u8>,
+    /// SHA3-256 of the encoding of the public key.
+    #[serde(with = "hex")]
+    pub public_key_sha3: Vec<u8>,
+    /// The encoding of the ciphertext.
+    #[serde(with = "hex")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// The known-answer test vectors of a set of parameters.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KatSuite {
+    /// The degree of the ring.
+    pub n: usize,
+    /// The ciphertext modulus.
+    pub q: u64,
+    /// The vectors.
+    pub vectors: Vec<KatVector>,
+}
+
+impl KatSuite {
+    /// The JSON encoding of the suite.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a suite is always serializable")
+    }
+
+    /// Parse a suite from JSON.
+    pub fn from_json(json: &str) -> Result<Self, KatError> {
+        serde_json::from_str(json).map_err(|e| KatError::Parse(e.to_string()))
+    }
+}
+
+/// The reasons for which a known-answer test fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KatError {
+    /// The JSON encoding is malformed.
+    Parse(String),
+    /// The suite was recorded for other parameters.
+    WrongParameters { n: usize, q: u64 },
+    /// A seed does not have 32 bytes, or the secret key or ciphertext of a vector is malformed.
+    Invalid { vector: usize },
+    /// A regenerated or decrypted value differs from the recorded one.
+    Mismatch { vector: usize, field: &'static str },
+}
+
+impl fmt::Display for KatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KatError::Parse(e) => write!(f, "malformed known-answer suite: {}", e),
+            KatError::WrongParameters { n, q } => {
+                write!(f, "known-answer suite recorded for n = {} and q = {}", n, q)
+            }
+            KatError::Invalid { vector } => {
+                write!(f, "known-answer vector {} is malformed", vector)
+            }
+            KatError::Mismatch { vector, field } => {
+                write!(f, "{} of known-answer vector {} differs", field, vector)
+            }
+        }
+    }
+}
+
+impl Error for KatError {}
+
+fn seed(bytes: &[u8]) -> Option<[u8; 32]> {
+    let mut seed = [0u8; 32];
+    if bytes.len() != seed.len() {
+        return None;
+    }
+    seed.copy_from_slice(bytes);
+    Some(seed)
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Record the known answers of a key seed, an encryption seed and a plaintext.
+    pub fn generate_kat(
+        &self,
+        key_seed: &[u8; 32],
+        encryption_seed: &[u8; 32],
+        pt: &FVPlaintext,
+    ) -> KatVector {
+        let (pk, sk) = self.generate_keypair_from_seed(&KeySeed::new(*key_seed));
+        let ct = with_seeded_rng(*encryption_seed, || self.encrypt(pt, &pk));
+        KatVector {
+            key_seed: key_seed.to_vec(),
+            encryption_seed: encryption_seed.to_vec(),
+            plaintext: pt.clone(),
+            secret_key: sk.0.unmasked_bytes(),
+            public_key_sha3: Sha3_256::digest(pk.to_bytes()).to_vec(),
+            ciphertext: ct.to_bytes(),
+        }
+    }
+
+    /// A suite of known answers for this scheme.
+    pub fn kat_suite(&self, vectors: Vec<KatVector>) -> KatSuite {
+        KatSuite {
+            n: self.n,
+            q: T::to_u64(self.q.clone()),
+            vectors,
+        }
+    }
+
+    /// Check that every key and ciphertext of a suite is regenerated bit for bit, and that every
+    /// ciphertext decrypts to its plaintext.
+    pub fn verify_kat_suite(&self, suite: &KatSuite) -> Result<(), KatError> {
+        if suite.n != self.n || suite.q != T::to_u64(self.q.clone()) {
+            return Err(KatError::WrongParameters {
+                n: suite.n,
+                q: suite.q,
+            });
+        }
+        for (i, vector) in suite.vectors.iter().enumerate() {
+            self.verify_kat(i, vector)?;
+        }
+        Ok(())
+    }
+
+    fn verify_kat(&self, i: usize, vector: &KatVector) -> Result<(), KatError> {
+        let invalid = KatError::Invalid { vector: i };
+        let mismatch = |field| Err(KatError::Mismatch { vector: i, field });
+        let key_seed = seed(&vector.key_seed).ok_or_else(|| invalid.clone())?;
+        let encryption_seed = seed(&vector.encryption_seed).ok_or_else(|| invalid.clone())?;
+
+        // The recorded ciphertext decrypts under the recorded secret key.
+        let packed = PackedTernary::from_unmasked_bytes(self.n, &vector.secret_key)
+            .ok_or_else(|| invalid.clone())?;
+        let sk = SecretKey(packed, None);
+        let ct = self
+            .ciphertext_from_bytes(&vector.ciphertext)
+            .map_err(|_| invalid.clone())?;
+        if self.decrypt(&ct, &sk) != vector.plaintext {
+            return mismatch("plaintext");
+        }
+
+        // The keys and the ciphertext are regenerated from the seeds.
+        let regenerated = self.generate_kat(&key_seed, &encryption_seed, &vector.plaintext);
+        if regenerated.secret_key != vector.secret_key {
+            return mismatch("secret key");
+        }
+        if regenerated.public_key_sha3 != vector.public_key_sha3 {
+            return mismatch("public key");
+        }
+        if regenerated.ciphertext != vector.ciphertext {
+            return mismatch("ciphertext");
+        }
+        Ok(())
+    }
+}
+
+// Byte strings as lowercase hex in JSON.
+mod hex {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom("odd number of hex digits"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| D::Error::custom("invalid hex digit"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    const SHIPPED: &str = include_str!("../kats/fv_default_2048.json");
+
+    #[test]
+    fn test_shipped_known_answers() {
+        let fv = FV::<Scalar>::default_2048();
+        let suite = KatSuite::from_json(SHIPPED).unwrap();
+        assert!(!suite.vectors.is_empty());
+        assert_eq!(fv.verify_kat_suite(&suite), Ok(()));
+        assert_eq!(KatSuite::from_json(&suite.to_json()).unwrap(), suite);
+    }
+
+    #[test]
+    fn test_known_answer_mismatches() {
+        let fv = FV::<Scalar>::default_2048();
+        let vector = fv.generate_kat(&[1; 32], &[2; 32], &vec![7; fv.n]);
+        let mut suite = fv.kat_suite(vec![vector]);
+        assert_eq!(fv.verify_kat_suite(&suite), Ok(()));
+
+        suite.vectors[0].encryption_seed[0] ^= 1;
+        assert_eq!(
+            fv.verify_kat_suite(&suite),
+            Err(KatError::Mismatch {
+                vector: 0,
+                field: "ciphertext"
+            })
+        );
+        suite.vectors[0].plaintext[0] ^= 1;
+        assert_eq!(
+            fv.verify_kat_suite(&suite),
+            Err(KatError::Mismatch {
+                vector: 0,
+                field: "plaintext"
+            })
+        );
+        suite.vectors[0].key_seed.pop();
+        assert_eq!(
+            fv.verify_kat_suite(&suite),
+            Err(KatError::Invalid { vector: 0 })
+        );
+
+        let toy = FV::new(16, &Scalar::new_modulus(65537));
+        assert_eq!(
+            toy.verify_kat_suite(&suite),
+            Err(KatError::WrongParameters {
+                n: 2048,
+                q: 18014398492704769
+            })
+        );
+        assert!(KatSuite::from_json("{\"n\": 1}").is_err());
+    }
+}
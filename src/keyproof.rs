@@ -0,0 +1,402 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Proofs that a public key is well-formed.
+//!
+//! A public key (a, b) is well-formed if b = a * s + e for a ternary s and a small e. The key
+//! owner proves this with a Fiat-Shamir transformed sigma protocol with rejection sampling, in
+//! the style of Lyubashevsky's signatures:
+//!
+//! 1. sample masks y_s, y_e uniform in [-B_s, B_s] and [-B_e, B_e], and let w = a * y_s + y_e;
+//! 2. derive a sparse challenge c with κ coefficients in {-1, 1} from a hash of (a, b, w);
+//! 3. respond with z_s = y_s + c * s and z_e = y_e + c * e over the integers, restarting unless
+//!    both are within B_s - κ and B_e - κE, where E bounds the coefficients of e, so that the
+//!    responses do not depend on the secrets.
+//!
+//! The verifier recomputes w = a * z_s + z_e - c * b and checks the challenge and the bounds. As
+//! usual for lattice proofs, the statement proven is relaxed: the key owner knows short
+//! (s', e') and a short c' with c' * b = a * s' + e'. This rules out keys whose b is uniform or
+//! whose noise is large enough to make decryption fail, provided B_e is much smaller than q, as
+//! it is for the default parameters.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::{FiniteRingElt, RqPoly, NTT};
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, SecretKey, FV};
+use rand::RngCore;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Digest, Sha3_256, Shake128};
+use std::error::Error;
+use std::fmt;
+
+/// Number of non-zero coefficients of a challenge.
+const CHALLENGE_WEIGHT: usize = 60;
+
+/// A non-interactive proof that a public key is well-formed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKeyProof {
+    /// The hash from which the challenge is expanded.
+    pub challenge: [u8; 32],
+    /// The response for the secret key, with coefficients in [-(B_s - κ), B_s - κ].
+    pub z_s: Vec<i64>,
+    /// The response for the error, with coefficients in [-(B_e - κE), B_e - κE].
+    pub z_e: Vec<i64>,
+}
+
+/// The reasons for which a proof can be rejected or cannot be produced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyProofError {
+    /// The public key or the ciphertext is not well-formed for the scheme.
+    Malformed(ValidationError),
+    /// A response does not have n coefficients.
+    WrongLength,
+    /// A response coefficient exceeds its bound.
+    ResponseOutOfRange,
+    /// The challenge does not match the commitment recomputed from the responses.
+    ChallengeMismatch,
+    /// The secret key does not match the public key.
+    InvalidWitness,
+    /// The noise of the ciphertext is too large for its decryption to be proven.
+    NoiseTooLarge,
+}
+
+impl fmt::Display for KeyProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyProofError::Malformed(e) => write!(f, "malformed input: {}", e),
+            KeyProofError::WrongLength => write!(f, "proof response has the wrong length"),
+            KeyProofError::ResponseOutOfRange => write!(f, "proof response is out of range"),
+            KeyProofError::ChallengeMismatch => write!(f, "proof challenge does not match"),
+            KeyProofError::InvalidWitness => {
+                write!(f, "the secret key does not match the public key")
+            }
+            KeyProofError::NoiseTooLarge => {
+                write!(f, "ciphertext noise too large for a decryption proof")
+            }
+        }
+    }
+}
+
+impl Error for KeyProofError {}
+
+impl From<ValidationError> for KeyProofError {
+    fn from(e: ValidationError) -> Self {
+        KeyProofError::Malformed(e)
+    }
+}
+
+/// The public bounds of a proof for given parameters.
+pub(crate) struct ProofBounds {
+    pub(crate) weight: usize,
+    pub(crate) error: i64,
+    pub(crate) mask_s: i64,
+    pub(crate) mask_e: i64,
+}
+
+impl ProofBounds {
+    pub(crate) fn response_s(&self) -> i64 {
+        self.mask_s - self.weight as i64
+    }
+
+    pub(crate) fn response_e(&self) -> i64 {
+        self.mask_e - self.weight as i64 * self.error
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Prove that a public key is well-formed, using its secret key. The public key must have
+    /// been generated with `generate_keypair`.
+    pub fn prove_public_key(
+        &self,
+        pk: &FVCiphertext<T>,
+        sk: &SecretKey<T>,
+    ) -> Result<PublicKeyProof, KeyProofError> {
+        self.validate_public_key(pk)?;
+        let bounds = self.proof_bounds();
+        let (mut s, mut e) = self.key_witness(pk, sk, &bounds)?;
+
+        let proof = loop {
+            let mut y_s = sample_centered(self.n, bounds.mask_s);
+            let mut y_e = sample_centered(self.n, bounds.mask_e);
+            let mut w = (self.poly_multiplier)(&pk.0, &self.to_ring(&y_s));
+            w.add_inplace(&self.to_ring(&y_e));
+            let challenge = challenge_hash(b"cupcake public key proof", &[&pk.0, &pk.1, &w], &[]);
+            let c = expand_challenge(&challenge, self.n, bounds.weight);
+
+            let mut z_s = add_sparse_product(&y_s, &c, &s);
+            let mut z_e = add_sparse_product(&y_e, &c, &e);
+            wipe(&mut y_s);
+            wipe(&mut y_e);
+            if within(&z_s, bounds.response_s()) && within(&z_e, bounds.response_e()) {
+                break PublicKeyProof {
+                    challenge,
+                    z_s,
+                    z_e,
+                };
+            }
+            wipe(&mut z_s);
+            wipe(&mut z_e);
+        };
+        wipe(&mut s);
+        wipe(&mut e);
+        Ok(proof)
+    }
+
+    /// The secret s and the error e = b - a * s of a public key, as centered integers. The
+    /// caller should wipe them after use.
+    pub(crate) fn key_witness(
+        &self,
+        pk: &FVCiphertext<T>,
+        sk: &SecretKey<T>,
+        bounds: &ProofBounds,
+    ) -> Result<(Vec<i64>, Vec<i64>), KeyProofError> {
+        let mut s_poly = sk.0.unpack(self.context.clone());
+        let mut s = self.centered(&s_poly);
+        wipe(&mut s_poly);
+        let mut e_poly = pk.1.clone();
+        if e_poly.is_ntt_form() {
+            e_poly.inverse_transform();
+        }
+        let mut a_s = self.multiply_secret(&pk.0, sk);
+        e_poly.sub_inplace(&a_s);
+        wipe(&mut a_s);
+        let mut e = self.centered(&e_poly);
+        wipe(&mut e_poly);
+        if e.iter().any(|x| x.abs() > bounds.error) {
+            wipe(&mut s);
+            wipe(&mut e);
+            return Err(KeyProofError::InvalidWitness);
+        }
+        Ok((s, e))
+    }
+
+    /// Verify a proof that a public key is well-formed, before encrypting to it.
+    pub fn verify_public_key(
+        &self,
+        pk: &FVCiphertext<T>,
+        proof: &PublicKeyProof,
+    ) -> Result<(), KeyProofError> {
+        self.validate_public_key(pk)?;
+        let bounds = self.proof_bounds();
+        if proof.z_s.len() != self.n || proof.z_e.len() != self.n {
+            return Err(KeyProofError::WrongLength);
+        }
+        if !within(&proof.z_s, bounds.response_s()) || !within(&proof.z_e, bounds.response_e()) {
+            return Err(KeyProofError::ResponseOutOfRange);
+        }
+
+        // w = a * z_s + z_e - c * b.
+        let c = self.to_ring(&dense_challenge(&proof.challenge, self.n, bounds.weight));
+        let mut w = (self.poly_multiplier)(&pk.0, &self.to_ring(&proof.z_s));
+        w.add_inplace(&self.to_ring(&proof.z_e));
+        w.sub_inplace(&(self.poly_multiplier)(&pk.1, &c));
+
+        let challenge = challenge_hash(b"cupcake public key proof", &[&pk.0, &pk.1, &w], &[]);
+        if challenge == proof.challenge {
+            Ok(())
+        } else {
+            Err(KeyProofError::ChallengeMismatch)
+        }
+    }
+
+    pub(crate) fn proof_bounds(&self) -> ProofBounds {
+        let weight = CHALLENGE_WEIGHT.min(self.n);
+        let error = self.noise_model().fresh_sk().worst_case as i64;
+        // with masks of 2nκ times the largest coefficient of c * s (resp. c * e), each response
+        // is accepted with probability about exp(-1/2).
+        let mask_s = 2 * (self.n * weight) as i64;
+        ProofBounds {
+            weight,
+            error,
+            mask_s,
+            mask_e: mask_s * error,
+        }
+    }
+
+    /// The coefficients of a polynomial in coefficient form, as residues in (-q/2, q/2].
+    pub(crate) fn centered(&self, poly: &RqPoly<T>) -> Vec<i64> {
+        let q = T::to_u64(self.q.clone());
+        poly.coeffs
+            .iter()
+            .map(|x| {
+                let v = T::to_u64(x.clone());
+                if v > q / 2 {
+                    v as i64 - q as i64
+                } else {
+                    v as i64
+                }
+            })
+            .collect()
+    }
+
+    /// The polynomial with the given integer coefficients, reduced modulo q.
+    pub(crate) fn to_ring(&self, v: &[i64]) -> RqPoly<T> {
+        let coeffs = v
+            .iter()
+            .map(|x| {
+                let abs = T::modulus(&T::from_u64_raw(x.unsigned_abs()), &self.q);
+                if *x < 0 {
+                    T::sub_mod(&T::zero(), &abs, &self.q)
+                } else {
+                    abs
+                }
+            })
+            .collect();
+        RqPoly::from_coeffs(self.context.clone(), coeffs)
+    }
+}
+
+/// Sample n integers uniformly in [-bound, bound].
+pub(crate) fn sample_centered(n: usize, bound: i64) -> Vec<i64> {
+    let range = 2 * bound as u64 + 1;
+    let mask = range.next_power_of_two() - 1;
+    with_rng(|rng| {
+        (0..n)
+            .map(|_| loop {
+                let x = rng.next_u64() & mask;
+                if x < range {
+                    break x as i64 - bound;
+                }
+            })
+            .collect()
+    })
+}
+
+pub(crate) fn within(v: &[i64], bound: i64) -> bool {
+    v.iter().all(|x| x.abs() <= bound)
+}
+
+/// Compute y + c * v in Z[x]/(x^n + 1), for a sparse c given by its (index, negative) pairs.
+pub(crate) fn add_sparse_product(y: &[i64], c: &[(usize, bool)], v: &[i64]) -> Vec<i64> {
+    let n = y.len();
+    let mut res = y.to_vec();
+    for (j, negative) in c.iter() {
+        for (i, x) in v.iter().enumerate() {
+            // x^(i + j) = -x^(i + j - n) when i + j >= n.
+            let wraps = i + j >= n;
+            let term = if *negative != wraps { -x } else { *x };
+            res[(i + j) % n] += term;
+        }
+    }
+    res
+}
+
+/// Hash the statement and the commitments into a challenge seed.
+pub(crate) fn challenge_hash<T>(domain: &[u8], polys: &[&RqPoly<T>], extra: &[u8]) -> [u8; 32]
+where
+    T: ArithUtils<T> + Clone,
+{
+    let mut hasher = Sha3_256::new();
+    Digest::update(&mut hasher, domain);
+    Digest::update(&mut hasher, extra);
+    for poly in polys.iter() {
+        Digest::update(&mut hasher, [poly.is_ntt_form as u8]);
+        for x in poly.coeffs.iter() {
+            Digest::update(&mut hasher, T::to_u64(x.clone()).to_le_bytes());
+        }
+    }
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&hasher.finalize());
+    seed
+}
+
+/// The challenge expanded from a seed, as a dense vector of coefficients.
+pub(crate) fn dense_challenge(seed: &[u8; 32], n: usize, weight: usize) -> Vec<i64> {
+    let mut c = vec![0i64; n];
+    for (index, negative) in expand_challenge(seed, n, weight).iter() {
+        c[*index] = if *negative { -1 } else { 1 };
+    }
+    c
+}
+
+/// Expand a seed into `weight` distinct positions in [0, n), each with a sign.
+pub(crate) fn expand_challenge(seed: &[u8; 32], n: usize, weight: usize) -> Vec<(usize, bool)> {
+    let mut hasher = Shake128::default();
+    hasher.update(b"cupcake challenge");
+    hasher.update(seed);
+    let mut reader = hasher.finalize_xof();
+    // reject the top values of a u32 so that the positions are uniform.
+    let limit = (u32::MAX as u64 + 1) / n as u64 * n as u64;
+    let mut c: Vec<(usize, bool)> = Vec::with_capacity(weight);
+    let mut buf = [0u8; 5];
+    while c.len() < weight {
+        reader.read(&mut buf);
+        let x = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as u64;
+        if x >= limit {
+            continue;
+        }
+        let index = (x % n as u64) as usize;
+        if c.iter().all(|(i, _)| *i != index) {
+            c.push((index, buf[4] & 1 == 1));
+        }
+    }
+    c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_prove_and_verify_public_key() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let proof = fv.prove_public_key(&pk, &sk).unwrap();
+        assert_eq!(fv.verify_public_key(&pk, &proof), Ok(()));
+
+        let (other_pk, other_sk) = fv.generate_keypair();
+        assert_eq!(
+            fv.verify_public_key(&other_pk, &proof),
+            Err(KeyProofError::ChallengeMismatch)
+        );
+        assert_eq!(
+            fv.prove_public_key(&pk, &other_sk),
+            Err(KeyProofError::InvalidWitness)
+        );
+    }
+
+    #[test]
+    fn test_reject_tampered_proof() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let proof = fv.prove_public_key(&pk, &sk).unwrap();
+
+        let mut bad = proof.clone();
+        bad.z_s[0] += 1;
+        assert_eq!(
+            fv.verify_public_key(&pk, &bad),
+            Err(KeyProofError::ChallengeMismatch)
+        );
+
+        let mut bad = proof.clone();
+        bad.z_e[5] = i64::MAX;
+        assert_eq!(
+            fv.verify_public_key(&pk, &bad),
+            Err(KeyProofError::ResponseOutOfRange)
+        );
+
+        let mut bad = proof;
+        bad.z_e.pop();
+        assert_eq!(
+            fv.verify_public_key(&pk, &bad),
+            Err(KeyProofError::WrongLength)
+        );
+    }
+
+    #[test]
+    fn test_add_sparse_product() {
+        // (x^3) * (1 + 2x) = x^3 + 2x^4 = x^3 - 2 in Z[x]/(x^4 + 1).
+        let res = add_sparse_product(&[0, 0, 0, 0], &[(3, false)], &[1, 2, 0, 0]);
+        assert_eq!(res, vec![-2, 0, 0, 1]);
+        let res = add_sparse_product(&[1, 1, 1, 1], &[(0, true)], &[1, 2, 0, 0]);
+        assert_eq!(res, vec![0, -1, 1, 1]);
+    }
+}
@@ -0,0 +1,521 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Keypairs and key stores.
+//!
+//! A [`KeyStore`] holds keypairs under string ids, together with their creation times and public
+//! key fingerprints. Secret keys never leave a store: decryption is a method of the store, so
+//! that the trait can also be implemented on top of an HSM or a KMS. [`MemoryKeyStore`] keeps
+//! the keys in memory, and [`FileKeyStore`] keeps one file per key in a directory.
+//!
+//! The files of a [`FileKeyStore`] hold the secret keys in the clear, readable by their owner
+//! only on Unix, and should be kept on an encrypted volume.
+use crate::fingerprint::{self, Fingerprint};
+use crate::integer_arith::ArithUtils;
+use crate::packed::PackedTernary;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{SKEncryption, Serializable};
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A public key together with its secret key.
+pub struct KeyPair<T: ArithUtils<T>> {
+    pub public: FVCiphertext<T>,
+    pub secret: SecretKey<T>,
+}
+
+impl<T> KeyPair<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The fingerprint of the public key.
+    pub fn fingerprint(&self) -> Fingerprint {
+        fingerprint::public_key_fingerprint(&self.public)
+    }
+}
+
+impl<T> fmt::Debug for KeyPair<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KeyPair {{ fingerprint: {} }}", self.fingerprint())
+    }
+}
+
+impl<T: ArithUtils<T>> From<(FVCiphertext<T>, SecretKey<T>)> for KeyPair<T> {
+    fn from((public, secret): (FVCiphertext<T>, SecretKey<T>)) -> Self {
+        KeyPair { public, secret }
+    }
+}
+
+/// The public information about a stored keypair.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyMetadata {
+    pub id: String,
+    pub fingerprint: Fingerprint,
+    pub created: SystemTime,
+}
+
+/// The reasons for which a key store operation fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyStoreError {
+    /// No key is stored under this id or fingerprint.
+    NotFound,
+    /// A key is already stored under this id.
+    DuplicateId(String),
+    /// The id is empty or contains characters other than ASCII letters, digits, '-', '_' and '.'.
+    InvalidId(String),
+    /// The stored key or the ciphertext is malformed.
+    Malformed(ValidationError),
+    /// The backing storage failed.
+    Io(io::ErrorKind),
+}
+
+impl fmt::Display for KeyStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyStoreError::NotFound => write!(f, "key not found"),
+            KeyStoreError::DuplicateId(id) => write!(f, "a key is already stored as {}", id),
+            KeyStoreError::InvalidId(id) => write!(f, "invalid key id {:?}", id),
+            KeyStoreError::Malformed(e) => write!(f, "malformed key or ciphertext: {}", e),
+            KeyStoreError::Io(kind) => write!(f, "key storage failed: {:?}", kind),
+        }
+    }
+}
+
+impl Error for KeyStoreError {}
+
+impl From<ValidationError> for KeyStoreError {
+    fn from(e: ValidationError) -> Self {
+        KeyStoreError::Malformed(e)
+    }
+}
+
+impl From<io::Error> for KeyStoreError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => KeyStoreError::NotFound,
+            kind => KeyStoreError::Io(kind),
+        }
+    }
+}
+
+/// A store of keypairs.
+pub trait KeyStore<T: ArithUtils<T>> {
+    /// Store a keypair under a new id.
+    fn insert(&mut self, id: &str, keypair: KeyPair<T>) -> Result<KeyMetadata, KeyStoreError>;
+
+    /// Remove the keypair stored under an id.
+    fn remove(&mut self, id: &str) -> Result<(), KeyStoreError>;
+
+    /// The metadata of all the stored keypairs, ordered by id.
+    fn list(&self) -> Result<Vec<KeyMetadata>, KeyStoreError>;
+
+    /// The metadata of the keypair stored under an id.
+    fn metadata(&self, id: &str) -> Result<KeyMetadata, KeyStoreError>;
+
+    /// The public key stored under an id.
+    fn public_key(&self, id: &str) -> Result<FVCiphertext<T>, KeyStoreError>;
+
+    /// Decrypt a ciphertext with the secret key stored under an id.
+    fn decrypt(
+        &self,
+        fv: &FV<T>,
+        id: &str,
+        ct: &FVCiphertext<T>,
+    ) -> Result<FVPlaintext, KeyStoreError>;
+
+    /// The metadata of the keypair with a given public key fingerprint.
+    fn find(&self, fingerprint: &Fingerprint) -> Result<KeyMetadata, KeyStoreError> {
+        self.list()?
+            .into_iter()
+            .find(|m| m.fingerprint == *fingerprint)
+            .ok_or(KeyStoreError::NotFound)
+    }
+}
+
+fn check_id(id: &str) -> Result<(), KeyStoreError> {
+    let valid = id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if id.is_empty() || !valid || id.starts_with('.') {
+        return Err(KeyStoreError::InvalidId(id.to_string()));
+    }
+    Ok(())
+}
+
+/// A key store in memory.
+pub struct MemoryKeyStore<T: ArithUtils<T>> {
+    keys: BTreeMap<String, (KeyMetadata, KeyPair<T>)>,
+}
+
+impl<T: ArithUtils<T>> MemoryKeyStore<T> {
+    pub fn new() -> Self {
+        MemoryKeyStore {
+            keys: BTreeMap::new(),
+        }
+    }
+
+    fn get(&self, id: &str) -> Result<&(KeyMetadata, KeyPair<T>), KeyStoreError> {
+        self.keys.get(id).ok_or(KeyStoreError::NotFound)
+    }
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for MemoryKeyStore<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.keys.keys()).finish()
+    }
+}
+
+impl<T: ArithUtils<T>> Default for MemoryKeyStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> KeyStore<T> for MemoryKeyStore<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    fn insert(&mut self, id: &str, keypair: KeyPair<T>) -> Result<KeyMetadata, KeyStoreError> {
+        check_id(id)?;
+        if self.keys.contains_key(id) {
+            return Err(KeyStoreError::DuplicateId(id.to_string()));
+        }
+        let metadata = KeyMetadata {
+            id: id.to_string(),
+            fingerprint: keypair.fingerprint(),
+            created: SystemTime::now(),
+        };
+        self.keys
+            .insert(id.to_string(), (metadata.clone(), keypair));
+        Ok(metadata)
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), KeyStoreError> {
+        self.keys
+            .remove(id)
+            .map(|_| ())
+            .ok_or(KeyStoreError::NotFound)
+    }
+
+    fn list(&self) -> Result<Vec<KeyMetadata>, KeyStoreError> {
+        Ok(self.keys.values().map(|(m, _)| m.clone()).collect())
+    }
+
+    fn metadata(&self, id: &str) -> Result<KeyMetadata, KeyStoreError> {
+        Ok(self.get(id)?.0.clone())
+    }
+
+    fn public_key(&self, id: &str) -> Result<FVCiphertext<T>, KeyStoreError> {
+        Ok(self.get(id)?.1.public.clone())
+    }
+
+    fn decrypt(
+        &self,
+        fv: &FV<T>,
+        id: &str,
+        ct: &FVCiphertext<T>,
+    ) -> Result<FVPlaintext, KeyStoreError> {
+        fv.validate(ct)?;
+        Ok(fv.decrypt(ct, &self.get(id)?.1.secret))
+    }
+}
+
+/// The metadata, public key and optionally secret key parsed from a key file.
+type KeyFile<T> = (KeyMetadata, FVCiphertext<T>, Option<SecretKey<T>>);
+
+/// A key store keeping each keypair in a file `<id>.key` of a directory.
+///
+/// A file holds the creation time as seconds and nanoseconds since the Unix epoch, as 8-byte and
+/// 4-byte little-endian integers, the encoded public key, and the 2-bit packed secret key.
+pub struct FileKeyStore<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    dir: PathBuf,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for FileKeyStore<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FileKeyStore({})", self.dir.display())
+    }
+}
+
+impl<'a, T> FileKeyStore<'a, T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Open a store of keys for the parameters `fv` in a directory, creating it if needed.
+    pub fn open<P: Into<PathBuf>>(fv: &'a FV<T>, dir: P) -> Result<Self, KeyStoreError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileKeyStore { fv, dir })
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, KeyStoreError> {
+        check_id(id)?;
+        Ok(self.dir.join(format!("{}.key", id)))
+    }
+
+    /// Read a key file, and parse its metadata, public key and, if requested, secret key.
+    fn read(&self, id: &str, with_secret: bool) -> Result<KeyFile<T>, KeyStoreError> {
+        let mut bytes = fs::read(self.path(id)?)?;
+        let parsed = self.parse(id, &bytes, with_secret);
+        wipe(&mut bytes);
+        Ok(parsed?)
+    }
+
+    fn parse(
+        &self,
+        id: &str,
+        bytes: &[u8],
+        with_secret: bool,
+    ) -> Result<KeyFile<T>, ValidationError> {
+        let pk_len = self.fv.ciphertext_byte_len();
+        let expected = 12 + pk_len + self.fv.n.div_ceil(4);
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let mut secs = [0u8; 8];
+        let mut nanos = [0u8; 4];
+        secs.copy_from_slice(&bytes[..8]);
+        nanos.copy_from_slice(&bytes[8..12]);
+        let nanos = u32::from_le_bytes(nanos) % 1_000_000_000;
+        let public = self.fv.public_key_from_bytes(&bytes[12..12 + pk_len])?;
+        let metadata = KeyMetadata {
+            id: id.to_string(),
+            fingerprint: fingerprint::public_key_fingerprint(&public),
+            created: UNIX_EPOCH + Duration::new(u64::from_le_bytes(secs), nanos),
+        };
+        let secret = if with_secret {
+            let packed = PackedTernary::from_unmasked_bytes(self.fv.n, &bytes[12 + pk_len..])
+                .ok_or(ValidationError::InvalidSecretKey)?;
+            Some(SecretKey(packed, Some(metadata.fingerprint)))
+        } else {
+            None
+        };
+        Ok((metadata, public, secret))
+    }
+}
+
+impl<'a, T> KeyStore<T> for FileKeyStore<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    fn insert(&mut self, id: &str, keypair: KeyPair<T>) -> Result<KeyMetadata, KeyStoreError> {
+        let path = self.path(id)?;
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut bytes = since_epoch.as_secs().to_le_bytes().to_vec();
+        bytes.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+        bytes.extend_from_slice(&keypair.public.to_bytes());
+        let mut packed = keypair.secret.0.unmasked_bytes();
+        bytes.extend_from_slice(&packed);
+        wipe(&mut packed);
+
+        if path.exists() {
+            wipe(&mut bytes);
+            return Err(KeyStoreError::DuplicateId(id.to_string()));
+        }
+        // Write to a temporary file in the same directory and rename it into place, so that a
+        // failed write never leaves a truncated key file behind.
+        let temp = self.dir.join(format!(".{}.{}.tmp", id, std::process::id()));
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let written = options
+            .open(&temp)
+            .and_then(|mut file| {
+                file.write_all(&bytes)?;
+                file.sync_all()
+            })
+            .and_then(|()| fs::rename(&temp, &path));
+        wipe(&mut bytes);
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(e.into());
+        }
+        Ok(KeyMetadata {
+            id: id.to_string(),
+            fingerprint: keypair.fingerprint(),
+            created: UNIX_EPOCH + since_epoch,
+        })
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), KeyStoreError> {
+        Ok(fs::remove_file(self.path(id)?)?)
+    }
+
+    fn list(&self) -> Result<Vec<KeyMetadata>, KeyStoreError> {
+        let mut ids = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            if let Some(id) = name.to_str().and_then(|n| n.strip_suffix(".key")) {
+                if check_id(id).is_ok() {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids.sort();
+        ids.iter().map(|id| self.metadata(id)).collect()
+    }
+
+    fn metadata(&self, id: &str) -> Result<KeyMetadata, KeyStoreError> {
+        Ok(self.read(id, false)?.0)
+    }
+
+    fn public_key(&self, id: &str) -> Result<FVCiphertext<T>, KeyStoreError> {
+        Ok(self.read(id, false)?.1)
+    }
+
+    fn decrypt(
+        &self,
+        fv: &FV<T>,
+        id: &str,
+        ct: &FVCiphertext<T>,
+    ) -> Result<FVPlaintext, KeyStoreError> {
+        fv.validate(ct)?;
+        let secret = self.read(id, true)?.2.expect("the secret key is parsed");
+        Ok(fv.decrypt(ct, &secret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    fn exercise<S: KeyStore<Scalar>>(fv: &FV<Scalar>, store: &mut S) {
+        let keypair = KeyPair::from(fv.generate_keypair());
+        let fingerprint = keypair.fingerprint();
+        let ct = fv.encrypt(&vec![4; fv.n], &keypair.public);
+        let metadata = store.insert("tenant-1", keypair).unwrap();
+        assert_eq!(metadata.fingerprint, fingerprint);
+        assert_eq!(store.metadata("tenant-1"), Ok(metadata.clone()));
+        assert_eq!(store.find(&fingerprint), Ok(metadata.clone()));
+        assert_eq!(
+            store.find(&Fingerprint([0; 16])),
+            Err(KeyStoreError::NotFound)
+        );
+        assert_eq!(
+            fv.public_key_fingerprint(&store.public_key("tenant-1").unwrap()),
+            fingerprint
+        );
+        assert_eq!(store.decrypt(fv, "tenant-1", &ct), Ok(vec![4; fv.n]));
+
+        let other = KeyPair::from(fv.generate_keypair());
+        assert_eq!(
+            store.insert("tenant-1", other).unwrap_err(),
+            KeyStoreError::DuplicateId("tenant-1".to_string())
+        );
+        let other = KeyPair::from(fv.generate_keypair());
+        assert_eq!(
+            store.insert("../tenant-2", other).unwrap_err(),
+            KeyStoreError::InvalidId("../tenant-2".to_string())
+        );
+        assert_eq!(store.list(), Ok(vec![metadata]));
+        let mut short = ct.clone();
+        short.1.coeffs.pop();
+        assert!(matches!(
+            store.decrypt(fv, "tenant-1", &short),
+            Err(KeyStoreError::Malformed(
+                ValidationError::WrongDegree { .. }
+            ))
+        ));
+        assert_eq!(store.remove("tenant-1"), Ok(()));
+        assert_eq!(store.remove("tenant-1"), Err(KeyStoreError::NotFound));
+        assert_eq!(store.metadata("tenant-1"), Err(KeyStoreError::NotFound));
+        assert_eq!(
+            store.decrypt(fv, "tenant-1", &ct),
+            Err(KeyStoreError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_memory_key_store() {
+        let fv = FV::<Scalar>::default_2048();
+        exercise(&fv, &mut MemoryKeyStore::new());
+    }
+
+    #[test]
+    fn test_file_key_store() {
+        let fv = FV::<Scalar>::default_2048();
+        let dir = std::env::temp_dir().join(format!("cupcake-keystore-{}", std::process::id()));
+        let mut store = FileKeyStore::open(&fv, &dir).unwrap();
+        exercise(&fv, &mut store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_key_files() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let dir = std::env::temp_dir().join(format!("cupcake-corrupt-{}", std::process::id()));
+        let mut store = FileKeyStore::open(&fv, &dir).unwrap();
+        let keypair = KeyPair::from(fv.generate_keypair());
+        let ct = fv.encrypt(&vec![1; fv.n], &keypair.public);
+        store.insert("key", keypair).unwrap();
+        // only the key file is left, without the temporary file it was written to.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let path = dir.join("key.key");
+        let bytes = fs::read(&path).unwrap();
+        let pk_len = fv.ciphertext_byte_len();
+
+        // a truncated file.
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(
+            store.metadata("key"),
+            Err(KeyStoreError::Malformed(ValidationError::WrongLength {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            }))
+        );
+        assert!(store.list().is_err());
+
+        // a secret key with an invalid code, which only fails once the secret key is read.
+        let mut corrupted = bytes.clone();
+        corrupted[12 + pk_len] = 0xff;
+        fs::write(&path, &corrupted).unwrap();
+        assert!(store.metadata("key").is_ok());
+        assert_eq!(
+            store.decrypt(&fv, "key", &ct),
+            Err(KeyStoreError::Malformed(ValidationError::InvalidSecretKey))
+        );
+
+        // a public key coefficient out of range.
+        let mut corrupted = bytes;
+        corrupted[12..12 + pk_len]
+            .iter_mut()
+            .for_each(|x| *x = 0xff);
+        fs::write(&path, &corrupted).unwrap();
+        assert!(matches!(
+            store.public_key("key"),
+            Err(KeyStoreError::Malformed(_))
+        ));
+
+        // a store whose directory is a file.
+        let file = dir.join("not-a-dir");
+        fs::write(&file, b"").unwrap();
+        assert!(matches!(
+            FileKeyStore::open(&fv, file.join("store")),
+            Err(KeyStoreError::Io(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
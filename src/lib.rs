@@ -15,9 +15,11 @@
 //! # Setup
 //! In order to encrypt and decrypt data, we needs to generate a keypair, i.e. a secret key and a public key.
 //! ```
+//! # #[cfg(feature = "std")] {
 //! let scheme = cupcake::default();
 //! use cupcake::traits::{SKEncryption, PKEncryption};
 //! let (pk, sk) = scheme.generate_keypair();
+//! # }
 //! ```
 //! The public key can be used for encryption and the secret key can be used for encryption or decryption.
 //!
@@ -25,15 +27,18 @@
 //!
 //! The library currently supports one plaintext type, which is `vec<u8>` of fixed size n. We can encrypt a vector under a public key like so
 //! ```
+//! # #[cfg(feature = "std")] {
 //! # let scheme = cupcake::default();
 //! # use cupcake::traits::{SKEncryption, PKEncryption};
 //! # let (pk, sk) = scheme.generate_keypair();
 //! let v = vec![1; scheme.n];
 //! let ct = scheme.encrypt(&v, &pk);
+//! # }
 //! ```
 //! Then, the ciphertext `ct` can be decrypted using the secret key:
 //!
 //! ```
+//! # #[cfg(feature = "std")] {
 //! # let scheme = cupcake::default();
 //! # use cupcake::traits::{SKEncryption, PKEncryption};
 //! # let (pk, sk) = scheme.generate_keypair();
@@ -41,11 +46,13 @@
 //! # let ct = scheme.encrypt(&v, &pk);
 //! let w = scheme.decrypt(&ct, &sk);
 //! assert_eq!(v, w);
+//! # }
 //! ```
 //! # Homomorphic Operations
 //!
 //! We can encrypt two vectors and add up the resulting ciphertexts.
 //! ```
+//! # #[cfg(feature = "std")] {
 //! # let scheme = cupcake::default();
 //! # use cupcake::traits::{SKEncryption, PKEncryption};
 //! # let (pk, sk) = scheme.generate_keypair();
@@ -59,9 +66,11 @@
 //! let expected = vec![3; scheme.n];
 //! let actual = scheme.decrypt(&ctz1, &sk);
 //! assert_eq!(actual, expected);
+//! # }
 //! ```
 //! Alternatively, we can add a plaintext vector into a ciphertext
 //! ```
+//! # #[cfg(feature = "std")] {
 //! # let scheme = cupcake::default();
 //! # use cupcake::traits::{SKEncryption, PKEncryption};
 //! # let (pk, sk) = scheme.generate_keypair();
@@ -74,10 +83,12 @@
 //! let expected = vec![5; scheme.n];
 //! let actual = scheme.decrypt(&ctz, &sk);
 //! assert_eq!(actual, expected);
+//! # }
 //! ```
 //! # Rerandomization
 //! Furthermore, you can rerandomize a ciphertext using the public key. The output is another ciphertext which will be still decrypt to the same plaintext, but cannot be linked to the input.
 //! ```
+//! # #[cfg(feature = "std")] {
 //! # let scheme = cupcake::default();
 //! # use cupcake::traits::{SKEncryption, PKEncryption};
 //! # let (pk, sk) = scheme.generate_keypair();
@@ -90,35 +101,322 @@
 //! let expected = mu;
 //! assert_eq!(actual, expected);
 
-
-pub(crate) mod integer_arith;
-mod rqpoly;
+//! # }
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "audit")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod authenticated;
+#[cfg(feature = "std")]
+pub mod binding;
+#[cfg(feature = "std")]
+pub mod blind;
+pub mod capabilities;
+#[cfg(feature = "std")]
+pub mod chunked;
+#[cfg(feature = "std")]
+pub mod ciphertext;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+#[cfg(feature = "compat")]
+pub mod compat;
+#[cfg(feature = "cose")]
+pub mod cose;
+#[cfg(feature = "std")]
+pub mod counter;
+#[cfg(feature = "std")]
+pub mod countmin;
+#[cfg(feature = "std")]
+pub mod decproof;
+#[cfg(feature = "std")]
+pub mod derive;
+#[cfg(feature = "std")]
+pub mod dkg;
+#[cfg(feature = "std")]
+pub mod dp;
+#[cfg(feature = "std")]
+pub mod dynamic;
+#[cfg(feature = "std")]
+pub mod envelope;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod evaluator;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fingerprint;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod flooding;
+#[cfg(feature = "std")]
+pub mod gradient;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "std")]
+pub mod histogram;
+#[cfg(feature = "hybrid")]
+pub mod hybrid;
+pub mod info;
+pub mod integer_arith;
+#[cfg(feature = "std")]
+pub mod iter;
+#[cfg(feature = "kat")]
+pub mod kat;
+#[cfg(feature = "std")]
+pub mod keyproof;
+#[cfg(feature = "std")]
+pub mod keystore;
+#[cfg(feature = "std")]
+pub mod metering;
+#[cfg(feature = "mnemonic")]
+pub mod mnemonic;
+#[cfg(feature = "mobile")]
+pub mod mobile;
+#[cfg(feature = "std")]
+pub mod mixer;
+#[cfg(feature = "std")]
+pub mod multikey;
+#[cfg(feature = "node")]
+pub mod node;
+#[cfg(feature = "std")]
+pub mod noise;
+#[cfg(feature = "tokio")]
+pub mod nonblocking;
+#[cfg(feature = "std")]
+pub mod observer;
+#[cfg(feature = "openfhe")]
+pub mod openfhe;
+#[cfg(feature = "std")]
+pub mod prelude;
+#[cfg(feature = "std")]
+mod packed;
+#[cfg(feature = "std")]
+pub mod pir;
+#[cfg(feature = "std")]
+pub mod psa;
+#[cfg(feature = "std")]
+pub mod psi;
+#[cfg(feature = "std")]
+pub mod pubkey;
+#[cfg(feature = "std")]
+pub mod refresh;
+#[cfg(feature = "std")]
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod rotation;
+pub mod rqpoly;
+#[cfg(feature = "std")]
+pub mod scratch;
+#[cfg(feature = "mlock")]
+pub mod secure;
+#[cfg(feature = "std")]
+pub mod selftest;
+mod serialize;
+#[cfg(feature = "std")]
+pub mod shamir;
+#[cfg(any(feature = "sqlx", feature = "diesel"))]
+pub mod sql;
+#[cfg(feature = "std")]
+pub mod tally;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod threshold;
+#[cfg(any(all(test, feature = "std"), feature = "timing"))]
+pub mod timing;
 pub mod traits;
+#[cfg(debug_assertions)]
+pub mod transparent;
 mod utils;
+pub mod validation;
+#[cfg(feature = "std")]
+pub mod vector;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "mobile")]
+uniffi::setup_scaffolding!();
 
 use integer_arith::scalar::Scalar;
 use integer_arith::ArithUtils;
+#[cfg(feature = "std")]
 use traits::*;
-use std::sync::Arc;
+#[cfg(any(feature = "std", feature = "zeroize"))]
+use utils::wipe;
+use fingerprint::Fingerprint;
+#[cfg(feature = "std")]
+use observer::Operation;
+#[cfg(feature = "std")]
+use packed::PackedTernary;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use rand::{CryptoRng, RngCore};
 
 /// Plaintext type
 pub type FVPlaintext = Vec<u8>;
 
-/// Ciphertext type
+/// Ciphertext type, as consumed by the scheme traits. See [`ciphertext::Ciphertext`] for a
+/// ciphertext which carries its parameters.
 pub type FVCiphertext<T> = (RqPoly<T>, RqPoly<T>);
 
 /// Default scheme type
 pub type DefaultShemeType = FV<Scalar>;
 
-/// SecretKey type
-pub struct SecretKey<T>(RqPoly<T>);
+/// A shared handle on a scheme, e.g. for the request handlers of a server.
+pub type FVRef<T> = Arc<FV<T>>;
+
+/// SecretKey type, holding the secret polynomial and, for keys generated as part of a keypair,
+/// the fingerprint of the matching public key. The ternary polynomial is stored packed and
+/// masked, and is only unpacked for the duration of a multiplication.
+#[cfg(feature = "std")]
+pub struct SecretKey<T: ArithUtils<T>>(PackedTernary<T>, Option<Fingerprint>);
+
+/// The randomness consumed by a public key encryption, namely the ternary polynomial u and the
+/// two error polynomials e1 and e2. Encrypting the same plaintext under the same public key and
+/// the same randomness always produces the same ciphertext.
+#[derive(Clone)]
+pub struct EncRandomness<T: ArithUtils<T>> {
+    u: RqPoly<T>,
+    e1: RqPoly<T>,
+    e2: RqPoly<T>,
+}
+
+#[cfg(feature = "std")]
+impl<T> SecretKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// A fingerprint identifying this key, which does not reveal its coefficients.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut packed = self.0.unmasked_bytes();
+        let fp = fingerprint::fingerprint_bytes(b"cupcake secret key", &packed);
+        wipe(&mut packed);
+        fp
+    }
+
+    /// Draw a fresh mask for the stored key. Long-lived keys can be remasked periodically, so
+    /// that the memory never holds the same shares for long.
+    pub fn remask(&mut self) {
+        self.0.remask();
+    }
+
+    /// The fingerprint of the matching public key, if this key was generated as part of a
+    /// keypair.
+    pub fn public_key_fingerprint(&self) -> Option<Fingerprint> {
+        self.1
+    }
+}
+
+// Keys are never formatted with their coefficients, so that deriving Debug on a struct holding a
+// key cannot leak it into logs.
+#[cfg(feature = "std")]
+impl<T> fmt::Debug for SecretKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretKey {{ fingerprint: {} }}", self.fingerprint())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Clone for SecretKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn clone(&self) -> Self {
+        SecretKey(self.0.clone(), self.1)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> PartialEq for SecretKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Compare two keys in time independent of their coefficients.
+    fn eq(&self, other: &Self) -> bool {
+        let mut a = self.0.unmasked_bytes();
+        let mut b = other.0.unmasked_bytes();
+        let equal = utils::ct_eq(&a, &b);
+        wipe(&mut a);
+        wipe(&mut b);
+        equal
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Eq for SecretKey<T> where T: ArithUtils<T> + Clone {}
+
+#[cfg(feature = "std")]
+impl<T> fmt::Display for SecretKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretKey({})", self.fingerprint())
+    }
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for EncRandomness<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EncRandomness(..)")
+    }
+}
+
+#[cfg(all(feature = "std", feature = "zeroize"))]
+impl<T: ArithUtils<T>> zeroize::Zeroize for SecretKey<T> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(all(feature = "std", feature = "zeroize"))]
+impl<T: ArithUtils<T>> Drop for SecretKey<T> {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+#[cfg(all(feature = "std", feature = "zeroize"))]
+impl<T: ArithUtils<T>> zeroize::ZeroizeOnDrop for SecretKey<T> {}
+
+#[cfg(feature = "zeroize")]
+impl<T: ArithUtils<T>> Drop for EncRandomness<T> {
+    fn drop(&mut self) {
+        wipe(&mut self.u);
+        wipe(&mut self.e1);
+        wipe(&mut self.e2);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: ArithUtils<T>> zeroize::ZeroizeOnDrop for EncRandomness<T> {}
 use rqpoly::{FiniteRingElt, RqPoly, RqPolyContext, NTT};
 
 pub fn default() -> DefaultShemeType {
     FV::<Scalar>::default_2048()
 }
 
+/// A shared handle on the default scheme.
+pub fn default_shared() -> FVRef<Scalar> {
+    default().into_shared()
+}
+
+pub use capabilities::CAPABILITIES;
+#[cfg(feature = "std")]
+pub use selftest::self_test;
+
 /// (Additive only version of) the Fan-Vercauteren homomoprhic encryption scheme.
+///
+/// Cloning a scheme is cheap: the precomputed NTT tables are shared behind an `Arc`. A scheme is
+/// `Send` and `Sync`, and can also be shared as an [`FVRef`].
+#[derive(Clone)]
 pub struct FV<T>
 where
     T: ArithUtils<T>,
@@ -133,35 +431,63 @@ where
     poly_multiplier: fn(&RqPoly<T>, &RqPoly<T>) -> RqPoly<T>,
 }
 
+impl<T> fmt::Debug for FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FV")
+            .field("n", &self.n)
+            .field("q", &T::to_u64(self.q.clone()))
+            .field("stdev", &self.stdev)
+            .field("flooding_stdev", &self.flooding_stdev)
+            .field("ntt", &self.context.is_ntt_enabled)
+            .finish()
+    }
+}
+
+// Schemes are equal if they have the same parameters.
+impl<T> PartialEq for FV<T>
+where
+    T: ArithUtils<T> + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.n == other.n
+            && self.q == other.q
+            && self.stdev == other.stdev
+            && self.flooding_stdev == other.flooding_stdev
+    }
+}
+
+#[cfg(feature = "std")]
 impl<T> AdditiveHomomorphicScheme<FVCiphertext<T>, FVPlaintext, SecretKey<T>> for FV<T>
 where
     RqPoly<T>: FiniteRingElt,
     T: Clone + ArithUtils<T> + PartialEq,
 {
     fn add_inplace(&self, ct1: &mut FVCiphertext<T>, ct2: &FVCiphertext<T>) {
-        ct1.0.add_inplace(&ct2.0);
-        ct1.1.add_inplace(&ct2.1);
+        observer::observe(Operation::Addition, || {
+            ct1.0.add_inplace(&ct2.0);
+            ct1.1.add_inplace(&ct2.1);
+        })
     }
 
     // add a plaintext into a FVCiphertext.
     fn add_plain_inplace(&self, ct: &mut FVCiphertext<T>, pt: &FVPlaintext) {
-        // ct1
-        for (ct_coeff, pt_coeff) in ct.1.coeffs.iter_mut().zip(pt.iter()) {
-            let temp = T::mul(&T::from_u32_raw(*pt_coeff as u32), &self.delta);
-            *ct_coeff = T::add_mod(ct_coeff, &temp, &self.q);
-        }
+        observer::observe(Operation::PlainAddition, || {
+            // ct1
+            for (ct_coeff, pt_coeff) in ct.1.coeffs.iter_mut().zip(pt.iter()) {
+                let temp = T::mul(&T::from_u32_raw(*pt_coeff as u32), &self.delta);
+                *ct_coeff = T::add_mod(ct_coeff, &temp, &self.q);
+            }
+        })
     }
 
     // rerandomize a ciphertext
     fn rerandomize(&self, ct: &mut FVCiphertext<T>, pk: &FVCiphertext<T>) {
-        // add a public key encryption of zero.
-        let c_mask = self.encrypt_zero(pk);
-        self.add_inplace(ct, &c_mask);
-
-        // add large noise poly for noise flooding.
-        let elarge =
-            rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.flooding_stdev);
-        ct.1.add_inplace(&elarge);
+        observer::observe(Operation::Rerandomization, || {
+            self.rerandomize_with_stdev(ct, pk, self.flooding_stdev);
+        })
     }
 }
 
@@ -172,22 +498,30 @@ where
     RqPoly<T>: FiniteRingElt + NTT<T>,
 {
     pub fn new(n: usize, q: &T) -> Self {
-        let context = Arc::new(RqPolyContext::new(n, q));
+        Self::from_context(Arc::new(RqPolyContext::new(n, q)))
+    }
+
+    /// A scheme whose NTT is computed from the given primitive 2n-th root of unity modulo q,
+    /// see [`RqPolyContext::with_root`].
+    pub fn with_ntt_root(n: usize, q: &T, root: &T) -> Result<Self, rqpoly::RootError> {
+        Ok(Self::from_context(Arc::new(RqPolyContext::with_root(n, q, root)?)))
+    }
+
+    fn from_context(context: Arc<RqPolyContext<T>>) -> Self {
+        let n = context.n;
+        let q = context.q.clone();
         type RqPolyMultiplier<T> = fn(&RqPoly<T>, &RqPoly<T>) -> RqPoly<T>;
-        let default_multiplier: RqPolyMultiplier<T>;
-        if context.is_ntt_enabled {
-            default_multiplier =
-                |op1: &RqPoly<T>, op2: &RqPoly<T>| -> RqPoly<T> { op1.multiply_fast(op2) };
+        let default_multiplier: RqPolyMultiplier<T> = if context.is_ntt_enabled {
+            |op1: &RqPoly<T>, op2: &RqPoly<T>| -> RqPoly<T> { op1.multiply_fast(op2) }
         } else {
-            default_multiplier =
-                |op1: &RqPoly<T>, op2: &RqPoly<T>| -> RqPoly<T> { op1.multiply(op2) };
-        }
+            |op1: &RqPoly<T>, op2: &RqPoly<T>| -> RqPoly<T> { op1.multiply(op2) }
+        };
         FV {
             n,
             flooding_stdev: 1f64,
-            delta: T::div(q, &T::from_u32_raw(256)), // &q/256,
-            qdivtwo: T::div(q, &T::from_u32_raw(2)), // &q/2,
-            q: q.clone(),
+            delta: T::div(&q, &T::from_u32_raw(256)), // &q/256,
+            qdivtwo: T::div(&q, &T::from_u32_raw(2)), // &q/2,
+            q,
             stdev: 3.2,
             context,
             poly_multiplier: default_multiplier,
@@ -213,8 +547,8 @@ impl FV<Scalar> {
             delta: Scalar::div(&q, &Scalar::from_u32_raw(256)), // &q/256,
             qdivtwo: Scalar::div(&q, &Scalar::from_u32_raw(2)), // &q/2,
             stdev: 3.2,
-            flooding_stdev: 2f64.powi(40),
-            context: context,
+            flooding_stdev: (1u64 << 40) as f64,
+            context,
             poly_multiplier: default_multiplier,
         }
     }
@@ -242,88 +576,222 @@ impl FV<BigInt> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<T> PKEncryption<FVCiphertext<T>, FVPlaintext, SecretKey<T>> for FV<T>
 where
     RqPoly<T>: FiniteRingElt,
     T: Clone + ArithUtils<T> + PartialEq,
 {
     fn encrypt(&self, pt: &FVPlaintext, pk: &FVCiphertext<T>) -> FVCiphertext<T> {
-        // use public key to encrypt
-        // pk = (a, as+e) = (a,b)
+        observer::observe(Operation::Encryption, || {
+            // use public key to encrypt
+            // pk = (a, as+e) = (a,b)
+
+            let (c0, mut c1) = self.encrypt_zero(pk);
+
+            // c1 = bu+e2 + Delta*m
+            let iter = c1.coeffs.iter_mut().zip(pt.iter());
+            for (x, y) in iter {
+                let temp = T::mul(&T::from_u32_raw(*y as u32), &self.delta);
+                *x = T::add_mod(x, &temp, &self.q);
+            }
+            (c0, c1)
+        })
+    }
 
-        let (c0, mut c1) = self.encrypt_zero(pk);
+    fn encrypt_zero(&self, pk: &FVCiphertext<T>) -> FVCiphertext<T> {
+        observer::observe(Operation::Encryption, || {
+            let r = self.sample_randomness();
+            self.encrypt_zero_with_randomness(pk, &r)
+        })
+    }
 
-        // c1 = bu+e2 + Delta*m
-        let iter = c1.coeffs.iter_mut().zip(pt.iter());
-        for (x, y) in iter {
-            let temp = T::mul(&T::from_u32_raw(*y as u32), &self.delta);
-            *x = T::add_mod(x, &temp, &self.q);
+    fn generate_keypair(&self) -> (FVCiphertext<T>, SecretKey<T>) {
+        observer::observe(Operation::KeyGeneration, || {
+            #[cfg(feature = "tracing")]
+            let _span =
+                tracing::debug_span!("generate_keypair", params_id = %self.params_id()).entered();
+            let mut sk = self.generate_key();
+            let mut pk = self.encrypt_zero_sk(&sk);
+            if self.context.is_ntt_enabled {
+                pk.0.forward_transform();
+                pk.1.forward_transform();
+            }
+            sk.1 = Some(self.public_key_fingerprint(&pk));
+            (pk, sk)
+        })
+    }
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// A fingerprint identifying a public key, suitable for logging.
+    pub fn public_key_fingerprint(&self, pk: &FVCiphertext<T>) -> Fingerprint {
+        fingerprint::public_key_fingerprint(pk)
+    }
+
+    /// The context of the ring of this scheme, to build polynomials compatible with its keys and
+    /// ciphertexts.
+    pub fn ring_context(&self) -> Arc<RqPolyContext<T>> {
+        self.context.clone()
+    }
+
+    /// Move this scheme behind a shared handle.
+    pub fn into_shared(self) -> FVRef<T> {
+        Arc::new(self)
+    }
+
+    /// A fingerprint identifying the parameters (n, q) of this scheme.
+    pub fn params_id(&self) -> Fingerprint {
+        fingerprint::params_fingerprint(self.n, T::to_u64(self.q.clone()))
+    }
+}
+
+// public key encryption with caller-supplied randomness.
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Sample fresh randomness for a public key encryption.
+    #[cfg(feature = "std")]
+    pub fn sample_randomness(&self) -> EncRandomness<T> {
+        EncRandomness {
+            u: rng::with_rng(|rng| {
+                rqpoly::randutils::sample_ternary_poly(self.context.clone(), rng)
+            }),
+            e1: rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev),
+            e2: rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev),
         }
-        (c0, c1)
     }
 
-    fn encrypt_zero(&self, pk: &FVCiphertext<T>) -> FVCiphertext<T> {
-        let mut u = rqpoly::randutils::sample_ternary_poly_prng(self.context.clone());
-        let e1 = rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev);
-        let e2 = rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev);
+    /// Sample fresh randomness for a public key encryption from the given CSPRNG, e.g. a
+    /// hardware generator on a target without std.
+    pub fn sample_randomness_with<R>(&self, rng: &mut R) -> EncRandomness<T>
+    where
+        R: RngCore + CryptoRng,
+    {
+        let context = self.context.clone();
+        EncRandomness {
+            u: rqpoly::randutils::sample_ternary_poly(context.clone(), rng),
+            e1: rqpoly::randutils::sample_gaussian_poly_with(context.clone(), self.stdev, rng),
+            e2: rqpoly::randutils::sample_gaussian_poly_with(context, self.stdev, rng),
+        }
+    }
 
+    /// Assemble encryption randomness agreed upon out of band, e.g. by the parties of an MPC
+    /// protocol. The ternary polynomial u and the errors e1 and e2 must have n coefficients in
+    /// [0, q) and be in coefficient form; their distribution is the caller's responsibility.
+    pub fn randomness_from_polys(
+        &self,
+        u: RqPoly<T>,
+        e1: RqPoly<T>,
+        e2: RqPoly<T>,
+    ) -> Result<EncRandomness<T>, validation::ValidationError> {
+        self.validate_poly(&u, 0, false)?;
+        self.validate_poly(&e1, 1, false)?;
+        self.validate_poly(&e2, 2, false)?;
+        Ok(EncRandomness { u, e1, e2 })
+    }
+
+    /// Encrypt zero under the public key, using the given randomness instead of sampling it.
+    pub fn encrypt_zero_with_randomness(
+        &self,
+        pk: &FVCiphertext<T>,
+        r: &EncRandomness<T>,
+    ) -> FVCiphertext<T> {
+        let mut u = r.u.clone();
         if self.context.is_ntt_enabled {
             u.forward_transform();
         }
         // c0 = au + e1
         let mut c0 = (self.poly_multiplier)(&pk.0, &u);
-        c0.add_inplace(&e1);
+        c0.add_inplace(&r.e1);
 
         // c1 = bu + e2
         let mut c1 = (self.poly_multiplier)(&pk.1, &u);
-        c1.add_inplace(&e2);
+        c1.add_inplace(&r.e2);
 
         (c0, c1)
     }
 
-    fn generate_keypair(&self) -> (FVCiphertext<T>, SecretKey<T>) {
-        let sk = self.generate_key();
-        let mut pk = self.encrypt_zero_sk(&sk);
-        if self.context.is_ntt_enabled {
-            pk.0.forward_transform();
-            pk.1.forward_transform();
+    /// Encrypt a plaintext under the public key, using the given randomness instead of sampling it.
+    /// This allows several parties (e.g. in an MPC protocol or a proof verifier) to reproduce a
+    /// ciphertext from agreed-upon randomness.
+    pub fn encrypt_with_randomness(
+        &self,
+        pt: &FVPlaintext,
+        pk: &FVCiphertext<T>,
+        r: &EncRandomness<T>,
+    ) -> FVCiphertext<T> {
+        let (c0, mut c1) = self.encrypt_zero_with_randomness(pk, r);
+
+        // c1 = bu+e2 + Delta*m
+        let iter = c1.coeffs.iter_mut().zip(pt.iter());
+        for (x, y) in iter {
+            let temp = T::mul(&T::from_u32_raw(*y as u32), &self.delta);
+            *x = T::add_mod(x, &temp, &self.q);
         }
-        (pk, sk)
+        (c0, c1)
     }
 }
 
-// This implements the sk-encryption for BFV scheme.
-impl<T> SKEncryption<FVCiphertext<T>, FVPlaintext, SecretKey<T>> for FV<T>
+// batch encryption on a rayon thread pool.
+#[cfg(feature = "parallel")]
+impl<T> FV<T>
 where
     RqPoly<T>: FiniteRingElt,
-    T: Clone + ArithUtils<T>,
+    T: Clone + ArithUtils<T> + PartialEq + Send + Sync,
 {
-    fn generate_key(&self) -> SecretKey<T> {
-        let mut skpoly = rqpoly::randutils::sample_ternary_poly(self.context.clone());
-        if self.context.is_ntt_enabled {
-            skpoly.forward_transform();
+    /// Encrypt a batch of plaintexts in parallel. Every worker thread samples from its own
+    /// independently seeded RNG stream, so the workers never contend on a shared generator.
+    /// Inside `DeterministicRng::run`, each plaintext is instead encrypted with a seed
+    /// drawn in order from the seeded generator, so that the batch is reproducible.
+    pub fn encrypt_par(&self, pts: &[FVPlaintext], pk: &FVCiphertext<T>) -> Vec<FVCiphertext<T>> {
+        use rayon::prelude::*;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "encrypt_batch",
+            params_id = %self.params_id(),
+            count = pts.len()
+        )
+        .entered();
+        if let Some(seeds) = rng::task_seeds(pts.len()) {
+            return pts
+                .par_iter()
+                .zip(seeds)
+                .map(|(pt, seed)| rng::with_seeded_rng(seed, || self.encrypt(pt, pk)))
+                .collect();
         }
-        SecretKey(skpoly)
-    }
-
-    fn encrypt_zero_sk(&self, sk: &SecretKey<T>) -> FVCiphertext<T> {
-        let e = rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev);
-        let a = rqpoly::randutils::sample_uniform_poly(self.context.clone());
-        let mut b = (self.poly_multiplier)(&a, &sk.0);
-        b.add_inplace(&e);
-        (a, b)
+        pts.par_iter().map(|pt| self.encrypt(pt, pk)).collect()
     }
+}
 
-    // todo: handle the case when SK is in NTT form.
-
-    fn encrypt_sk(&self, pt: &FVPlaintext, sk: &SecretKey<T>) -> FVCiphertext<T> {
-        let e = rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev);
-        let a = rqpoly::randutils::sample_uniform_poly(self.context.clone());
-
-        let mut b = (self.poly_multiplier)(&a, &sk.0);
+// secret key encryption with a seeded uniform component.
+#[cfg(feature = "std")]
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T>,
+{
+    /// Encrypt a given plaintext under the secret key, where the uniform component of the
+    /// ciphertext is expanded from `seed`. The first component can then be transmitted as the
+    /// 32-byte seed instead of n coefficients. The seed must be fresh for every encryption.
+    pub fn encrypt_sk_seeded(
+        &self,
+        pt: &FVPlaintext,
+        sk: &SecretKey<T>,
+        seed: &[u8; 32],
+    ) -> FVCiphertext<T> {
+        let mut e = rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev);
+        let a = rqpoly::randutils::sample_uniform_poly_from_seed(self.context.clone(), seed);
+
+        let mut b = self.multiply_secret(&a, sk);
         b.add_inplace(&e);
+        wipe(&mut e);
 
-        // add scaled plaintext to
         let iter = b.coeffs.iter_mut().zip(pt.iter());
         for (x, y) in iter {
             let temp = T::mul(&T::from_u32_raw(*y as u32), &self.delta);
@@ -331,28 +799,124 @@ where
         }
         (a, b)
     }
+}
 
-    fn decrypt(&self, ct: &FVCiphertext<T>, sk: &SecretKey<T>) -> FVPlaintext {
-        let temp1 = (self.poly_multiplier)(&ct.0, &sk.0);
-        let mut phase = ct.1.clone();
-        phase.sub_inplace(&temp1);
-        // then, extract value from phase.
+#[cfg(feature = "std")]
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T>,
+{
+    /// The product of a polynomial in coefficient form with the secret key. The key is unpacked
+    /// into a temporary polynomial, which is wiped afterwards.
+    ///
+    /// Unpacking costs one forward NTT of the key per call, about 15% of a decryption with the
+    /// default parameters (the `decryption` bench takes ~400us, the NTT ~65us). The NTT form is
+    /// not cached, since it is the raw key which the packed, masked representation protects.
+    pub(crate) fn multiply_secret(&self, a: &RqPoly<T>, sk: &SecretKey<T>) -> RqPoly<T> {
+        let mut s = sk.0.unpack(self.context.clone());
+        if self.context.is_ntt_enabled {
+            s.forward_transform();
+        }
+        let product = (self.poly_multiplier)(a, &s);
+        wipe(&mut s);
+        product
+    }
+
+    /// Round the phase of a ciphertext to the plaintext.
+    pub(crate) fn decode_phase(&self, phase: &RqPoly<T>) -> FVPlaintext {
         let mut c: Vec<u8> = vec![];
-        for x in phase.coeffs {
+        for x in phase.coeffs.iter() {
             // let mut tmp = x << 8;  // x * t, need to make sure there's no overflow.
-            let mut tmp = T::mul(&x, &T::from_u32_raw(256));
+            let mut tmp = T::mul(x, &T::from_u32_raw(256));
             // tmp += &self.qdivtwo;
             tmp = T::add(&tmp, &self.qdivtwo);
             // tmp /= &self.q;
-            tmp = T::div(&tmp, &self.q);
+            tmp = T::div_by_modulus(&tmp, &self.q);
             // modulo t and cast to u8.
             c.push(T::to_u64(tmp) as u8);
         }
         c
     }
+
+    /// The phase c1 - c0 * s of a ciphertext, which is Delta * m plus the noise.
+    pub(crate) fn phase(&self, ct: &FVCiphertext<T>, sk: &SecretKey<T>) -> RqPoly<T> {
+        let mut temp1 = self.multiply_secret(&ct.0, sk);
+        let mut phase = ct.1.clone();
+        phase.sub_inplace(&temp1);
+        wipe(&mut temp1);
+        phase
+    }
 }
 
-#[cfg(test)]
+// This implements the sk-encryption for BFV scheme.
+#[cfg(feature = "std")]
+impl<T> SKEncryption<FVCiphertext<T>, FVPlaintext, SecretKey<T>> for FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T>,
+{
+    fn generate_key(&self) -> SecretKey<T> {
+        observer::observe(Operation::KeyGeneration, || {
+            #[cfg(feature = "tracing")]
+            let _span =
+                tracing::debug_span!("generate_key", params_id = %self.params_id()).entered();
+            let mut skpoly = rng::with_rng(|rng| {
+                rqpoly::randutils::sample_ternary_poly(self.context.clone(), rng)
+            });
+            let packed = PackedTernary::pack(&skpoly, &self.q);
+            wipe(&mut skpoly);
+            SecretKey(packed, None)
+        })
+    }
+
+    fn encrypt_zero_sk(&self, sk: &SecretKey<T>) -> FVCiphertext<T> {
+        observer::observe(Operation::Encryption, || {
+            let mut e = rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev);
+            let a = rqpoly::randutils::sample_uniform_poly(self.context.clone());
+            let mut b = self.multiply_secret(&a, sk);
+            b.add_inplace(&e);
+            wipe(&mut e);
+            (a, b)
+        })
+    }
+
+    // todo: handle the case when SK is in NTT form.
+
+    fn encrypt_sk(&self, pt: &FVPlaintext, sk: &SecretKey<T>) -> FVCiphertext<T> {
+        observer::observe(Operation::Encryption, || {
+            let mut e = rqpoly::randutils::sample_gaussian_poly(self.context.clone(), self.stdev);
+            let a = rqpoly::randutils::sample_uniform_poly(self.context.clone());
+
+            let mut b = self.multiply_secret(&a, sk);
+            b.add_inplace(&e);
+            wipe(&mut e);
+
+            // add scaled plaintext to
+            let iter = b.coeffs.iter_mut().zip(pt.iter());
+            for (x, y) in iter {
+                let temp = T::mul(&T::from_u32_raw(*y as u32), &self.delta);
+                *x = T::add_mod(x, &temp, &self.q);
+            }
+            (a, b)
+        })
+    }
+
+    /// Constant time with respect to the secret key and the plaintext, provided the parameters
+    /// enable the NTT: the product with the secret key is computed by the NTT, whose memory
+    /// access pattern is fixed and whose modular operations are branch-free, and the rounding
+    /// below divides by q through `div_by_modulus` instead of a hardware division.
+    fn decrypt(&self, ct: &FVCiphertext<T>, sk: &SecretKey<T>) -> FVPlaintext {
+        observer::observe(Operation::Decryption, || {
+            let mut phase = self.phase(ct, sk);
+            let c = self.decode_phase(&phase);
+            wipe(&mut phase);
+            c
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod fv_scalar_tests {
     use super::*;
     #[test]
@@ -406,6 +970,91 @@ mod fv_scalar_tests {
         assert_eq!(v, pt_actual);
     }
 
+    #[test]
+    fn test_encrypt_with_randomness_scalar() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+
+        let v = vec![7; fv.n];
+        let r = fv.sample_randomness();
+        let ct1 = fv.encrypt_with_randomness(&v, &pk, &r);
+        let ct2 = fv.encrypt_with_randomness(&v, &pk, &r);
+
+        assert_eq!(ct1.0.coeffs, ct2.0.coeffs);
+        assert_eq!(ct1.1.coeffs, ct2.1.coeffs);
+        assert_eq!(fv.decrypt(&ct1, &sk), v);
+    }
+
+    #[test]
+    fn test_encrypt_with_caller_rng() {
+        use rand::SeedableRng;
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let v = vec![9; fv.n];
+        let mut rng = rand::rngs::StdRng::from_seed([3; 32]);
+        let ct1 = fv.encrypt_with_randomness(&v, &pk, &fv.sample_randomness_with(&mut rng));
+        let mut rng = rand::rngs::StdRng::from_seed([3; 32]);
+        let ct2 = fv.encrypt_with_randomness(&v, &pk, &fv.sample_randomness_with(&mut rng));
+        assert_eq!(ct1, ct2);
+        assert_eq!(fv.decrypt(&ct1, &sk), v);
+    }
+
+    #[test]
+    fn test_randomness_from_polys() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let v = vec![5; fv.n];
+        // ternary polynomials are in range for every sampler.
+        let sample = || fv.sample_randomness().u.clone();
+        let r = EncRandomness {
+            u: sample(),
+            e1: sample(),
+            e2: sample(),
+        };
+        let rebuilt = fv
+            .randomness_from_polys(r.u.clone(), r.e1.clone(), r.e2.clone())
+            .unwrap();
+        let ct1 = fv.encrypt_with_randomness(&v, &pk, &rebuilt);
+        let ct2 = fv.encrypt_with_randomness(&v, &pk, &r);
+        assert_eq!(ct1.0.coeffs, ct2.0.coeffs);
+        assert_eq!(ct1.1.coeffs, ct2.1.coeffs);
+        assert_eq!(fv.decrypt(&ct1, &sk), v);
+
+        let mut short = r.e2.clone();
+        short.coeffs.pop();
+        assert_eq!(
+            fv.randomness_from_polys(r.u.clone(), r.e1.clone(), short).err(),
+            Some(validation::ValidationError::WrongDegree {
+                expected: fv.n,
+                actual: fv.n - 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_encrypt_sk_seeded_scalar() {
+        let fv = FV::<Scalar>::default_2048();
+        let sk = fv.generate_key();
+
+        let v = vec![3; fv.n];
+        let ct1 = fv.encrypt_sk_seeded(&v, &sk, &[5u8; 32]);
+        let ct2 = fv.encrypt_sk_seeded(&v, &sk, &[5u8; 32]);
+
+        assert_eq!(ct1.0.coeffs, ct2.0.coeffs);
+        assert_eq!(fv.decrypt(&ct1, &sk), v);
+    }
+
+    #[test]
+    fn test_secret_key_debug_is_redacted() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let fp = sk.fingerprint();
+        assert_eq!(format!("{:?}", sk), format!("SecretKey {{ fingerprint: {} }}", fp));
+        assert_eq!(format!("{}", sk), format!("SecretKey({})", fp));
+        assert_ne!(fv.public_key_fingerprint(&pk), fp);
+        assert_ne!(fv.generate_key().fingerprint(), fp);
+    }
+
     #[test]
     fn test_rerandomize_scalar() {
         let fv = FV::<Scalar>::default_2048();
@@ -483,6 +1132,186 @@ mod fv_scalar_tests {
 
         assert_eq!(pt_after_add, vplusw);
     }
+
+    #[test]
+    fn test_clone_eq_debug() {
+        let fv = FV::<Scalar>::default_2048();
+        let copy = fv.clone();
+        assert_eq!(fv, copy);
+        assert_ne!(fv, FV::new(16, &Scalar::new_modulus(65537)));
+        assert!(format!("{:?}", fv)
+            .starts_with("FV { n: 2048, q: 18014398492704769, stdev: 3.2, flooding_stdev: "));
+
+        let (pk, sk) = copy.generate_keypair();
+        let sk2 = sk.clone();
+        assert_eq!(sk, sk2);
+        assert_ne!(sk.0.shares(), sk2.0.shares());
+        assert_ne!(sk, fv.generate_key());
+
+        let ct = fv.encrypt(&vec![1; fv.n], &pk);
+        assert_eq!(ct, ct.clone());
+        assert_ne!(ct, fv.encrypt(&vec![1; fv.n], &pk));
+        assert_eq!(fv.decrypt(&ct, &sk2), vec![1; fv.n]);
+        assert_eq!(
+            format!("{:?}", fv.sample_randomness()),
+            "EncRandomness(..)"
+        );
+    }
+
+    #[test]
+    fn test_shared_scheme() {
+        let fv = default_shared();
+        let (pk, sk) = fv.generate_keypair();
+        let pk = Arc::new(pk);
+        let handles: Vec<_> = (0..4u8)
+            .map(|i| {
+                let (fv, pk) = (FVRef::clone(&fv), Arc::clone(&pk));
+                std::thread::spawn(move || fv.encrypt(&vec![i; fv.n], &pk))
+            })
+            .collect();
+        let cts: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        for (i, ct) in cts.iter().enumerate() {
+            assert_eq!(fv.decrypt(ct, &sk), vec![i as u8; fv.n]);
+        }
+        assert!(Arc::ptr_eq(&fv.context, &fv.as_ref().clone().context));
+    }
+
+    #[test]
+    fn test_scheme_with_ntt_root() {
+        let default = FV::<Scalar>::default_2048();
+        let psi = default.context.root().unwrap();
+        let psi3 = Scalar::pow_mod(&psi, &Scalar::from_u64_raw(3), &default.q);
+        let fv = FV::with_ntt_root(2048, &default.q, &psi3).unwrap();
+        let (pk, sk) = fv.generate_keypair();
+        assert_eq!(fv.decrypt(&fv.encrypt(&vec![7; fv.n], &pk), &sk), vec![7; fv.n]);
+        assert!(FV::with_ntt_root(2048, &default.q, &Scalar::from_u64_raw(1)).is_err());
+    }
+
+    #[test]
+    fn test_owned_operations_scalar() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+
+        let ct1 = fv.encrypt(&vec![2; fv.n], &pk);
+        let ct2 = fv.encrypt(&vec![5; fv.n], &pk);
+        let sum = fv.add(&ct1, &ct2);
+        let shifted = fv.add_plain(&sum, &vec![1; fv.n]);
+        let fresh = fv.rerandomized(&shifted, &pk);
+
+        assert_eq!(fv.decrypt(&ct1, &sk), vec![2; fv.n]);
+        assert_eq!(fv.decrypt(&sum, &sk), vec![7; fv.n]);
+        assert_eq!(fv.decrypt(&shifted, &sk), vec![8; fv.n]);
+        assert_eq!(fv.decrypt(&fresh, &sk), vec![8; fv.n]);
+        assert_ne!(fresh.1.coeffs, shifted.1.coeffs);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+#[cfg(test)]
+mod fv_zeroize_tests {
+    use super::*;
+    use zeroize::Zeroize;
+
+    #[test]
+    fn test_zeroize_secret_key() {
+        let fv = FV::<Scalar>::default_2048();
+        let mut sk = fv.generate_key();
+        sk.zeroize();
+        assert!(sk.0.unmasked_bytes().iter().all(|x| *x == 0));
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[cfg(test)]
+mod fv_parallel_tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_par() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let pts: Vec<FVPlaintext> = (0..8u8).map(|i| vec![i; fv.n]).collect();
+        let cts = fv.encrypt_par(&pts, &pk);
+        for (ct, pt) in cts.iter().zip(pts.iter()) {
+            assert_eq!(&fv.decrypt(ct, &sk), pt);
+        }
+    }
+
+    #[cfg(feature = "deterministic")]
+    #[test]
+    fn test_encrypt_par_deterministic() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let harness = rng::DeterministicRng::from_seed([5u8; 32]);
+        let (pk, sk) = harness.run(|| fv.generate_keypair());
+        let pts: Vec<FVPlaintext> = (0..8u8).map(|i| vec![i; fv.n]).collect();
+        let cts = harness.run(|| fv.encrypt_par(&pts, &pk));
+        let again = harness.run(|| fv.encrypt_par(&pts, &pk));
+        for (a, b) in cts.iter().zip(again.iter()) {
+            assert_eq!(a.0.coeffs, b.0.coeffs);
+            assert_eq!(a.1.coeffs, b.1.coeffs);
+        }
+        for (ct, pt) in cts.iter().zip(pts.iter()) {
+            assert_eq!(&fv.decrypt(ct, &sk), pt);
+        }
+    }
+}
+
+#[cfg(feature = "deterministic")]
+#[cfg(test)]
+mod fv_deterministic_tests {
+    use super::*;
+    use crate::rng::DeterministicRng;
+
+    #[test]
+    fn test_deterministic_keys_and_ciphertexts() {
+        let fv = FV::<Scalar>::default_2048();
+        let v = vec![9; fv.n];
+        let simulate = || {
+            let (pk, sk) = fv.generate_keypair();
+            let mut ct = fv.encrypt(&v, &pk);
+            fv.rerandomize(&mut ct, &pk);
+            (sk.fingerprint(), ct.0.coeffs, ct.1.coeffs)
+        };
+        let harness = DeterministicRng::from_seed([42u8; 32]);
+        assert_eq!(harness.run(simulate), harness.run(simulate));
+    }
+}
+
+// known answers which must be identical on every platform.
+#[cfg(all(test, feature = "std"))]
+mod fv_kat_tests {
+    use super::*;
+    use crate::derive::KeySeed;
+    use crate::rng::with_seeded_rng;
+    use sha3::{Digest, Sha3_256};
+
+    fn digest(bytes: &[u8]) -> String {
+        Sha3_256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_known_answers() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair_from_seed(&KeySeed::new([1u8; 32]));
+        let v: FVPlaintext = (0..fv.n).map(|i| i as u8).collect();
+        assert_eq!(
+            digest(&pk.to_bytes()),
+            "a9fa270b1d0ce59f4bfbeef5f4b41f903a06ef747086fb43c5e8002f9a99b823"
+        );
+
+        let mut ct = with_seeded_rng([2u8; 32], || fv.encrypt(&v, &pk));
+        assert_eq!(
+            digest(&ct.to_bytes()),
+            "684b239ac0343d1c0adb709cb9eba29d26c481a559614345ff3c6172302e2240"
+        );
+
+        with_seeded_rng([3u8; 32], || fv.rerandomize(&mut ct, &pk));
+        assert_eq!(
+            digest(&ct.to_bytes()),
+            "c6c1ce74305bfecb35e186f3e634691303453fe65fea10cdb20b2681fad2103f"
+        );
+        assert_eq!(fv.decrypt(&ct, &sk), v);
+    }
 }
 
 // unit tests.
@@ -0,0 +1,592 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Aggregation of encrypted meter readings with proofs of bounded usage.
+//!
+//! In every epoch, each meter encrypts its usage, one byte per register in the first coefficients
+//! of a plaintext, and proves that the ciphertext is a fresh encryption of usage of at most
+//! `max_usage` per register. The proof is the sigma protocol of [`crate::keyproof`] for the
+//! relation (c0, c1) = (a u + e1, b u + e2 + Delta m), with a ternary u, errors e1 and e2 bounded
+//! like those of fresh encryptions, and the coefficients of m in [0, max_usage]: the meter
+//! commits to masks y of its witnesses with w0 = a y_u + y_1 and w1 = b y_u + y_2 + Delta y_m,
+//! and responds to the challenge c, bound to the meter, the epoch and the bound on the usage,
+//! with z = y + c * witness, restarting unless every response is within its bound.
+//!
+//! A [`UsageAggregator`] sums the readings of one epoch whose proof verifies, once per meter, and
+//! rejects the others, so that a malformed reading cannot corrupt the sum. As for public key
+//! proofs, the statement proven is relaxed: the meter knows a short c' and short witnesses with
+//! c' (c0, c1) = (a u' + e1', b u' + e2' + Delta m'), where the coefficients of m' are bounded by
+//! the response bound instead of `max_usage`. The proof thus binds a reading to a bounded witness
+//! of its meter, but is only an approximate range proof: the plaintext modulus is too small for
+//! the bound to carry over exactly to the decrypted usage.
+use crate::integer_arith::ArithUtils;
+use crate::keyproof::{
+    add_sparse_product, challenge_hash, dense_challenge, expand_challenge, sample_centered, within,
+    KeyProofError,
+};
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, SKEncryption, Serializable};
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+
+const DOMAIN: &[u8] = b"cupcake usage proof";
+
+/// Number of non-zero coefficients of a challenge.
+const CHALLENGE_WEIGHT: usize = 60;
+
+/// The reasons for which a reading cannot be produced or aggregated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MeteringError {
+    /// The usage of a register exceeds the bound, or there are more registers than slots.
+    UsageOutOfRange,
+    /// The reading is for another epoch.
+    EpochMismatch { expected: u64, actual: u64 },
+    /// The meter already contributed to this sum.
+    DuplicateMeter(u64),
+    /// Another reading could take a register of the sum to 256.
+    Overflow,
+    /// The ciphertext of the reading is not well-formed.
+    Invalid(ValidationError),
+    /// The proof of the reading does not verify.
+    Proof(KeyProofError),
+}
+
+impl fmt::Display for MeteringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeteringError::UsageOutOfRange => write!(f, "usage out of range"),
+            MeteringError::EpochMismatch { expected, actual } => {
+                write!(f, "reading for epoch {}, expected {}", actual, expected)
+            }
+            MeteringError::DuplicateMeter(meter) => write!(f, "duplicate reading of {}", meter),
+            MeteringError::Overflow => write!(f, "the sum could exceed 255"),
+            MeteringError::Invalid(e) => write!(f, "{}", e),
+            MeteringError::Proof(e) => write!(f, "invalid usage proof: {}", e),
+        }
+    }
+}
+
+impl Error for MeteringError {}
+
+impl From<ValidationError> for MeteringError {
+    fn from(e: ValidationError) -> Self {
+        MeteringError::Invalid(e)
+    }
+}
+
+impl From<KeyProofError> for MeteringError {
+    fn from(e: KeyProofError) -> Self {
+        MeteringError::Proof(e)
+    }
+}
+
+/// A non-interactive proof that a ciphertext encrypts bounded usage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsageProof {
+    /// The hash from which the challenge is expanded.
+    pub challenge: [u8; 32],
+    /// The response for u.
+    pub z_u: Vec<i64>,
+    /// The response for e1.
+    pub z_e1: Vec<i64>,
+    /// The response for e2.
+    pub z_e2: Vec<i64>,
+    /// The response for the usage.
+    pub z_m: Vec<i64>,
+}
+
+/// The encrypted usage of a meter in an epoch, with its proof.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeterReading<T> {
+    /// The identifier of the meter.
+    pub meter: u64,
+    /// The epoch of the reading.
+    pub epoch: u64,
+    /// The encryption of the usage.
+    pub ciphertext: FVCiphertext<T>,
+    /// The proof that the usage is bounded.
+    pub proof: UsageProof,
+}
+
+impl<T> Serializable for MeterReading<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The meter and epoch as 8-byte little-endian integers, the ciphertext, the challenge, and
+    /// the responses as 8-byte little-endian integers.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.meter.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        bytes.extend_from_slice(&self.ciphertext.to_bytes());
+        bytes.extend_from_slice(&self.proof.challenge);
+        let p = &self.proof;
+        for x in p.z_u.iter().chain(&p.z_e1).chain(&p.z_e2).chain(&p.z_m) {
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// The public bounds of a usage proof.
+struct UsageBounds {
+    weight: usize,
+    error: i64,
+    usage: i64,
+    mask_u: i64,
+}
+
+impl UsageBounds {
+    fn mask_e(&self) -> i64 {
+        self.mask_u * self.error
+    }
+
+    fn mask_m(&self) -> i64 {
+        self.mask_u * self.usage.max(1)
+    }
+
+    fn response_u(&self) -> i64 {
+        self.mask_u - self.weight as i64
+    }
+
+    fn response_e(&self) -> i64 {
+        self.mask_e() - self.weight as i64 * self.error
+    }
+
+    fn response_m(&self) -> i64 {
+        self.mask_m() - self.weight as i64 * self.usage
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    fn usage_bounds(&self, max_usage: u8) -> UsageBounds {
+        let weight = CHALLENGE_WEIGHT.min(self.n);
+        UsageBounds {
+            weight,
+            error: self.noise_model().fresh_sk().worst_case as i64,
+            usage: max_usage as i64,
+            // with masks of 2nκ times the largest coefficient of c times each witness, each
+            // response is accepted with probability about exp(-1/2).
+            mask_u: 2 * (self.n * weight) as i64,
+        }
+    }
+
+    /// Delta times a polynomial.
+    fn scale_by_delta(&self, mut poly: RqPoly<T>) -> RqPoly<T> {
+        for x in poly.coeffs.iter_mut() {
+            *x = T::mul_mod(x, &self.delta, &self.q);
+        }
+        poly
+    }
+
+    /// The commitments w0 = a y_u + y_1 - c c0 and w1 = b y_u + y_2 + Delta y_m - c c1, for the
+    /// masks y, or for the responses z and the challenge c when verifying.
+    fn usage_commitments(
+        &self,
+        pk: &FVCiphertext<T>,
+        ys: [&[i64]; 4],
+        c: Option<(&FVCiphertext<T>, &[i64])>,
+    ) -> (RqPoly<T>, RqPoly<T>) {
+        let y_u = self.to_ring(ys[0]);
+        let mut w0 = (self.poly_multiplier)(&pk.0, &y_u);
+        w0.add_inplace(&self.to_ring(ys[1]));
+        let mut w1 = (self.poly_multiplier)(&pk.1, &y_u);
+        w1.add_inplace(&self.to_ring(ys[2]));
+        w1.add_inplace(&self.scale_by_delta(self.to_ring(ys[3])));
+        if let Some((ct, c)) = c {
+            let c = self.to_ring(c);
+            w0.sub_inplace(&(self.poly_multiplier)(&ct.0, &c));
+            w1.sub_inplace(&(self.poly_multiplier)(&ct.1, &c));
+        }
+        (w0, w1)
+    }
+
+    fn usage_challenge(
+        &self,
+        pk: &FVCiphertext<T>,
+        reading: (u64, u64, &FVCiphertext<T>),
+        max_usage: u8,
+        w: &(RqPoly<T>, RqPoly<T>),
+    ) -> [u8; 32] {
+        let (meter, epoch, ct) = reading;
+        let mut extra = meter.to_le_bytes().to_vec();
+        extra.extend_from_slice(&epoch.to_le_bytes());
+        extra.push(max_usage);
+        challenge_hash(DOMAIN, &[&pk.0, &pk.1, &ct.0, &ct.1, &w.0, &w.1], &extra)
+    }
+
+    /// Encrypt the usage of a meter in an epoch, one byte per register, and prove that every
+    /// register is at most `max_usage`.
+    pub fn encrypt_usage(
+        &self,
+        pk: &FVCiphertext<T>,
+        meter: u64,
+        epoch: u64,
+        usage: &[u8],
+        max_usage: u8,
+    ) -> Result<MeterReading<T>, MeteringError> {
+        if usage.len() > self.n || usage.iter().any(|x| *x > max_usage) {
+            return Err(MeteringError::UsageOutOfRange);
+        }
+        self.validate_public_key(pk)?;
+        let bounds = self.usage_bounds(max_usage);
+        let mut pt = usage.to_vec();
+        pt.resize(self.n, 0);
+
+        // resample the rare randomness whose errors exceed the bound of the proof.
+        let (r, mut u, mut e1, mut e2) = loop {
+            let r = self.sample_randomness();
+            let u = self.centered(&r.u);
+            let e1 = self.centered(&r.e1);
+            let e2 = self.centered(&r.e2);
+            if within(&e1, bounds.error) && within(&e2, bounds.error) {
+                break (r, u, e1, e2);
+            }
+        };
+        let ciphertext = self.encrypt_with_randomness(&pt, pk, &r);
+        let mut m: Vec<i64> = pt.iter().map(|x| *x as i64).collect();
+        wipe(&mut pt);
+
+        let proof = loop {
+            let mut ys = [
+                sample_centered(self.n, bounds.mask_u),
+                sample_centered(self.n, bounds.mask_e()),
+                sample_centered(self.n, bounds.mask_e()),
+                sample_centered(self.n, bounds.mask_m()),
+            ];
+            let w = self.usage_commitments(pk, [&ys[0], &ys[1], &ys[2], &ys[3]], None);
+            let challenge = self.usage_challenge(pk, (meter, epoch, &ciphertext), max_usage, &w);
+            let c = expand_challenge(&challenge, self.n, bounds.weight);
+            let mut z = [
+                add_sparse_product(&ys[0], &c, &u),
+                add_sparse_product(&ys[1], &c, &e1),
+                add_sparse_product(&ys[2], &c, &e2),
+                add_sparse_product(&ys[3], &c, &m),
+            ];
+            ys.iter_mut().for_each(wipe);
+            if within(&z[0], bounds.response_u())
+                && within(&z[1], bounds.response_e())
+                && within(&z[2], bounds.response_e())
+                && within(&z[3], bounds.response_m())
+            {
+                let [z_u, z_e1, z_e2, z_m] = z;
+                break UsageProof {
+                    challenge,
+                    z_u,
+                    z_e1,
+                    z_e2,
+                    z_m,
+                };
+            }
+            z.iter_mut().for_each(wipe);
+        };
+        wipe(&mut u);
+        wipe(&mut e1);
+        wipe(&mut e2);
+        wipe(&mut m);
+        Ok(MeterReading {
+            meter,
+            epoch,
+            ciphertext,
+            proof,
+        })
+    }
+
+    /// Verify the proof of a reading, for usage bounded by `max_usage`.
+    pub fn verify_usage(
+        &self,
+        pk: &FVCiphertext<T>,
+        reading: &MeterReading<T>,
+        max_usage: u8,
+    ) -> Result<(), KeyProofError> {
+        self.validate_public_key(pk)?;
+        self.validate(&reading.ciphertext)?;
+        let bounds = self.usage_bounds(max_usage);
+        let p = &reading.proof;
+        let responses = [&p.z_u, &p.z_e1, &p.z_e2, &p.z_m];
+        if responses.iter().any(|z| z.len() != self.n) {
+            return Err(KeyProofError::WrongLength);
+        }
+        if !within(&p.z_u, bounds.response_u())
+            || !within(&p.z_e1, bounds.response_e())
+            || !within(&p.z_e2, bounds.response_e())
+            || !within(&p.z_m, bounds.response_m())
+        {
+            return Err(KeyProofError::ResponseOutOfRange);
+        }
+        let c = dense_challenge(&p.challenge, self.n, bounds.weight);
+        let w = self.usage_commitments(
+            pk,
+            [&p.z_u, &p.z_e1, &p.z_e2, &p.z_m],
+            Some((&reading.ciphertext, &c)),
+        );
+        let challenge = self.usage_challenge(
+            pk,
+            (reading.meter, reading.epoch, &reading.ciphertext),
+            max_usage,
+            &w,
+        );
+        if challenge == p.challenge {
+            Ok(())
+        } else {
+            Err(KeyProofError::ChallengeMismatch)
+        }
+    }
+
+    /// Decode a reading. Its ciphertext is validated, but its proof is only verified by
+    /// [`FV::verify_usage`].
+    pub fn meter_reading_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<MeterReading<T>, ValidationError> {
+        let ct_len = self.ciphertext_byte_len();
+        let expected = 16 + ct_len + 32 + 4 * 8 * self.n;
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let word = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        let ciphertext = self.ciphertext_from_bytes(&bytes[16..16 + ct_len])?;
+        let mut challenge = [0u8; 32];
+        challenge.copy_from_slice(&bytes[16 + ct_len..48 + ct_len]);
+        let response = |k: usize| -> Vec<i64> {
+            let start = 48 + ct_len + 8 * k * self.n;
+            (0..self.n).map(|i| word(start + 8 * i) as i64).collect()
+        };
+        Ok(MeterReading {
+            meter: word(0),
+            epoch: word(8),
+            ciphertext,
+            proof: UsageProof {
+                challenge,
+                z_u: response(0),
+                z_e1: response(1),
+                z_e2: response(2),
+                z_m: response(3),
+            },
+        })
+    }
+}
+
+/// The sum of the verified readings of one epoch.
+pub struct UsageAggregator<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    pk: FVCiphertext<T>,
+    epoch: u64,
+    max_usage: u8,
+    meters: HashSet<u64>,
+    sum: FVCiphertext<T>,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for UsageAggregator<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "UsageAggregator(epoch {}, {} meters)",
+            self.epoch,
+            self.meters.len()
+        )
+    }
+}
+
+impl<'a, T> UsageAggregator<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// An empty sum of the readings of `epoch` under `pk`, of usage bounded by `max_usage`.
+    pub fn new(fv: &'a FV<T>, pk: FVCiphertext<T>, epoch: u64, max_usage: u8) -> Self {
+        UsageAggregator {
+            fv,
+            pk,
+            epoch,
+            max_usage,
+            meters: HashSet::new(),
+            sum: fv.trivial_zero(),
+        }
+    }
+
+    /// The number of readings in the sum.
+    pub fn readings(&self) -> usize {
+        self.meters.len()
+    }
+
+    /// The number of readings whose sum cannot exceed 255.
+    pub fn capacity(&self) -> usize {
+        255 / (self.max_usage.max(1) as usize)
+    }
+
+    /// The encrypted sum.
+    pub fn sum(&self) -> &FVCiphertext<T> {
+        &self.sum
+    }
+
+    /// Verify a reading and add it to the sum.
+    pub fn add(&mut self, reading: &MeterReading<T>) -> Result<(), MeteringError> {
+        if reading.epoch != self.epoch {
+            return Err(MeteringError::EpochMismatch {
+                expected: self.epoch,
+                actual: reading.epoch,
+            });
+        }
+        if self.meters.contains(&reading.meter) {
+            return Err(MeteringError::DuplicateMeter(reading.meter));
+        }
+        if self.readings() == self.capacity() {
+            return Err(MeteringError::Overflow);
+        }
+        self.fv.verify_usage(&self.pk, reading, self.max_usage)?;
+        self.fv.add_inplace(&mut self.sum, &reading.ciphertext);
+        self.meters.insert(reading.meter);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            params_id = %self.fv.params_id(),
+            readings = self.readings(),
+            "added a meter reading"
+        );
+        Ok(())
+    }
+
+    /// The total usage per register, decrypted with the secret key.
+    pub fn totals(&self, sk: &SecretKey<T>) -> FVPlaintext {
+        self.fv.decrypt(&self.sum, sk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::PKEncryption;
+
+    #[test]
+    fn test_metering() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut aggregator = UsageAggregator::new(&fv, pk.clone(), 7, 50);
+        assert_eq!(aggregator.capacity(), 5);
+        for meter in 0..3u64 {
+            let usage = [10 * meter as u8, 50, 1];
+            let reading = fv.encrypt_usage(&pk, meter, 7, &usage, 50).unwrap();
+            let reading = fv.meter_reading_from_bytes(&reading.to_bytes()).unwrap();
+            aggregator.add(&reading).unwrap();
+        }
+        let totals = aggregator.totals(&sk);
+        assert_eq!(totals[..4], [30, 150, 3, 0]);
+
+        let reading = fv.encrypt_usage(&pk, 1, 7, &[1], 50).unwrap();
+        assert_eq!(
+            aggregator.add(&reading),
+            Err(MeteringError::DuplicateMeter(1))
+        );
+        let reading = fv.encrypt_usage(&pk, 5, 8, &[1], 50).unwrap();
+        assert_eq!(
+            aggregator.add(&reading),
+            Err(MeteringError::EpochMismatch {
+                expected: 7,
+                actual: 8
+            })
+        );
+        assert_eq!(
+            fv.encrypt_usage(&pk, 5, 7, &[51], 50),
+            Err(MeteringError::UsageOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_reject_forged_readings() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair();
+        let mut aggregator = UsageAggregator::new(&fv, pk.clone(), 1, 10);
+        let reading = fv.encrypt_usage(&pk, 3, 1, &[4, 4], 10).unwrap();
+
+        // Adding 200 to a register after the fact invalidates the proof.
+        let mut forged = reading.clone();
+        let mut pt = vec![0; fv.n];
+        pt[0] = 200;
+        fv.add_plain_inplace(&mut forged.ciphertext, &pt);
+        assert_eq!(
+            aggregator.add(&forged),
+            Err(MeteringError::Proof(KeyProofError::ChallengeMismatch))
+        );
+        // The proof is bound to the meter and to the bound on the usage.
+        let mut forged = reading.clone();
+        forged.meter = 4;
+        assert!(aggregator.add(&forged).is_err());
+        assert!(fv.verify_usage(&pk, &reading, 9).is_err());
+
+        let mut forged = reading.clone();
+        forged.proof.z_m[0] = i64::MAX;
+        assert_eq!(
+            aggregator.add(&forged),
+            Err(MeteringError::Proof(KeyProofError::ResponseOutOfRange))
+        );
+        aggregator.add(&reading).unwrap();
+        assert_eq!(aggregator.readings(), 1);
+    }
+
+    #[test]
+    fn test_metering_error_paths() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair();
+        let reading = fv.encrypt_usage(&pk, 3, 1, &[200], 255).unwrap();
+        let bytes = reading.to_bytes();
+
+        // encodings of the wrong length, or with a malformed ciphertext, are rejected.
+        assert_eq!(
+            fv.meter_reading_from_bytes(&bytes[1..]),
+            Err(ValidationError::WrongLength {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            })
+        );
+        let mut tampered = bytes.clone();
+        tampered[16 + 8..16 + 16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(fv.meter_reading_from_bytes(&tampered).is_err());
+
+        // a tampered challenge or a truncated response fails verification.
+        let mut aggregator = UsageAggregator::new(&fv, pk.clone(), 1, 255);
+        assert_eq!(aggregator.capacity(), 1);
+        let mut tampered = bytes.clone();
+        tampered[16 + fv.ciphertext_byte_len()] ^= 1;
+        let forged = fv.meter_reading_from_bytes(&tampered).unwrap();
+        assert_eq!(
+            aggregator.add(&forged),
+            Err(MeteringError::Proof(KeyProofError::ChallengeMismatch))
+        );
+        let mut forged = reading.clone();
+        forged.proof.z_u.pop();
+        assert_eq!(
+            aggregator.add(&forged),
+            Err(MeteringError::Proof(KeyProofError::WrongLength))
+        );
+
+        // a second reading of 255 could overflow the registers.
+        aggregator.add(&reading).unwrap();
+        let other = fv.encrypt_usage(&pk, 4, 1, &[1], 255).unwrap();
+        assert_eq!(aggregator.add(&other), Err(MeteringError::Overflow));
+
+        // a malformed public key is rejected before encrypting.
+        let mut bad_pk = pk.clone();
+        bad_pk.0.coeffs.pop();
+        assert!(matches!(
+            fv.encrypt_usage(&bad_pk, 5, 1, &[1], 255),
+            Err(MeteringError::Invalid(ValidationError::WrongDegree { .. }))
+        ));
+        assert_eq!(
+            MeteringError::Overflow.to_string(),
+            "the sum could exceed 255"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Mixing of ciphertexts.
+//!
+//! A [`Mixer`] receives a batch of ciphertexts under a public key, rerandomizes them and returns
+//! them in a random order, so that no one without the secret key can link an output to an input.
+//! The mixer also returns a commitment H(tag || nonce || permutation) to the permutation it
+//! applied, and keeps the opening, which it can later reveal to an auditor. The i-th output is
+//! the rerandomization of the input at position `permutation[i]`.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::scratch::NoiseScratch;
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use rand::{Rng, RngCore};
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+/// A batch of ciphertexts to mix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MixRequest<T> {
+    pub ciphertexts: Vec<FVCiphertext<T>>,
+}
+
+/// The mixed ciphertexts, with a commitment to the permutation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MixResponse<T> {
+    pub ciphertexts: Vec<FVCiphertext<T>>,
+    pub commitment: [u8; 32],
+}
+
+/// The opening of a permutation commitment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PermutationOpening {
+    pub permutation: Vec<u32>,
+    pub nonce: [u8; 32],
+}
+
+impl PermutationOpening {
+    fn commitment(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(b"cupcake mix permutation");
+        hasher.update(self.nonce);
+        hasher.update((self.permutation.len() as u32).to_le_bytes());
+        for i in self.permutation.iter() {
+            hasher.update(i.to_le_bytes());
+        }
+        let mut commitment = [0u8; 32];
+        commitment.copy_from_slice(&hasher.finalize());
+        commitment
+    }
+
+    /// Check that this opening matches a commitment, and is a permutation of `len` elements.
+    pub fn verify(&self, commitment: &[u8; 32], len: usize) -> bool {
+        let mut seen = vec![false; len];
+        for &i in self.permutation.iter() {
+            match seen.get_mut(i as usize) {
+                Some(s) if !*s => *s = true,
+                _ => return false,
+            }
+        }
+        self.permutation.len() == len && self.commitment() == *commitment
+    }
+}
+
+/// The reasons for which a mixer rejects a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MixError {
+    /// The request holds no ciphertexts.
+    EmptyBatch,
+    /// A ciphertext is not well-formed.
+    Malformed(ValidationError),
+}
+
+impl fmt::Display for MixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MixError::EmptyBatch => write!(f, "empty batch"),
+            MixError::Malformed(e) => write!(f, "malformed ciphertext: {}", e),
+        }
+    }
+}
+
+impl Error for MixError {}
+
+impl From<ValidationError> for MixError {
+    fn from(e: ValidationError) -> Self {
+        MixError::Malformed(e)
+    }
+}
+
+/// The mixer role.
+pub trait Mixer<T> {
+    /// Rerandomize and shuffle a batch of ciphertexts.
+    fn mix(&mut self, request: MixRequest<T>) -> Result<MixResponse<T>, MixError>;
+
+    /// The opening of the permutation with the given commitment, if it was applied by this
+    /// mixer.
+    fn open(&self, commitment: &[u8; 32]) -> Option<PermutationOpening>;
+}
+
+/// A mixer running in the current process, which keeps the openings of all its permutations.
+pub struct LocalMixer<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    pk: FVCiphertext<T>,
+    openings: Vec<([u8; 32], PermutationOpening)>,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for LocalMixer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LocalMixer({} batches)", self.openings.len())
+    }
+}
+
+impl<'a, T: ArithUtils<T>> LocalMixer<'a, T> {
+    /// A mixer for ciphertexts under `pk`.
+    pub fn new(fv: &'a FV<T>, pk: FVCiphertext<T>) -> Self {
+        LocalMixer {
+            fv,
+            pk,
+            openings: vec![],
+        }
+    }
+}
+
+impl<'a, T> Mixer<T> for LocalMixer<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    fn mix(&mut self, request: MixRequest<T>) -> Result<MixResponse<T>, MixError> {
+        if request.ciphertexts.is_empty() {
+            return Err(MixError::EmptyBatch);
+        }
+        for ct in request.ciphertexts.iter() {
+            self.fv.validate(ct)?;
+        }
+        let len = request.ciphertexts.len();
+        let mut permutation: Vec<u32> = (0..len as u32).collect();
+        let mut nonce = [0u8; 32];
+        with_rng(|rng| {
+            // Fisher-Yates shuffle.
+            for i in (1..len).rev() {
+                permutation.swap(i, rng.gen_range(0..=i));
+            }
+            rng.fill_bytes(&mut nonce);
+        });
+
+        let mut inputs: Vec<Option<FVCiphertext<T>>> =
+            request.ciphertexts.into_iter().map(Some).collect();
+        let mut scratch = NoiseScratch::new(self.fv);
+        let ciphertexts = permutation
+            .iter()
+            .map(|&i| {
+                let mut ct = inputs[i as usize]
+                    .take()
+                    .expect("permutation entries are distinct");
+                self.fv
+                    .rerandomize_with_pool(&mut ct, &self.pk, &mut scratch);
+                ct
+            })
+            .collect();
+        let opening = PermutationOpening { permutation, nonce };
+        let commitment = opening.commitment();
+        self.openings.push((commitment, opening));
+        Ok(MixResponse {
+            ciphertexts,
+            commitment,
+        })
+    }
+
+    fn open(&self, commitment: &[u8; 32]) -> Option<PermutationOpening> {
+        self.openings
+            .iter()
+            .find(|(c, _)| c == commitment)
+            .map(|(_, opening)| opening.clone())
+    }
+}
+
+fn encode_batch<T>(ciphertexts: &[FVCiphertext<T>]) -> Vec<u8>
+where
+    T: ArithUtils<T> + Clone,
+{
+    let mut bytes = (ciphertexts.len() as u32).to_le_bytes().to_vec();
+    for ct in ciphertexts.iter() {
+        bytes.extend_from_slice(&ct.to_bytes());
+    }
+    bytes
+}
+
+impl<T> Serializable for MixRequest<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The number of ciphertexts as a 4-byte little-endian integer, followed by the ciphertexts.
+    fn to_bytes(&self) -> Vec<u8> {
+        encode_batch(&self.ciphertexts)
+    }
+}
+
+impl<T> Serializable for MixResponse<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The commitment, followed by the ciphertexts encoded as in a request.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.commitment.to_vec();
+        bytes.extend_from_slice(&encode_batch(&self.ciphertexts));
+        bytes
+    }
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Decode and validate a mix request.
+    pub fn mix_request_from_bytes(&self, bytes: &[u8]) -> Result<MixRequest<T>, ValidationError> {
+        if bytes.len() < 4 {
+            return Err(ValidationError::WrongLength {
+                expected: 4,
+                actual: bytes.len(),
+            });
+        }
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&bytes[..4]);
+        let count = u32::from_le_bytes(word) as usize;
+        let ct_len = self.ciphertext_byte_len();
+        let expected = count
+            .checked_mul(ct_len)
+            .and_then(|len| len.checked_add(4))
+            .unwrap_or(usize::MAX);
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let ciphertexts = bytes[4..]
+            .chunks_exact(ct_len)
+            .map(|chunk| self.ciphertext_from_bytes(chunk))
+            .collect::<Result<_, _>>()?;
+        Ok(MixRequest { ciphertexts })
+    }
+
+    /// Decode and validate a mix response.
+    pub fn mix_response_from_bytes(&self, bytes: &[u8]) -> Result<MixResponse<T>, ValidationError> {
+        if bytes.len() < 32 {
+            return Err(ValidationError::WrongLength {
+                expected: 32,
+                actual: bytes.len(),
+            });
+        }
+        let mut commitment = [0u8; 32];
+        commitment.copy_from_slice(&bytes[..32]);
+        let request = self.mix_request_from_bytes(&bytes[32..])?;
+        Ok(MixResponse {
+            ciphertexts: request.ciphertexts,
+            commitment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_mix() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut mixer = LocalMixer::new(&fv, pk.clone());
+        let request = MixRequest {
+            ciphertexts: (0..5u8).map(|i| fv.encrypt(&vec![i; fv.n], &pk)).collect(),
+        };
+        let request = fv.mix_request_from_bytes(&request.to_bytes()).unwrap();
+        let response = mixer.mix(request.clone()).unwrap();
+        let response = fv.mix_response_from_bytes(&response.to_bytes()).unwrap();
+
+        let opening = mixer.open(&response.commitment).unwrap();
+        assert!(opening.verify(&response.commitment, 5));
+        for (ct, &i) in response.ciphertexts.iter().zip(opening.permutation.iter()) {
+            assert_eq!(fv.decrypt(ct, &sk), vec![i as u8; fv.n]);
+            assert_ne!(ct.1.coeffs, request.ciphertexts[i as usize].1.coeffs);
+        }
+
+        let mut forged = opening.clone();
+        forged.permutation.swap(0, 1);
+        assert!(!forged.verify(&response.commitment, 5));
+        assert!(!opening.verify(&response.commitment, 4));
+        assert_eq!(
+            mixer
+                .mix(MixRequest {
+                    ciphertexts: vec![]
+                })
+                .unwrap_err(),
+            MixError::EmptyBatch
+        );
+    }
+}
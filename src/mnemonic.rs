@@ -0,0 +1,137 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Mnemonic backups of key seeds.
+//!
+//! A [`KeySeed`] is exported as the 24 English words of the BIP39 encoding of its 32 bytes, the
+//! last word of which carries an 8-bit checksum, so that an operator can keep an offline paper
+//! backup of every key derived from it. Unlike in BIP39 wallets, the words encode the seed
+//! itself: it is not stretched with a passphrase.
+use crate::derive::KeySeed;
+use bip39::{Language, Mnemonic};
+use std::error::Error;
+use std::fmt;
+
+/// The number of words of a mnemonic.
+pub const MNEMONIC_WORDS: usize = 24;
+
+/// The reasons for which a mnemonic is rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MnemonicError {
+    /// The mnemonic does not have 24 words.
+    WrongWordCount { expected: usize, actual: usize },
+    /// The word at this position is not in the word list.
+    UnknownWord { position: usize },
+    /// The checksum does not match, e.g. because two words were swapped.
+    ChecksumMismatch,
+}
+
+impl fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MnemonicError::WrongWordCount { expected, actual } => {
+                write!(f, "expected {} words, found {}", expected, actual)
+            }
+            MnemonicError::UnknownWord { position } => {
+                write!(f, "word {} is not in the word list", position)
+            }
+            MnemonicError::ChecksumMismatch => write!(f, "mnemonic checksum mismatch"),
+        }
+    }
+}
+
+impl Error for MnemonicError {}
+
+impl KeySeed {
+    /// The mnemonic of this seed, as 24 words separated by spaces.
+    pub fn to_mnemonic(&self) -> String {
+        Mnemonic::from_entropy_in(Language::English, self.as_bytes())
+            .expect("32 bytes of entropy have a mnemonic")
+            .to_string()
+    }
+
+    /// Recover a seed from its mnemonic. Words may be separated by any whitespace and are
+    /// case-insensitive.
+    pub fn from_mnemonic(phrase: &str) -> Result<KeySeed, MnemonicError> {
+        let words: Vec<String> = phrase
+            .split_whitespace()
+            .map(|w| w.to_lowercase())
+            .collect();
+        if words.len() != MNEMONIC_WORDS {
+            return Err(MnemonicError::WrongWordCount {
+                expected: MNEMONIC_WORDS,
+                actual: words.len(),
+            });
+        }
+        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &words.join(" ")).map_err(
+            |e| match e {
+                bip39::Error::UnknownWord(position) => MnemonicError::UnknownWord { position },
+                _ => MnemonicError::ChecksumMismatch,
+            },
+        )?;
+        let (entropy, len) = mnemonic.to_entropy_array();
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(&entropy[..len]);
+        Ok(KeySeed::new(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::FV;
+
+    #[test]
+    fn test_mnemonic_roundtrip() {
+        let seed = KeySeed::generate();
+        let phrase = seed.to_mnemonic();
+        assert_eq!(phrase.split(' ').count(), MNEMONIC_WORDS);
+        let recovered = KeySeed::from_mnemonic(&phrase.to_uppercase()).unwrap();
+        assert_eq!(recovered.as_bytes(), seed.as_bytes());
+
+        let zero = KeySeed::new([0u8; 32]).to_mnemonic();
+        assert!(zero.starts_with("abandon abandon"));
+        assert!(zero.ends_with("art"));
+    }
+
+    #[test]
+    fn test_restored_key_known_answer() {
+        // A paper backup must restore the same key with every later version of the crate.
+        let fv = FV::<Scalar>::default_2048();
+        let phrase = ["abandon"; 23].join(" ") + " art";
+        let seed = KeySeed::from_mnemonic(&phrase).unwrap();
+        let (pk, _) = fv.generate_keypair_from_seed(&seed);
+        assert_eq!(
+            fv.public_key_fingerprint(&pk).to_string(),
+            "cdd3513c09fe0f71d39ad86151520785"
+        );
+    }
+
+    #[test]
+    fn test_invalid_mnemonics() {
+        let phrase = KeySeed::new([1u8; 32]).to_mnemonic();
+        let mut words: Vec<_> = phrase.split(' ').collect();
+        assert_eq!(
+            KeySeed::from_mnemonic(&words[1..].join(" ")).unwrap_err(),
+            MnemonicError::WrongWordCount {
+                expected: 24,
+                actual: 23
+            }
+        );
+        words[3] = "cupcake";
+        assert_eq!(
+            KeySeed::from_mnemonic(&words.join(" ")).unwrap_err(),
+            MnemonicError::UnknownWord { position: 3 }
+        );
+        let mut words: Vec<_> = phrase.split(' ').collect();
+        words.swap(0, 1);
+        if words[0] != words[1] {
+            assert_eq!(
+                KeySeed::from_mnemonic(&words.join(" ")).unwrap_err(),
+                MnemonicError::ChecksumMismatch
+            );
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Kotlin and Swift bindings through UniFFI, with the `mobile` feature.
+//!
+//! An app creates a [`Scheme`] with the default parameters, and encrypts telemetry on-device
+//! under the encoded public key of the collector. Keys and ciphertexts cross the boundary in
+//! their canonical encoding, as `ByteArray`s in Kotlin and `Data` in Swift. A [`KeyPair`] keeps
+//! its secret key inside the library, and only exposes its public key.
+//!
+//! The library is built for a device with `cargo rustc --release --features mobile
+//! --crate-type cdylib --target aarch64-linux-android` (or `--crate-type staticlib` and
+//! `aarch64-apple-ios` for iOS), and the bindings are generated from it with
+//! `uniffi-bindgen generate --library`.
+//!
+//! All randomness is drawn through [`crate::rng`], whose generators are seeded from `OsRng`,
+//! that is the `getrandom` system call, or `/dev/urandom` on older kernels, on Android, and
+//! `SecRandomCopyBytes` on iOS. There is no fallback to a weaker source: if the OS generator
+//! fails, seeding panics.
+use crate::error::CupcakeError;
+use crate::integer_arith::scalar::Scalar;
+use crate::pubkey::PublicKey;
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// An error raised to Kotlin or Swift, with the message of the underlying [`CupcakeError`].
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Error)]
+pub enum MobileError {
+    /// A plaintext does not have the length of the scheme.
+    WrongLength { message: String },
+    /// An encoded key or ciphertext is malformed.
+    InvalidEncoding { message: String },
+    /// A key or ciphertext belongs to a scheme with other parameters.
+    ParameterMismatch { message: String },
+}
+
+impl fmt::Display for MobileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MobileError::WrongLength { message }
+            | MobileError::InvalidEncoding { message }
+            | MobileError::ParameterMismatch { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for MobileError {}
+
+impl From<CupcakeError> for MobileError {
+    fn from(e: CupcakeError) -> Self {
+        let message = e.to_string();
+        match e {
+            CupcakeError::PlaintextLength { .. } | CupcakeError::SecretKeyLength { .. } => {
+                MobileError::WrongLength { message }
+            }
+            CupcakeError::Invalid(ValidationError::ParameterMismatch { .. })
+            | CupcakeError::WrongScheme => MobileError::ParameterMismatch { message },
+            CupcakeError::Invalid(_) => MobileError::InvalidEncoding { message },
+        }
+    }
+}
+
+impl From<ValidationError> for MobileError {
+    fn from(e: ValidationError) -> Self {
+        CupcakeError::from(e).into()
+    }
+}
+
+/// The default scheme.
+#[derive(uniffi::Object)]
+pub struct Scheme {
+    fv: FV<Scalar>,
+}
+
+/// A keypair of a [`Scheme`].
+#[derive(uniffi::Object)]
+pub struct KeyPair {
+    pk: PublicKey<Scalar>,
+    sk: SecretKey<Scalar>,
+}
+
+#[uniffi::export]
+impl KeyPair {
+    /// The encoding of the public key.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.pk.to_bytes()
+    }
+}
+
+impl Default for Scheme {
+    fn default() -> Self {
+        Scheme {
+            fv: FV::<Scalar>::default_2048(),
+        }
+    }
+}
+
+#[uniffi::export]
+impl Scheme {
+    /// The scheme with the default parameters.
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Scheme::default())
+    }
+
+    /// The number of bytes of a plaintext.
+    pub fn plaintext_length(&self) -> u32 {
+        self.fv.n as u32
+    }
+
+    /// Generate a keypair.
+    pub fn generate_keypair(&self) -> Arc<KeyPair> {
+        let (pk, sk) = self.fv.generate_typed_keypair();
+        Arc::new(KeyPair { pk, sk })
+    }
+
+    /// Encrypt a plaintext under an encoded public key, and return the encoded ciphertext.
+    pub fn encrypt(&self, pt: Vec<u8>, public_key: Vec<u8>) -> Result<Vec<u8>, MobileError> {
+        let pk = self.fv.typed_public_key_from_bytes(&public_key)?;
+        Ok(self.fv.try_encrypt(&pt, pk.as_pair())?.to_bytes())
+    }
+
+    /// The encoding of the sum of two encoded ciphertexts.
+    pub fn add(&self, ct1: Vec<u8>, ct2: Vec<u8>) -> Result<Vec<u8>, MobileError> {
+        let mut sum = self.fv.ciphertext_from_bytes(&ct1)?;
+        self.fv
+            .try_add_inplace(&mut sum, &self.fv.ciphertext_from_bytes(&ct2)?)?;
+        Ok(sum.to_bytes())
+    }
+
+    /// Rerandomize an encoded ciphertext under an encoded public key.
+    pub fn rerandomize(&self, ct: Vec<u8>, public_key: Vec<u8>) -> Result<Vec<u8>, MobileError> {
+        let mut ct = self.fv.ciphertext_from_bytes(&ct)?;
+        let pk = self.fv.typed_public_key_from_bytes(&public_key)?;
+        self.fv.try_rerandomize(&mut ct, pk.as_pair())?;
+        Ok(ct.to_bytes())
+    }
+
+    /// Decrypt an encoded ciphertext with the secret key of a keypair.
+    pub fn decrypt(&self, ct: Vec<u8>, keypair: Arc<KeyPair>) -> Result<Vec<u8>, MobileError> {
+        let ct = self.fv.ciphertext_from_bytes(&ct)?;
+        Ok(self.fv.try_decrypt(&ct, &keypair.sk)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mobile_bindings() {
+        let scheme = Scheme::new();
+        let n = scheme.plaintext_length() as usize;
+        let keypair = scheme.generate_keypair();
+        let pk = keypair.public_key();
+        let a = scheme.encrypt(vec![2; n], pk.clone()).unwrap();
+        let b = scheme.encrypt(vec![3; n], pk.clone()).unwrap();
+        let sum = scheme.add(a.clone(), b).unwrap();
+        let sum = scheme.rerandomize(sum, pk.clone()).unwrap();
+        assert_eq!(scheme.decrypt(sum, keypair).unwrap(), vec![5; n]);
+
+        assert!(matches!(
+            scheme.encrypt(vec![1; 3], pk.clone()),
+            Err(MobileError::WrongLength { .. })
+        ));
+        // A ciphertext is not a valid public key: it is not in NTT form.
+        assert!(matches!(
+            scheme.encrypt(vec![1; n], a),
+            Err(MobileError::InvalidEncoding { .. })
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Additive homomorphism across public keys.
+//!
+//! A ciphertext (c0, c1) under the key s has the phase c1 - c0 * s. Ciphertexts under the keys
+//! s_1, ..., s_k add up to an expanded ciphertext (c1, c0_1, ..., c0_k) with the phase
+//! c1 - c0_1 * s_1 - ... - c0_k * s_k: the c1 parts are summed into a common body, and the c0
+//! parts are summed per key. Decryption needs every key holder to compute its partial decryption
+//! d_i = c0_i * s_i + f_i, flooded as in [`crate::threshold`], after which the plaintext is
+//! recovered from c1 - (d_1 + ... + d_k). Keys are identified by their public key fingerprints.
+use crate::fingerprint::Fingerprint;
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{randutils, FiniteRingElt, RqPoly};
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// A ciphertext under several public keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiKeyCiphertext<T> {
+    body: RqPoly<T>,
+    components: Vec<(Fingerprint, RqPoly<T>)>,
+}
+
+impl<T> MultiKeyCiphertext<T> {
+    /// The fingerprints of the public keys whose secret keys are needed for decryption, in
+    /// increasing order.
+    pub fn keys(&self) -> Vec<Fingerprint> {
+        self.components.iter().map(|(key, _)| *key).collect()
+    }
+}
+
+/// The partial decryption of a multi-key ciphertext by the holder of one secret key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultiKeyPartialDecryption<T> {
+    key: Fingerprint,
+    poly: RqPoly<T>,
+}
+
+impl<T> MultiKeyPartialDecryption<T> {
+    /// The fingerprint of the public key of the secret key used.
+    pub fn key(&self) -> Fingerprint {
+        self.key
+    }
+}
+
+/// The reasons for which a multi-key ciphertext cannot be decrypted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MultiKeyError {
+    /// The secret key was not generated with its public key, so its fingerprint is unknown.
+    UnknownKey,
+    /// The ciphertext is not encrypted under this key.
+    KeyNotInvolved(Fingerprint),
+    /// There are several partial decryptions with this key.
+    DuplicatePartial(Fingerprint),
+    /// The partial decryptions for these keys are missing.
+    MissingPartials(Vec<Fingerprint>),
+}
+
+impl fmt::Display for MultiKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultiKeyError::UnknownKey => write!(f, "the public key of the secret key is unknown"),
+            MultiKeyError::KeyNotInvolved(key) => {
+                write!(f, "the ciphertext is not encrypted under key {}", key)
+            }
+            MultiKeyError::DuplicatePartial(key) => {
+                write!(f, "several partial decryptions with key {}", key)
+            }
+            MultiKeyError::MissingPartials(keys) => {
+                write!(f, "missing partial decryptions for keys")?;
+                for key in keys.iter() {
+                    write!(f, " {}", key)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for MultiKeyError {}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Expand a ciphertext under `pk` into a multi-key ciphertext.
+    pub fn to_multi_key(
+        &self,
+        ct: &FVCiphertext<T>,
+        pk: &FVCiphertext<T>,
+    ) -> MultiKeyCiphertext<T> {
+        MultiKeyCiphertext {
+            body: ct.1.clone(),
+            components: vec![(self.public_key_fingerprint(pk), ct.0.clone())],
+        }
+    }
+
+    /// Add a ciphertext under `pk` to a multi-key ciphertext.
+    pub fn add_multi_key_inplace(
+        &self,
+        mk: &mut MultiKeyCiphertext<T>,
+        ct: &FVCiphertext<T>,
+        pk: &FVCiphertext<T>,
+    ) {
+        let other = self.to_multi_key(ct, pk);
+        self.add_multi_keys_inplace(mk, &other);
+    }
+
+    /// Add two multi-key ciphertexts, whose sets of keys may differ.
+    pub fn add_multi_keys_inplace(
+        &self,
+        mk: &mut MultiKeyCiphertext<T>,
+        other: &MultiKeyCiphertext<T>,
+    ) {
+        mk.body.add_inplace(&other.body);
+        for (key, c0) in other.components.iter() {
+            match mk.components.binary_search_by(|(k, _)| k.cmp(key)) {
+                Ok(i) => mk.components[i].1.add_inplace(c0),
+                Err(i) => mk.components.insert(i, (*key, c0.clone())),
+            }
+        }
+    }
+
+    /// Partially decrypt a multi-key ciphertext with one of its secret keys. The result is
+    /// flooded with noise of standard deviation `self.flooding_stdev`.
+    pub fn multi_key_partial_decrypt(
+        &self,
+        mk: &MultiKeyCiphertext<T>,
+        sk: &SecretKey<T>,
+    ) -> Result<MultiKeyPartialDecryption<T>, MultiKeyError> {
+        let key = sk
+            .public_key_fingerprint()
+            .ok_or(MultiKeyError::UnknownKey)?;
+        let (_, c0) = mk
+            .components
+            .iter()
+            .find(|(k, _)| *k == key)
+            .ok_or(MultiKeyError::KeyNotInvolved(key))?;
+        let mut poly = self.multiply_secret(c0, sk);
+        poly.add_inplace(&randutils::sample_flooding_poly(
+            self.context.clone(),
+            self.flooding_stdev,
+        ));
+        Ok(MultiKeyPartialDecryption { key, poly })
+    }
+
+    /// Combine the partial decryptions of a multi-key ciphertext by all its keys into its
+    /// plaintext.
+    pub fn combine_multi_key_partials(
+        &self,
+        mk: &MultiKeyCiphertext<T>,
+        partials: &[MultiKeyPartialDecryption<T>],
+    ) -> Result<FVPlaintext, MultiKeyError> {
+        let mut seen = vec![false; mk.components.len()];
+        for p in partials.iter() {
+            let i = mk
+                .components
+                .binary_search_by(|(k, _)| k.cmp(&p.key))
+                .map_err(|_| MultiKeyError::KeyNotInvolved(p.key))?;
+            if seen[i] {
+                return Err(MultiKeyError::DuplicatePartial(p.key));
+            }
+            seen[i] = true;
+        }
+        let missing: Vec<_> = mk
+            .components
+            .iter()
+            .zip(seen.iter())
+            .filter(|(_, seen)| !**seen)
+            .map(|((key, _), _)| *key)
+            .collect();
+        if !missing.is_empty() {
+            return Err(MultiKeyError::MissingPartials(missing));
+        }
+
+        let mut phase = mk.body.clone();
+        for p in partials.iter() {
+            phase.sub_inplace(&p.poly);
+        }
+        Ok(self.decode_phase(&phase))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_multi_key_addition() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk1, sk1) = fv.generate_keypair();
+        let (pk2, sk2) = fv.generate_keypair();
+        let (pk3, sk3) = fv.generate_keypair();
+
+        let mut mk = fv.to_multi_key(&fv.encrypt(&vec![1; fv.n], &pk1), &pk1);
+        fv.add_multi_key_inplace(&mut mk, &fv.encrypt(&vec![2; fv.n], &pk2), &pk2);
+        fv.add_multi_key_inplace(&mut mk, &fv.encrypt(&vec![3; fv.n], &pk1), &pk1);
+        let other = fv.to_multi_key(&fv.encrypt(&vec![4; fv.n], &pk3), &pk3);
+        fv.add_multi_keys_inplace(&mut mk, &other);
+        assert_eq!(mk.keys().len(), 3);
+
+        let partials: Vec<_> = [&sk1, &sk2, &sk3]
+            .iter()
+            .map(|sk| fv.multi_key_partial_decrypt(&mk, sk).unwrap())
+            .collect();
+        assert_eq!(
+            fv.combine_multi_key_partials(&mk, &partials),
+            Ok(vec![10; fv.n])
+        );
+    }
+
+    #[test]
+    fn test_multi_key_missing_partials() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk1, sk1) = fv.generate_keypair();
+        let (pk2, _) = fv.generate_keypair();
+        let (_, sk3) = fv.generate_keypair();
+        let mut mk = fv.to_multi_key(&fv.encrypt(&vec![1; fv.n], &pk1), &pk1);
+        fv.add_multi_key_inplace(&mut mk, &fv.encrypt(&vec![2; fv.n], &pk2), &pk2);
+
+        let p1 = fv.multi_key_partial_decrypt(&mk, &sk1).unwrap();
+        assert_eq!(
+            fv.combine_multi_key_partials(&mk, std::slice::from_ref(&p1)),
+            Err(MultiKeyError::MissingPartials(vec![
+                fv.public_key_fingerprint(&pk2)
+            ]))
+        );
+        assert_eq!(
+            fv.combine_multi_key_partials(&mk, &[p1.clone(), p1]),
+            Err(MultiKeyError::DuplicatePartial(
+                sk1.public_key_fingerprint().unwrap()
+            ))
+        );
+        assert_eq!(
+            fv.multi_key_partial_decrypt(&mk, &sk3).unwrap_err(),
+            MultiKeyError::KeyNotInvolved(sk3.public_key_fingerprint().unwrap())
+        );
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Node.js bindings, with the `node` feature.
+//!
+//! An aggregation backend creates a [`Scheme`] with the default parameters, and exchanges public
+//! keys and ciphertexts with clients in their canonical encoding, as `Buffer`s:
+//!
+//! ```js
+//! const { Scheme } = require('./cupcake.node');
+//! const scheme = new Scheme();
+//! const sum = scheme.add(scheme.rerandomize(ct1, publicKey), ct2);
+//! ```
+//!
+//! A [`KeyPair`] keeps its secret key inside the addon, and only exposes its public key. Errors
+//! are thrown as `Error`s carrying the message of the underlying [`CupcakeError`]. The addon is
+//! built with `cargo rustc --release --features node --crate-type cdylib`, and the resulting
+//! library renamed to `cupcake.node`.
+use crate::error::CupcakeError;
+use crate::integer_arith::scalar::Scalar;
+use crate::pubkey::PublicKey;
+use crate::traits::Serializable;
+use crate::{SecretKey, FV};
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Status};
+use napi_derive::napi;
+
+/// The default scheme.
+#[napi]
+pub struct Scheme {
+    fv: FV<Scalar>,
+}
+
+/// A keypair of a [`Scheme`].
+#[napi]
+pub struct KeyPair {
+    pk: PublicKey<Scalar>,
+    sk: SecretKey<Scalar>,
+}
+
+fn to_js(e: CupcakeError) -> Error {
+    Error::new(Status::InvalidArg, e.to_string())
+}
+
+#[napi]
+impl KeyPair {
+    /// The encoding of the public key.
+    #[napi(getter)]
+    pub fn public_key(&self) -> Buffer {
+        self.pk.to_bytes().into()
+    }
+}
+
+impl Default for Scheme {
+    fn default() -> Self {
+        Scheme {
+            fv: FV::<Scalar>::default_2048(),
+        }
+    }
+}
+
+#[napi]
+impl Scheme {
+    /// The scheme with the default parameters.
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        Scheme::default()
+    }
+
+    /// The number of bytes of a plaintext.
+    #[napi(getter)]
+    pub fn plaintext_length(&self) -> u32 {
+        self.fv.n as u32
+    }
+
+    /// Generate a keypair.
+    #[napi]
+    pub fn generate_keypair(&self) -> KeyPair {
+        let (pk, sk) = self.fv.generate_typed_keypair();
+        KeyPair { pk, sk }
+    }
+
+    /// Encrypt a plaintext under an encoded public key, and return the encoded ciphertext.
+    #[napi]
+    pub fn encrypt(&self, pt: Buffer, public_key: Buffer) -> napi::Result<Buffer> {
+        self.try_encrypt(&pt, &public_key)
+            .map(Buffer::from)
+            .map_err(to_js)
+    }
+
+    /// The encoding of the sum of two encoded ciphertexts.
+    #[napi]
+    pub fn add(&self, ct1: Buffer, ct2: Buffer) -> napi::Result<Buffer> {
+        self.try_add(&ct1, &ct2).map(Buffer::from).map_err(to_js)
+    }
+
+    /// Rerandomize an encoded ciphertext under an encoded public key.
+    #[napi]
+    pub fn rerandomize(&self, ct: Buffer, public_key: Buffer) -> napi::Result<Buffer> {
+        self.try_rerandomize(&ct, &public_key)
+            .map(Buffer::from)
+            .map_err(to_js)
+    }
+
+    /// Decrypt an encoded ciphertext with the secret key of a keypair.
+    #[napi]
+    pub fn decrypt(&self, ct: Buffer, keypair: &KeyPair) -> napi::Result<Buffer> {
+        self.try_decrypt(&ct, keypair)
+            .map(Buffer::from)
+            .map_err(to_js)
+    }
+}
+
+// The operations, with errors which can be inspected outside of a Node.js runtime.
+impl Scheme {
+    fn try_encrypt(&self, pt: &[u8], public_key: &[u8]) -> Result<Vec<u8>, CupcakeError> {
+        let pk = self.fv.typed_public_key_from_bytes(public_key)?;
+        Ok(self.fv.try_encrypt(&pt.to_vec(), pk.as_pair())?.to_bytes())
+    }
+
+    fn try_add(&self, ct1: &[u8], ct2: &[u8]) -> Result<Vec<u8>, CupcakeError> {
+        let mut sum = self.fv.ciphertext_from_bytes(ct1)?;
+        self.fv
+            .try_add_inplace(&mut sum, &self.fv.ciphertext_from_bytes(ct2)?)?;
+        Ok(sum.to_bytes())
+    }
+
+    fn try_rerandomize(&self, ct: &[u8], public_key: &[u8]) -> Result<Vec<u8>, CupcakeError> {
+        let mut ct = self.fv.ciphertext_from_bytes(ct)?;
+        let pk = self.fv.typed_public_key_from_bytes(public_key)?;
+        self.fv.try_rerandomize(&mut ct, pk.as_pair())?;
+        Ok(ct.to_bytes())
+    }
+
+    fn try_decrypt(&self, ct: &[u8], keypair: &KeyPair) -> Result<Vec<u8>, CupcakeError> {
+        let ct = self.fv.ciphertext_from_bytes(ct)?;
+        self.fv.try_decrypt(&ct, &keypair.sk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::ValidationError;
+
+    #[test]
+    fn test_node_bindings() {
+        let scheme = Scheme::new();
+        let n = scheme.fv.n;
+        let keypair = scheme.generate_keypair();
+        let pk = keypair.pk.to_bytes();
+        let a = scheme.try_encrypt(&vec![2; n], &pk).unwrap();
+        let b = scheme.try_encrypt(&vec![3; n], &pk).unwrap();
+        let sum = scheme.try_add(&a, &b).unwrap();
+        let sum = scheme.try_rerandomize(&sum, &pk).unwrap();
+        assert_eq!(scheme.try_decrypt(&sum, &keypair).unwrap(), vec![5; n]);
+
+        assert_eq!(
+            scheme.try_rerandomize(&a, &a[1..]).unwrap_err(),
+            CupcakeError::Invalid(ValidationError::WrongLength {
+                expected: pk.len(),
+                actual: a.len() - 1
+            })
+        );
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Measurement and estimation of the noise in ciphertexts.
+//!
+//! [`FV::noise_budget`] measures the noise of a ciphertext exactly using the secret key, while
+//! [`NoiseModel`] tracks upper bounds on the noise through homomorphic operations without any
+//! key, so that parameter choices can be validated offline.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::randutils::{flooding_bound, CDT_BOUND, CDT_STDEV};
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::SKEncryption;
+use crate::utils::wipe;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The plaintext modulus t of the scheme.
+const PLAINTEXT_MODULUS: u64 = 256;
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T>,
+{
+    /// The largest invariant noise of a ciphertext, i.e. the maximum over all coefficients of
+    /// |t * (c1 - c0 * s) mod q|, where the residue is taken in (-q/2, q/2]. A ciphertext
+    /// decrypts correctly if and only if this is smaller than q/2.
+    pub fn invariant_noise(&self, ct: &FVCiphertext<T>, sk: &SecretKey<T>) -> u64 {
+        let q = T::to_u64(self.q.clone()) as u128;
+        let mut phase = self.phase(ct, sk);
+        let mut max = 0u128;
+        for x in phase.coeffs.iter() {
+            let w = (PLAINTEXT_MODULUS as u128 * T::to_u64(x.clone()) as u128) % q;
+            let w = if w > q / 2 { q - w } else { w };
+            max = max.max(w);
+        }
+        wipe(&mut phase);
+        max as u64
+    }
+
+    /// The remaining noise budget of a ciphertext in bits, like the invariant noise budget of
+    /// SEAL: log2(q / 2) - log2(invariant noise). Every doubling of the noise, e.g. by adding
+    /// two ciphertexts with the same noise, consumes about one bit. The ciphertext fails to
+    /// decrypt once the budget reaches zero.
+    pub fn noise_budget(&self, ct: &FVCiphertext<T>, sk: &SecretKey<T>) -> f64 {
+        let q = T::to_u64(self.q.clone()) as f64;
+        let noise = self.invariant_noise(ct, sk).max(1) as f64;
+        ((q / 2f64).log2() - noise.log2()).max(0f64)
+    }
+
+    /// Decrypt a ciphertext, unless its noise is too large for the result to be trusted.
+    ///
+    /// The ciphertext is rejected when its invariant noise is at least q/4, i.e. when less than
+    /// one bit of noise budget is left. Once the noise has wrapped around, the invariant noise of
+    /// each of the n coefficients is essentially uniform in (-q/2, q/2], so the largest one
+    /// exceeds q/4 except with probability 2^-n: instead of wrapped-around garbage, such a
+    /// ciphertext yields an error.
+    pub fn decrypt_checked(
+        &self,
+        ct: &FVCiphertext<T>,
+        sk: &SecretKey<T>,
+    ) -> Result<FVPlaintext, DecryptionNoiseTooLarge> {
+        let noise_budget = self.noise_budget(ct, sk);
+        if noise_budget < 1f64 {
+            return Err(DecryptionNoiseTooLarge { noise_budget });
+        }
+        Ok(self.decrypt(ct, sk))
+    }
+}
+
+/// The error returned when a ciphertext is too noisy to be decrypted reliably.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecryptionNoiseTooLarge {
+    /// The remaining noise budget of the ciphertext in bits, which is below one bit.
+    pub noise_budget: f64,
+}
+
+impl fmt::Display for DecryptionNoiseTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "decryption noise too large: {:.2} bits of noise budget left",
+            self.noise_budget
+        )
+    }
+}
+
+impl Error for DecryptionNoiseTooLarge {}
+
+/// Number of standard deviations used to bound a Gaussian whose sampler has no tail cut, and
+/// to turn average-case estimates into high-probability bounds. A centered Gaussian exceeds 8
+/// standard deviations with probability about 2^-50.
+const TAIL_FACTOR: f64 = 8.0;
+
+/// An estimate of the noise e in the phase c1 - c0 * s = Delta * m + e of a ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseEstimate {
+    /// An upper bound on the magnitude of every noise coefficient.
+    pub worst_case: f64,
+    /// The standard deviation of a noise coefficient, under the heuristic that noise
+    /// coefficients behave as independent Gaussians.
+    pub stdev: f64,
+}
+
+/// A static model of the noise growth of the scheme, which does not need any key.
+///
+/// Each method returns the noise estimate of the output of the corresponding operation, given the
+/// estimates of its inputs. The worst-case bounds hold for every execution, while the
+/// average-case bounds use the central limit heuristic.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseModel {
+    n: f64,
+    q: f64,
+    delta: f64,
+    stdev: f64,
+    error_bound: f64,
+    flooding_stdev: f64,
+}
+
+impl NoiseModel {
+    /// Noise of a fresh secret key encryption: e.
+    pub fn fresh_sk(&self) -> NoiseEstimate {
+        NoiseEstimate {
+            worst_case: self.error_bound,
+            stdev: self.stdev,
+        }
+    }
+
+    /// Noise of a fresh public key encryption: e * u + e2 - e1 * s, where u and s are ternary.
+    pub fn fresh_pk(&self) -> NoiseEstimate {
+        NoiseEstimate {
+            worst_case: (2f64 * self.n + 1f64) * self.error_bound,
+            stdev: (2f64 * self.n * self.stdev.powi(2) * 2f64 / 3f64 + self.stdev.powi(2)).sqrt(),
+        }
+    }
+
+    /// Noise of the sum of two ciphertexts. When the sum of the plaintexts wraps around modulo
+    /// t, the noise also absorbs the rounding error r = q - t * Delta.
+    pub fn add(&self, a: &NoiseEstimate, b: &NoiseEstimate) -> NoiseEstimate {
+        NoiseEstimate {
+            worst_case: a.worst_case + b.worst_case + self.rounding_error(),
+            stdev: (a.stdev.powi(2) + b.stdev.powi(2)).sqrt(),
+        }
+    }
+
+    /// Noise of a ciphertext after adding a plaintext into it.
+    pub fn add_plain(&self, a: &NoiseEstimate) -> NoiseEstimate {
+        NoiseEstimate {
+            worst_case: a.worst_case + self.rounding_error(),
+            stdev: a.stdev,
+        }
+    }
+
+    /// Noise of the sum of `count` ciphertexts with noise `a`.
+    pub fn sum(&self, a: &NoiseEstimate, count: u64) -> NoiseEstimate {
+        let count = count as f64;
+        NoiseEstimate {
+            worst_case: count * a.worst_case + (count - 1f64).max(0f64) * self.rounding_error(),
+            stdev: count.sqrt() * a.stdev,
+        }
+    }
+
+    /// Noise of a ciphertext after rerandomization, which adds a fresh public key encryption of
+    /// zero and the flooding noise.
+    pub fn rerandomize(&self, a: &NoiseEstimate) -> NoiseEstimate {
+        self.rerandomize_with_stdev(a, self.flooding_stdev)
+    }
+
+    /// Noise of a ciphertext after rerandomization with flooding noise of the given standard
+    /// deviation.
+    pub fn rerandomize_with_stdev(&self, a: &NoiseEstimate, flooding_stdev: f64) -> NoiseEstimate {
+        let zero = self.fresh_pk();
+        let flooding_error_bound = (flooding_bound(flooding_stdev) + CDT_BOUND) as f64;
+        NoiseEstimate {
+            worst_case: a.worst_case + zero.worst_case + flooding_error_bound,
+            stdev: (a.stdev.powi(2) + zero.stdev.powi(2) + flooding_stdev.powi(2)).sqrt(),
+        }
+    }
+
+    /// Noise of the phase recovered from a ciphertext with noise `a` by combining `parties`
+    /// partial decryptions, each flooded with noise of the given standard deviation.
+    pub fn combine_partials(
+        &self,
+        a: &NoiseEstimate,
+        parties: usize,
+        flooding_stdev: f64,
+    ) -> NoiseEstimate {
+        let parties = parties as f64;
+        let flooding_error_bound = (flooding_bound(flooding_stdev) + CDT_BOUND) as f64;
+        NoiseEstimate {
+            worst_case: a.worst_case + parties * flooding_error_bound,
+            stdev: (a.stdev.powi(2) + parties * flooding_stdev.powi(2)).sqrt(),
+        }
+    }
+
+    /// The largest noise magnitude for which decryption is guaranteed to be correct.
+    pub fn max_noise(&self) -> f64 {
+        (self.delta - self.rounding_error()) / 2f64
+    }
+
+    /// The remaining noise budget in bits, if the noise reaches its worst-case bound.
+    pub fn worst_case_budget(&self, est: &NoiseEstimate) -> f64 {
+        self.budget(est.worst_case)
+    }
+
+    /// The remaining noise budget in bits, if the noise stays within TAIL_FACTOR standard
+    /// deviations, which fails with probability about 2^-50 per coefficient.
+    pub fn average_case_budget(&self, est: &NoiseEstimate) -> f64 {
+        self.budget(TAIL_FACTOR * est.stdev)
+    }
+
+    /// Whether decryption is guaranteed to be correct for this noise estimate.
+    pub fn is_correct(&self, est: &NoiseEstimate) -> bool {
+        est.worst_case <= self.max_noise()
+    }
+
+    /// The number of ciphertexts with noise `est` which can be summed with guaranteed
+    /// decryption correctness.
+    pub fn max_additions(&self, est: &NoiseEstimate) -> u64 {
+        (self.max_noise() / (est.worst_case + self.rounding_error())).floor() as u64
+    }
+
+    fn rounding_error(&self) -> f64 {
+        self.q - PLAINTEXT_MODULUS as f64 * self.delta
+    }
+
+    fn budget(&self, noise: f64) -> f64 {
+        (self.max_noise().log2() - noise.max(1f64).log2()).max(0f64)
+    }
+}
+
+impl<T> FV<T>
+where
+    T: Clone + ArithUtils<T>,
+{
+    /// The static noise model of this scheme.
+    pub fn noise_model(&self) -> NoiseModel {
+        let gaussian_bound = |stdev: f64| {
+            if stdev == CDT_STDEV {
+                CDT_BOUND as f64
+            } else {
+                (TAIL_FACTOR * stdev).ceil()
+            }
+        };
+        NoiseModel {
+            n: self.n as f64,
+            q: T::to_u64(self.q.clone()) as f64,
+            delta: T::to_u64(self.delta.clone()) as f64,
+            stdev: self.stdev,
+            error_bound: gaussian_bound(self.stdev),
+            flooding_stdev: self.flooding_stdev,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecryptionNoiseTooLarge, NoiseEstimate};
+    use crate::integer_arith::scalar::Scalar;
+    use crate::rqpoly::randutils;
+    use crate::rqpoly::FiniteRingElt;
+    use crate::traits::*;
+    use crate::FV;
+
+    #[test]
+    fn test_noise_budget_fresh_ciphertext() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let ct = fv.encrypt(&vec![1; fv.n], &pk);
+        let budget = fv.noise_budget(&ct, &sk);
+        // q has 54 bits, t has 8 bits and the fresh noise has about 15 bits.
+        assert!(budget > 25f64 && budget < 40f64);
+    }
+
+    #[test]
+    fn test_noise_budget_decreases() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct = fv.encrypt(&vec![1; fv.n], &pk);
+        let fresh = fv.noise_budget(&ct, &sk);
+        fv.rerandomize(&mut ct, &pk);
+        let flooded = fv.noise_budget(&ct, &sk);
+        assert!(flooded < fresh - 20f64);
+        assert_eq!(fv.decrypt(&ct, &sk), vec![1; fv.n]);
+    }
+
+    #[test]
+    fn test_noise_model_bounds_measured_noise() {
+        let fv = FV::<Scalar>::default_2048();
+        let model = fv.noise_model();
+        let (pk, sk) = fv.generate_keypair();
+
+        let fresh = model.fresh_pk();
+        let mut ct = fv.encrypt(&vec![1; fv.n], &pk);
+        assert!(fv.noise_budget(&ct, &sk) >= model.worst_case_budget(&fresh));
+        assert!(fv.noise_budget(&ct, &sk) >= model.average_case_budget(&fresh));
+
+        let ct2 = fv.encrypt(&vec![2; fv.n], &pk);
+        fv.add_inplace(&mut ct, &ct2);
+        let sum = model.add(&fresh, &fresh);
+        assert!(fv.noise_budget(&ct, &sk) >= model.worst_case_budget(&sum));
+
+        fv.rerandomize(&mut ct, &pk);
+        let flooded = model.rerandomize(&sum);
+        assert!(model.is_correct(&flooded));
+        assert!(fv.noise_budget(&ct, &sk) >= model.worst_case_budget(&flooded));
+    }
+
+    #[test]
+    fn test_noise_model_max_additions() {
+        let fv = FV::<Scalar>::default_2048();
+        let model = fv.noise_model();
+        let fresh = model.fresh_pk();
+        let k = model.max_additions(&fresh);
+        assert!(k > 1000);
+
+        let mut sum = fresh;
+        for _ in 1..1000 {
+            sum = model.add(&sum, &fresh);
+        }
+        assert!(model.is_correct(&sum));
+        let closed_form = model.sum(&fresh, 1000);
+        assert!((closed_form.worst_case - sum.worst_case).abs() < 1e-6 * sum.worst_case);
+
+        let at_limit = NoiseEstimate {
+            worst_case: k as f64 * fresh.worst_case,
+            stdev: 0f64,
+        };
+        let past_limit = NoiseEstimate {
+            worst_case: (k + 1) as f64 * fresh.worst_case * 1.01,
+            stdev: 0f64,
+        };
+        assert!(model.is_correct(&at_limit));
+        assert!(!model.is_correct(&past_limit));
+    }
+
+    #[test]
+    fn test_decrypt_checked() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct = fv.encrypt(&vec![1; fv.n], &pk);
+        assert_eq!(fv.decrypt_checked(&ct, &sk), Ok(vec![1; fv.n]));
+
+        // overwhelm the noise with a uniform polynomial.
+        ct.1.add_inplace(&randutils::sample_uniform_poly(fv.context.clone()));
+        match fv.decrypt_checked(&ct, &sk) {
+            Err(DecryptionNoiseTooLarge { noise_budget }) => assert!(noise_budget < 1f64),
+            Ok(_) => panic!("decryption of a garbage ciphertext succeeded"),
+        }
+    }
+}
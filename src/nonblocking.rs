@@ -0,0 +1,86 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Asynchronous wrappers of the heavy operations, with the `tokio` feature.
+//!
+//! Key generation and encryption at n = 2048 take milliseconds of NTTs, which would stall the
+//! reactor of an async server if run on one of its worker threads. The methods below move the
+//! work to the blocking thread pool of the tokio runtime with `spawn_blocking`, so they must be
+//! awaited from within a runtime. They take their arguments by value, as the work outlives the
+//! borrow of the caller. A panic of the work is resumed in the awaiting task.
+//!
+//! The work samples from the generator of a blocking pool thread, so a
+//! `DeterministicRng` installed on the calling thread does not apply to it.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{PKEncryption, SKEncryption};
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::panic;
+
+/// Run `f` on the blocking thread pool of the current runtime.
+async fn run_blocking<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) => match e.try_into_panic() {
+            Ok(payload) => panic::resume_unwind(payload),
+            Err(e) => panic!("blocking task failed: {}", e),
+        },
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq + Send + Sync + 'static,
+{
+    /// Generate a keypair on the blocking thread pool.
+    pub async fn generate_keypair_async(&self) -> (FVCiphertext<T>, SecretKey<T>) {
+        let fv = self.clone();
+        run_blocking(move || fv.generate_keypair()).await
+    }
+
+    /// Encrypt a batch of plaintexts under a public key on the blocking thread pool.
+    pub async fn encrypt_batch_async(
+        &self,
+        pts: Vec<FVPlaintext>,
+        pk: FVCiphertext<T>,
+    ) -> Vec<FVCiphertext<T>> {
+        let fv = self.clone();
+        run_blocking(move || pts.iter().map(|pt| fv.encrypt(pt, &pk)).collect()).await
+    }
+
+    /// Decrypt a batch of ciphertexts on the blocking thread pool.
+    pub async fn decrypt_batch_async(
+        &self,
+        cts: Vec<FVCiphertext<T>>,
+        sk: SecretKey<T>,
+    ) -> Vec<FVPlaintext> {
+        let fv = self.clone();
+        run_blocking(move || cts.iter().map(|ct| fv.decrypt(ct, &sk)).collect()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_async_operations() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let fv = FV::<Scalar>::default_2048();
+        runtime.block_on(async {
+            let (pk, sk) = fv.generate_keypair_async().await;
+            let pts: Vec<FVPlaintext> = (0..4u8).map(|i| vec![i; fv.n]).collect();
+            let cts = fv.encrypt_batch_async(pts.clone(), pk).await;
+            assert_eq!(fv.decrypt_batch_async(cts, sk).await, pts);
+        });
+    }
+}
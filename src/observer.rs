@@ -0,0 +1,248 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Hooks reporting every operation of the scheme, e.g. to export metrics.
+//!
+//! A [`SchemeObserver`] installed with [`set_observer`] is called after each key generation,
+//! encryption, decryption, addition and rerandomization of an [`crate::FV`] scheme, on the
+//! thread which performed it, with the time it took. Operations which other operations perform
+//! internally, such as the encryption of zero in a rerandomization, are not reported separately.
+//! [`OperationCounters`] is an observer which accumulates counts and times, for an exporter to
+//! read periodically.
+//!
+//! Without an observer, an operation costs one atomic load more.
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// The operations reported to an observer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Generation of a secret key or a key pair.
+    KeyGeneration,
+    /// Encryption under a public or a secret key.
+    Encryption,
+    /// Decryption.
+    Decryption,
+    /// Addition of two ciphertexts.
+    Addition,
+    /// Addition of a plaintext to a ciphertext.
+    PlainAddition,
+    /// Rerandomization.
+    Rerandomization,
+}
+
+impl Operation {
+    /// All the operations, in the order of their indices.
+    pub const ALL: [Operation; 6] = [
+        Operation::KeyGeneration,
+        Operation::Encryption,
+        Operation::Decryption,
+        Operation::Addition,
+        Operation::PlainAddition,
+        Operation::Rerandomization,
+    ];
+
+    /// A snake case name, e.g. for a metric label.
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::KeyGeneration => "key_generation",
+            Operation::Encryption => "encryption",
+            Operation::Decryption => "decryption",
+            Operation::Addition => "addition",
+            Operation::PlainAddition => "plain_addition",
+            Operation::Rerandomization => "rerandomization",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A receiver of the operations of the scheme. It is called synchronously, and should be cheap.
+pub trait SchemeObserver: Send + Sync {
+    /// Called after an operation completed.
+    fn on_operation(&self, operation: Operation, elapsed: Duration);
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static OBSERVER: RwLock<Option<Arc<dyn SchemeObserver>>> = RwLock::new(None);
+
+thread_local! {
+    // The number of operations in progress on this thread, so that nested ones are not reported.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Install an observer of all the operations, replacing the previous one.
+pub fn set_observer(observer: Arc<dyn SchemeObserver>) {
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = Some(observer);
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Remove the observer, if any.
+pub fn clear_observer() {
+    ENABLED.store(false, Ordering::Release);
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Run an operation, and report it to the observer unless it is nested in another one.
+pub(crate) fn observe<R>(operation: Operation, f: impl FnOnce() -> R) -> R {
+    if !ENABLED.load(Ordering::Acquire) {
+        return f();
+    }
+    struct Restore(usize);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DEPTH.with(|d| d.set(self.0));
+        }
+    }
+
+    let depth = DEPTH.with(|d| d.replace(d.get() + 1));
+    let restore = Restore(depth);
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    drop(restore);
+    if depth == 0 {
+        let observer = OBSERVER.read().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some(observer) = observer {
+            observer.on_operation(operation, elapsed);
+        }
+    }
+    result
+}
+
+/// An observer counting the operations and their total time.
+#[derive(Debug, Default)]
+pub struct OperationCounters {
+    counts: [AtomicU64; 6],
+    nanos: [AtomicU64; 6],
+}
+
+impl OperationCounters {
+    /// Counters at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of operations of a kind.
+    pub fn count(&self, operation: Operation) -> u64 {
+        self.counts[operation.index()].load(Ordering::Relaxed)
+    }
+
+    /// The total time of the operations of a kind.
+    pub fn total_time(&self, operation: Operation) -> Duration {
+        Duration::from_nanos(self.nanos[operation.index()].load(Ordering::Relaxed))
+    }
+}
+
+impl SchemeObserver for OperationCounters {
+    fn on_operation(&self, operation: Operation, elapsed: Duration) {
+        let i = operation.index();
+        self.counts[i].fetch_add(1, Ordering::Relaxed);
+        self.nanos[i].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+    use crate::FV;
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    /// Tests installing observers must not run concurrently.
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    /// Forwards the operations of one thread only, ignoring those of the other tests.
+    struct SameThread(ThreadId, Arc<dyn SchemeObserver>);
+
+    impl SchemeObserver for SameThread {
+        fn on_operation(&self, operation: Operation, elapsed: Duration) {
+            if thread::current().id() == self.0 {
+                self.1.on_operation(operation, elapsed);
+            }
+        }
+    }
+
+    fn observe_this_thread(observer: Arc<dyn SchemeObserver>) {
+        set_observer(Arc::new(SameThread(thread::current().id(), observer)));
+    }
+
+    #[test]
+    fn test_operation_counters() {
+        let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let fv = FV::<Scalar>::new(16, &Scalar::new_modulus(65537));
+        let counters = Arc::new(OperationCounters::new());
+        observe_this_thread(counters.clone());
+        let (pk, sk) = fv.generate_keypair();
+        let mut ct = fv.encrypt(&vec![1; 16], &pk);
+        let other = fv.encrypt_sk(&vec![2; 16], &sk);
+        fv.add_inplace(&mut ct, &other);
+        fv.add_plain_inplace(&mut ct, &vec![3; 16]);
+        fv.rerandomize(&mut ct, &pk);
+        let pt = fv.decrypt(&ct, &sk);
+        clear_observer();
+        fv.encrypt(&pt, &pk);
+
+        assert_eq!(pt, vec![6; 16]);
+        let counts: Vec<u64> = Operation::ALL
+            .iter()
+            .map(|op| counters.count(*op))
+            .collect();
+        assert_eq!(counts, vec![1, 2, 1, 1, 1, 1]);
+        assert!(counters.total_time(Operation::KeyGeneration) > Duration::from_nanos(0));
+    }
+
+    struct Recorder(Mutex<Vec<Operation>>);
+
+    impl SchemeObserver for Recorder {
+        fn on_operation(&self, operation: Operation, _elapsed: Duration) {
+            self.0.lock().unwrap().push(operation);
+        }
+    }
+
+    #[test]
+    fn test_nested_operations_are_not_reported() {
+        let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let fv = FV::<Scalar>::new(16, &Scalar::new_modulus(65537));
+        let recorder = Arc::new(Recorder(Mutex::new(vec![])));
+        observe_this_thread(recorder.clone());
+        let (pk, _) = fv.generate_keypair();
+        let mut ct = fv.encrypt_zero(&pk);
+        fv.rerandomize(&mut ct, &pk);
+        clear_observer();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                Operation::KeyGeneration,
+                Operation::Encryption,
+                Operation::Rerandomization
+            ]
+        );
+    }
+
+    #[test]
+    fn test_panicking_operation_restores_depth() {
+        let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let recorder = Arc::new(Recorder(Mutex::new(vec![])));
+        observe_this_thread(recorder.clone());
+        let caught = std::panic::catch_unwind(|| observe(Operation::Encryption, || panic!("boom")));
+        observe(Operation::Decryption, || ());
+        clear_observer();
+        assert!(caught.is_err());
+        assert_eq!(*recorder.0.lock().unwrap(), vec![Operation::Decryption]);
+    }
+}
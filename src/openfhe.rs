@@ -0,0 +1,323 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Conversion of parameters, keys and ciphertexts to and from the BFV scheme of OpenFHE and
+//! PALISADE, with the `openfhe` feature.
+//!
+//! The two libraries share the regime of a single RNS tower: a `DCRTPoly` with one modulus q
+//! below 2^60 with q = 1 mod 2n, a plaintext modulus t = 256 with coefficient packing, a uniform
+//! ternary secret key, and the `STANDARD` encryption technique, which scales the plaintext by
+//! floor(q / t) like this crate. [`OpenFheParams`] lists the values to pass to the setters of
+//! `CCParams<CryptoContextBFVRNS>` for a scheme of this crate.
+//!
+//! Polynomials are exchanged as their n coefficients in [0, q) in the `COEFFICIENT` format, as
+//! the NTT of OpenFHE uses its own roots of unity; call `SetFormat(EVALUATION)` after loading
+//! them. This crate decrypts a ciphertext (a, b) as b - a * s, while OpenFHE decrypts (c0, c1)
+//! as c0 + c1 * s, so both ciphertexts and public keys are exchanged as (c0, c1) = (b, -a). The
+//! secret key s is the same polynomial in both libraries.
+use crate::integer_arith::scalar::Scalar;
+use crate::integer_arith::ArithUtils;
+use crate::packed::PackedTernary;
+use crate::rqpoly::{FiniteRingElt, RqPoly, NTT};
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The plaintext modulus of this crate, whose plaintexts are bytes.
+pub const PLAINTEXT_MODULUS: u64 = 256;
+
+/// The largest size in bits of an RNS tower of OpenFHE.
+pub const MAX_MODULUS_BITS: u32 = 60;
+
+/// The parameters of an OpenFHE BFV crypto context equivalent to a scheme of this crate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenFheParams {
+    /// The ring dimension n, for `SetRingDim`.
+    pub ring_dim: usize,
+    /// The plaintext modulus, for `SetPlaintextModulus`.
+    pub plaintext_modulus: u64,
+    /// The moduli of the RNS towers of the ciphertext modulus.
+    pub ciphertext_moduli: Vec<u64>,
+    /// The standard deviation of the errors, for `SetStandardDeviation`.
+    pub standard_deviation: f64,
+}
+
+/// The reasons for which parameters are outside the regime shared with OpenFHE.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OpenFheError {
+    /// The ring dimension is not a power of two.
+    RingDimension(usize),
+    /// The ciphertext modulus is not a single tower, which this crate does not support.
+    Towers(usize),
+    /// The modulus has more than 60 bits, or is not 1 modulo 2n.
+    Modulus(u64),
+    /// The plaintext modulus is not 256.
+    PlaintextModulus(u64),
+}
+
+impl fmt::Display for OpenFheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpenFheError::RingDimension(n) => {
+                write!(f, "ring dimension {} is not a power of two", n)
+            }
+            OpenFheError::Towers(count) => {
+                write!(f, "expected a single RNS tower, found {}", count)
+            }
+            OpenFheError::Modulus(q) => write!(f, "modulus {} is not supported by OpenFHE", q),
+            OpenFheError::PlaintextModulus(t) => {
+                write!(f, "plaintext modulus {} is not {}", t, PLAINTEXT_MODULUS)
+            }
+        }
+    }
+}
+
+impl Error for OpenFheError {}
+
+fn check_regime(n: usize, q: u64) -> Result<(), OpenFheError> {
+    if !n.is_power_of_two() {
+        return Err(OpenFheError::RingDimension(n));
+    }
+    if 64 - q.leading_zeros() > MAX_MODULUS_BITS || q % (2 * n as u64) != 1 {
+        return Err(OpenFheError::Modulus(q));
+    }
+    Ok(())
+}
+
+impl FV<Scalar> {
+    /// The scheme of an OpenFHE crypto context, which must be in the shared regime.
+    pub fn from_openfhe_params(params: &OpenFheParams) -> Result<Self, OpenFheError> {
+        if params.plaintext_modulus != PLAINTEXT_MODULUS {
+            return Err(OpenFheError::PlaintextModulus(params.plaintext_modulus));
+        }
+        let q = match params.ciphertext_moduli.as_slice() {
+            [q] => *q,
+            moduli => return Err(OpenFheError::Towers(moduli.len())),
+        };
+        check_regime(params.ring_dim, q)?;
+        let mut fv = FV::new(params.ring_dim, &Scalar::new_modulus(q));
+        fv.stdev = params.standard_deviation;
+        Ok(fv)
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt + NTT<T>,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The parameters of the equivalent OpenFHE crypto context.
+    pub fn openfhe_params(&self) -> Result<OpenFheParams, OpenFheError> {
+        let q = T::to_u64(self.q.clone());
+        check_regime(self.n, q)?;
+        Ok(OpenFheParams {
+            ring_dim: self.n,
+            plaintext_modulus: PLAINTEXT_MODULUS,
+            ciphertext_moduli: vec![q],
+            standard_deviation: self.stdev,
+        })
+    }
+
+    /// The OpenFHE elements (c0, c1) of a ciphertext.
+    pub fn ciphertext_to_openfhe(&self, ct: &FVCiphertext<T>) -> [Vec<u64>; 2] {
+        self.pair_to_openfhe(ct)
+    }
+
+    /// Import and validate the OpenFHE elements (c0, c1) of a ciphertext.
+    pub fn ciphertext_from_openfhe(
+        &self,
+        elements: &[Vec<u64>; 2],
+    ) -> Result<FVCiphertext<T>, ValidationError> {
+        let ct = self.pair_from_openfhe(elements)?;
+        self.validate(&ct)?;
+        Ok(ct)
+    }
+
+    /// The OpenFHE elements (pk0, pk1) of a public key.
+    pub fn public_key_to_openfhe(&self, pk: &FVCiphertext<T>) -> [Vec<u64>; 2] {
+        self.pair_to_openfhe(pk)
+    }
+
+    /// Import and validate the OpenFHE elements (pk0, pk1) of a public key.
+    pub fn public_key_from_openfhe(
+        &self,
+        elements: &[Vec<u64>; 2],
+    ) -> Result<FVCiphertext<T>, ValidationError> {
+        let mut pk = self.pair_from_openfhe(elements)?;
+        if self.context.is_ntt_enabled {
+            pk.0.forward_transform();
+            pk.1.forward_transform();
+        }
+        self.validate_public_key(&pk)?;
+        Ok(pk)
+    }
+
+    /// The OpenFHE polynomial of a secret key, with -1 represented by q - 1.
+    pub fn secret_key_to_openfhe(&self, sk: &SecretKey<T>) -> Vec<u64> {
+        let mut s = sk.0.unpack(self.context.clone());
+        let coefficients = s.coeffs.iter().map(|x| T::to_u64(x.clone())).collect();
+        wipe(&mut s);
+        coefficients
+    }
+
+    /// Import an OpenFHE secret key, whose coefficients must be 0, 1 or q - 1.
+    pub fn secret_key_from_openfhe(&self, s: &[u64]) -> Result<SecretKey<T>, ValidationError> {
+        let q = T::to_u64(self.q.clone());
+        if s.len() != self.n {
+            return Err(ValidationError::WrongDegree {
+                expected: self.n,
+                actual: s.len(),
+            });
+        }
+        if s.iter().any(|&x| x > 1 && x != q - 1) {
+            return Err(ValidationError::InvalidSecretKey);
+        }
+        let mut poly = self.poly_from_openfhe(s);
+        let packed = PackedTernary::pack(&poly, &self.q);
+        wipe(&mut poly);
+        Ok(SecretKey(packed, None))
+    }
+
+    fn pair_to_openfhe(&self, pair: &FVCiphertext<T>) -> [Vec<u64>; 2] {
+        let coefficients = |poly: &RqPoly<T>| {
+            let mut poly = poly.clone();
+            if poly.is_ntt_form() {
+                poly.inverse_transform();
+            }
+            poly.coeffs
+        };
+        let (a, b) = (coefficients(&pair.0), coefficients(&pair.1));
+        let c1 = a
+            .iter()
+            .map(|x| T::to_u64(T::sub_mod(&T::zero(), x, &self.q)))
+            .collect();
+        let c0 = b.into_iter().map(T::to_u64).collect();
+        [c0, c1]
+    }
+
+    fn pair_from_openfhe(
+        &self,
+        elements: &[Vec<u64>; 2],
+    ) -> Result<FVCiphertext<T>, ValidationError> {
+        let q = T::to_u64(self.q.clone());
+        for (component, element) in elements.iter().enumerate() {
+            if element.len() != self.n {
+                return Err(ValidationError::WrongDegree {
+                    expected: self.n,
+                    actual: element.len(),
+                });
+            }
+            if let Some(index) = element.iter().position(|&x| x >= q) {
+                return Err(ValidationError::CoefficientOutOfRange { component, index });
+            }
+        }
+        let b = self.poly_from_openfhe(&elements[0]);
+        let mut a = self.poly_from_openfhe(&elements[1]);
+        for x in a.coeffs.iter_mut() {
+            *x = T::sub_mod(&T::zero(), x, &self.q);
+        }
+        Ok((a, b))
+    }
+
+    fn poly_from_openfhe(&self, coefficients: &[u64]) -> RqPoly<T> {
+        let coeffs = coefficients.iter().map(|&x| T::from_u64_raw(x)).collect();
+        RqPoly::from_coeffs(self.context.clone(), coeffs)
+    }
+}
+
+/// The values of a coefficient-packed OpenFHE plaintext, in [-128, 128).
+pub fn plaintext_to_openfhe(pt: &FVPlaintext) -> Vec<i64> {
+    pt.iter().map(|&x| x as i8 as i64).collect()
+}
+
+/// A plaintext of this crate from the values of a coefficient-packed OpenFHE plaintext, reduced
+/// modulo 256.
+pub fn plaintext_from_openfhe(values: &[i64]) -> FVPlaintext {
+    values
+        .iter()
+        .map(|x| x.rem_euclid(PLAINTEXT_MODULUS as i64) as u8)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::*;
+
+    #[test]
+    fn test_openfhe_decryption_convention() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let pt: FVPlaintext = (0..fv.n).map(|i| i as u8).collect();
+        let ct = fv.encrypt(&pt, &pk);
+
+        // Decrypt as OpenFHE does, computing c0 + c1 * s.
+        let [c0, c1] = fv.ciphertext_to_openfhe(&ct);
+        let s = fv.poly_from_openfhe(&fv.secret_key_to_openfhe(&sk));
+        let mut phase = fv.poly_from_openfhe(&c1).multiply(&s);
+        phase.add_inplace(&fv.poly_from_openfhe(&c0));
+        assert_eq!(fv.decode_phase(&phase), pt);
+
+        // A public key is also small under this convention.
+        let [pk0, pk1] = fv.public_key_to_openfhe(&pk);
+        let mut e = fv.poly_from_openfhe(&pk1).multiply(&s);
+        e.add_inplace(&fv.poly_from_openfhe(&pk0));
+        assert_eq!(fv.decode_phase(&e), vec![0; fv.n]);
+
+        assert_eq!(plaintext_from_openfhe(&plaintext_to_openfhe(&pt)), pt);
+        assert_eq!(plaintext_to_openfhe(&vec![255]), vec![-1]);
+    }
+
+    #[test]
+    fn test_openfhe_round_trips() {
+        let fv = FV::<Scalar>::default_2048();
+        let params = fv.openfhe_params().unwrap();
+        assert_eq!(params.ciphertext_moduli, vec![18014398492704769]);
+        let imported_fv = FV::from_openfhe_params(&params).unwrap();
+        assert_eq!((imported_fv.n, imported_fv.q), (fv.n, fv.q.clone()));
+
+        let (pk, sk) = fv.generate_keypair();
+        let ct = fv.encrypt(&vec![9; fv.n], &pk);
+        let imported = fv.ciphertext_from_openfhe(&fv.ciphertext_to_openfhe(&ct));
+        assert_eq!(imported, Ok(ct.clone()));
+        let imported_pk = fv.public_key_from_openfhe(&fv.public_key_to_openfhe(&pk));
+        assert_eq!(imported_pk, Ok(pk));
+        let imported_sk = fv
+            .secret_key_from_openfhe(&fv.secret_key_to_openfhe(&sk))
+            .unwrap();
+        assert_eq!(fv.decrypt(&ct, &imported_sk), vec![9; fv.n]);
+
+        let mut elements = fv.ciphertext_to_openfhe(&ct);
+        elements[1][3] = 18014398492704769;
+        assert_eq!(
+            fv.ciphertext_from_openfhe(&elements),
+            Err(ValidationError::CoefficientOutOfRange {
+                component: 1,
+                index: 3
+            })
+        );
+        assert_eq!(
+            fv.secret_key_from_openfhe(&vec![2; fv.n]).err(),
+            Some(ValidationError::InvalidSecretKey)
+        );
+
+        let towers = OpenFheParams {
+            ciphertext_moduli: vec![65537, 65537],
+            ..params.clone()
+        };
+        assert_eq!(
+            FV::from_openfhe_params(&towers).err(),
+            Some(OpenFheError::Towers(2))
+        );
+        let toy = FV::new(16, &Scalar::new_modulus(65537));
+        let odd = FV::new(16, &Scalar::new_modulus(65539));
+        assert_eq!(toy.openfhe_params().map(|_| ()), Ok(()));
+        assert_eq!(
+            odd.openfhe_params().err(),
+            Some(OpenFheError::Modulus(65539))
+        );
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! A packed and masked representation of ternary secrets.
+//!
+//! Every coefficient in {-1, 0, 1} is encoded on two bits (01 for 1, 10 for -1), four
+//! coefficients per byte, and the packed bytes are stored XORed with a uniformly random mask of
+//! the same length. Neither buffer alone reveals anything about the secret, and the full key is
+//! only unpacked into a polynomial for the duration of a multiplication.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::{RqPoly, RqPolyContext};
+use crate::utils::wipe;
+use rand::RngCore;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// A ternary polynomial stored as two shares whose XOR is its 2-bit packed encoding.
+pub(crate) struct PackedTernary<T> {
+    n: usize,
+    masked: Vec<u8>,
+    mask: Vec<u8>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> PackedTernary<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Pack a polynomial in coefficient form with coefficients in {-1, 0, 1} mod q.
+    pub(crate) fn pack(poly: &RqPoly<T>, q: &T) -> Self {
+        let n = poly.coeffs.len();
+        let minus_one = T::to_u64(q.clone()) - 1;
+        let mut masked = vec![0u8; n.div_ceil(4)];
+        for (i, x) in poly.coeffs.iter().enumerate() {
+            let v = T::to_u64(x.clone());
+            let code = (v == 1) as u8 | ((v == minus_one) as u8) << 1;
+            masked[i / 4] |= code << (2 * (i % 4));
+        }
+        let mut mask = vec![0u8; masked.len()];
+        with_rng(|rng| rng.fill_bytes(&mut mask));
+        for (x, m) in masked.iter_mut().zip(mask.iter()) {
+            *x ^= m;
+        }
+        PackedTernary {
+            n,
+            masked,
+            mask,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Mask a packed encoding of `n` coefficients, as returned by `unmasked_bytes`. Returns
+    /// `None` if the encoding has the wrong length or an invalid 2-bit code.
+    pub(crate) fn from_unmasked_bytes(n: usize, bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != n.div_ceil(4) {
+            return None;
+        }
+        let mut invalid = 0u8;
+        for (i, byte) in bytes.iter().enumerate() {
+            for j in 0..4 {
+                let code = (byte >> (2 * j)) & 3;
+                // 11 is not a code, and the codes past the last coefficient must be 00.
+                invalid |= (code == 3) as u8 | ((4 * i + j >= n && code != 0) as u8);
+            }
+        }
+        if invalid != 0 {
+            return None;
+        }
+        let mut mask = vec![0u8; bytes.len()];
+        with_rng(|rng| rng.fill_bytes(&mut mask));
+        let masked = bytes.iter().zip(mask.iter()).map(|(x, m)| x ^ m).collect();
+        Some(PackedTernary {
+            n,
+            masked,
+            mask,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Unpack into a polynomial in coefficient form. The caller should wipe it after use.
+    pub(crate) fn unpack(&self, context: Arc<RqPolyContext<T>>) -> RqPoly<T> {
+        let q = context.q.clone();
+        let mut coeffs = Vec::with_capacity(self.n);
+        for i in 0..self.n {
+            let byte = self.masked[i / 4] ^ self.mask[i / 4];
+            let code = (byte >> (2 * (i % 4))) & 3;
+            coeffs.push(T::sub_mod(
+                &T::from_u32_raw((code & 1) as u32),
+                &T::from_u32_raw((code >> 1) as u32),
+                &q,
+            ));
+        }
+        RqPoly::from_coeffs(context, coeffs)
+    }
+
+    /// Draw a fresh mask, leaving the secret unchanged.
+    pub(crate) fn remask(&mut self) {
+        let mut fresh = vec![0u8; self.mask.len()];
+        with_rng(|rng| rng.fill_bytes(&mut fresh));
+        for ((x, m), r) in self
+            .masked
+            .iter_mut()
+            .zip(self.mask.iter_mut())
+            .zip(fresh.iter())
+        {
+            *x ^= r;
+            *m ^= r;
+        }
+        wipe(&mut fresh);
+    }
+
+    /// The packed encoding of the secret, which the caller should wipe after use.
+    pub(crate) fn unmasked_bytes(&self) -> Vec<u8> {
+        self.masked
+            .iter()
+            .zip(self.mask.iter())
+            .map(|(x, m)| x ^ m)
+            .collect()
+    }
+}
+
+impl<T> Clone for PackedTernary<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// A copy of the secret under a fresh mask, so that two copies never share their shares.
+    fn clone(&self) -> Self {
+        let mut copy = PackedTernary {
+            n: self.n,
+            masked: self.masked.clone(),
+            mask: self.mask.clone(),
+            _marker: PhantomData,
+        };
+        copy.remask();
+        copy
+    }
+}
+
+impl<T> PackedTernary<T> {
+    /// The number of coefficients.
+    pub(crate) fn degree(&self) -> usize {
+        self.n
+    }
+
+    /// The two shares, e.g. to lock them in memory.
+    #[cfg(any(test, feature = "mlock"))]
+    pub(crate) fn shares(&self) -> [&[u8]; 2] {
+        [&self.masked, &self.mask]
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> zeroize::Zeroize for PackedTernary<T> {
+    fn zeroize(&mut self) {
+        self.masked.zeroize();
+        self.mask.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_pack_unpack() {
+        let q = Scalar::new_modulus(65537);
+        let context = Arc::new(RqPolyContext::new(16, &q));
+        let poly =
+            with_rng(|rng| crate::rqpoly::randutils::sample_ternary_poly(context.clone(), rng));
+        let mut packed = PackedTernary::pack(&poly, &q);
+        assert_eq!(packed.unpack(context.clone()).coeffs, poly.coeffs);
+
+        let before = packed.masked.clone();
+        packed.remask();
+        assert_ne!(packed.masked, before);
+        assert_eq!(packed.unpack(context).coeffs, poly.coeffs);
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Single-server private information retrieval.
+//!
+//! A database is a list of records of at most n bytes, each read as a plaintext polynomial. To
+//! retrieve record i without revealing i, the client sends a [`PirQuery`] holding one encryption
+//! per record of the constant polynomial 1 for record i and 0 for the others. The server
+//! multiplies each encryption by its record, a product of a ciphertext with a plaintext
+//! polynomial, sums the products into a [`PirResponse`] which encrypts record i, and rerandomizes
+//! it under the public key of the client, so that its noise reveals nothing of the other
+//! records. The client decrypts the response with [`FV::pir_decode`].
+//!
+//! The query has one ciphertext per record, so it suits databases of up to a few thousand
+//! records. The product with a record multiplies the noise of a selection ciphertext by up to
+//! 255 n, which stays well below the flooding noise of the response with the default parameters.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption, Serializable};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// A query for one record of a database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PirQuery<T> {
+    /// An encryption of 1 for the queried record, and of 0 for the others.
+    pub selection: Vec<FVCiphertext<T>>,
+}
+
+/// The encrypted record answering a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PirResponse<T> {
+    /// The encryption of the record.
+    pub ciphertext: FVCiphertext<T>,
+}
+
+/// The reasons for which a query cannot be answered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PirError {
+    /// The query does not have one ciphertext per record.
+    WrongLength { expected: usize, actual: usize },
+    /// A record is longer than n bytes.
+    RecordTooLong { index: usize },
+    /// A ciphertext of the query is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for PirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PirError::WrongLength { expected, actual } => write!(
+                f,
+                "query of {} ciphertexts for {} records",
+                actual, expected
+            ),
+            PirError::RecordTooLong { index } => write!(f, "record {} is too long", index),
+            PirError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for PirError {}
+
+impl From<ValidationError> for PirError {
+    fn from(e: ValidationError) -> Self {
+        PirError::Invalid(e)
+    }
+}
+
+impl<T> Serializable for PirQuery<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The encodings of the ciphertexts, concatenated.
+    fn to_bytes(&self) -> Vec<u8> {
+        self.selection.iter().flat_map(|ct| ct.to_bytes()).collect()
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// A query for record `index` of a database of `len` records.
+    ///
+    /// # Panics
+    /// If `index` is not below `len`.
+    pub fn pir_query(&self, pk: &FVCiphertext<T>, index: usize, len: usize) -> PirQuery<T> {
+        assert!(index < len, "record index out of range");
+        let mut one = vec![0; self.n];
+        one[0] = 1;
+        let zero = vec![0; self.n];
+        let selection = (0..len)
+            .map(|i| self.encrypt(if i == index { &one } else { &zero }, pk))
+            .collect();
+        PirQuery { selection }
+    }
+
+    /// Decode and validate a query.
+    pub fn pir_query_from_bytes(&self, bytes: &[u8]) -> Result<PirQuery<T>, ValidationError> {
+        let selection = self.ciphertexts_from_bytes(bytes)?;
+        Ok(PirQuery { selection })
+    }
+
+    /// Answer a query, rerandomizing the response under the public key of the client.
+    pub fn pir_answer(
+        &self,
+        db: &[FVPlaintext],
+        query: &PirQuery<T>,
+        pk: &FVCiphertext<T>,
+    ) -> Result<PirResponse<T>, PirError> {
+        if query.selection.len() != db.len() {
+            return Err(PirError::WrongLength {
+                expected: db.len(),
+                actual: query.selection.len(),
+            });
+        }
+        if let Some(index) = db.iter().position(|record| record.len() > self.n) {
+            return Err(PirError::RecordTooLong { index });
+        }
+        let mut sum = self.trivial_zero();
+        for (ct, record) in query.selection.iter().zip(db.iter()) {
+            self.validate(ct)?;
+            let product = self.multiply_plain(ct, record);
+            self.add_inplace(&mut sum, &product);
+        }
+        self.rerandomize(&mut sum, pk);
+        Ok(PirResponse { ciphertext: sum })
+    }
+
+    /// Decrypt the record of a response.
+    pub fn pir_decode(&self, response: &PirResponse<T>, sk: &SecretKey<T>) -> FVPlaintext {
+        self.decrypt(&response.ciphertext, sk)
+    }
+
+    /// Decode and validate concatenated ciphertext encodings.
+    pub(crate) fn ciphertexts_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Vec<FVCiphertext<T>>, ValidationError> {
+        let len = self.ciphertext_byte_len();
+        if !bytes.len().is_multiple_of(len) {
+            return Err(ValidationError::WrongLength {
+                expected: bytes.len() - bytes.len() % len,
+                actual: bytes.len(),
+            });
+        }
+        bytes
+            .chunks(len)
+            .map(|chunk| self.ciphertext_from_bytes(chunk))
+            .collect()
+    }
+
+    /// The product of a ciphertext with a plaintext polynomial.
+    pub(crate) fn multiply_plain(&self, ct: &FVCiphertext<T>, pt: &FVPlaintext) -> FVCiphertext<T> {
+        let mut coeffs = vec![T::zero(); self.n];
+        for (c, &x) in coeffs.iter_mut().zip(pt.iter()) {
+            *c = T::from_u32_raw(x as u32);
+        }
+        let m = RqPoly::from_coeffs(self.context.clone(), coeffs);
+        (
+            (self.poly_multiplier)(&ct.0, &m),
+            (self.poly_multiplier)(&ct.1, &m),
+        )
+    }
+
+    /// The trivial encryption of zero, with both polynomials zero.
+    pub(crate) fn trivial_zero(&self) -> FVCiphertext<T> {
+        let zero = RqPoly::from_coeffs(self.context.clone(), vec![T::zero(); self.n]);
+        (zero.clone(), zero)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_pir_retrieval() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let db: Vec<FVPlaintext> = (0..5u8)
+            .map(|i| (0..fv.n).map(|j| (j as u8).wrapping_mul(i + 1)).collect())
+            .collect();
+
+        let query = fv.pir_query(&pk, 3, db.len());
+        let query = fv.pir_query_from_bytes(&query.to_bytes()).unwrap();
+        let response = fv.pir_answer(&db, &query, &pk).unwrap();
+        assert_eq!(fv.pir_decode(&response, &sk), db[3]);
+
+        // A short record is padded with zeros.
+        let short = vec![vec![9; 10], vec![1; 10]];
+        let response = fv
+            .pir_answer(&short, &fv.pir_query(&pk, 0, 2), &pk)
+            .unwrap();
+        let mut expected = vec![9; 10];
+        expected.resize(fv.n, 0);
+        assert_eq!(fv.pir_decode(&response, &sk), expected);
+    }
+
+    #[test]
+    fn test_pir_rejections() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair();
+        let query = fv.pir_query(&pk, 0, 2);
+        assert_eq!(
+            fv.pir_answer(&[vec![0; 4]], &query, &pk),
+            Err(PirError::WrongLength {
+                expected: 1,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            fv.pir_answer(&[vec![0; 4], vec![0; fv.n + 1]], &query, &pk),
+            Err(PirError::RecordTooLong { index: 1 })
+        );
+        assert!(fv.pir_query_from_bytes(&query.to_bytes()[1..]).is_err());
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! The commonly used items of the crate, for a single glob import:
+//!
+//! ```
+//! use cupcake::prelude::*;
+//!
+//! let fv = cupcake::default();
+//! let (pk, sk) = fv.generate_keypair();
+//! let ct = fv.encrypt(&vec![1; fv.n], &pk);
+//! assert_eq!(fv.decrypt(&ct, &sk), vec![1; fv.n]);
+//! ```
+//!
+//! [`crate::dynamic::HomomorphicScheme`] is not included, as its methods share their names with
+//! those of the generic traits.
+pub use crate::ciphertext::Ciphertext;
+pub use crate::error::CupcakeError;
+pub use crate::evaluator::{Evaluation, Evaluator};
+pub use crate::fingerprint::Fingerprint;
+pub use crate::info::SchemeInfo;
+pub use crate::integer_arith::scalar::Scalar;
+pub use crate::integer_arith::ArithUtils;
+pub use crate::pubkey::PublicKey;
+pub use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption, Serializable};
+pub use crate::validation::ValidationError;
+pub use crate::{default, default_shared};
+pub use crate::{DefaultShemeType, FVCiphertext, FVPlaintext, FVRef, SecretKey, FV};
@@ -0,0 +1,313 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Private stream aggregation.
+//!
+//! In the scheme of Shi et al., clients 1..=count report a value in every epoch, and an untrusted
+//! aggregator learns the sum of the values of each epoch and nothing else. A dealer samples
+//! uniform keys s_1, ..., s_count in Rq and gives the aggregator s_0 = -(s_1 + ... + s_count),
+//! so that the keys sum to zero. Epoch t has a public uniform polynomial a_t, expanded from t,
+//! which makes the effective key of client i in epoch t the RLWE sample a_t s_i + e_i. A client
+//! reports x_i as c_i = a_t s_i + e_i + Delta x_i, and the aggregator computes
+//! c_1 + ... + c_count + a_t s_0 = Delta (x_1 + ... + x_count) + e_1 + ... + e_count, from which
+//! it decodes the sum, coefficient-wise modulo 256.
+//!
+//! The aggregator needs the reports of all clients to decode anything, so a client missing an
+//! epoch blocks its sum. Two reports of a client in the same epoch would reveal the difference of
+//! their values: a [`PsaClientKey`] refuses to report twice in an epoch, or in an earlier one.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{randutils, FiniteRingElt, RqPoly, NTT};
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVPlaintext, FV};
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+const DOMAIN: &[u8] = b"cupcake psa epoch";
+
+/// The reasons for which a value cannot be reported, or the sum of an epoch computed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PsaError {
+    /// The client already reported in this epoch or a later one.
+    EpochReused { epoch: u64 },
+    /// A report is for another epoch.
+    EpochMismatch { expected: u64, actual: u64 },
+    /// Not every client has exactly one report.
+    MissingReports { expected: usize, actual: usize },
+}
+
+impl fmt::Display for PsaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PsaError::EpochReused { epoch } => {
+                write!(f, "already reported in epoch {} or later", epoch)
+            }
+            PsaError::EpochMismatch { expected, actual } => {
+                write!(f, "report for epoch {}, expected {}", actual, expected)
+            }
+            PsaError::MissingReports { expected, actual } => write!(
+                f,
+                "expected reports of {} distinct clients, found {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl Error for PsaError {}
+
+/// The key of a client.
+pub struct PsaClientKey<T: ArithUtils<T>> {
+    index: usize,
+    poly: RqPoly<T>,
+    last_epoch: Option<u64>,
+}
+
+impl<T: ArithUtils<T>> PsaClientKey<T> {
+    /// The index of the client, in [1, count].
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for PsaClientKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PsaClientKey({})", self.index)
+    }
+}
+
+/// The key of the aggregator.
+pub struct PsaAggregatorKey<T: ArithUtils<T>> {
+    count: usize,
+    poly: RqPoly<T>,
+}
+
+impl<T: ArithUtils<T>> PsaAggregatorKey<T> {
+    /// The number of clients.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for PsaAggregatorKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PsaAggregatorKey({} clients)", self.count)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: ArithUtils<T>> Drop for PsaClientKey<T> {
+    fn drop(&mut self) {
+        crate::utils::wipe(&mut self.poly);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: ArithUtils<T>> Drop for PsaAggregatorKey<T> {
+    fn drop(&mut self) {
+        crate::utils::wipe(&mut self.poly);
+    }
+}
+
+/// The report of a client in an epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PsaReport<T> {
+    /// The index of the client.
+    pub index: usize,
+    /// The epoch of the report.
+    pub epoch: u64,
+    /// a_t s_i + e_i + Delta x_i, in coefficient form.
+    pub poly: RqPoly<T>,
+}
+
+impl<T> Serializable for PsaReport<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The index and epoch as 4 and 8-byte little-endian integers, followed by the polynomial.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.index as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.epoch.to_le_bytes());
+        bytes.extend_from_slice(&self.poly.to_bytes());
+        bytes
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt + NTT<T>,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The keys of the aggregator and of `count` clients, summing to zero.
+    ///
+    /// # Panics
+    /// If `count` is zero.
+    pub fn psa_setup(&self, count: usize) -> (PsaAggregatorKey<T>, Vec<PsaClientKey<T>>) {
+        assert!(count > 0, "at least one client");
+        let mut last = RqPoly::from_coeffs(self.context.clone(), vec![T::zero(); self.n]);
+        let mut clients = Vec::with_capacity(count);
+        for index in 1..=count {
+            let mut poly = randutils::sample_uniform_poly(self.context.clone());
+            last.sub_inplace(&poly);
+            if self.context.is_ntt_enabled {
+                poly.forward_transform();
+            }
+            clients.push(PsaClientKey {
+                index,
+                poly,
+                last_epoch: None,
+            });
+        }
+        if self.context.is_ntt_enabled {
+            last.forward_transform();
+        }
+        (PsaAggregatorKey { count, poly: last }, clients)
+    }
+
+    /// The public polynomial a_t of an epoch.
+    fn psa_epoch_poly(&self, epoch: u64) -> RqPoly<T> {
+        let mut hasher = Sha3_256::new();
+        hasher.update(DOMAIN);
+        hasher.update(epoch.to_le_bytes());
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(&hasher.finalize());
+        randutils::sample_uniform_poly_from_seed(self.context.clone(), &seed)
+    }
+
+    /// The report of `pt` by a client in `epoch`, which must be later than its previous report.
+    pub fn psa_report(
+        &self,
+        key: &mut PsaClientKey<T>,
+        epoch: u64,
+        pt: &FVPlaintext,
+    ) -> Result<PsaReport<T>, PsaError> {
+        if key.last_epoch.is_some_and(|last| epoch <= last) {
+            return Err(PsaError::EpochReused { epoch });
+        }
+        key.last_epoch = Some(epoch);
+        let mut poly = (self.poly_multiplier)(&self.psa_epoch_poly(epoch), &key.poly);
+        poly.add_inplace(&randutils::sample_gaussian_poly(
+            self.context.clone(),
+            self.stdev,
+        ));
+        for (c, &x) in poly.coeffs.iter_mut().zip(pt.iter()) {
+            let scaled = T::mul(&T::from_u32_raw(x as u32), &self.delta);
+            *c = T::add_mod(c, &scaled, &self.q);
+        }
+        Ok(PsaReport {
+            index: key.index,
+            epoch,
+            poly,
+        })
+    }
+
+    /// The sum of the values reported by all clients in `epoch`.
+    pub fn psa_aggregate(
+        &self,
+        key: &PsaAggregatorKey<T>,
+        epoch: u64,
+        reports: &[PsaReport<T>],
+    ) -> Result<FVPlaintext, PsaError> {
+        if let Some(r) = reports.iter().find(|r| r.epoch != epoch) {
+            return Err(PsaError::EpochMismatch {
+                expected: epoch,
+                actual: r.epoch,
+            });
+        }
+        let mut seen = vec![false; key.count];
+        for r in reports
+            .iter()
+            .filter(|r| r.index >= 1 && r.index <= key.count)
+        {
+            seen[r.index - 1] = true;
+        }
+        let distinct = seen.iter().filter(|x| **x).count();
+        if distinct != key.count || reports.len() != key.count {
+            return Err(PsaError::MissingReports {
+                expected: key.count,
+                actual: distinct,
+            });
+        }
+        let mut phase = (self.poly_multiplier)(&self.psa_epoch_poly(epoch), &key.poly);
+        for r in reports {
+            phase.add_inplace(&r.poly);
+        }
+        Ok(self.decode_phase(&phase))
+    }
+
+    /// Decode and validate a report.
+    pub fn psa_report_from_bytes(&self, bytes: &[u8]) -> Result<PsaReport<T>, ValidationError> {
+        let expected = 12 + self.poly_byte_len();
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let mut index = [0u8; 4];
+        index.copy_from_slice(&bytes[..4]);
+        let mut epoch = [0u8; 8];
+        epoch.copy_from_slice(&bytes[4..12]);
+        let poly = self.poly_from_bytes(&bytes[12..], 0)?;
+        self.validate_poly(&poly, 0, false)?;
+        Ok(PsaReport {
+            index: u32::from_le_bytes(index) as usize,
+            epoch: u64::from_le_bytes(epoch),
+            poly,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_stream_aggregation() {
+        let fv = FV::<Scalar>::default_2048();
+        let (aggregator, mut clients) = fv.psa_setup(4);
+        assert_eq!(aggregator.count(), 4);
+        for epoch in [3, 4, 9] {
+            let reports: Vec<_> = clients
+                .iter_mut()
+                .map(|key| {
+                    let pt = vec![key.index() as u8 * epoch as u8; fv.n];
+                    let report = fv.psa_report(key, epoch, &pt).unwrap();
+                    fv.psa_report_from_bytes(&report.to_bytes()).unwrap()
+                })
+                .collect();
+            let sum = fv.psa_aggregate(&aggregator, epoch, &reports).unwrap();
+            assert_eq!(sum, vec![10 * epoch as u8; fv.n]);
+        }
+    }
+
+    #[test]
+    fn test_stream_aggregation_failures() {
+        let fv = FV::<Scalar>::default_2048();
+        let (aggregator, mut clients) = fv.psa_setup(2);
+        let pt = vec![1; fv.n];
+        let r1 = fv.psa_report(&mut clients[0], 5, &pt).unwrap();
+        assert_eq!(
+            fv.psa_report(&mut clients[0], 5, &pt),
+            Err(PsaError::EpochReused { epoch: 5 })
+        );
+        assert_eq!(
+            fv.psa_aggregate(&aggregator, 5, &[r1.clone(), r1.clone()]),
+            Err(PsaError::MissingReports {
+                expected: 2,
+                actual: 1
+            })
+        );
+        let r2 = fv.psa_report(&mut clients[1], 6, &pt).unwrap();
+        assert_eq!(
+            fv.psa_aggregate(&aggregator, 5, &[r1, r2]),
+            Err(PsaError::EpochMismatch {
+                expected: 5,
+                actual: 6
+            })
+        );
+    }
+}
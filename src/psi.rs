@@ -0,0 +1,310 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Private set intersection cardinality.
+//!
+//! A client and a server holding sets of byte strings learn how many elements they have in
+//! common: the server learns nothing of the set of the client, and the client learns the count
+//! but not which elements. Both hash their elements to the slots of a table of `ciphertexts * n`
+//! slots, keyed by a shared [`PsiTable`]. The client sends a [`PsiQuery`], the encryption of its
+//! indicator vector: coefficient j of ciphertext k encrypts 1 if an element of the client falls
+//! in slot k n + j, and 0 otherwise. For each of its own elements, the server multiplies the
+//! ciphertext of its slot by the monomial moving the coefficient of the slot to the constant
+//! coefficient, and sums the products, so that the constant coefficient of the sum counts the
+//! elements of the server whose slot is marked by the client.
+//!
+//! The plaintext modulus is 256, so the server splits its set at random into groups of at most
+//! [`GROUP_LEN`] elements and answers with one ciphertext per group. It blinds the other
+//! coefficients of each ciphertext, which depend on its set, with random bytes, and rerandomizes
+//! the ciphertext under the public key of the client. [`FV::psi_cardinality`] adds the counts of
+//! the groups. Besides the cardinality, the client learns the number of groups and the count of
+//! each group, a random subset of the set of the server.
+//!
+//! An element of the server outside the set of the client is counted when it hashes to a slot
+//! marked by the client, with probability at most the size of the set of the client over the
+//! number of slots: the table should be much larger than the product of the sizes of the sets.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption, Serializable};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, SecretKey, FV};
+use rand::{Rng, RngCore};
+use sha3::{Digest, Sha3_256};
+use std::error::Error;
+use std::fmt;
+
+/// The largest number of elements of the server counted in one ciphertext of a response, so
+/// that the count does not wrap around the plaintext modulus.
+pub const GROUP_LEN: usize = 255;
+
+const DOMAIN: &[u8] = b"cupcake psi slot";
+
+/// The table to which both parties hash their elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PsiTable {
+    /// The key of the hash, agreed on by both parties.
+    pub key: [u8; 32],
+    /// The number of ciphertexts of a query, each covering n slots.
+    pub ciphertexts: usize,
+}
+
+/// The encrypted indicator vector of the set of the client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PsiQuery<T> {
+    /// The encryptions of the indicators of the slots, n per ciphertext.
+    pub indicators: Vec<FVCiphertext<T>>,
+}
+
+/// The blinded counts of the groups of the set of the server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PsiResponse<T> {
+    /// One ciphertext per group, whose constant coefficient encrypts its count.
+    pub counts: Vec<FVCiphertext<T>>,
+}
+
+/// The reasons for which a query cannot be answered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PsiError {
+    /// The query does not have one ciphertext per n slots of the table.
+    WrongLength { expected: usize, actual: usize },
+    /// A ciphertext of the query is invalid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for PsiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PsiError::WrongLength { expected, actual } => write!(
+                f,
+                "query of {} ciphertexts for a table of {}",
+                actual, expected
+            ),
+            PsiError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for PsiError {}
+
+impl From<ValidationError> for PsiError {
+    fn from(e: ValidationError) -> Self {
+        PsiError::Invalid(e)
+    }
+}
+
+impl<T> Serializable for PsiQuery<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The encodings of the ciphertexts, concatenated.
+    fn to_bytes(&self) -> Vec<u8> {
+        self.indicators
+            .iter()
+            .flat_map(|ct| ct.to_bytes())
+            .collect()
+    }
+}
+
+impl<T> Serializable for PsiResponse<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The encodings of the ciphertexts, concatenated.
+    fn to_bytes(&self) -> Vec<u8> {
+        self.counts.iter().flat_map(|ct| ct.to_bytes()).collect()
+    }
+}
+
+impl PsiTable {
+    /// A table of `ciphertexts` ciphertexts, hashing with `key`.
+    ///
+    /// # Panics
+    /// If `ciphertexts` is zero.
+    pub fn new(key: [u8; 32], ciphertexts: usize) -> Self {
+        assert!(ciphertexts > 0, "empty table");
+        PsiTable { key, ciphertexts }
+    }
+
+    /// The slot of an element, in a table of `ciphertexts * n` slots.
+    pub fn slot(&self, n: usize, element: &[u8]) -> usize {
+        let mut hasher = Sha3_256::new();
+        hasher.update(DOMAIN);
+        hasher.update(self.key);
+        hasher.update(element);
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&hasher.finalize()[..8]);
+        (u64::from_le_bytes(word) % (self.ciphertexts * n) as u64) as usize
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The query of a client holding `set`.
+    pub fn psi_query<I, E>(&self, pk: &FVCiphertext<T>, table: &PsiTable, set: I) -> PsiQuery<T>
+    where
+        I: IntoIterator<Item = E>,
+        E: AsRef<[u8]>,
+    {
+        let mut indicators = vec![vec![0u8; self.n]; table.ciphertexts];
+        for element in set {
+            let slot = table.slot(self.n, element.as_ref());
+            indicators[slot / self.n][slot % self.n] = 1;
+        }
+        let indicators = indicators.iter().map(|pt| self.encrypt(pt, pk)).collect();
+        PsiQuery { indicators }
+    }
+
+    /// Decode and validate a query.
+    pub fn psi_query_from_bytes(&self, bytes: &[u8]) -> Result<PsiQuery<T>, ValidationError> {
+        let indicators = self.ciphertexts_from_bytes(bytes)?;
+        Ok(PsiQuery { indicators })
+    }
+
+    /// Answer a query with the blinded counts of `set`, rerandomized under the public key of
+    /// the client.
+    pub fn psi_respond<I, E>(
+        &self,
+        pk: &FVCiphertext<T>,
+        table: &PsiTable,
+        query: &PsiQuery<T>,
+        set: I,
+    ) -> Result<PsiResponse<T>, PsiError>
+    where
+        I: IntoIterator<Item = E>,
+        E: AsRef<[u8]>,
+    {
+        if query.indicators.len() != table.ciphertexts {
+            return Err(PsiError::WrongLength {
+                expected: table.ciphertexts,
+                actual: query.indicators.len(),
+            });
+        }
+        for ct in query.indicators.iter() {
+            self.validate(ct)?;
+        }
+        let mut slots: Vec<usize> = set
+            .into_iter()
+            .map(|element| table.slot(self.n, element.as_ref()))
+            .collect();
+        with_rng(|rng| {
+            // Fisher-Yates shuffle.
+            for i in (1..slots.len()).rev() {
+                slots.swap(i, rng.gen_range(0..=i));
+            }
+        });
+
+        let counts = slots
+            .chunks(GROUP_LEN)
+            .map(|group| {
+                let mut count = self.group_count(&query.indicators, group);
+                let mut blinding = vec![0u8; self.n];
+                with_rng(|rng| rng.fill_bytes(&mut blinding[1..]));
+                self.add_plain_inplace(&mut count, &blinding);
+                self.rerandomize(&mut count, pk);
+                count
+            })
+            .collect();
+        Ok(PsiResponse { counts })
+    }
+
+    /// Decode and validate a response.
+    pub fn psi_response_from_bytes(&self, bytes: &[u8]) -> Result<PsiResponse<T>, ValidationError> {
+        let counts = self.ciphertexts_from_bytes(bytes)?;
+        Ok(PsiResponse { counts })
+    }
+
+    /// The size of the intersection of the sets, up to hash collisions.
+    pub fn psi_cardinality(&self, response: &PsiResponse<T>, sk: &SecretKey<T>) -> u64 {
+        response
+            .counts
+            .iter()
+            .map(|ct| self.decrypt(ct, sk)[0] as u64)
+            .sum()
+    }
+
+    /// The encryption of the number of slots of `group` marked in `indicators`, in the constant
+    /// coefficient.
+    fn group_count(&self, indicators: &[FVCiphertext<T>], group: &[usize]) -> FVCiphertext<T> {
+        // For each ciphertext, the sum of the monomials X^-j of the slots j it covers, where
+        // X^-j = -X^(n-j) for j > 0.
+        let mut multipliers = vec![vec![0i64; self.n]; indicators.len()];
+        for &slot in group {
+            let (k, j) = (slot / self.n, slot % self.n);
+            if j == 0 {
+                multipliers[k][0] += 1;
+            } else {
+                multipliers[k][self.n - j] -= 1;
+            }
+        }
+        let mut sum = self.trivial_zero();
+        for (ct, multiplier) in indicators.iter().zip(multipliers.iter()) {
+            if multiplier.iter().all(|&c| c == 0) {
+                continue;
+            }
+            let coeffs = multiplier
+                .iter()
+                .map(|&c| {
+                    let magnitude = T::from_u64_raw(c.unsigned_abs());
+                    if c < 0 {
+                        T::sub(&self.q, &magnitude)
+                    } else {
+                        magnitude
+                    }
+                })
+                .collect();
+            let m = RqPoly::from_coeffs(self.context.clone(), coeffs);
+            let product = (
+                (self.poly_multiplier)(&ct.0, &m),
+                (self.poly_multiplier)(&ct.1, &m),
+            );
+            self.add_inplace(&mut sum, &product);
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_psi_cardinality() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let table = PsiTable::new([7; 32], 16);
+        let client: Vec<String> = (0..20).map(|i| format!("user{}", i)).collect();
+        // 300 elements, more than a group, of which 12 are shared.
+        let server: Vec<String> = (8..308).map(|i| format!("user{}", i)).collect();
+
+        let query = fv.psi_query(&pk, &table, &client);
+        let query = fv.psi_query_from_bytes(&query.to_bytes()).unwrap();
+        let response = fv.psi_respond(&pk, &table, &query, &server).unwrap();
+        assert_eq!(response.counts.len(), 2);
+        let response = fv.psi_response_from_bytes(&response.to_bytes()).unwrap();
+        assert_eq!(fv.psi_cardinality(&response, &sk), 12);
+
+        let empty = fv.psi_respond(&pk, &table, &query, Vec::<&[u8]>::new());
+        assert_eq!(fv.psi_cardinality(&empty.unwrap(), &sk), 0);
+    }
+
+    #[test]
+    fn test_psi_rejections() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, _) = fv.generate_keypair();
+        let query = fv.psi_query(&pk, &PsiTable::new([0; 32], 2), [b"a"]);
+        assert_eq!(
+            fv.psi_respond(&pk, &PsiTable::new([0; 32], 3), &query, [b"a"]),
+            Err(PsiError::WrongLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert!(fv.psi_query_from_bytes(&query.to_bytes()[1..]).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Public keys as a type distinct from ciphertexts.
+//!
+//! An FV public key is an encryption of zero, and the scheme traits give it the ciphertext type,
+//! so nothing prevents passing a ciphertext where a key is expected. A [`PublicKey`] wraps the
+//! pair of polynomials of a key, and the methods below take it where [`PKEncryption`] takes a
+//! bare pair. Converting between a key and a pair is explicit, with [`PublicKey::from_pair`],
+//! [`PublicKey::as_pair`] and [`PublicKey::into_pair`].
+use crate::ciphertext::Ciphertext;
+use crate::fingerprint::{self, Fingerprint};
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, Serializable};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::fmt;
+
+/// A public key.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(
+    feature = "diesel",
+    derive(diesel::expression::AsExpression, diesel::deserialize::FromSqlRow),
+    diesel(sql_type = diesel::sql_types::Binary)
+)]
+pub struct PublicKey<T> {
+    polys: FVCiphertext<T>,
+    fingerprint: Fingerprint,
+}
+
+impl<T> PublicKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// A public key from its pair of polynomials, as returned by
+    /// [`PKEncryption::generate_keypair`]. The pair is not validated, see
+    /// [`FV::typed_public_key_from_bytes`] for keys from untrusted sources.
+    pub fn from_pair(polys: FVCiphertext<T>) -> Self {
+        let fingerprint = fingerprint::public_key_fingerprint(&polys);
+        PublicKey { polys, fingerprint }
+    }
+
+    /// The pair of polynomials of the key, to pass to the generic scheme traits.
+    pub fn as_pair(&self) -> &FVCiphertext<T> {
+        &self.polys
+    }
+
+    /// The pair of polynomials of the key.
+    pub fn into_pair(self) -> FVCiphertext<T> {
+        self.polys
+    }
+
+    /// The fingerprint of the key, as returned by [`FV::public_key_fingerprint`].
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+}
+
+impl<T> fmt::Debug for PublicKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PublicKey")
+            .field("n", &self.polys.0.coeffs.len())
+            .field("q", &T::to_u64(self.polys.0.modulus().clone()))
+            .field("fingerprint", &format_args!("{}", self.fingerprint))
+            .finish()
+    }
+}
+
+impl<T> Serializable for PublicKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The encoding of the underlying pair.
+    fn to_bytes(&self) -> Vec<u8> {
+        self.polys.to_bytes()
+    }
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Decode and validate a public key.
+    pub fn typed_public_key_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<PublicKey<T>, ValidationError> {
+        Ok(PublicKey::from_pair(self.public_key_from_bytes(bytes)?))
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Generate a keypair, with the public key typed as such.
+    pub fn generate_typed_keypair(&self) -> (PublicKey<T>, SecretKey<T>) {
+        let (pk, sk) = self.generate_keypair();
+        (PublicKey::from_pair(pk), sk)
+    }
+
+    /// Encrypt a plaintext under a public key.
+    pub fn encrypt_to(&self, pt: &FVPlaintext, pk: &PublicKey<T>) -> Ciphertext<T> {
+        Ciphertext::from(self.encrypt(pt, pk.as_pair()))
+    }
+
+    /// Rerandomize a ciphertext under a public key.
+    pub fn rerandomize_with(&self, ct: &mut Ciphertext<T>, pk: &PublicKey<T>) {
+        self.rerandomize(ct.as_mut(), pk.as_pair());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_typed_public_key() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_typed_keypair();
+        assert_eq!(pk.fingerprint(), fv.public_key_fingerprint(pk.as_pair()));
+        assert_eq!(sk.public_key_fingerprint(), Some(pk.fingerprint()));
+
+        let pk = fv.typed_public_key_from_bytes(&pk.to_bytes()).unwrap();
+        let mut ct = fv.encrypt_to(&vec![5; fv.n], &pk);
+        let before = ct.clone();
+        fv.rerandomize_with(&mut ct, &pk);
+        assert_ne!(ct.as_ref().1.coeffs, before.as_ref().1.coeffs);
+        assert_eq!(fv.decrypt(ct.as_ref(), &sk), vec![5; fv.n]);
+
+        // A ciphertext is not a valid public key: it is not in NTT form.
+        assert!(fv.typed_public_key_from_bytes(&ct.to_bytes()).is_err());
+        assert_eq!(
+            fv.decrypt(&fv.encrypt(&vec![1; fv.n], &pk.into_pair()), &sk),
+            vec![1; fv.n]
+        );
+    }
+
+    #[test]
+    fn test_debug_prints_no_coefficients() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_typed_keypair();
+        assert_eq!(
+            format!("{:?}", pk),
+            format!(
+                "PublicKey {{ n: 16, q: 65537, fingerprint: {} }}",
+                pk.fingerprint()
+            )
+        );
+        let ct = fv.encrypt_to(&vec![1; fv.n], &pk);
+        let debug = format!("{:?}", ct);
+        assert!(debug.starts_with(&format!(
+            "Ciphertext {{ params: {}, ntt_flags: [false, false], fingerprint: ",
+            fv.params_id()
+        )));
+        let poly = &ct.as_ref().0;
+        let debug = format!("{:?}", poly);
+        assert!(debug.starts_with("RqPoly { n: 16, q: Scalar { "));
+        assert!(debug.len() < 400);
+        assert!(format!("{:?}", fv.context).len() < 400);
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Proactive refresh of additive secret key shares.
+//!
+//! The holders of the shares s_0, ..., s_{n-1} of a secret key (see [`crate::threshold`])
+//! periodically re-randomize them without changing their sum: every holder i splits zero into n
+//! uniform polynomials r_{i,0} + ... + r_{i,n-1} = 0 and sends r_{i,j} to holder j, who replaces
+//! its share by s_j + r_{0,j} + ... + r_{n-1,j}. The refreshed shares are independent of the old
+//! ones, so that shares stolen before a refresh cannot be combined with shares stolen after it.
+//!
+//! A refresh message reveals the difference between the old and new shares of its recipient, so
+//! it must be sent over a private channel and erased after use.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{randutils, FiniteRingElt, RqPoly, NTT};
+use crate::threshold::{SecretKeyShare, ThresholdError};
+use crate::traits::Serializable;
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::FV;
+use std::fmt;
+
+/// The refresh contribution of holder `from` to the share of holder `to`.
+#[derive(Clone)]
+pub struct RefreshMessage<T: ArithUtils<T>> {
+    from: usize,
+    to: usize,
+    count: usize,
+    poly: RqPoly<T>,
+}
+
+impl<T: ArithUtils<T>> RefreshMessage<T> {
+    /// The index of the sender.
+    pub fn from(&self) -> usize {
+        self.from
+    }
+
+    /// The index of the recipient.
+    pub fn to(&self) -> usize {
+        self.to
+    }
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for RefreshMessage<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RefreshMessage({} -> {} of {})",
+            self.from, self.to, self.count
+        )
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: ArithUtils<T>> Drop for RefreshMessage<T> {
+    fn drop(&mut self) {
+        crate::utils::wipe(&mut self.poly);
+    }
+}
+
+impl<T> Serializable for RefreshMessage<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The sender, recipient and count as 4-byte little-endian integers, followed by the
+    /// polynomial.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.from as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(self.to as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.count as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.poly.to_bytes());
+        bytes
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The refresh messages of the holder of `share` to every holder, itself included.
+    pub fn refresh_messages(&self, share: &SecretKeyShare<T>) -> Vec<RefreshMessage<T>> {
+        let count = share.count();
+        let mut last = RqPoly::from_coeffs(self.context.clone(), vec![T::zero(); self.n]);
+        let mut messages = Vec::with_capacity(count);
+        for to in 0..count - 1 {
+            let poly = randutils::sample_uniform_poly(self.context.clone());
+            last.sub_inplace(&poly);
+            messages.push(RefreshMessage {
+                from: share.index(),
+                to,
+                count,
+                poly,
+            });
+        }
+        messages.push(RefreshMessage {
+            from: share.index(),
+            to: count - 1,
+            count,
+            poly: last,
+        });
+        messages
+    }
+
+    /// Refresh a share with the messages addressed to it by every holder.
+    pub fn apply_refresh(
+        &self,
+        share: &mut SecretKeyShare<T>,
+        messages: &[RefreshMessage<T>],
+    ) -> Result<(), ThresholdError> {
+        let count = share.count();
+        if let Some(m) = messages.iter().find(|m| m.count != count) {
+            return Err(ThresholdError::CountMismatch {
+                expected: count,
+                actual: m.count,
+            });
+        }
+        if messages.iter().any(|m| m.to != share.index()) {
+            return Err(ThresholdError::ParticipantMismatch);
+        }
+        let mut seen = vec![false; count];
+        for m in messages.iter().filter(|m| m.from < count) {
+            seen[m.from] = true;
+        }
+        let distinct = seen.iter().filter(|x| **x).count();
+        if distinct != count || messages.len() != count {
+            return Err(ThresholdError::MissingShares {
+                expected: count,
+                actual: distinct,
+            });
+        }
+
+        let mut delta = RqPoly::from_coeffs(self.context.clone(), vec![T::zero(); self.n]);
+        for m in messages.iter() {
+            delta.add_inplace(&m.poly);
+        }
+        if self.context.is_ntt_enabled {
+            delta.forward_transform();
+        }
+        share.poly_mut().add_inplace(&delta);
+        wipe(&mut delta);
+        Ok(())
+    }
+
+    /// Decode and validate a refresh message.
+    pub fn refresh_message_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<RefreshMessage<T>, ValidationError> {
+        let expected = 12 + self.poly_byte_len();
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let word = |i: usize| {
+            let mut w = [0u8; 4];
+            w.copy_from_slice(&bytes[i..i + 4]);
+            u32::from_le_bytes(w) as usize
+        };
+        let (from, to, count) = (word(0), word(4), word(8));
+        for &index in [from, to].iter() {
+            if index >= count {
+                return Err(ValidationError::InvalidShareIndex { index, count });
+            }
+        }
+        let poly = self.poly_from_bytes(&bytes[12..], 0)?;
+        self.validate_poly(&poly, 0, false)?;
+        Ok(RefreshMessage {
+            from,
+            to,
+            count,
+            poly,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_share_refresh() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut shares = fv.split_secret_key(&sk, 3);
+        let ct = fv.encrypt(&vec![6; fv.n], &pk);
+        let stale = fv.partial_decrypt(&ct, &shares[0]);
+
+        let outboxes: Vec<_> = shares.iter().map(|s| fv.refresh_messages(s)).collect();
+        for share in shares.iter_mut() {
+            let inbox: Vec<_> = outboxes
+                .iter()
+                .map(|outbox| {
+                    let bytes = outbox[share.index()].to_bytes();
+                    fv.refresh_message_from_bytes(&bytes).unwrap()
+                })
+                .collect();
+            assert_eq!(fv.apply_refresh(share, &inbox), Ok(()));
+        }
+
+        let mut partials: Vec<_> = shares
+            .iter()
+            .map(|share| fv.partial_decrypt(&ct, share))
+            .collect();
+        assert_eq!(fv.combine_partials(&ct, &partials), Ok(vec![6; fv.n]));
+        partials[0] = stale;
+        assert_ne!(fv.combine_partials(&ct, &partials), Ok(vec![6; fv.n]));
+    }
+
+    #[test]
+    fn test_incomplete_refresh() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (_, sk) = fv.generate_keypair();
+        let mut shares = fv.split_secret_key(&sk, 2);
+        let outboxes: Vec<_> = shares.iter().map(|s| fv.refresh_messages(s)).collect();
+        assert_eq!(
+            fv.apply_refresh(&mut shares[0], &[outboxes[0][0].clone()]),
+            Err(ThresholdError::MissingShares {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            fv.apply_refresh(
+                &mut shares[0],
+                &[outboxes[0][1].clone(), outboxes[1][1].clone()]
+            ),
+            Err(ThresholdError::ParticipantMismatch)
+        );
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Management of the random number generators used for sampling.
+//!
+//! All encryption randomness is drawn from a per-thread CSPRNG which is reseeded from the
+//! operating system after a configurable number of output bytes. The generator also records the
+//! id of the process that seeded it, and reseeds when it detects that it is running in a
+//! different process, so that children of a pre-forking server never reuse the randomness of
+//! their parent. The process id is checked once each time the generator is entered, e.g. once
+//! per sampled polynomial, rather than on every draw.
+//!
+//! With the `deterministic` feature, [`DeterministicRng`] can temporarily replace the generator
+//! of the current thread by one expanded from a recorded seed, so that simulations produce
+//! bit-identical keys and ciphertexts across runs. Seeds are expanded with SHAKE256 rather than
+//! a generator of `rand`, whose algorithms may change between its versions.
+//!
+//! With the `parallel` feature, the generator of every new thread is seeded from an independent
+//! stream derived from a per-process master key, instead of a separate request to the OS.
+//!
+//! With the `wasm` feature, seeds are requested from `getrandom`, which calls
+//! `crypto.getRandomValues` in a browser.
+use rand::rngs::StdRng;
+use rand::{CryptoRng, Error, RngCore, SeedableRng};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Shake256, Shake256Reader};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Default number of output bytes after which the generator is reseeded from the OS.
+pub const DEFAULT_RESEED_THRESHOLD: u64 = 1 << 24;
+
+static RESEED_THRESHOLD: AtomicU64 = AtomicU64::new(DEFAULT_RESEED_THRESHOLD);
+
+/// Set the number of output bytes after which the generators are reseeded from the OS.
+pub fn set_reseed_threshold(bytes: u64) {
+    RESEED_THRESHOLD.store(bytes, Ordering::Relaxed);
+}
+
+/// The number of output bytes after which the generators are reseeded from the OS.
+pub fn reseed_threshold() -> u64 {
+    RESEED_THRESHOLD.load(Ordering::Relaxed)
+}
+
+/// A generator expanding a seed as SHAKE256(tag || seed), so that its output is fixed by the
+/// seed alone.
+pub(crate) struct SeededRng(Shake256Reader);
+
+impl SeededRng {
+    pub(crate) fn from_seed(seed: [u8; 32]) -> Self {
+        let mut hasher = Shake256::default();
+        hasher.update(b"cupcake seeded rng");
+        hasher.update(&seed);
+        SeededRng(hasher.finalize_xof())
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.0.read(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.0.read(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.read(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.0.read(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for SeededRng {}
+
+/// The generator behind a [`ManagedRng`].
+enum Stream {
+    Entropy(StdRng),
+    Seeded(SeededRng),
+}
+
+impl Stream {
+    fn rng(&mut self) -> &mut dyn RngCore {
+        match self {
+            Stream::Entropy(rng) => rng,
+            Stream::Seeded(rng) => rng,
+        }
+    }
+}
+
+/// A CSPRNG which reseeds itself from the OS after a number of output bytes or after a fork.
+pub(crate) struct ManagedRng {
+    inner: Stream,
+    bytes_since_reseed: u64,
+    pid: u32,
+    threshold: Option<u64>,
+    deterministic: bool,
+}
+
+impl ManagedRng {
+    /// Create a generator following the global reseeding threshold.
+    pub(crate) fn new() -> Self {
+        ManagedRng {
+            inner: Stream::Entropy(new_stream()),
+            bytes_since_reseed: 0,
+            pid: process_id(),
+            threshold: None,
+            deterministic: false,
+        }
+    }
+
+    /// Create a generator expanded from `seed`, which is never reseeded.
+    fn from_seed(seed: [u8; 32]) -> Self {
+        ManagedRng {
+            inner: Stream::Seeded(SeededRng::from_seed(seed)),
+            bytes_since_reseed: 0,
+            pid: process_id(),
+            threshold: None,
+            deterministic: true,
+        }
+    }
+
+    #[cfg(test)]
+    fn with_threshold(threshold: u64) -> Self {
+        ManagedRng {
+            threshold: Some(threshold),
+            ..Self::new()
+        }
+    }
+
+    fn reseed(&mut self) {
+        self.inner = Stream::Entropy(entropy_stream());
+        self.bytes_since_reseed = 0;
+        self.pid = process_id();
+    }
+
+    /// Reseed if the process has been forked since the last seeding.
+    fn check_fork(&mut self) {
+        if !self.deterministic && self.pid != process_id() {
+            self.reseed();
+        }
+    }
+
+    /// Reseed if needed before producing `len` more bytes.
+    fn prepare(&mut self, len: usize) {
+        if self.deterministic {
+            return;
+        }
+        let threshold = self.threshold.unwrap_or_else(reseed_threshold);
+        if self.bytes_since_reseed + len as u64 > threshold {
+            self.reseed();
+        }
+        self.bytes_since_reseed += len as u64;
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn new_stream() -> StdRng {
+    entropy_stream()
+}
+
+/// A generator seeded from the OS.
+#[cfg(not(feature = "wasm"))]
+fn entropy_stream() -> StdRng {
+    StdRng::from_entropy()
+}
+
+/// A generator seeded from `getrandom`, which also works on wasm32-unknown-unknown, where the
+/// OS generator of `rand` is unavailable.
+#[cfg(feature = "wasm")]
+fn entropy_stream() -> StdRng {
+    let mut seed = [0u8; 32];
+    getrandom::getrandom(&mut seed).expect("no entropy source available");
+    StdRng::from_seed(seed)
+}
+
+/// The id of the current process, or 0 on wasm32, which has no processes.
+fn process_id() -> u32 {
+    if cfg!(target_arch = "wasm32") {
+        0
+    } else {
+        std::process::id()
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn new_stream() -> StdRng {
+    StdRng::from_seed(streams::next_stream_seed())
+}
+
+#[cfg(feature = "parallel")]
+mod streams {
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+    use sha3::digest::{ExtendableOutput, Update, XofReader};
+    use sha3::Shake128;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::OnceLock;
+
+    static MASTER_KEY: OnceLock<[u8; 32]> = OnceLock::new();
+    static STREAM_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    /// Derive the seed of a new stream as SHAKE128(master key || pid || stream index). The pid is
+    /// included so that the streams of forked children do not collide with each other.
+    pub(super) fn next_stream_seed() -> [u8; 32] {
+        let master = MASTER_KEY.get_or_init(|| {
+            let mut key = [0u8; 32];
+            OsRng.fill_bytes(&mut key);
+            key
+        });
+        let index = STREAM_COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let mut hasher = Shake128::default();
+        hasher.update(b"cupcake rng stream");
+        hasher.update(master);
+        hasher.update(&super::process_id().to_le_bytes());
+        hasher.update(&index.to_le_bytes());
+        let mut seed = [0u8; 32];
+        hasher.finalize_xof().read(&mut seed);
+        seed
+    }
+}
+
+impl RngCore for ManagedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.prepare(4);
+        self.inner.rng().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.prepare(8);
+        self.inner.rng().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.prepare(dest.len());
+        self.inner.rng().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.prepare(dest.len());
+        self.inner.rng().try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for ManagedRng {}
+
+thread_local! {
+    static THREAD_RNG: RefCell<ManagedRng> = RefCell::new(ManagedRng::new());
+}
+
+/// Run `f` with this thread's managed generator, after checking for a fork.
+pub(crate) fn with_rng<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut ManagedRng) -> R,
+{
+    THREAD_RNG.with(|rng| {
+        let mut rng = rng.borrow_mut();
+        rng.check_fork();
+        f(&mut rng)
+    })
+}
+
+/// If the generator of the current thread is seeded, draw from it one seed per task, so that
+/// tasks run on other threads are driven by seeds in a fixed order; otherwise `None`.
+#[cfg(feature = "parallel")]
+pub(crate) fn task_seeds(count: usize) -> Option<Vec<[u8; 32]>> {
+    with_rng(|rng| {
+        if !rng.deterministic {
+            return None;
+        }
+        let mut seeds = vec![[0u8; 32]; count];
+        for seed in seeds.iter_mut() {
+            rng.fill_bytes(seed);
+        }
+        Some(seeds)
+    })
+}
+
+/// Run `f` with all sampling on the current thread driven by `seed`. The previous generator of
+/// the thread is restored afterwards, even if `f` panics.
+pub(crate) fn with_seeded_rng<F, R>(seed: [u8; 32], f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    struct Restore(Option<ManagedRng>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(previous) = self.0.take() {
+                THREAD_RNG.with(|rng| *rng.borrow_mut() = previous);
+            }
+        }
+    }
+
+    let seeded = ManagedRng::from_seed(seed);
+    let previous = THREAD_RNG.with(|rng| std::mem::replace(&mut *rng.borrow_mut(), seeded));
+    let _restore = Restore(Some(previous));
+    f()
+}
+
+/// A harness driving all sampling of the crate on the current thread from a single seed.
+///
+/// The seed can be recorded with [`DeterministicRng::seed`] and replayed later; for a fixed
+/// version of the crate, running the same sequence of operations inside [`DeterministicRng::run`]
+/// produces the same keys and ciphertexts. This must never be used to protect real data.
+#[cfg(feature = "deterministic")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeterministicRng {
+    seed: [u8; 32],
+}
+
+#[cfg(feature = "deterministic")]
+impl DeterministicRng {
+    /// Create a harness from a recorded seed.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        DeterministicRng { seed }
+    }
+
+    /// Create a harness from a fresh random seed, to be recorded with `seed()`.
+    pub fn from_entropy() -> Self {
+        let mut seed = [0u8; 32];
+        with_rng(|rng| rng.fill_bytes(&mut seed));
+        DeterministicRng { seed }
+    }
+
+    /// The seed driving this harness.
+    pub fn seed(&self) -> [u8; 32] {
+        self.seed
+    }
+
+    /// Run `f` with all sampling on the current thread driven by the seed. The previous
+    /// generator of the thread is restored afterwards, even if `f` panics.
+    pub fn run<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        with_seeded_rng(self.seed, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reseed_after_threshold() {
+        let mut rng = ManagedRng::with_threshold(16);
+        rng.next_u64();
+        rng.next_u64();
+        assert_eq!(rng.bytes_since_reseed, 16);
+        rng.next_u32();
+        assert_eq!(rng.bytes_since_reseed, 4);
+    }
+
+    #[test]
+    fn test_reseed_on_pid_change() {
+        with_rng(|rng| {
+            rng.next_u64();
+            // pretend we have been forked.
+            rng.pid = rng.pid.wrapping_add(1);
+        });
+        with_rng(|rng| {
+            assert_eq!(rng.pid, process_id());
+            assert_eq!(rng.bytes_since_reseed, 0);
+        });
+    }
+
+    #[cfg(feature = "deterministic")]
+    #[test]
+    fn test_deterministic_run() {
+        let harness = DeterministicRng::from_entropy();
+        let a = harness.run(|| with_rng(|rng| rng.next_u64()));
+        let b = DeterministicRng::from_seed(harness.seed()).run(|| with_rng(|rng| rng.next_u64()));
+        assert_eq!(a, b);
+        // the thread generator is restored afterwards.
+        assert!(!with_rng(|rng| rng.deterministic));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_independent_streams() {
+        let a = std::thread::spawn(|| with_rng(|rng| rng.next_u64())).join().unwrap();
+        let b = std::thread::spawn(|| with_rng(|rng| rng.next_u64())).join().unwrap();
+        assert_ne!(a, b);
+    }
+}
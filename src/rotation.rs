@@ -0,0 +1,305 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Key rotation.
+//!
+//! Stored ciphertexts are tagged with the fingerprint of the public key they are encrypted under
+//! (see [`crate::binding`]).
+//! A [`KeyRotation`] generates a new keypair and moves a corpus of ciphertexts from the old key
+//! to the new one, by decrypting each ciphertext and encrypting it again under the new key. The
+//! old key is used either directly, or under threshold control through the partial decryptions of
+//! its share holders (see [`crate::threshold`]).
+//!
+//! Large corpora are rotated in batches. The position of the rotation is kept in a
+//! [`RotationProgress`], which can be serialized after each batch and passed to
+//! [`FV::resume_key_rotation`] to continue after an interruption. Ciphertexts already tagged with
+//! the new key are skipped, so that replaying a batch is harmless.
+use crate::binding::TaggedCiphertext;
+use crate::fingerprint::Fingerprint;
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::threshold::{PartialDecryption, ThresholdError};
+use crate::traits::{PKEncryption, SKEncryption, Serializable};
+use crate::utils::wipe;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The resumable position of a key rotation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RotationProgress {
+    /// The fingerprint of the old public key.
+    pub old_key: Fingerprint,
+    /// The fingerprint of the new public key.
+    pub new_key: Fingerprint,
+    /// The number of ciphertexts of the corpus already rotated.
+    pub rotated: usize,
+}
+
+impl Serializable for RotationProgress {
+    /// The two fingerprints, followed by the count as an 8-byte little-endian integer.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.old_key.0.to_vec();
+        bytes.extend_from_slice(&self.new_key.0);
+        bytes.extend_from_slice(&(self.rotated as u64).to_le_bytes());
+        bytes
+    }
+}
+
+impl RotationProgress {
+    /// Decode a serialized rotation progress.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ValidationError> {
+        if bytes.len() != 40 {
+            return Err(ValidationError::WrongLength {
+                expected: 40,
+                actual: bytes.len(),
+            });
+        }
+        let mut old_key = [0u8; 16];
+        let mut new_key = [0u8; 16];
+        let mut rotated = [0u8; 8];
+        old_key.copy_from_slice(&bytes[..16]);
+        new_key.copy_from_slice(&bytes[16..32]);
+        rotated.copy_from_slice(&bytes[32..]);
+        Ok(RotationProgress {
+            old_key: Fingerprint(old_key),
+            new_key: Fingerprint(new_key),
+            rotated: u64::from_le_bytes(rotated) as usize,
+        })
+    }
+}
+
+/// A key rotation in progress.
+#[derive(Clone, Debug)]
+pub struct KeyRotation<T> {
+    new_pk: FVCiphertext<T>,
+    progress: RotationProgress,
+}
+
+impl<T> KeyRotation<T> {
+    /// The new public key.
+    pub fn new_public_key(&self) -> &FVCiphertext<T> {
+        &self.new_pk
+    }
+
+    /// The position of the rotation, to be saved to resume it later.
+    pub fn progress(&self) -> RotationProgress {
+        self.progress
+    }
+
+    /// Whether a corpus of `len` ciphertexts is fully rotated.
+    pub fn is_complete(&self, len: usize) -> bool {
+        self.progress.rotated >= len
+    }
+}
+
+/// The reasons for which a ciphertext cannot be rotated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RotationError {
+    /// The ciphertext is tagged with neither the old nor the new key.
+    UnexpectedKey(Fingerprint),
+    /// The ciphertext is not tagged with a key.
+    Untagged,
+    /// The secret key does not belong to the old public key.
+    WrongSecretKey,
+    /// The new public key does not match the saved progress.
+    WrongPublicKey,
+    /// The partial decryptions of the old key cannot be combined.
+    Threshold(ThresholdError),
+}
+
+impl fmt::Display for RotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RotationError::UnexpectedKey(key) => {
+                write!(f, "ciphertext encrypted under unexpected key {}", key)
+            }
+            RotationError::Untagged => write!(f, "ciphertext not tagged with a key"),
+            RotationError::WrongSecretKey => {
+                write!(f, "the secret key does not belong to the old key")
+            }
+            RotationError::WrongPublicKey => {
+                write!(f, "the public key does not match the rotation progress")
+            }
+            RotationError::Threshold(e) => write!(f, "threshold decryption failed: {}", e),
+        }
+    }
+}
+
+impl Error for RotationError {}
+
+impl From<ThresholdError> for RotationError {
+    fn from(e: ThresholdError) -> Self {
+        RotationError::Threshold(e)
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Start rotating away from `old_pk`, and return the rotation with the new secret key.
+    pub fn begin_key_rotation(&self, old_pk: &FVCiphertext<T>) -> (KeyRotation<T>, SecretKey<T>) {
+        let (new_pk, new_sk) = self.generate_keypair();
+        let progress = RotationProgress {
+            old_key: self.public_key_fingerprint(old_pk),
+            new_key: self.public_key_fingerprint(&new_pk),
+            rotated: 0,
+        };
+        (KeyRotation { new_pk, progress }, new_sk)
+    }
+
+    /// Resume a rotation from its saved progress.
+    pub fn resume_key_rotation(
+        &self,
+        new_pk: FVCiphertext<T>,
+        progress: RotationProgress,
+    ) -> Result<KeyRotation<T>, RotationError> {
+        if self.public_key_fingerprint(&new_pk) != progress.new_key {
+            return Err(RotationError::WrongPublicKey);
+        }
+        Ok(KeyRotation { new_pk, progress })
+    }
+
+    /// Rotate the next ciphertexts of `corpus`, at most `batch` of them, with the old secret key.
+    /// Returns the number of ciphertexts processed by this call.
+    pub fn rotate_batch(
+        &self,
+        rotation: &mut KeyRotation<T>,
+        corpus: &mut [TaggedCiphertext<T>],
+        old_sk: &SecretKey<T>,
+        batch: usize,
+    ) -> Result<usize, RotationError> {
+        if let Some(key) = old_sk.public_key_fingerprint() {
+            if key != rotation.progress.old_key {
+                return Err(RotationError::WrongSecretKey);
+            }
+        }
+        let start = rotation.progress.rotated.min(corpus.len());
+        let end = start.saturating_add(batch).min(corpus.len());
+        for tagged in corpus[start..end].iter_mut() {
+            if self.check_rotation_tag(rotation, tagged)? {
+                let mut pt = self.decrypt(&tagged.ct, old_sk);
+                self.retag(rotation, tagged, &mut pt);
+            }
+            rotation.progress.rotated += 1;
+        }
+        Ok(end - start)
+    }
+
+    /// Rotate one ciphertext under threshold control, from the partial decryptions of all the
+    /// shares of the old secret key.
+    pub fn rotate_with_partials(
+        &self,
+        rotation: &KeyRotation<T>,
+        tagged: &mut TaggedCiphertext<T>,
+        partials: &[PartialDecryption<T>],
+    ) -> Result<(), RotationError> {
+        if self.check_rotation_tag(rotation, tagged)? {
+            let mut pt = self.combine_partials(&tagged.ct, partials)?;
+            self.retag(rotation, tagged, &mut pt);
+        }
+        Ok(())
+    }
+
+    /// Whether a ciphertext still needs to be rotated.
+    fn check_rotation_tag(
+        &self,
+        rotation: &KeyRotation<T>,
+        tagged: &TaggedCiphertext<T>,
+    ) -> Result<bool, RotationError> {
+        match tagged.key {
+            Some(key) if key == rotation.progress.new_key => Ok(false),
+            Some(key) if key == rotation.progress.old_key => Ok(true),
+            Some(key) => Err(RotationError::UnexpectedKey(key)),
+            None => Err(RotationError::Untagged),
+        }
+    }
+
+    fn retag(
+        &self,
+        rotation: &KeyRotation<T>,
+        tagged: &mut TaggedCiphertext<T>,
+        pt: &mut FVPlaintext,
+    ) {
+        tagged.ct = self.encrypt(pt, &rotation.new_pk);
+        tagged.key = Some(rotation.progress.new_key);
+        wipe(pt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_resumable_rotation() {
+        let fv = FV::<Scalar>::default_2048();
+        let (old_pk, old_sk) = fv.generate_keypair();
+        let mut corpus: Vec<_> = (0..5u8)
+            .map(|i| fv.encrypt_tagged(&vec![i; fv.n], &old_pk))
+            .collect();
+
+        let (mut rotation, new_sk) = fv.begin_key_rotation(&old_pk);
+        assert_eq!(
+            fv.rotate_batch(&mut rotation, &mut corpus, &old_sk, 2),
+            Ok(2)
+        );
+        let saved = rotation.progress().to_bytes();
+
+        let progress = RotationProgress::from_bytes(&saved).unwrap();
+        let mut rotation = fv
+            .resume_key_rotation(rotation.new_public_key().clone(), progress)
+            .unwrap();
+        while !rotation.is_complete(corpus.len()) {
+            fv.rotate_batch(&mut rotation, &mut corpus, &old_sk, 2)
+                .unwrap();
+        }
+        for (i, tagged) in corpus.iter().enumerate() {
+            assert_eq!(tagged.key, Some(rotation.progress().new_key));
+            assert_eq!(fv.decrypt(&tagged.ct, &new_sk), vec![i as u8; fv.n]);
+        }
+    }
+
+    #[test]
+    fn test_threshold_rotation() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (old_pk, old_sk) = fv.generate_keypair();
+        let shares = fv.split_secret_key(&old_sk, 2);
+        let mut tagged = fv.encrypt_tagged(&vec![9; fv.n], &old_pk);
+        let (rotation, new_sk) = fv.begin_key_rotation(&old_pk);
+        let partials: Vec<_> = shares
+            .iter()
+            .map(|share| fv.partial_decrypt(&tagged.ct, share))
+            .collect();
+        assert_eq!(
+            fv.rotate_with_partials(&rotation, &mut tagged, &partials[..1]),
+            Err(RotationError::Threshold(ThresholdError::MissingShares {
+                expected: 2,
+                actual: 1
+            }))
+        );
+        assert_eq!(
+            fv.rotate_with_partials(&rotation, &mut tagged, &partials),
+            Ok(())
+        );
+        assert_eq!(fv.decrypt(&tagged.ct, &new_sk), vec![9; fv.n]);
+
+        let (other_pk, _) = fv.generate_keypair();
+        let other_key = fv.public_key_fingerprint(&other_pk);
+        tagged.key = Some(other_key);
+        assert_eq!(
+            fv.rotate_with_partials(&rotation, &mut tagged, &partials),
+            Err(RotationError::UnexpectedKey(other_key))
+        );
+        tagged.key = None;
+        assert_eq!(
+            fv.rotate_with_partials(&rotation, &mut tagged, &partials),
+            Err(RotationError::Untagged)
+        );
+    }
+}
@@ -2,62 +2,244 @@
 //
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
+//! Arithmetic in the ring `Rq = Zq[x]/(x^n + 1)`.
+//!
+//! An [`RqPoly`] is a polynomial of Rq, which shares an [`RqPolyContext`] holding n, q and the
+//! precomputed roots of unity with the other polynomials of its ring. A polynomial is either in
+//! coefficient form or in NTT form: additions work in both forms, as long as both operands are
+//! in the same form, while coefficient-wise multiplication needs the NTT form. Operations mixing
+//! forms, or polynomials of rings with different parameters, panic instead of returning a wrong
+//! result; [`crate::FV::validate`] reports the latter as an error for untrusted inputs.
+//!
+//! A scheme exposes the context of its ring with [`crate::FV::ring_context`], so that custom
+//! protocols can operate on the polynomials of its keys and ciphertexts.
+use crate::fingerprint::fingerprint_debug;
 use crate::integer_arith::ArithUtils;
-use crate::utils::reverse_bits_perm;
-use std::sync::Arc;
+use crate::utils::{bit_reverse, reverse_bits_perm, wipe};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
 
 /// Holds the context information for RqPolys, including degree n, modulus q, and optionally precomputed
 /// roots of unity for NTT purposes.
-#[derive(Debug)]
-pub(crate) struct RqPolyContext<T> {
-    pub n: usize,
-    pub q: T,
-    pub is_ntt_enabled: bool,
-    pub roots: Vec<T>,
-    pub invroots: Vec<T>,
+pub struct RqPolyContext<T> {
+    pub(crate) n: usize,
+    pub(crate) q: T,
+    pub(crate) is_ntt_enabled: bool,
+    pub(crate) roots: Vec<T>,
+    pub(crate) invroots: Vec<T>,
 }
 
-/// Polynomials in Rq = Zq[x]/(x^n + 1).
-#[derive(Clone, Debug)]
+/// The reason an explicit root of unity is rejected by [`RqPolyContext::with_root`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RootError {
+    /// The root is not reduced modulo q.
+    NotReduced,
+    /// The root is not a primitive 2n-th root of unity modulo q.
+    NotPrimitive,
+}
+
+impl fmt::Display for RootError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RootError::NotReduced => write!(f, "root of unity is not reduced modulo q"),
+            RootError::NotPrimitive => write!(f, "not a primitive 2n-th root of unity modulo q"),
+        }
+    }
+}
+
+impl Error for RootError {}
+
+/// The reason the operands of a ring operation are rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DomainError {
+    /// The operands belong to rings with different parameters.
+    DifferentRings,
+    /// An operand is not in the form the operation needs.
+    WrongForm { ntt_form: bool },
+}
+
+impl fmt::Display for DomainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DomainError::DifferentRings => write!(f, "operands belong to different rings"),
+            DomainError::WrongForm { ntt_form } => write!(
+                f,
+                "expected operands in {} form",
+                if *ntt_form { "ntt" } else { "coefficient" }
+            ),
+        }
+    }
+}
+
+impl Error for DomainError {}
+
+/// Polynomials in `Rq = Zq[x]/(x^n + 1)`.
+#[derive(Clone)]
 pub struct RqPoly<T> {
     context: Arc<RqPolyContext<T>>,
+    /// The coefficients, or the NTT values, reduced modulo q. Prefer the accessors of
+    /// [`RqPoly`], which check indices and reduce the values they store.
     pub coeffs: Vec<T>,
+    /// Whether `coeffs` holds the NTT form of the polynomial.
     pub is_ntt_form: bool,
 }
 
+// The roots of unity are summarized by the primitive root they are computed from.
+impl<T: fmt::Debug> fmt::Debug for RqPolyContext<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let root = if self.is_ntt_enabled {
+            self.roots.get(bit_reverse(1, self.n))
+        } else {
+            None
+        };
+        f.debug_struct("RqPolyContext")
+            .field("n", &self.n)
+            .field("q", &self.q)
+            .field("root", &root)
+            .finish()
+    }
+}
+
+// The coefficients may be secret, and are summarized by a fingerprint.
+impl<T: fmt::Debug> fmt::Debug for RqPoly<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fingerprint = fingerprint_debug(b"cupcake polynomial", &self.coeffs);
+        f.debug_struct("RqPoly")
+            .field("n", &self.context.n)
+            .field("q", &self.context.q)
+            .field("is_ntt_form", &self.is_ntt_form)
+            .field("fingerprint", &format_args!("{}", fingerprint))
+            .finish()
+    }
+}
+
+// Polynomials are equal if they have the same modulus, form and coefficients.
+impl<T: PartialEq> PartialEq for RqPoly<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.context.q == other.context.q
+            && self.is_ntt_form == other.is_ntt_form
+            && self.coeffs == other.coeffs
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T> zeroize::Zeroize for RqPoly<T>
+where
+    T: ArithUtils<T>,
+{
+    fn zeroize(&mut self) {
+        self.coeffs.iter_mut().for_each(zeroize::Zeroize::zeroize);
+    }
+}
+
 /// Number-theoretic transform (NTT) and fast polynomial multiplication based on NTT.
+///
+/// The forward transform is not normalized: position p of the NTT form of a polynomial a holds
+/// a(psi^(2 bitrev(p) + 1)), where psi is the root of unity of the context and bitrev reverses
+/// the log2(n) bits of p. The inverse transform includes the factor 1/n, so that
+/// `inverse_transform` undoes `forward_transform` exactly, and conversely. Both transforms panic
+/// if the NTT is not enabled for the ring.
+#[allow(clippy::upper_case_acronyms)]
 pub trait NTT<T>: Clone {
+    /// Whether the polynomial is in NTT form.
     fn is_ntt_form(&self) -> bool;
 
+    /// Mark the polynomial as being in NTT form or not, without transforming it.
     fn set_ntt_form(&mut self, value: bool);
 
+    /// Transform from coefficient form to NTT form. Panics if already in NTT form.
     fn forward_transform(&mut self);
 
+    /// Transform from NTT form to coefficient form. Panics if already in coefficient form.
     fn inverse_transform(&mut self);
 
+    /// The coefficient-wise product of two polynomials in NTT form.
     fn coeffwise_multiply(&self, other: &Self) -> Self;
 
+    /// The product of two polynomials in either form, computed with the NTT. The result is in
+    /// coefficient form.
     fn multiply_fast(&self, other: &Self) -> Self;
 }
 
 /// Arithmetics on general ring elements.
 pub trait FiniteRingElt {
+    /// The type of the coefficients of the ring elements.
+    type Coeff;
+
+    /// Add a polynomial in the same form into this one.
     fn add_inplace(&mut self, other: &Self);
 
+    /// Subtract a polynomial in the same form from this one.
     fn sub_inplace(&mut self, other: &Self);
 
+    /// Negate this polynomial.
     fn negate_inplace(&mut self);
 
+    /// Multiply this polynomial by a constant reduced modulo q. This works in both forms, as the
+    /// NTT is linear.
+    fn scale_inplace(&mut self, c: &Self::Coeff);
+
+    /// The product of two polynomials in coefficient form, computed with the schoolbook
+    /// algorithm.
     fn multiply(&self, other: &Self) -> Self;
 }
 
+impl<T> RqPolyContext<T> {
+    /// The degree n of the ring.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// The modulus q of the ring.
+    pub fn modulus(&self) -> &T {
+        &self.q
+    }
+
+    /// Whether q admits a primitive 2n-th root of unity, so that polynomials can be multiplied
+    /// with the NTT.
+    pub fn is_ntt_enabled(&self) -> bool {
+        self.is_ntt_enabled
+    }
+}
+
+// Contexts are equal if they have the same degree and modulus, and the same root of unity if the
+// NTT is enabled, so that polynomials of both are interchangeable.
+impl<T> PartialEq for RqPolyContext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.n == other.n
+            && T::to_u64(self.q.clone()) == T::to_u64(other.q.clone())
+            && self.is_ntt_enabled == other.is_ntt_enabled
+            && self.root().map(T::to_u64) == other.root().map(T::to_u64)
+    }
+}
+
+impl<T: Clone> RqPolyContext<T> {
+    /// The primitive 2n-th root of unity psi from which the NTT is computed, or `None` if the
+    /// NTT is not enabled. Position p of a polynomial in NTT form holds its evaluation at
+    /// psi^(2 bitrev(p) + 1).
+    pub fn root(&self) -> Option<T> {
+        if !self.is_ntt_enabled {
+            return None;
+        }
+        Some(self.roots[bit_reverse(1, self.n)].clone())
+    }
+}
+
 impl<T> RqPolyContext<T>
 where
     T: ArithUtils<T> + PartialEq + Clone,
 {
+    /// The context of the ring of degree n and modulus q. The NTT is enabled if a primitive
+    /// 2n-th root of unity modulo q is found.
     pub fn new(n: usize, q: &T) -> Self {
         let mut a = RqPolyContext {
-            n: n,
+            n,
             q: q.clone(),
             is_ntt_enabled: false,
             invroots: vec![],
@@ -67,17 +249,41 @@ where
         a
     }
 
-    fn compute_roots(&mut self) {
-        let mut roots = vec![];
+    /// The context of the ring of degree n and modulus q, with the NTT computed from the given
+    /// primitive 2n-th root of unity instead of the one found by [`RqPolyContext::find_root`],
+    /// e.g. to match the twiddle factors of another implementation. n must be a power of two.
+    pub fn with_root(n: usize, q: &T, root: &T) -> Result<Self, RootError> {
+        if T::modulus(root, q) != *root {
+            return Err(RootError::NotReduced);
+        }
+        // As n is a power of two, psi^n = -1 holds iff psi has order exactly 2n.
+        let q_minus_one = T::sub(q, &T::one());
+        if T::pow_mod(root, &T::from_u64_raw(n as u64), q) != q_minus_one {
+            return Err(RootError::NotPrimitive);
+        }
+        let mut a = RqPolyContext {
+            n,
+            q: q.clone(),
+            is_ntt_enabled: true,
+            invroots: vec![],
+            roots: vec![],
+        };
+        a.compute_roots_from(root.clone());
+        Ok(a)
+    }
 
-        let root = self.find_root();
-        if root.is_none() {
-            self.is_ntt_enabled = false;
-            return;
+    fn compute_roots(&mut self) {
+        match self.find_root() {
+            Some(phi) => {
+                self.is_ntt_enabled = true;
+                self.compute_roots_from(phi);
+            }
+            None => self.is_ntt_enabled = false,
         }
-        self.is_ntt_enabled = true;
-        let phi = root.unwrap();
+    }
 
+    fn compute_roots_from(&mut self, phi: T) {
+        let mut roots = vec![];
         let mut s = T::one();
         for _ in 0..self.n {
             roots.push(s.clone());
@@ -95,6 +301,7 @@ where
         self.invroots = invroots;
     }
 
+    /// Search for a primitive 2n-th root of unity modulo q.
     pub fn find_root(&self) -> Option<T> {
         let bign = T::from_u32_raw(self.n as u32);
         let q_minus_one = T::sub(&self.q, &T::one());
@@ -120,6 +327,192 @@ where
     }
 }
 
+impl<T> RqPoly<T> {
+    /// A polynomial in coefficient form with the given coefficients, which must be reduced
+    /// modulo q. Panics unless there are n coefficients.
+    pub fn from_coeffs(context: Arc<RqPolyContext<T>>, coeffs: Vec<T>) -> Self {
+        assert_eq!(coeffs.len(), context.n, "expected n coefficients");
+        RqPoly {
+            context,
+            coeffs,
+            is_ntt_form: false,
+        }
+    }
+
+    /// The modulus q of the ring of this polynomial.
+    pub fn modulus(&self) -> &T {
+        &self.context.q
+    }
+
+    /// The context of the ring of this polynomial.
+    pub fn context(&self) -> &Arc<RqPolyContext<T>> {
+        &self.context
+    }
+
+    /// The coefficient of x^i in coefficient form, or the i-th value in NTT form, or `None` if
+    /// i is not below n.
+    pub fn coeff(&self, i: usize) -> Option<&T> {
+        self.coeffs.get(i)
+    }
+
+    /// A read-only view of the coefficients, or of the values in NTT form.
+    pub fn as_slice(&self) -> &[T] {
+        &self.coeffs
+    }
+
+    /// An iterator over the coefficients, or over the values in NTT form.
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.coeffs.iter()
+    }
+}
+
+impl<T> RqPoly<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Whether both polynomials belong to the same ring with the same NTT, i.e. share their
+    /// context or have equal contexts.
+    pub fn same_ring(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.context, &other.context) || *self.context == *other.context
+    }
+
+    /// Set the coefficient of x^i in coefficient form, or the i-th value in NTT form, to v mod q.
+    /// Panics unless i is below n.
+    pub fn set_coeff(&mut self, i: usize, v: &T) {
+        assert!(i < self.context.n, "coefficient index out of range");
+        self.coeffs[i] = T::modulus(v, &self.context.q);
+    }
+
+    /// Panic unless the ring of this polynomial has an NTT.
+    fn assert_ntt_enabled(&self) {
+        if !self.context.is_ntt_enabled {
+            panic!("the ntt is not enabled for this ring");
+        }
+    }
+
+    /// Check that both polynomials belong to the same ring and are in the given domain, so that
+    /// a coefficient-wise operation never silently mixes rings, or NTT and coefficient
+    /// representations.
+    fn check_domain(&self, other: &Self, ntt_form: bool) -> Result<(), DomainError> {
+        if !self.same_ring(other) {
+            return Err(DomainError::DifferentRings);
+        }
+        if self.is_ntt_form != ntt_form || other.is_ntt_form != ntt_form {
+            return Err(DomainError::WrongForm { ntt_form });
+        }
+        Ok(())
+    }
+
+    /// Panic unless both polynomials belong to the same ring and are in the given domain.
+    fn assert_domain(&self, other: &Self, ntt_form: bool) {
+        if let Err(e) = self.check_domain(other, ntt_form) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Add a polynomial into this one, or return an error if they belong to different rings or
+    /// are in different forms.
+    pub fn try_add_inplace(&mut self, other: &Self) -> Result<(), DomainError> {
+        self.check_domain(other, self.is_ntt_form)?;
+        self.add_inplace(other);
+        Ok(())
+    }
+
+    /// Subtract a polynomial from this one, or return an error if they belong to different
+    /// rings or are in different forms.
+    pub fn try_sub_inplace(&mut self, other: &Self) -> Result<(), DomainError> {
+        self.check_domain(other, self.is_ntt_form)?;
+        self.sub_inplace(other);
+        Ok(())
+    }
+
+    /// The schoolbook product of two polynomials, or an error if they belong to different rings
+    /// or are not both in coefficient form.
+    pub fn try_multiply(&self, other: &Self) -> Result<Self, DomainError> {
+        self.check_domain(other, false)?;
+        Ok(self.multiply(other))
+    }
+
+    #[cfg(feature = "std")]
+    /// The product of two polynomials in NTT form, written in coefficient form into `out`,
+    /// reusing its allocation.
+    pub(crate) fn multiply_ntt_into(&self, other: &Self, out: &mut Self) {
+        self.assert_domain(other, true);
+        let q = &self.context.q;
+        out.context = self.context.clone();
+        out.coeffs.clear();
+        out.coeffs.extend(
+            self.coeffs
+                .iter()
+                .zip(other.coeffs.iter())
+                .map(|(x, y)| T::mul_mod(x, y, q)),
+        );
+        out.is_ntt_form = true;
+        out.inverse_transform();
+    }
+
+    /// The image a(x^k) of this polynomial under the automorphism x -> x^k of Rq, for an odd k.
+    /// The result is in the same form as the input: in coefficient form the coefficients are
+    /// permuted with signs, and in NTT form the evaluations are permuted.
+    pub fn apply_automorphism(&self, k: usize) -> Self {
+        if k.is_multiple_of(2) {
+            panic!("automorphism index must be odd");
+        }
+        let n = self.context.n;
+        let two_n = 2 * n;
+        let k = k % two_n;
+        let mut coeffs = vec![T::zero(); n];
+        if self.is_ntt_form {
+            // Position p holds the evaluation at psi^(2 bitrev(p) + 1), and a(x^k) evaluated at
+            // psi^e is a evaluated at psi^(e k).
+            for (p, y) in coeffs.iter_mut().enumerate() {
+                let e = 2 * bit_reverse(p, n) + 1;
+                let j = (e * k % two_n - 1) / 2;
+                *y = self.coeffs[bit_reverse(j, n)].clone();
+            }
+        } else {
+            // x^i maps to x^(i k), and x^n = -1.
+            for (i, x) in self.coeffs.iter().enumerate() {
+                let e = i * k % two_n;
+                if e < n {
+                    coeffs[e] = x.clone();
+                } else {
+                    coeffs[e - n] = T::sub_mod(&T::zero(), x, &self.context.q);
+                }
+            }
+        }
+        RqPoly {
+            context: self.context.clone(),
+            coeffs,
+            is_ntt_form: self.is_ntt_form,
+        }
+    }
+
+    /// The value a(x) mod q of this polynomial, as a polynomial of degree below n over Zq, at a
+    /// point x reduced modulo q. Evaluation is compatible with the ring operations only at the
+    /// roots of x^n + 1. Panics unless in coefficient form.
+    pub fn evaluate(&self, x: &T) -> T {
+        if self.is_ntt_form {
+            panic!("expected operands in coefficient form");
+        }
+        // Horner's rule, from the leading coefficient.
+        self.coeffs.iter().rev().fold(T::zero(), |acc, c| {
+            T::add_mod(&T::mul_mod(&acc, x, &self.context.q), c, &self.context.q)
+        })
+    }
+
+    /// The dot product of the coefficient vectors of two polynomials in the same form, modulo q.
+    pub fn dot(&self, other: &Self) -> T {
+        self.assert_domain(other, self.is_ntt_form);
+        self.coeffs
+            .iter()
+            .zip(other.coeffs.iter())
+            .fold(T::zero(), |acc, (a, b)| {
+                T::add_mod(&acc, &T::mul_mod(a, b, &self.context.q), &self.context.q)
+            })
+    }
+}
+
 // NTT implementation
 impl<T> NTT<T> for RqPoly<T>
 where
@@ -137,6 +530,7 @@ where
         if self.is_ntt_form {
             panic!("is already in ntt");
         }
+        self.assert_ntt_enabled();
 
         let n = self.context.n;
         let q = self.context.q.clone();
@@ -150,7 +544,7 @@ where
                 let j2 = j1 + t - 1;
                 let phi = &self.context.roots[m + i];
                 for j in j1..j2 + 1 {
-                    let x = T::mul_mod(&self.coeffs[j + t], &phi, &q);
+                    let x = T::mul_mod(&self.coeffs[j + t], phi, &q);
                     self.coeffs[j + t] = T::sub_mod(&self.coeffs[j], &x, &q);
                     self.coeffs[j] = T::add_mod(&self.coeffs[j], &x, &q);
                 }
@@ -164,6 +558,7 @@ where
         if !self.is_ntt_form {
             panic!("is already not in ntt");
         }
+        self.assert_ntt_enabled();
         let n = self.context.n;
         let q = self.context.q.clone();
 
@@ -182,7 +577,7 @@ where
                     self.coeffs[j] = T::add_mod(&u, &v, &q);
 
                     let tmp = T::sub_mod(&u, &v, &q);
-                    self.coeffs[j + t] = T::mul_mod(&tmp, &s, &q);
+                    self.coeffs[j + t] = T::mul_mod(&tmp, s, &q);
                 }
                 j1 += 2 * t;
             }
@@ -196,6 +591,7 @@ where
     }
 
     fn coeffwise_multiply(&self, other: &Self) -> Self {
+        self.assert_domain(other, true);
         let mut c = self.clone();
         for (inputs, cc) in self
             .coeffs
@@ -220,6 +616,8 @@ where
         }
         let mut c = a.coeffwise_multiply(&b);
         c.inverse_transform();
+        wipe(&mut a);
+        wipe(&mut b);
         c
     }
 }
@@ -228,7 +626,10 @@ impl<T> FiniteRingElt for RqPoly<T>
 where
     T: ArithUtils<T> + Clone,
 {
+    type Coeff = T;
+
     fn add_inplace(&mut self, other: &Self) {
+        self.assert_domain(other, self.is_ntt_form);
         let iter = self.coeffs.iter_mut().zip(other.coeffs.iter());
         for (x, y) in iter {
             *x = T::add_mod(x, y, &self.context.q);
@@ -236,6 +637,7 @@ where
     }
 
     fn sub_inplace(&mut self, other: &Self) {
+        self.assert_domain(other, self.is_ntt_form);
         let iter = self.coeffs.iter_mut().zip(other.coeffs.iter());
         for (x, y) in iter {
             *x = T::sub_mod(x, y, &self.context.q);
@@ -244,12 +646,19 @@ where
 
     fn negate_inplace(&mut self) {
         for x in self.coeffs.iter_mut() {
-            *x = T::sub_mod(&self.context.q, x, &self.context.q);
+            *x = T::sub_mod(&T::zero(), x, &self.context.q);
+        }
+    }
+
+    fn scale_inplace(&mut self, c: &T) {
+        for x in self.coeffs.iter_mut() {
+            *x = T::mul_mod(x, c, &self.context.q);
         }
     }
 
     // naive multiplication
     fn multiply(&self, other: &Self) -> Self {
+        self.assert_domain(other, false);
         let f = &self.coeffs;
         let g = &other.coeffs;
         let n = self.context.n;
@@ -275,66 +684,251 @@ where
     }
 }
 
-/// Utility functions for generating random polynomials.
-pub(crate) mod randutils {
-    use rand::distributions::{Distribution, Normal};
-    use rand::rngs::{OsRng, StdRng};
-    use rand::FromEntropy;
-    use rand::{thread_rng, Rng};
+pub mod randutils {
+    //! Utility functions for generating random polynomials.
+    //!
+    //! The public functions take the generator as an argument, so that a protocol built on the
+    //! scheme can sample ring elements from its own CSPRNG, with the same distributions as the
+    //! scheme uses for its keys and noise. [`sample_poly`] dispatches on a [`Distribution`].
+    #[cfg(feature = "std")]
+    use rand_distr::{Distribution as _, Normal};
+    #[cfg(feature = "std")]
+    use crate::rng::with_rng;
+    use crate::utils::wipe;
+    use rand::{CryptoRng, RngCore};
+    use sha3::digest::{ExtendableOutput, Update, XofReader};
+    use sha3::Shake128;
     use super::*;
 
-    pub(crate) fn sample_ternary_poly<T>(context: Arc<RqPolyContext<T>>) -> RqPoly<T>
+    /// Standard deviation for which the constant-time CDT sampler is available.
+    pub(crate) const CDT_STDEV: f64 = 3.2;
+
+    /// Largest magnitude output by the CDT sampler.
+    pub(crate) const CDT_BOUND: u64 = 30;
+
+    /// Cumulative distribution table of |X| for the discrete Gaussian X over the integers with
+    /// standard deviation 3.2, i.e. entry k is floor(2^63 * Pr[|X| <= k]).
+    const CDT_TABLE: [u64; CDT_BOUND as usize] = [
+        1149872835429266008, 3340023666152832877, 5231742854224525755,
+        6713673034491318533, 7766573326200196558, 8445050402542556633,
+        8841576285654612683, 9051758678878186096, 9152802451769415979,
+        9196859074767746705, 9214281206174004120, 9220529764022708440,
+        9222562339745873205, 9223161995634596963, 9223322447917711088,
+        9223361386320111732, 9223369956674611011, 9223371667508612690,
+        9223371977254386295, 9223372028116140532, 9223372035690845298,
+        9223372036713969870, 9223372036839307001, 9223372036853232777,
+        9223372036854636067, 9223372036854764319, 9223372036854774950,
+        9223372036854775749, 9223372036854775804, 9223372036854775807,
+    ];
+
+    /// Sample the magnitude and the sign of a discrete Gaussian with standard deviation 3.2.
+    /// The whole table is always scanned and the comparisons are computed arithmetically, so the
+    /// running time does not depend on the sampled value.
+    pub(crate) fn sample_cdt<R: RngCore>(rng: &mut R) -> (u64, u64) {
+        let rand = rng.next_u64();
+        let r = rand >> 1;
+        let sign = rand & 1;
+        let mut x = 0u64;
+        for entry in CDT_TABLE.iter() {
+            // the top bit is set iff r >= entry, as both are smaller than 2^63.
+            x += entry.wrapping_sub(r).wrapping_sub(1) >> 63;
+        }
+        (x, sign)
+    }
+
+    /// A distribution of the coefficients of a polynomial.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Distribution {
+        /// Uniform in Zq.
+        Uniform,
+        /// Uniform in {-1, 0, 1}.
+        Ternary,
+        /// Discrete Gaussian with the given standard deviation. Only the standard deviation 3.2 is
+        /// sampled with integer arithmetic; other values go through a floating point sampler
+        /// whose output may differ across platforms for the same generator.
+        Gaussian(f64),
+        /// Centered binomial with parameter k, i.e. the difference of the number of set bits in
+        /// two independent k-bit strings, so that coefficients lie in [-k, k]. k is at most 64.
+        Binomial(u32),
+    }
+
+    /// Sample a polynomial in coefficient form with coefficients drawn from a distribution.
+    pub fn sample_poly<T, R>(
+        context: Arc<RqPolyContext<T>>,
+        distribution: Distribution,
+        rng: &mut R,
+    ) -> RqPoly<T>
     where
         T: ArithUtils<T>,
+        R: RngCore + CryptoRng,
     {
-        let mut rng = OsRng::new().unwrap();
-        let mut c = vec![];
-        for _x in 0..context.n {
-            let t = rng.gen_range(-1i32, 2i32);
-            if t >= 0 {
-                c.push(T::from_u32_raw(t as u32));
-            } else {
-                c.push(T::sub(&context.q, &T::one()));
-            }
+        match distribution {
+            Distribution::Uniform => sample_uniform_poly_with(context, rng),
+            Distribution::Ternary => sample_ternary_poly(context, rng),
+            Distribution::Gaussian(stdev) => sample_gaussian_poly_with(context, stdev, rng),
+            Distribution::Binomial(k) => sample_binomial_poly(context, k, rng),
         }
-        RqPoly {
-            coeffs: c,
+    }
+
+    /// Sample a polynomial with discrete Gaussian coefficients of standard deviation 3.2 in
+    /// constant time from the given generator. A coefficient x is mapped to x or q - x without
+    /// branching on its sign.
+    pub(crate) fn sample_gaussian_poly_ct<T, R>(
+        context: Arc<RqPolyContext<T>>,
+        rng: &mut R,
+    ) -> RqPoly<T>
+    where
+        T: ArithUtils<T>,
+        R: RngCore,
+    {
+        let mut poly = RqPoly {
+            coeffs: Vec::with_capacity(context.n),
             is_ntt_form: false,
-            context: context.clone(),
-        }
+            context,
+        };
+        sample_gaussian_poly_ct_into(&mut poly, rng);
+        poly
     }
 
-    pub(crate) fn sample_ternary_poly_prng<T>(context: Arc<RqPolyContext<T>>) -> RqPoly<T>
+    /// Resample a polynomial as [`sample_gaussian_poly_ct`], reusing its allocation.
+    pub(crate) fn sample_gaussian_poly_ct_into<T, R>(poly: &mut RqPoly<T>, rng: &mut R)
     where
         T: ArithUtils<T>,
+        R: RngCore,
     {
-        let mut rng = StdRng::from_entropy();
-        let mut c = vec![];
-        for _x in 0..context.n {
-            let t = rng.gen_range(-1i32, 2i32);
-            if t >= 0 {
-                c.push(T::from_u32_raw(t as u32));
-            } else {
-                c.push(T::sub(&context.q, &T::one()));
-            }
+        let q = &poly.context.q;
+        poly.coeffs.clear();
+        poly.is_ntt_form = false;
+        for _ in 0..poly.context.n {
+            let (x, sign) = sample_cdt(rng);
+            let mut pos = T::from_u64_raw(x);
+            let mut neg = T::sub_mod(&T::zero(), &pos, q);
+            let mut keep = T::mul(&pos, &T::from_u64_raw(1 - sign));
+            let mut flip = T::mul(&neg, &T::from_u64_raw(sign));
+            poly.coeffs.push(T::add_mod(&keep, &flip, q));
+            wipe(&mut pos);
+            wipe(&mut neg);
+            wipe(&mut keep);
+            wipe(&mut flip);
         }
-        RqPoly {
-            coeffs: c,
+    }
+
+    /// Sample a polynomial with coefficients uniform in {-1, 0, 1} from the given CSPRNG.
+    /// Random bytes are drawn in bulk; every byte below 3^5 = 243 yields five base-3 digits and
+    /// bytes above are rejected, so the digits are unbiased.
+    pub fn sample_ternary_poly<T, R>(context: Arc<RqPolyContext<T>>, rng: &mut R) -> RqPoly<T>
+    where
+        T: ArithUtils<T>,
+        R: RngCore + CryptoRng,
+    {
+        let mut poly = RqPoly {
+            coeffs: Vec::with_capacity(context.n),
             is_ntt_form: false,
-            context: context,
+            context,
+        };
+        let mut buf = vec![];
+        sample_ternary_poly_into(&mut poly, &mut buf, rng);
+        wipe(&mut buf);
+        poly
+    }
+
+    /// Resample a polynomial as [`sample_ternary_poly`], reusing its allocation and that of the
+    /// buffer of random bytes, which is left holding secret values.
+    pub(crate) fn sample_ternary_poly_into<T, R>(
+        poly: &mut RqPoly<T>,
+        buf: &mut Vec<u8>,
+        rng: &mut R,
+    ) where
+        T: ArithUtils<T>,
+        R: RngCore + CryptoRng,
+    {
+        let n = poly.context.n;
+        let q = &poly.context.q;
+        let c = &mut poly.coeffs;
+        c.clear();
+        poly.is_ntt_form = false;
+        // on average 243/256 of the bytes are accepted.
+        buf.resize(n / 5 + 8, 0);
+        while c.len() < n {
+            rng.fill_bytes(buf);
+            for byte in buf.iter().filter(|b| **b < 243) {
+                let mut trits = *byte;
+                for _ in 0..5 {
+                    if c.len() == n {
+                        break;
+                    }
+                    // t in {0, 1, 2} maps to (t & 1) - (t >> 1) in {0, 1, -1}, without branching.
+                    let t = u64::from(trits % 3);
+                    let x = T::from_u64_raw(t & 1);
+                    let y = T::from_u64_raw(t >> 1);
+                    c.push(T::sub_mod(&x, &y, q));
+                    trits /= 3;
+                }
+            }
         }
     }
 
+    #[cfg(feature = "std")]
     /// Sample a polynomial with Gaussian coefficients in the ring Rq.
     pub(crate) fn sample_gaussian_poly<T>(context: Arc<RqPolyContext<T>>, stdev: f64) -> RqPoly<T>
     where
         T: ArithUtils<T>,
+    {
+        with_rng(|rng| sample_gaussian_poly_with(context, stdev, rng))
+    }
+
+    /// Sample a polynomial with Gaussian coefficients in the ring Rq from the given CSPRNG. For
+    /// the standard deviation 3.2 used by the scheme, this dispatches to the constant-time CDT
+    /// sampler.
+    pub fn sample_gaussian_poly_with<T, R>(
+        context: Arc<RqPolyContext<T>>,
+        stdev: f64,
+        rng: &mut R,
+    ) -> RqPoly<T>
+    where
+        T: ArithUtils<T>,
+        R: RngCore + CryptoRng,
+    {
+        if stdev == CDT_STDEV {
+            return sample_gaussian_poly_ct(context, rng);
+        }
+        sample_rounded_normal_poly(context, stdev, rng)
+    }
+
+    #[cfg(feature = "std")]
+    /// Resample a polynomial as [`sample_gaussian_poly_with`], reusing its allocation for the
+    /// standard deviation 3.2.
+    pub(crate) fn sample_gaussian_poly_into<T, R>(poly: &mut RqPoly<T>, stdev: f64, rng: &mut R)
+    where
+        T: ArithUtils<T>,
+        R: RngCore + CryptoRng,
+    {
+        if stdev == CDT_STDEV {
+            return sample_gaussian_poly_ct_into(poly, rng);
+        }
+        wipe(poly);
+        *poly = sample_rounded_normal_poly(poly.context.clone(), stdev, rng);
+    }
+
+    /// Sample a polynomial with coefficients drawn from a normal distribution and truncated to
+    /// integers, with floating point arithmetic. The sampler evaluates `exp` and `ln`, whose
+    /// last bits depend on the math library of the platform, so this is the one sampling path
+    /// which is not reproducible across platforms; the scheme only reaches it if its stdev is
+    /// changed from 3.2.
+    #[cfg(feature = "std")]
+    fn sample_rounded_normal_poly<T, R>(
+        context: Arc<RqPolyContext<T>>,
+        stdev: f64,
+        rng: &mut R,
+    ) -> RqPoly<T>
+    where
+        T: ArithUtils<T>,
+        R: RngCore,
     {
         let mut c = vec![];
-        let normal = Normal::new(0.0, stdev);
-        let mut rng = thread_rng();
+        let normal = Normal::new(0.0, stdev).expect("the standard deviation must be finite");
         for _ in 0..context.n {
-            let tmp = normal.sample(&mut rng);
+            let tmp = normal.sample(rng);
 
             // branch on sign
             if tmp >= 0.0 {
@@ -347,29 +941,207 @@ pub(crate) mod randutils {
         RqPoly {
             coeffs: c,
             is_ntt_form: false,
-            context: context,
+            context,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Sample a polynomial with wide noise for noise flooding, without floating point sampling.
+    ///
+    /// Each coefficient is X = U + G, where U is uniform over the integers in [-B, B] with
+    /// B = floor(sqrt(3) * stdev) and G is the constant-time discrete Gaussian of standard
+    /// deviation 3.2. The variance of X is B(B+1)/3 + 3.2^2, which is stdev^2 up to lower order
+    /// terms, and |X| <= B + 30. For any fixed shift e, the statistical distance between X and
+    /// X + e is at most |e| / (2B + 1), so flooding a noise term bounded by E hides it up to
+    /// E / (2B + 1). U is drawn by rejection sampling on uniform 64-bit words, and the sum is
+    /// computed with integer arithmetic only, so the distribution has no floating point bias.
+    ///
+    /// This is not an arbitrary precision sampler: stdev is an f64, B is capped below 2^62, and
+    /// the samples are only exact as centered representatives modulo q.
+    ///
+    /// # Panics
+    ///
+    /// Panics if B + 30 is not below q / 2, since the samples would wrap around modulo q.
+    pub(crate) fn sample_flooding_poly<T>(context: Arc<RqPolyContext<T>>, stdev: f64) -> RqPoly<T>
+    where
+        T: ArithUtils<T> + Clone,
+    {
+        let mut poly = RqPoly {
+            coeffs: Vec::with_capacity(context.n),
+            is_ntt_form: false,
+            context,
+        };
+        sample_flooding_poly_into(&mut poly, stdev);
+        poly
+    }
+
+    #[cfg(feature = "std")]
+    /// Resample a polynomial as [`sample_flooding_poly`], reusing its allocation.
+    pub(crate) fn sample_flooding_poly_into<T>(poly: &mut RqPoly<T>, stdev: f64)
+    where
+        T: ArithUtils<T> + Clone,
+    {
+        let bound = flooding_bound(stdev);
+        let q = T::to_u64(poly.context.q.clone());
+        assert!(
+            2 * (u128::from(bound) + 30) < u128::from(q),
+            "flooding stdev {} is too large for the modulus {}",
+            stdev,
+            q
+        );
+        let range = 2 * bound + 1;
+        let mask = u64::MAX >> range.leading_zeros();
+        let context = &poly.context;
+        let c = &mut poly.coeffs;
+        c.clear();
+        poly.is_ntt_form = false;
+        with_rng(|rng| {
+            for _ in 0..context.n {
+                let mut u = rng.next_u64() & mask;
+                while u >= range {
+                    u = rng.next_u64() & mask;
+                }
+                let (g, sign) = sample_cdt(rng);
+                // u - B + (-1)^sign g as a difference of two values below q, reduced modulo q
+                // without branching on its sign.
+                let negative = sign.wrapping_neg();
+                let x = T::from_u64_raw(u + (g & !negative));
+                let y = T::from_u64_raw(bound + (g & negative));
+                c.push(T::sub_mod(&x, &y, &context.q));
+            }
+        });
+    }
+
+    /// Without std, there is no floating point sampler, and only the CDT sampler is available.
+    #[cfg(not(feature = "std"))]
+    fn sample_rounded_normal_poly<T, R>(
+        _context: Arc<RqPolyContext<T>>,
+        _stdev: f64,
+        _rng: &mut R,
+    ) -> RqPoly<T> {
+        panic!("only the standard deviation 3.2 is supported without std");
+    }
+
+    /// Sample a polynomial with centered binomial coefficients of parameter k from the given
+    /// CSPRNG. Each coefficient is computed from two words without branching on its value.
+    pub fn sample_binomial_poly<T, R>(
+        context: Arc<RqPolyContext<T>>,
+        k: u32,
+        rng: &mut R,
+    ) -> RqPoly<T>
+    where
+        T: ArithUtils<T>,
+        R: RngCore + CryptoRng,
+    {
+        assert!((1..=64).contains(&k), "binomial parameter must be between 1 and 64");
+        let mask = u64::MAX >> (64 - k);
+        let mut c = Vec::with_capacity(context.n);
+        for _ in 0..context.n {
+            let mut x = T::from_u64_raw(u64::from((rng.next_u64() & mask).count_ones()));
+            let mut y = T::from_u64_raw(u64::from((rng.next_u64() & mask).count_ones()));
+            c.push(T::sub_mod(&x, &y, &context.q));
+            wipe(&mut x);
+            wipe(&mut y);
+        }
+        RqPoly {
+            coeffs: c,
+            is_ntt_form: false,
+            context,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// The half-width B = floor(sqrt(3) * stdev) of the uniform part of the flooding distribution
+    /// of a given stdev. It is computed exactly as the integer square root of floor(3 * stdev^2),
+    /// from the mantissa and exponent of stdev, so that it does not depend on the floating point
+    /// unit of the platform.
+    pub(crate) fn flooding_bound(stdev: f64) -> u64 {
+        assert!(stdev.is_finite(), "flooding stdev is too large");
+        let bits = stdev.abs().to_bits();
+        let exponent = (bits >> 52) as i32;
+        let fraction = bits & ((1u64 << 52) - 1);
+        // |stdev| = mantissa * 2^shift, with the implicit bit for normal numbers.
+        let (mantissa, shift) = if exponent == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | (1u64 << 52), exponent - 1075)
+        };
+        // 3 * stdev^2 = x * 2^(2 shift), where x < 2^108.
+        let x = 3 * (mantissa as u128) * (mantissa as u128);
+        let shift = 2 * shift;
+        let scaled = if shift >= 0 {
+            let bits = 128 - x.leading_zeros() as i32;
+            assert!(bits + shift <= 124, "flooding stdev is too large");
+            x << shift
+        } else if shift > -128 {
+            x >> -shift
+        } else {
+            0
+        };
+        scaled.isqrt() as u64
+    }
+
+    /// Expand a 32-byte seed into a uniform polynomial in the ring Rq, using SHAKE128 as an
+    /// extendable output function and rejection sampling on the lowest bit_length(q) bits of
+    /// each 8-byte chunk. The output only depends on the seed, n and q.
+    pub fn sample_uniform_poly_from_seed<T>(
+        context: Arc<RqPolyContext<T>>,
+        seed: &[u8; 32],
+    ) -> RqPoly<T>
+    where
+        T: ArithUtils<T> + Clone,
+    {
+        let mut hasher = Shake128::default();
+        hasher.update(seed);
+        let mut reader = hasher.finalize_xof();
+
+        let q = T::to_u64(context.q.clone());
+        let bits = 64 - q.leading_zeros();
+        let mask = if bits == 64 { u64::MAX } else { (1u64 << bits) - 1 };
+        let mut c = vec![];
+        let mut buf = [0u8; 8];
+        while c.len() < context.n {
+            reader.read(&mut buf);
+            let x = u64::from_le_bytes(buf) & mask;
+            if x < q {
+                c.push(T::from_u64_raw(x));
+            }
+        }
+        RqPoly {
+            coeffs: c,
+            is_ntt_form: false,
+            context,
         }
     }
 
+    #[cfg(feature = "std")]
     /// Sample a uniform polynomial in the ring Rq.
     pub(crate) fn sample_uniform_poly<T>(context: Arc<RqPolyContext<T>>) -> RqPoly<T>
     where
         T: ArithUtils<T>,
+    {
+        with_rng(|rng| sample_uniform_poly_with(context, rng))
+    }
+
+    /// Sample a uniform polynomial in the ring Rq from the given CSPRNG.
+    pub fn sample_uniform_poly_with<T, R>(context: Arc<RqPolyContext<T>>, rng: &mut R) -> RqPoly<T>
+    where
+        T: ArithUtils<T>,
+        R: RngCore + CryptoRng,
     {
         let mut c = vec![];
-        let mut rng = StdRng::from_entropy();
         for _x in 0..context.n {
-            c.push(T::sample_below_from_rng(&context.q, &mut rng));
+            c.push(T::sample_below_from_rng(&context.q, rng));
         }
         RqPoly {
             coeffs: c,
             is_ntt_form: false,
-            context: context.clone(),
+            context,
         }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     use crate::integer_arith::scalar::Scalar;
@@ -429,6 +1201,249 @@ mod tests {
         assert_eq!(a.coeffs, aa.coeffs);
     }
 
+    #[test]
+    fn test_ntt_round_trip_property() {
+        let mut rng = rand::rngs::OsRng;
+        let params = [(4, 12289u64), (16, 65537), (1024, 12289), (2048, 18014398492704769)];
+        for (n, q) in params.iter() {
+            let context = Arc::new(RqPolyContext::new(*n, &Scalar::new_modulus(*q)));
+            for _ in 0..4 {
+                let a = randutils::sample_uniform_poly_with(context.clone(), &mut rng);
+                let mut b = a.clone();
+                b.forward_transform();
+                assert!(b.is_ntt_form());
+                b.inverse_transform();
+                assert_eq!(b, a);
+
+                // NTT-form values are arbitrary, so the inverse is also a left inverse.
+                let mut c = a.clone();
+                c.set_ntt_form(true);
+                c.inverse_transform();
+                c.forward_transform();
+                assert_eq!(c.coeffs, a.coeffs);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ntt_evaluates_at_odd_powers_of_root() {
+        let q = Scalar::new_modulus(12289);
+        let context = Arc::new(RqPolyContext::new(16, &q));
+        let psi = context.root().unwrap();
+        let a = randutils::sample_uniform_poly(context);
+        let mut b = a.clone();
+        b.forward_transform();
+        for (p, y) in b.coeffs.iter().enumerate() {
+            let e = Scalar::from_u64_raw(2 * bit_reverse(p, 16) as u64 + 1);
+            assert_eq!(*y, a.evaluate(&Scalar::pow_mod(&psi, &e, &q)));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the ntt is not enabled for this ring")]
+    fn test_forward_transform_requires_ntt() {
+        // 12291 = 3 * 17 * 241 has no primitive 32-th root of unity.
+        let context = Arc::new(RqPolyContext::new(16, &Scalar::new_modulus(12291)));
+        randutils::sample_uniform_poly(context).forward_transform();
+    }
+
+    #[test]
+    fn test_public_ring_api() {
+        let fv = crate::FV::<Scalar>::default_2048();
+        let context = fv.ring_context();
+        assert_eq!(context.n(), 2048);
+        assert_eq!(context.modulus(), &fv.q);
+        assert!(context.is_ntt_enabled());
+
+        let a = randutils::sample_uniform_poly(context.clone());
+        let mut one = RqPoly::from_coeffs(context.clone(), vec![Scalar::zero(); 2048]);
+        one.coeffs[0] = Scalar::one();
+        assert_eq!(a.multiply_fast(&one), a);
+        let mut b = a.clone();
+        b.sub_inplace(&a);
+        assert!(b.coeffs.iter().all(|x| *x == Scalar::zero()));
+        assert!(Arc::ptr_eq(a.context(), &context));
+    }
+
+    #[test]
+    fn test_evaluate_and_dot() {
+        let q = Scalar::new_modulus(12289);
+        let context = Arc::new(RqPolyContext::new(4, &q));
+        // 1 + 2x + 3x^2 + 4x^3 at x = 10.
+        let a = from_vec(&vec![1, 2, 3, 4], context.clone());
+        assert_eq!(a.evaluate(&Scalar::from_u64_raw(10)), Scalar::from_u64_raw(4321));
+        let b = from_vec(&vec![5, 6, 7, 8], context.clone());
+        assert_eq!(a.dot(&b), Scalar::from_u64_raw(70));
+
+        // The evaluation at a root psi of x^n + 1 is multiplicative.
+        let psi = context.find_root().unwrap();
+        let product = a.multiply(&b);
+        assert_eq!(
+            product.evaluate(&psi),
+            Scalar::mul_mod(&a.evaluate(&psi), &b.evaluate(&psi), &q)
+        );
+    }
+
+    #[test]
+    fn test_scale_and_negate() {
+        let q = Scalar::new_modulus(12289);
+        let context = Arc::new(RqPolyContext::new(16, &q));
+        let a = randutils::sample_uniform_poly(context.clone());
+        let c = Scalar::from_u64_raw(1234);
+        let mut constant = RqPoly::from_coeffs(context, vec![Scalar::zero(); 16]);
+        constant.coeffs[0] = c.clone();
+
+        let mut scaled = a.clone();
+        scaled.scale_inplace(&c);
+        assert_eq!(scaled, a.multiply(&constant));
+
+        // scaling commutes with the NTT.
+        let mut ntt = a.clone();
+        ntt.forward_transform();
+        ntt.scale_inplace(&c);
+        ntt.inverse_transform();
+        assert_eq!(ntt, scaled);
+
+        let mut sum = a.clone();
+        sum.negate_inplace();
+        sum.add_inplace(&a);
+        assert!(sum.coeffs.iter().all(|x| *x == Scalar::zero()));
+    }
+
+    #[test]
+    fn test_automorphism() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let context = Arc::new(RqPolyContext::new(8, &q));
+        // 1 + 2x + 4x^2 + 3x^7 under x -> x^3 is 1 + 2x^3 + 4x^6 + 3x^21, where x^21 = x^5,
+        // and 5x^3 maps to 5x^9 = -5x.
+        let a = from_vec(&vec![1, 2, 4, 0, 0, 0, 0, 3], context.clone());
+        let b = a.apply_automorphism(3);
+        let expected: Vec<u64> = vec![1, 0, 0, 2, 0, 3, 4, 0];
+        let actual: Vec<u64> = b.coeffs.iter().map(|x| x.value()).collect();
+        assert_eq!(actual, expected);
+        let c = from_vec(&vec![0, 0, 0, 5, 0, 0, 0, 0], context.clone()).apply_automorphism(3);
+        let minus_five = Scalar::sub_mod(&Scalar::zero(), &Scalar::new(5), &q);
+        assert_eq!(c.coeffs[1], minus_five);
+        // x -> x^(2n + 1) is the identity.
+        assert_eq!(a.apply_automorphism(17), a);
+
+        let context = Arc::new(RqPolyContext::new(2048, &q));
+        let a = randutils::sample_uniform_poly(context);
+        for k in [3, 5, 4095].iter() {
+            let mut expected = a.apply_automorphism(*k);
+            expected.forward_transform();
+            let mut ntt = a.clone();
+            ntt.forward_transform();
+            assert_eq!(ntt.apply_automorphism(*k), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "expected operands in coefficient form")]
+    fn test_add_rejects_mixed_domains() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let context = Arc::new(RqPolyContext::new(8, &q));
+        let mut a = from_vec(&vec![1; 8], context.clone());
+        let mut b = from_vec(&vec![2; 8], context);
+        b.forward_transform();
+        a.add_inplace(&b);
+    }
+
+    #[test]
+    fn test_coefficient_accessors() {
+        let q = Scalar::new_modulus(12289);
+        let context = Arc::new(RqPolyContext::new(4, &q));
+        let mut a = from_vec(&vec![1, 2, 3, 4], context);
+        assert_eq!(a.coeff(2), Some(&Scalar::from_u64_raw(3)));
+        assert_eq!(a.coeff(4), None);
+        a.set_coeff(1, &Scalar::from_u64_raw(12289 + 5));
+        assert_eq!(a.coeff(1), Some(&Scalar::from_u64_raw(5)));
+        let values: Vec<u64> = a.iter().map(|x| Scalar::to_u64(x.clone())).collect();
+        assert_eq!(values, vec![1, 5, 3, 4]);
+        assert_eq!(a.as_slice().len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "coefficient index out of range")]
+    fn test_set_coeff_out_of_range() {
+        let context = Arc::new(RqPolyContext::new(4, &Scalar::new_modulus(12289)));
+        from_vec(&vec![1, 2, 3, 4], context).set_coeff(4, &Scalar::one());
+    }
+
+    #[test]
+    fn test_context_compatibility() {
+        let q = Scalar::new_modulus(12289);
+        let a = Arc::new(RqPolyContext::new(16, &q));
+        let b = Arc::new(RqPolyContext::new(16, &q));
+        assert!(*a == *b);
+        let mut x = randutils::sample_uniform_poly(a.clone());
+        let y = randutils::sample_uniform_poly(b);
+        assert!(x.same_ring(&y));
+        x.add_inplace(&y);
+
+        let psi = a.find_root().unwrap();
+        let psi3 = Scalar::pow_mod(&psi, &Scalar::from_u64_raw(3), &q);
+        let c = Arc::new(RqPolyContext::with_root(16, &q, &psi3).unwrap());
+        assert!(*a != *c);
+        assert!(*a != RqPolyContext::new(32, &q));
+        assert!(!x.same_ring(&randutils::sample_uniform_poly(c)));
+    }
+
+    #[test]
+    #[should_panic(expected = "operands belong to different rings")]
+    fn test_add_rejects_other_ring() {
+        let mut a = randutils::sample_uniform_poly(Arc::new(RqPolyContext::new(
+            16,
+            &Scalar::new_modulus(12289),
+        )));
+        let b = randutils::sample_uniform_poly(Arc::new(RqPolyContext::new(
+            16,
+            &Scalar::new_modulus(65537),
+        )));
+        a.add_inplace(&b);
+    }
+
+    #[test]
+    fn test_fallible_operations_reject_other_domains() {
+        let context = Arc::new(RqPolyContext::new(16, &Scalar::new_modulus(12289)));
+        let mut a = randutils::sample_uniform_poly(context.clone());
+        let mut b = randutils::sample_uniform_poly(context);
+        let c = randutils::sample_uniform_poly(Arc::new(RqPolyContext::new(
+            16,
+            &Scalar::new_modulus(65537),
+        )));
+        assert_eq!(a.try_add_inplace(&c), Err(DomainError::DifferentRings));
+        assert_eq!(a.try_sub_inplace(&c), Err(DomainError::DifferentRings));
+        assert_eq!(a.try_multiply(&c).err(), Some(DomainError::DifferentRings));
+
+        let expected = a.multiply(&b);
+        b.forward_transform();
+        assert_eq!(
+            a.try_add_inplace(&b),
+            Err(DomainError::WrongForm { ntt_form: false })
+        );
+        assert_eq!(
+            a.try_multiply(&b).err(),
+            Some(DomainError::WrongForm { ntt_form: false })
+        );
+        b.inverse_transform();
+        assert_eq!(a.try_multiply(&b).unwrap().coeffs, expected.coeffs);
+        let sum = a.clone();
+        a.try_add_inplace(&b).unwrap();
+        a.try_sub_inplace(&b).unwrap();
+        assert_eq!(a.coeffs, sum.coeffs);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected operands in ntt form")]
+    fn test_coeffwise_multiply_requires_ntt_form() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let context = Arc::new(RqPolyContext::new(8, &q));
+        let a = from_vec(&vec![1; 8], context.clone());
+        let b = from_vec(&vec![2; 8], context);
+        a.coeffwise_multiply(&b);
+    }
+
     #[test]
     fn test_fast_multiply_with_one() {
         let context = RqPolyContext::new(4, &Scalar::new_modulus(12289));
@@ -452,9 +1467,179 @@ mod tests {
         assert_eq!(c.coeffs, c1.coeffs);
     }
 
+    #[test]
+    fn test_cdt_sampler_moments() {
+        let mut rng = rand::thread_rng();
+        let trials = 100000;
+        let mut sum = 0f64;
+        let mut sumsq = 0f64;
+        for _ in 0..trials {
+            let (x, sign) = randutils::sample_cdt(&mut rng);
+            assert!(x <= 30);
+            let v = if sign == 1 { -(x as f64) } else { x as f64 };
+            sum += v;
+            sumsq += v * v;
+        }
+        let mean = sum / trials as f64;
+        let stdev = (sumsq / trials as f64 - mean * mean).sqrt();
+        assert!(mean.abs() < 0.1);
+        assert!((stdev - randutils::CDT_STDEV).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_gaussian_poly_ct_range() {
+        let q = Scalar::new_modulus(12289);
+        let context = Arc::new(RqPolyContext::new(1024, &q));
+        let e = randutils::sample_gaussian_poly_ct(context, &mut rand::rngs::OsRng);
+        for x in e.coeffs.iter() {
+            let v = Scalar::to_u64(x.clone());
+            assert!(v <= 30 || v >= 12289 - 30);
+        }
+    }
+
+    #[test]
+    fn test_uniform_poly_from_seed() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let context = Arc::new(RqPolyContext::new(2048, &q));
+        let a = randutils::sample_uniform_poly_from_seed(context.clone(), &[1u8; 32]);
+        let b = randutils::sample_uniform_poly_from_seed(context.clone(), &[1u8; 32]);
+        let c = randutils::sample_uniform_poly_from_seed(context.clone(), &[2u8; 32]);
+        assert_eq!(a.coeffs, b.coeffs);
+        assert_ne!(a.coeffs, c.coeffs);
+        for x in a.coeffs.iter() {
+            assert!(Scalar::to_u64(x.clone()) < 18014398492704769u64);
+        }
+    }
+
+    #[test]
+    fn test_ternary_poly_distribution() {
+        let q = Scalar::new_modulus(12289);
+        let context = Arc::new(RqPolyContext::new(2048, &q));
+        let mut rng = rand::rngs::OsRng;
+        let u = randutils::sample_ternary_poly(context, &mut rng);
+        assert_eq!(u.coeffs.len(), 2048);
+
+        let mut counts = [0usize; 3];
+        for x in u.coeffs.iter() {
+            match Scalar::to_u64(x.clone()) {
+                0 => counts[0] += 1,
+                1 => counts[1] += 1,
+                12288 => counts[2] += 1,
+                v => panic!("unexpected coefficient {}", v),
+            }
+        }
+        for count in counts.iter() {
+            assert!(*count > 500 && *count < 870);
+        }
+    }
+
+    #[test]
+    fn test_sample_poly_with_caller_rng() {
+        use rand::SeedableRng;
+        use randutils::Distribution;
+        let q = Scalar::new_modulus(12289);
+        let context = Arc::new(RqPolyContext::new(2048, &q));
+        let distributions = [
+            Distribution::Uniform,
+            Distribution::Ternary,
+            Distribution::Gaussian(3.2),
+            Distribution::Gaussian(10.0),
+            Distribution::Binomial(4),
+        ];
+        for d in distributions.iter() {
+            let mut rng = rand::rngs::StdRng::from_seed([1; 32]);
+            let a = randutils::sample_poly(context.clone(), *d, &mut rng);
+            let mut rng = rand::rngs::StdRng::from_seed([1; 32]);
+            let b = randutils::sample_poly(context.clone(), *d, &mut rng);
+            assert_eq!(a, b);
+            assert!(!a.is_ntt_form);
+        }
+
+        let mut rng = rand::rngs::StdRng::from_seed([2; 32]);
+        let e = randutils::sample_poly(context, Distribution::Binomial(4), &mut rng);
+        let mut sum = 0i64;
+        for x in e.coeffs.iter() {
+            let v = Scalar::to_u64(x.clone()) as i64;
+            let v = if v > 6144 { v - 12289 } else { v };
+            assert!(v.abs() <= 4);
+            sum += v * v;
+        }
+        // the variance of a centered binomial of parameter k is k / 2.
+        let variance = sum as f64 / 2048.0;
+        assert!((variance - 2.0).abs() < 0.3);
+    }
+
+    #[test]
+    fn test_flooding_bound_is_exact() {
+        assert_eq!(randutils::flooding_bound(0.0), 0);
+        assert_eq!(randutils::flooding_bound(1.0), 1);
+        assert_eq!(randutils::flooding_bound(3.2), 5);
+        assert_eq!(randutils::flooding_bound((1u64 << 40) as f64), 1904410002820);
+        assert_eq!(randutils::flooding_bound((1u64 << 61) as f64), 3993837246235628775);
+    }
+
+    #[test]
+    #[should_panic(expected = "is too large for the modulus 65537")]
+    fn test_flooding_poly_rejects_wide_noise() {
+        let q = Scalar::new_modulus(65537);
+        let context = Arc::new(RqPolyContext::new(16, &q));
+        randutils::sample_flooding_poly(context, 20000.0);
+    }
+
+    #[test]
+    fn test_flooding_poly_moments() {
+        let q = Scalar::new_modulus(18014398492704769u64);
+        let context = Arc::new(RqPolyContext::new(2048, &q));
+        let stdev = 2f64.powi(30);
+        let bound = randutils::flooding_bound(stdev) + 30;
+        let e = randutils::sample_flooding_poly(context, stdev);
+
+        let mut sumsq = 0f64;
+        for x in e.coeffs.iter() {
+            let v = Scalar::to_u64(x.clone());
+            let v = if v > 18014398492704769u64 / 2 {
+                -((18014398492704769u64 - v) as f64)
+            } else {
+                v as f64
+            };
+            assert!(v.abs() <= bound as f64);
+            sumsq += v * v;
+        }
+        let actual = (sumsq / 2048f64).sqrt();
+        assert!((actual / stdev - 1.0).abs() < 0.1);
+    }
+
     #[test]
     fn test_find_root_scalar(){
         let context2 = RqPolyContext::new(4, &Scalar::new_modulus(12289));
         assert_eq!(context2.find_root().unwrap(), Scalar::from_u64_raw(8246u64));
     }
+
+    #[test]
+    fn test_context_with_root() {
+        let q = Scalar::new_modulus(12289);
+        let default = RqPolyContext::new(16, &q);
+        let psi = default.find_root().unwrap();
+        assert_eq!(default.root(), Some(psi.clone()));
+
+        // psi^3 is another primitive 32-th root, with other twiddle factors.
+        let psi3 = Scalar::pow_mod(&psi, &Scalar::from_u64_raw(3), &q);
+        let context = Arc::new(RqPolyContext::with_root(16, &q, &psi3).unwrap());
+        assert_eq!(context.root(), Some(psi3));
+        assert_ne!(context.roots, default.roots);
+        let a = randutils::sample_uniform_poly(context.clone());
+        let b = randutils::sample_uniform_poly(context.clone());
+        assert_eq!(a.multiply_fast(&b), a.multiply(&b));
+
+        let psi2 = Scalar::mul_mod(&psi, &psi, &q);
+        assert_eq!(
+            RqPolyContext::with_root(16, &q, &psi2).unwrap_err(),
+            RootError::NotPrimitive
+        );
+        assert_eq!(
+            RqPolyContext::with_root(16, &q, &Scalar::add(&psi, &q)).unwrap_err(),
+            RootError::NotReduced
+        );
+        assert_eq!(RqPolyContext::new(16, &Scalar::new_modulus(12289 + 2)).root(), None);
+    }
 }
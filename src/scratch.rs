@@ -0,0 +1,154 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Rerandomization without allocations.
+//!
+//! [`crate::traits::AdditiveHomomorphicScheme::rerandomize`] samples four fresh polynomials, the
+//! randomness u, e1 and e2 of an encryption of zero and the flooding noise, and allocates the
+//! products of u with the public key. When millions of stored ciphertexts are rerandomized, e.g.
+//! in a mixing pass, a [`NoiseScratch`] created once holds these polynomials, and
+//! [`FV::rerandomize_with_pool`] resamples them in place. For the same generator state, the
+//! result is the same as that of `rerandomize`.
+use crate::integer_arith::ArithUtils;
+use crate::observer::{self, Operation};
+use crate::rng::with_rng;
+use crate::rqpoly::randutils;
+use crate::rqpoly::{FiniteRingElt, RqPoly, NTT};
+use crate::utils::wipe;
+use crate::{FVCiphertext, FV};
+use std::fmt;
+use std::sync::Arc;
+
+/// Preallocated polynomials for the noise of a rerandomization. They hold secret values between
+/// uses, and are wiped on drop with the `zeroize` feature.
+pub struct NoiseScratch<T: ArithUtils<T>> {
+    u: RqPoly<T>,
+    e1: RqPoly<T>,
+    e2: RqPoly<T>,
+    elarge: RqPoly<T>,
+    product: RqPoly<T>,
+    bytes: Vec<u8>,
+}
+
+impl<T> NoiseScratch<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Buffers for the ring of a scheme.
+    pub fn new(fv: &FV<T>) -> Self {
+        let poly = RqPoly::from_coeffs(fv.context.clone(), vec![T::zero(); fv.n]);
+        NoiseScratch {
+            u: poly.clone(),
+            e1: poly.clone(),
+            e2: poly.clone(),
+            elarge: poly.clone(),
+            product: poly,
+            bytes: vec![],
+        }
+    }
+
+    fn is_for(&self, fv: &FV<T>) -> bool {
+        Arc::ptr_eq(self.u.context(), &fv.context) || **self.u.context() == *fv.context
+    }
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for NoiseScratch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NoiseScratch(..)")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: ArithUtils<T>> Drop for NoiseScratch<T> {
+    fn drop(&mut self) {
+        wipe(&mut self.u);
+        wipe(&mut self.e1);
+        wipe(&mut self.e2);
+        wipe(&mut self.elarge);
+        wipe(&mut self.product);
+        wipe(&mut self.bytes);
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Rerandomize a ciphertext as [`crate::traits::AdditiveHomomorphicScheme::rerandomize`],
+    /// sampling the noise into the buffers of `scratch`. Buffers created for another ring are
+    /// replaced.
+    pub fn rerandomize_with_pool(
+        &self,
+        ct: &mut FVCiphertext<T>,
+        pk: &FVCiphertext<T>,
+        scratch: &mut NoiseScratch<T>,
+    ) {
+        observer::observe(Operation::Rerandomization, || {
+            if !scratch.is_for(self) {
+                *scratch = NoiseScratch::new(self);
+            }
+            let s = scratch;
+            with_rng(|rng| randutils::sample_ternary_poly_into(&mut s.u, &mut s.bytes, rng));
+            with_rng(|rng| randutils::sample_gaussian_poly_into(&mut s.e1, self.stdev, rng));
+            with_rng(|rng| randutils::sample_gaussian_poly_into(&mut s.e2, self.stdev, rng));
+            if self.context.is_ntt_enabled {
+                s.u.forward_transform();
+            }
+
+            // add the encryption of zero (a u + e1, b u + e2).
+            for (c, a, e) in [(&mut ct.0, &pk.0, &s.e1), (&mut ct.1, &pk.1, &s.e2)] {
+                if a.is_ntt_form && s.u.is_ntt_form {
+                    a.multiply_ntt_into(&s.u, &mut s.product);
+                } else {
+                    wipe(&mut s.product);
+                    s.product = (self.poly_multiplier)(a, &s.u);
+                }
+                c.add_inplace(&s.product);
+                c.add_inplace(e);
+            }
+
+            // add large noise poly for noise flooding.
+            randutils::sample_flooding_poly_into(&mut s.elarge, self.flooding_stdev);
+            ct.1.add_inplace(&s.elarge);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::rng::with_seeded_rng;
+    use crate::traits::*;
+
+    #[test]
+    fn test_rerandomize_with_pool_matches_rerandomize() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let ct = fv.encrypt(&vec![7; fv.n], &pk);
+        let mut scratch = NoiseScratch::new(&fv);
+        for seed in 0..3u8 {
+            let mut expected = ct.clone();
+            with_seeded_rng([seed; 32], || fv.rerandomize(&mut expected, &pk));
+            let mut pooled = ct.clone();
+            with_seeded_rng([seed; 32], || {
+                fv.rerandomize_with_pool(&mut pooled, &pk, &mut scratch)
+            });
+            assert_eq!(pooled, expected);
+            assert_eq!(fv.decrypt(&pooled, &sk), vec![7; fv.n]);
+        }
+    }
+
+    #[test]
+    fn test_scratch_of_another_ring_is_replaced() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let mut scratch = NoiseScratch::new(&FV::<Scalar>::default_2048());
+        let mut ct = fv.encrypt(&vec![1; fv.n], &pk);
+        fv.rerandomize_with_pool(&mut ct, &pk, &mut scratch);
+        assert!(scratch.is_for(&fv));
+        assert_eq!(fv.decrypt(&ct, &sk), vec![1; fv.n]);
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Storage of secret keys in memory locked against swapping.
+//!
+//! On unix platforms, the shares of a [`SecureSecretKey`] are locked in RAM with `mlock`,
+//! so that long-running services holding decryption keys never write them to swap. The
+//! shares are zeroized before the memory is unlocked. On other platforms the key is only
+//! zeroized, and [`SecureSecretKey::is_locked`] reports that the memory is not locked.
+use crate::integer_arith::ArithUtils;
+use crate::utils::wipe;
+use crate::SecretKey;
+use std::fmt;
+use std::io;
+
+/// A secret key whose shares are locked in memory for its whole lifetime.
+pub struct SecureSecretKey<T: ArithUtils<T>> {
+    key: SecretKey<T>,
+    locked: bool,
+}
+
+impl<T> SecureSecretKey<T>
+where
+    T: ArithUtils<T>,
+{
+    /// Move a secret key into locked memory. Fails if the OS refuses to lock the memory, e.g.
+    /// because of the RLIMIT_MEMLOCK limit.
+    pub fn new(key: SecretKey<T>) -> io::Result<Self> {
+        let [masked, mask] = key.0.shares();
+        lock(masked)?;
+        if let Err(e) = lock(mask) {
+            unlock(masked);
+            return Err(e);
+        }
+        Ok(SecureSecretKey {
+            locked: cfg!(unix),
+            key,
+        })
+    }
+
+    /// Whether the shares of the key are locked in memory.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// The secret key, e.g. for decryption.
+    pub fn secret_key(&self) -> &SecretKey<T> {
+        &self.key
+    }
+}
+
+impl<T> Drop for SecureSecretKey<T>
+where
+    T: ArithUtils<T>,
+{
+    fn drop(&mut self) {
+        wipe(&mut self.key.0);
+        if self.locked {
+            for share in self.key.0.shares().iter() {
+                unlock(share);
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for SecureSecretKey<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SecureSecretKey {{ fingerprint: {}, locked: {} }}",
+            self.key.fingerprint(),
+            self.locked
+        )
+    }
+}
+
+#[cfg(unix)]
+fn lock(buf: &[u8]) -> io::Result<()> {
+    if buf.is_empty() {
+        return Ok(());
+    }
+    // Safety: the range is the initialized buffer of a live Vec.
+    let res = unsafe { libc::mlock(buf.as_ptr() as *const libc::c_void, buf.len()) };
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(unix)]
+fn unlock(buf: &[u8]) {
+    if buf.is_empty() {
+        return;
+    }
+    // Safety: the range was locked by `lock` and is still owned by the key.
+    unsafe {
+        libc::munlock(buf.as_ptr() as *const libc::c_void, buf.len());
+    }
+}
+
+#[cfg(not(unix))]
+fn lock(_buf: &[u8]) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn unlock(_buf: &[u8]) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+    use crate::FV;
+
+    #[test]
+    fn test_decrypt_with_secure_key() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let fp = sk.fingerprint();
+        let secure = SecureSecretKey::new(sk).unwrap();
+        assert_eq!(secure.is_locked(), cfg!(unix));
+        assert_eq!(secure.secret_key().fingerprint(), fp);
+
+        let v = vec![4; fv.n];
+        let ct = fv.encrypt(&v, &pk);
+        assert_eq!(fv.decrypt(&ct, secure.secret_key()), v);
+    }
+}
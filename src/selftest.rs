@@ -0,0 +1,208 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! A quick health check of the scheme, e.g. for the startup probe of a service.
+//!
+//! [`self_test`] generates a keypair under small fixed parameters, encrypts two plaintexts, adds
+//! the ciphertexts and checks that the sum decrypts to the sum of the plaintexts. It uses the
+//! managed RNG of the thread, so it also exercises the generator, and takes a few milliseconds.
+use crate::fingerprint::Fingerprint;
+use crate::integer_arith::scalar::Scalar;
+use crate::observer::Operation;
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption};
+use crate::validation::ValidationError;
+use crate::FV;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+/// The degree of the test parameters, with the modulus of the default parameters.
+const TEST_DEGREE: usize = 256;
+
+/// The outcome of a successful self-test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// The id of the test parameters.
+    pub params_id: Fingerprint,
+    /// The time taken by each step, in order.
+    pub timings: Vec<(Operation, Duration)>,
+}
+
+impl SelfTestReport {
+    /// The total time of the steps.
+    pub fn total_time(&self) -> Duration {
+        self.timings.iter().map(|(_, elapsed)| *elapsed).sum()
+    }
+}
+
+/// The reasons for which a self-test can fail, with the step which failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SelfTestError {
+    /// A key or ciphertext produced by the step is malformed.
+    Invalid {
+        operation: Operation,
+        error: ValidationError,
+    },
+    /// The result of the step is wrong.
+    WrongResult { operation: Operation },
+    /// The step panicked, with the given message.
+    Panicked {
+        operation: Operation,
+        message: String,
+    },
+}
+
+impl SelfTestError {
+    /// The step which failed.
+    pub fn operation(&self) -> Operation {
+        match self {
+            SelfTestError::Invalid { operation, .. }
+            | SelfTestError::WrongResult { operation }
+            | SelfTestError::Panicked { operation, .. } => *operation,
+        }
+    }
+}
+
+impl fmt::Display for SelfTestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelfTestError::Invalid { operation, error } => {
+                write!(
+                    f,
+                    "self-test {} produced invalid output: {}",
+                    operation, error
+                )
+            }
+            SelfTestError::WrongResult { operation } => {
+                write!(f, "self-test {} produced a wrong result", operation)
+            }
+            SelfTestError::Panicked { operation, message } => {
+                write!(f, "self-test {} panicked: {}", operation, message)
+            }
+        }
+    }
+}
+
+impl Error for SelfTestError {}
+
+/// Runs the steps of a self-test, recording their timings.
+struct Steps(Vec<(Operation, Duration)>);
+
+impl Steps {
+    fn run<R>(&mut self, operation: Operation, f: impl FnOnce() -> R) -> Result<R, SelfTestError> {
+        let start = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            SelfTestError::Panicked { operation, message }
+        })?;
+        self.0.push((operation, start.elapsed()));
+        Ok(result)
+    }
+}
+
+fn check(operation: Operation, result: Result<(), ValidationError>) -> Result<(), SelfTestError> {
+    result.map_err(|error| SelfTestError::Invalid { operation, error })
+}
+
+/// Run a keygen, encrypt, add and decrypt round-trip under fixed test parameters.
+pub fn self_test() -> Result<SelfTestReport, SelfTestError> {
+    let fv = FV::new(TEST_DEGREE, &Scalar::new_modulus(18014398492704769));
+    run(&fv)
+}
+
+fn run(fv: &FV<Scalar>) -> Result<SelfTestReport, SelfTestError> {
+    let mut steps = Steps(vec![]);
+    let a: Vec<u8> = (0..fv.n).map(|i| (i * 7) as u8).collect();
+    let b: Vec<u8> = (0..fv.n).map(|i| 255 - (i * 3) as u8).collect();
+    let sum: Vec<u8> = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| x.wrapping_add(*y))
+        .collect();
+
+    let (pk, sk) = steps.run(Operation::KeyGeneration, || fv.generate_keypair())?;
+    check(Operation::KeyGeneration, fv.validate_public_key(&pk))?;
+
+    let (ct_a, ct_b) = steps.run(Operation::Encryption, || {
+        (fv.encrypt(&a, &pk), fv.encrypt_sk(&b, &sk))
+    })?;
+    check(Operation::Encryption, fv.validate(&ct_a))?;
+    check(Operation::Encryption, fv.validate(&ct_b))?;
+
+    let ct_sum = steps.run(Operation::Addition, || fv.add(&ct_a, &ct_b))?;
+    check(Operation::Addition, fv.validate(&ct_sum))?;
+
+    let (pt_a, pt_sum) = steps.run(Operation::Decryption, || {
+        (fv.decrypt(&ct_a, &sk), fv.decrypt(&ct_sum, &sk))
+    })?;
+    if pt_a != a {
+        return Err(SelfTestError::WrongResult {
+            operation: Operation::Decryption,
+        });
+    }
+    if pt_sum != sum {
+        return Err(SelfTestError::WrongResult {
+            operation: Operation::Addition,
+        });
+    }
+    Ok(SelfTestReport {
+        params_id: fv.params_id(),
+        timings: steps.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_test() {
+        let report = self_test().unwrap();
+        let steps: Vec<_> = report.timings.iter().map(|(op, _)| *op).collect();
+        assert_eq!(
+            steps,
+            [
+                Operation::KeyGeneration,
+                Operation::Encryption,
+                Operation::Addition,
+                Operation::Decryption
+            ]
+        );
+        assert!(report.total_time() > Duration::from_nanos(0));
+        assert_ne!(report.params_id, FV::<Scalar>::default_2048().params_id());
+    }
+
+    #[test]
+    fn test_self_test_detects_failure() {
+        // A modulus too small for the noise: decryption fails.
+        let fv = FV::new(TEST_DEGREE, &Scalar::new_modulus(12289));
+        let err = run(&fv).unwrap_err();
+        assert_eq!(
+            err,
+            SelfTestError::WrongResult {
+                operation: Operation::Decryption
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "self-test decryption produced a wrong result"
+        );
+
+        let err = Steps(vec![])
+            .run(Operation::Encryption, || panic!("no entropy"))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            SelfTestError::Panicked {
+                operation: Operation::Encryption,
+                message: "no entropy".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Canonical byte encoding of ciphertexts and public keys.
+//!
+//! A polynomial is encoded as one byte holding its NTT flag followed by its n coefficients as
+//! 8-byte little-endian integers, and a ciphertext or public key as its two polynomials. Decoded
+//! values are validated, so they can come from untrusted sources.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::RqPoly;
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FV};
+use alloc::vec::Vec;
+
+impl<T> Serializable for RqPoly<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(1 + 8 * self.coeffs.len());
+        bytes.push(self.is_ntt_form as u8);
+        for x in self.coeffs.iter() {
+            bytes.extend_from_slice(&T::to_u64(x.clone()).to_le_bytes());
+        }
+        bytes
+    }
+}
+
+impl<T> Serializable for FVCiphertext<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.0.to_bytes();
+        bytes.extend_from_slice(&self.1.to_bytes());
+        bytes
+    }
+}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The length of the encoding of a ciphertext or public key.
+    pub fn ciphertext_byte_len(&self) -> usize {
+        2 * self.poly_byte_len()
+    }
+
+    /// Decode and validate a ciphertext.
+    pub fn ciphertext_from_bytes(&self, bytes: &[u8]) -> Result<FVCiphertext<T>, ValidationError> {
+        let ct = self.pair_from_bytes(bytes)?;
+        self.validate(&ct)?;
+        Ok(ct)
+    }
+
+    /// Decode and validate a public key.
+    pub fn public_key_from_bytes(&self, bytes: &[u8]) -> Result<FVCiphertext<T>, ValidationError> {
+        let pk = self.pair_from_bytes(bytes)?;
+        self.validate_public_key(&pk)?;
+        Ok(pk)
+    }
+
+    pub(crate) fn poly_byte_len(&self) -> usize {
+        1 + 8 * self.n
+    }
+
+    fn pair_from_bytes(&self, bytes: &[u8]) -> Result<FVCiphertext<T>, ValidationError> {
+        if bytes.len() != self.ciphertext_byte_len() {
+            return Err(ValidationError::WrongLength {
+                expected: self.ciphertext_byte_len(),
+                actual: bytes.len(),
+            });
+        }
+        let (first, second) = bytes.split_at(self.poly_byte_len());
+        Ok((
+            self.poly_from_bytes(first, 0)?,
+            self.poly_from_bytes(second, 1)?,
+        ))
+    }
+
+    pub(crate) fn poly_from_bytes(
+        &self,
+        bytes: &[u8],
+        component: usize,
+    ) -> Result<RqPoly<T>, ValidationError> {
+        if bytes.len() != self.poly_byte_len() {
+            return Err(ValidationError::WrongLength {
+                expected: self.poly_byte_len(),
+                actual: bytes.len(),
+            });
+        }
+        let is_ntt_form = match bytes[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(ValidationError::InvalidNttFlag { component }),
+        };
+        let coeffs = bytes[1..]
+            .chunks_exact(8)
+            .map(|chunk| {
+                let mut word = [0u8; 8];
+                word.copy_from_slice(chunk);
+                T::from_u64_raw(u64::from_le_bytes(word))
+            })
+            .collect();
+        let mut poly = RqPoly::from_coeffs(self.context.clone(), coeffs);
+        poly.is_ntt_form = is_ntt_form;
+        Ok(poly)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_ciphertext_roundtrip() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let ct = fv.encrypt(&vec![5; fv.n], &pk);
+
+        let bytes = ct.to_bytes();
+        assert_eq!(bytes.len(), fv.ciphertext_byte_len());
+        let decoded = fv.ciphertext_from_bytes(&bytes).unwrap();
+        assert_eq!(fv.decrypt(&decoded, &sk), vec![5; fv.n]);
+
+        let decoded_pk = fv.public_key_from_bytes(&pk.to_bytes()).unwrap();
+        assert_eq!(decoded_pk.1.coeffs, pk.1.coeffs);
+    }
+
+    #[test]
+    fn test_reject_malformed_bytes() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let mut bytes = fv.encrypt(&vec![5; fv.n], &pk).to_bytes();
+        assert_eq!(
+            fv.ciphertext_from_bytes(&bytes[1..]).unwrap_err(),
+            ValidationError::WrongLength {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            }
+        );
+        bytes[0] = 2;
+        assert_eq!(
+            fv.ciphertext_from_bytes(&bytes).unwrap_err(),
+            ValidationError::InvalidNttFlag { component: 0 }
+        );
+        assert_eq!(
+            fv.poly_from_bytes(&[], 0).unwrap_err(),
+            ValidationError::WrongLength {
+                expected: fv.poly_byte_len(),
+                actual: 0
+            }
+        );
+    }
+}
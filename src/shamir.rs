@@ -0,0 +1,289 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! t-out-of-n threshold decryption with Shamir secret sharing.
+//!
+//! Every coefficient of the secret key s is shared with a random polynomial of degree t - 1 over
+//! Z_q, so that the share of holder i is s_i = s + a_1 x_i + ... + a_{t-1} x_i^{t-1} with
+//! x_i = i + 1. Any t holders can decrypt, and fewer learn nothing about s.
+//!
+//! The Lagrange coefficients λ_i are large modulo q, so they cannot be applied to flooded
+//! partial decryptions without amplifying the flooding noise. Instead, once the set of available
+//! holders is known, each of them computes d_i = c0 * (λ_i s_i) + f_i, and the combiner only sums
+//! the d_i, so that the noise of the result is the sum of the f_i.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{randutils, FiniteRingElt, RqPoly, NTT};
+use crate::threshold::ThresholdError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::fmt;
+
+/// The Shamir share of a secret key held by one holder.
+pub struct ShamirShare<T: ArithUtils<T>> {
+    index: usize,
+    threshold: usize,
+    poly: RqPoly<T>,
+}
+
+impl<T: ArithUtils<T>> ShamirShare<T> {
+    /// The index of the holder of this share.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The number of shares needed for decryption.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for ShamirShare<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ShamirShare {{ index: {}, threshold: {} }}",
+            self.index, self.threshold
+        )
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: ArithUtils<T>> Drop for ShamirShare<T> {
+    fn drop(&mut self) {
+        crate::utils::wipe(&mut self.poly);
+    }
+}
+
+/// The partial decryption of a ciphertext by one holder, for a given set of holders.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShamirPartialDecryption<T> {
+    index: usize,
+    threshold: usize,
+    participants: Vec<usize>,
+    poly: RqPoly<T>,
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt<Coeff = T>,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Split a secret key into `count` Shamir shares, any `threshold` of which can decrypt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threshold` is not between 1 and `count`, or if `count` is not below q, as the
+    /// abscissae 1, ..., count of the holders would not be distinct and nonzero modulo q.
+    pub fn shamir_split_secret_key(
+        &self,
+        sk: &SecretKey<T>,
+        threshold: usize,
+        count: usize,
+    ) -> Vec<ShamirShare<T>> {
+        assert!(
+            0 < threshold && threshold <= count,
+            "the threshold must be between 1 and the number of shares"
+        );
+        assert!(
+            (count as u64) < T::to_u64(self.q.clone()),
+            "the number of shares must be below the modulus"
+        );
+        let mut coeffs = vec![sk.0.unpack(self.context.clone())];
+        for _ in 1..threshold {
+            coeffs.push(randutils::sample_uniform_poly(self.context.clone()));
+        }
+        let shares = (0..count)
+            .map(|index| {
+                // Horner evaluation at x = index + 1.
+                let x = T::from_u32(index as u32 + 1, &self.q);
+                let mut poly = coeffs[threshold - 1].clone();
+                for c in coeffs[..threshold - 1].iter().rev() {
+                    poly.scale_inplace(&x);
+                    poly.add_inplace(c);
+                }
+                if self.context.is_ntt_enabled {
+                    poly.forward_transform();
+                }
+                ShamirShare {
+                    index,
+                    threshold,
+                    poly,
+                }
+            })
+            .collect();
+        for c in coeffs.iter_mut() {
+            crate::utils::wipe(c);
+        }
+        shares
+    }
+
+    /// Partially decrypt a ciphertext with one share, for decryption by the given set of holder
+    /// indices, which must include the index of the share. The result is flooded with noise of
+    /// standard deviation `self.flooding_stdev`.
+    pub fn shamir_partial_decrypt(
+        &self,
+        ct: &FVCiphertext<T>,
+        share: &ShamirShare<T>,
+        participants: &[usize],
+    ) -> ShamirPartialDecryption<T> {
+        self.shamir_partial_decrypt_with_stdev(ct, share, participants, self.flooding_stdev)
+    }
+
+    /// Partially decrypt a ciphertext with one share, flooded with noise of the given standard
+    /// deviation, e.g. as computed by `partial_decryption_stdev` for the number of participants.
+    pub fn shamir_partial_decrypt_with_stdev(
+        &self,
+        ct: &FVCiphertext<T>,
+        share: &ShamirShare<T>,
+        participants: &[usize],
+        flooding_stdev: f64,
+    ) -> ShamirPartialDecryption<T> {
+        let mut participants = participants.to_vec();
+        participants.sort_unstable();
+        participants.dedup();
+        assert!(
+            participants.contains(&share.index),
+            "the share holder must take part in the decryption"
+        );
+        assert!(
+            participants
+                .iter()
+                .all(|j| (*j as u64) + 1 < T::to_u64(self.q.clone())),
+            "the holder indices must be below q - 1"
+        );
+        let mut scaled = share.poly.clone();
+        scaled.scale_inplace(&self.lagrange_at_zero(share.index, &participants));
+        let mut poly = (self.poly_multiplier)(&ct.0, &scaled);
+        crate::utils::wipe(&mut scaled);
+        poly.add_inplace(&randutils::sample_flooding_poly(
+            self.context.clone(),
+            flooding_stdev,
+        ));
+        ShamirPartialDecryption {
+            index: share.index,
+            threshold: share.threshold,
+            participants,
+            poly,
+        }
+    }
+
+    /// Combine the partial decryptions of a ciphertext by a set of at least `threshold` holders.
+    pub fn shamir_combine_partials(
+        &self,
+        ct: &FVCiphertext<T>,
+        partials: &[ShamirPartialDecryption<T>],
+    ) -> Result<FVPlaintext, ThresholdError> {
+        let first = match partials.first() {
+            Some(first) => first,
+            None => {
+                return Err(ThresholdError::MissingShares {
+                    expected: 1,
+                    actual: 0,
+                })
+            }
+        };
+        if partials
+            .iter()
+            .any(|p| p.participants != first.participants || p.threshold != first.threshold)
+        {
+            return Err(ThresholdError::ParticipantMismatch);
+        }
+        if first.participants.len() < first.threshold {
+            return Err(ThresholdError::BelowThreshold {
+                threshold: first.threshold,
+                actual: first.participants.len(),
+            });
+        }
+        let mut indices: Vec<usize> = partials.iter().map(|p| p.index).collect();
+        indices.sort_unstable();
+        indices.dedup();
+        if indices != first.participants || partials.len() != indices.len() {
+            return Err(ThresholdError::MissingShares {
+                expected: first.participants.len(),
+                actual: indices.len(),
+            });
+        }
+
+        let mut phase = ct.1.clone();
+        for p in partials.iter() {
+            phase.sub_inplace(&p.poly);
+        }
+        Ok(self.decode_phase(&phase))
+    }
+
+    /// The Lagrange coefficient of holder `index` for interpolating at zero from the given
+    /// holders: the product over the other holders j of x_j / (x_j - x_i).
+    fn lagrange_at_zero(&self, index: usize, participants: &[usize]) -> T {
+        let x_i = T::from_u32(index as u32 + 1, &self.q);
+        let mut num = T::one();
+        let mut den = T::one();
+        for j in participants.iter().filter(|j| **j != index) {
+            let x_j = T::from_u32(*j as u32 + 1, &self.q);
+            num = T::mul_mod(&num, &x_j, &self.q);
+            den = T::mul_mod(&den, &T::sub_mod(&x_j, &x_i, &self.q), &self.q);
+        }
+        T::mul_mod(&num, &T::inv_mod(&den, &self.q), &self.q)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_shamir_threshold_decryption() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let shares = fv.shamir_split_secret_key(&sk, 3, 5);
+        let ct = fv.encrypt(&vec![9; fv.n], &pk);
+
+        // holders 1 and 3 are unavailable.
+        let participants = [0, 2, 4];
+        let partials: Vec<_> = participants
+            .iter()
+            .map(|i| fv.shamir_partial_decrypt(&ct, &shares[*i], &participants))
+            .collect();
+        assert_eq!(
+            fv.shamir_combine_partials(&ct, &partials),
+            Ok(vec![9; fv.n])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the number of shares must be below the modulus")]
+    fn test_shamir_too_many_shares() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (_, sk) = fv.generate_keypair();
+        fv.shamir_split_secret_key(&sk, 2, 65537);
+    }
+
+    #[test]
+    fn test_shamir_below_threshold() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let shares = fv.shamir_split_secret_key(&sk, 3, 5);
+        let ct = fv.encrypt(&vec![9; fv.n], &pk);
+
+        let participants = [1, 3];
+        let partials: Vec<_> = participants
+            .iter()
+            .map(|i| fv.shamir_partial_decrypt(&ct, &shares[*i], &participants))
+            .collect();
+        assert_eq!(
+            fv.shamir_combine_partials(&ct, &partials),
+            Err(ThresholdError::BelowThreshold {
+                threshold: 3,
+                actual: 2
+            })
+        );
+
+        let p = fv.shamir_partial_decrypt(&ct, &shares[0], &[0, 1, 2]);
+        let q = fv.shamir_partial_decrypt(&ct, &shares[1], &[0, 1, 3]);
+        assert_eq!(
+            fv.shamir_combine_partials(&ct, &[p, q]),
+            Err(ThresholdError::ParticipantMismatch)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Postgres `BYTEA` encodings of typed ciphertexts and public keys, with the `sqlx` and
+//! `diesel` features.
+//!
+//! A [`Ciphertext`] or [`PublicKey`] of the default scheme is stored as its canonical encoding,
+//! and can be bound to queries and read from rows of `BYTEA` columns directly: with sqlx through
+//! `Type`, `Encode` and `Decode`, and with diesel through `ToSql` and `FromSql` for the
+//! `Binary` SQL type, the types deriving `AsExpression` and `FromSqlRow`.
+//!
+//! Reading a value decodes and validates it for the default parameters, with the scheme
+//! returned by [`sql_scheme`], which is built once per process. A value stored under other
+//! parameters is rejected with the [`ValidationError`] as error of the driver.
+use crate::ciphertext::Ciphertext;
+use crate::integer_arith::scalar::Scalar;
+use crate::pubkey::PublicKey;
+use crate::validation::ValidationError;
+use crate::FV;
+use std::sync::OnceLock;
+
+static SCHEME: OnceLock<FV<Scalar>> = OnceLock::new();
+
+/// The scheme against which the values read from a database are validated.
+pub fn sql_scheme() -> &'static FV<Scalar> {
+    SCHEME.get_or_init(FV::<Scalar>::default_2048)
+}
+
+fn decode_ciphertext(bytes: &[u8]) -> Result<Ciphertext<Scalar>, ValidationError> {
+    Ok(sql_scheme().ciphertext_from_bytes(bytes)?.into())
+}
+
+fn decode_public_key(bytes: &[u8]) -> Result<PublicKey<Scalar>, ValidationError> {
+    sql_scheme().typed_public_key_from_bytes(bytes)
+}
+
+#[cfg(feature = "sqlx")]
+mod sqlx_impls {
+    use super::*;
+    use crate::traits::Serializable;
+    use sqlx::encode::IsNull;
+    use sqlx::error::BoxDynError;
+    use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef, Postgres};
+    use sqlx::{Decode, Encode, Type};
+
+    macro_rules! impl_sqlx {
+        ($ty:ty, $decode:ident) => {
+            impl Type<Postgres> for $ty {
+                fn type_info() -> PgTypeInfo {
+                    <Vec<u8> as Type<Postgres>>::type_info()
+                }
+            }
+
+            impl Encode<'_, Postgres> for $ty {
+                fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+                    <Vec<u8> as Encode<Postgres>>::encode(self.to_bytes(), buf)
+                }
+            }
+
+            impl<'r> Decode<'r, Postgres> for $ty {
+                fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+                    let bytes = <Vec<u8> as Decode<Postgres>>::decode(value)?;
+                    Ok($decode(&bytes)?)
+                }
+            }
+        };
+    }
+
+    impl_sqlx!(Ciphertext<Scalar>, decode_ciphertext);
+    impl_sqlx!(PublicKey<Scalar>, decode_public_key);
+}
+
+#[cfg(feature = "diesel")]
+mod diesel_impls {
+    use super::*;
+    use crate::traits::Serializable;
+    use diesel::deserialize::{self, FromSql};
+    use diesel::pg::{Pg, PgValue};
+    use diesel::serialize::{self, IsNull, Output, ToSql};
+    use diesel::sql_types::Binary;
+    use std::io::Write;
+
+    macro_rules! impl_diesel {
+        ($ty:ty, $decode:ident) => {
+            impl ToSql<Binary, Pg> for $ty {
+                fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+                    out.write_all(&self.to_bytes())?;
+                    Ok(IsNull::No)
+                }
+            }
+
+            impl FromSql<Binary, Pg> for $ty {
+                fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
+                    Ok($decode(value.as_bytes())?)
+                }
+            }
+        };
+    }
+
+    impl_diesel!(Ciphertext<Scalar>, decode_ciphertext);
+    impl_diesel!(PublicKey<Scalar>, decode_public_key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::*;
+
+    #[test]
+    fn test_sql_decoding() {
+        let fv = sql_scheme();
+        let (pk, _) = fv.generate_typed_keypair();
+        let ct = fv.encrypt_to(&vec![1; fv.n], &pk);
+        assert_eq!(decode_ciphertext(&ct.to_bytes()), Ok(ct));
+        assert_eq!(decode_public_key(&pk.to_bytes()), Ok(pk));
+        assert!(decode_public_key(&[0; 8]).is_err());
+    }
+
+    #[cfg(feature = "sqlx")]
+    #[test]
+    fn test_sqlx_type() {
+        use sqlx::{postgres::Postgres, Type};
+        assert_eq!(
+            <Ciphertext<Scalar> as Type<Postgres>>::type_info(),
+            <Vec<u8> as Type<Postgres>>::type_info()
+        );
+    }
+}
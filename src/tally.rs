@@ -0,0 +1,334 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Homomorphic tallies of one-hot ballots.
+//!
+//! A ballot for candidate c among k encrypts the plaintext with coefficient c set to 1 and all
+//! others 0, see [`FV::cast_ballot`]. A [`Tally`] adds up the ballots, so that coefficient c of
+//! the sum counts the votes for candidate c. The plaintext modulus is 256, so the tally starts a
+//! new sum every [`BALLOTS_PER_SUM`] ballots, and the final count of a candidate is the sum of its
+//! counts in each of them.
+//!
+//! The counts are recovered with the secret key, with the additive shares of
+//! [`crate::threshold`], or with a proof of correct decryption of each sum from
+//! [`crate::decproof`], which anyone holding the ballots can check with
+//! [`Tally::verify_counts`]. The well-formedness of the ballots is not proven: a voter can encrypt
+//! several votes, or votes for a coefficient beyond the candidates.
+use crate::decproof::DecryptionProof;
+use crate::integer_arith::ArithUtils;
+use crate::keyproof::KeyProofError;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::threshold::{PartialDecryption, SecretKeyShare, ThresholdError};
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The number of ballots added into one sum, so that no count wraps around the plaintext
+/// modulus.
+pub const BALLOTS_PER_SUM: usize = 255;
+
+/// The reasons for which a ballot cannot be tallied, or counts cannot be recovered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TallyError {
+    /// A ballot is invalid.
+    Invalid(ValidationError),
+    /// There is not one partial decryption or proof per sum.
+    WrongLength { expected: usize, actual: usize },
+    /// The partial decryptions of a sum cannot be combined.
+    Threshold(ThresholdError),
+    /// A proof of decryption is invalid.
+    Proof(KeyProofError),
+    /// The counts do not match the proven decryptions.
+    CountMismatch,
+}
+
+impl fmt::Display for TallyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TallyError::Invalid(e) => write!(f, "invalid ballot: {}", e),
+            TallyError::WrongLength { expected, actual } => {
+                write!(f, "{} values for a tally of {} sums", actual, expected)
+            }
+            TallyError::Threshold(e) => write!(f, "{}", e),
+            TallyError::Proof(e) => write!(f, "{}", e),
+            TallyError::CountMismatch => write!(f, "counts do not match the decryptions"),
+        }
+    }
+}
+
+impl Error for TallyError {}
+
+impl From<ValidationError> for TallyError {
+    fn from(e: ValidationError) -> Self {
+        TallyError::Invalid(e)
+    }
+}
+
+impl From<ThresholdError> for TallyError {
+    fn from(e: ThresholdError) -> Self {
+        TallyError::Threshold(e)
+    }
+}
+
+impl From<KeyProofError> for TallyError {
+    fn from(e: KeyProofError) -> Self {
+        TallyError::Proof(e)
+    }
+}
+
+/// The decryptions of the sums of a tally, with their proofs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TallyProof {
+    /// The decryption of each sum.
+    pub plaintexts: Vec<FVPlaintext>,
+    /// The proof of each decryption.
+    pub proofs: Vec<DecryptionProof>,
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// The plaintext of a vote for `choice` among `candidates`.
+    ///
+    /// # Panics
+    /// If `choice` is not below `candidates`, or `candidates` exceeds n.
+    pub fn encode_ballot(&self, candidates: usize, choice: usize) -> FVPlaintext {
+        assert!(candidates <= self.n, "more candidates than coefficients");
+        assert!(choice < candidates, "choice out of range");
+        let mut pt = vec![0; self.n];
+        pt[choice] = 1;
+        pt
+    }
+
+    /// The encrypted ballot of a vote for `choice` among `candidates`.
+    pub fn cast_ballot(
+        &self,
+        pk: &FVCiphertext<T>,
+        candidates: usize,
+        choice: usize,
+    ) -> FVCiphertext<T> {
+        self.encrypt(&self.encode_ballot(candidates, choice), pk)
+    }
+}
+
+/// An accumulator of the ballots of an election.
+pub struct Tally<'a, T: ArithUtils<T>> {
+    fv: &'a FV<T>,
+    candidates: usize,
+    sums: Vec<FVCiphertext<T>>,
+    ballots: usize,
+}
+
+impl<'a, T: ArithUtils<T>> fmt::Debug for Tally<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Tally({} ballots, {} candidates)",
+            self.ballots, self.candidates
+        )
+    }
+}
+
+impl<'a, T> Tally<'a, T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// An empty tally of an election among `candidates`.
+    ///
+    /// # Panics
+    /// If `candidates` exceeds n.
+    pub fn new(fv: &'a FV<T>, candidates: usize) -> Self {
+        assert!(candidates <= fv.n, "more candidates than coefficients");
+        Tally {
+            fv,
+            candidates,
+            sums: vec![],
+            ballots: 0,
+        }
+    }
+
+    /// The number of ballots added.
+    pub fn ballots(&self) -> usize {
+        self.ballots
+    }
+
+    /// The encrypted sums, one per [`BALLOTS_PER_SUM`] ballots.
+    pub fn sums(&self) -> &[FVCiphertext<T>] {
+        &self.sums
+    }
+
+    /// Validate and add a ballot.
+    pub fn add(&mut self, ballot: &FVCiphertext<T>) -> Result<(), TallyError> {
+        self.fv.validate(ballot)?;
+        if self.ballots.is_multiple_of(BALLOTS_PER_SUM) {
+            self.sums.push(ballot.clone());
+        } else {
+            let sum = self.sums.last_mut().expect("a sum per started batch");
+            self.fv.add_inplace(sum, ballot);
+        }
+        self.ballots += 1;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            params_id = %self.fv.params_id(),
+            ballots = self.ballots,
+            "added a ballot"
+        );
+        Ok(())
+    }
+
+    /// The counts of the candidates, decrypted with the secret key.
+    pub fn counts(&self, sk: &SecretKey<T>) -> Vec<u64> {
+        let plaintexts: Vec<_> = self.sums.iter().map(|ct| self.fv.decrypt(ct, sk)).collect();
+        self.add_counts(&plaintexts)
+    }
+
+    /// The partial decryptions of the sums by the holder of one share.
+    pub fn partial_decrypt(&self, share: &SecretKeyShare<T>) -> Vec<PartialDecryption<T>> {
+        self.sums
+            .iter()
+            .map(|ct| self.fv.partial_decrypt(ct, share))
+            .collect()
+    }
+
+    /// The counts of the candidates, combined from the partial decryptions of every holder.
+    pub fn combine_counts(
+        &self,
+        partials: &[Vec<PartialDecryption<T>>],
+    ) -> Result<Vec<u64>, TallyError> {
+        if let Some(p) = partials.iter().find(|p| p.len() != self.sums.len()) {
+            return Err(TallyError::WrongLength {
+                expected: self.sums.len(),
+                actual: p.len(),
+            });
+        }
+        let plaintexts = self
+            .sums
+            .iter()
+            .enumerate()
+            .map(|(i, ct)| {
+                let column: Vec<_> = partials.iter().map(|p| p[i].clone()).collect();
+                self.fv.combine_partials(ct, &column)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.add_counts(&plaintexts))
+    }
+
+    /// The counts of the candidates, with a proof of decryption of each sum under the secret key
+    /// of `pk`.
+    pub fn prove_counts(
+        &self,
+        sk: &SecretKey<T>,
+        pk: &FVCiphertext<T>,
+    ) -> Result<(Vec<u64>, TallyProof), TallyError> {
+        let (plaintexts, proofs) = self
+            .sums
+            .iter()
+            .map(|ct| self.fv.decrypt_with_proof(ct, sk, pk))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+        let proof = TallyProof { plaintexts, proofs };
+        Ok((self.add_counts(&proof.plaintexts), proof))
+    }
+
+    /// Verify that `counts` are the counts of the candidates, decrypted under the secret key of
+    /// `pk`.
+    pub fn verify_counts(
+        &self,
+        pk: &FVCiphertext<T>,
+        counts: &[u64],
+        proof: &TallyProof,
+    ) -> Result<(), TallyError> {
+        for len in [proof.plaintexts.len(), proof.proofs.len()] {
+            if len != self.sums.len() {
+                return Err(TallyError::WrongLength {
+                    expected: self.sums.len(),
+                    actual: len,
+                });
+            }
+        }
+        for ((ct, pt), p) in self
+            .sums
+            .iter()
+            .zip(proof.plaintexts.iter())
+            .zip(proof.proofs.iter())
+        {
+            self.fv.verify_decryption(pk, ct, pt, p)?;
+        }
+        if counts != self.add_counts(&proof.plaintexts).as_slice() {
+            return Err(TallyError::CountMismatch);
+        }
+        Ok(())
+    }
+
+    fn add_counts(&self, plaintexts: &[FVPlaintext]) -> Vec<u64> {
+        let mut counts = vec![0u64; self.candidates];
+        for pt in plaintexts {
+            for (count, &x) in counts.iter_mut().zip(pt.iter()) {
+                *count += x as u64;
+            }
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_tally() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut tally = Tally::new(&fv, 3);
+        // More ballots than fit in one sum.
+        for i in 0..300 {
+            tally.add(&fv.cast_ballot(&pk, 3, i % 3)).unwrap();
+        }
+        assert_eq!(tally.ballots(), 300);
+        assert_eq!(tally.sums().len(), 2);
+        assert_eq!(tally.counts(&sk), vec![100, 100, 100]);
+
+        let shares = fv.split_secret_key(&sk, 3);
+        let partials: Vec<_> = shares.iter().map(|s| tally.partial_decrypt(s)).collect();
+        assert_eq!(tally.combine_counts(&partials), Ok(vec![100, 100, 100]));
+        assert_eq!(
+            tally.combine_counts(&[vec![]]),
+            Err(TallyError::WrongLength {
+                expected: 2,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_verifiable_tally() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let mut tally = Tally::new(&fv, 2);
+        for choice in [0, 1, 1] {
+            tally.add(&fv.cast_ballot(&pk, 2, choice)).unwrap();
+        }
+        let (counts, proof) = tally.prove_counts(&sk, &pk).unwrap();
+        assert_eq!(counts, vec![1, 2]);
+        assert_eq!(tally.verify_counts(&pk, &counts, &proof), Ok(()));
+        assert_eq!(
+            tally.verify_counts(&pk, &[2, 1], &proof),
+            Err(TallyError::CountMismatch)
+        );
+
+        let mut forged = proof;
+        forged.plaintexts[0][0] = 2;
+        assert_eq!(
+            tally.verify_counts(&pk, &[2, 2], &forged),
+            Err(TallyError::Proof(KeyProofError::ChallengeMismatch))
+        );
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Proptest strategies for property tests against the types of the crate.
+//!
+//! [`params`] generates schemes of various degrees and moduli, [`plaintext`] their plaintexts,
+//! and [`keyed_scheme`] and [`encrypted`] keys and ciphertexts. All the randomness of key
+//! generation and encryption is drawn from proptest, so that a failing case is replayed exactly
+//! from its persisted seed, and plaintexts shrink towards zero.
+//!
+//! ```
+//! use cupcake::testing::*;
+//! use cupcake::traits::*;
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     fn add_plain_decrypts((keyed, (pt, ct), other) in keyed_scheme().prop_flat_map(|k| {
+//!         let n = k.fv.n;
+//!         (Just(k.clone()), encrypted(&k), plaintext(n))
+//!     })) {
+//!         let KeyedScheme { fv, sk, .. } = &keyed;
+//!         let sum: Vec<u8> = pt.iter().zip(&other).map(|(x, y)| x.wrapping_add(*y)).collect();
+//!         prop_assert_eq!(fv.decrypt(&fv.add_plain(&ct, &other), sk), sum);
+//!     }
+//! }
+//! # add_plain_decrypts();
+//! ```
+use crate::integer_arith::scalar::Scalar;
+use crate::rng::with_seeded_rng;
+use crate::traits::{PKEncryption, SKEncryption};
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use proptest::prelude::*;
+
+/// Prime moduli with an NTT for every generated degree.
+pub const MODULI: [u64; 3] = [1073750017, 1099511795713, 18014398492704769];
+
+/// The number of fresh ciphertexts which a generated scheme can sum, then rerandomize, with
+/// guaranteed decryption correctness.
+pub const MIN_ADDITIONS: u64 = 16;
+
+/// The degrees and moduli of the schemes generated by [`params`]: every pair of a power of two
+/// from 16 to 2048 and a modulus of [`MODULI`] which supports [`MIN_ADDITIONS`].
+pub fn valid_params() -> Vec<(usize, u64)> {
+    let mut valid = vec![];
+    for log_n in 4..=11 {
+        for &q in MODULI.iter() {
+            let fv = FV::new(1 << log_n, &Scalar::new_modulus(q));
+            let model = fv.noise_model();
+            let sum = model.sum(&model.fresh_pk(), MIN_ADDITIONS);
+            if model.is_correct(&model.rerandomize(&sum)) {
+                valid.push((1 << log_n, q));
+            }
+        }
+    }
+    valid
+}
+
+/// A scheme with valid parameters.
+pub fn params() -> impl Strategy<Value = FV<Scalar>> {
+    proptest::sample::select(valid_params()).prop_map(|(n, q)| FV::new(n, &Scalar::new_modulus(q)))
+}
+
+/// A plaintext of n slots.
+pub fn plaintext(n: usize) -> impl Strategy<Value = FVPlaintext> {
+    proptest::collection::vec(any::<u8>(), n)
+}
+
+/// A scheme with a keypair.
+#[derive(Clone, Debug)]
+pub struct KeyedScheme {
+    pub fv: FV<Scalar>,
+    pub pk: FVCiphertext<Scalar>,
+    pub sk: SecretKey<Scalar>,
+}
+
+/// A scheme of valid parameters, with a keypair generated for it.
+pub fn keyed_scheme() -> impl Strategy<Value = KeyedScheme> {
+    (params(), any::<[u8; 32]>()).prop_map(|(fv, seed)| {
+        let (pk, sk) = with_seeded_rng(seed, || fv.generate_keypair());
+        KeyedScheme { fv, pk, sk }
+    })
+}
+
+/// A plaintext and its encryption under the public key of `keyed`.
+pub fn encrypted(
+    keyed: &KeyedScheme,
+) -> impl Strategy<Value = (FVPlaintext, FVCiphertext<Scalar>)> {
+    let keyed = keyed.clone();
+    (plaintext(keyed.fv.n), any::<[u8; 32]>()).prop_map(move |(pt, seed)| {
+        let ct = with_seeded_rng(seed, || keyed.fv.encrypt(&pt, &keyed.pk));
+        (pt, ct)
+    })
+}
+
+/// A plaintext and its encryption under the secret key of `keyed`.
+pub fn encrypted_sk(
+    keyed: &KeyedScheme,
+) -> impl Strategy<Value = (FVPlaintext, FVCiphertext<Scalar>)> {
+    let keyed = keyed.clone();
+    (plaintext(keyed.fv.n), any::<[u8; 32]>()).prop_map(move |(pt, seed)| {
+        let ct = with_seeded_rng(seed, || keyed.fv.encrypt_sk(&pt, &keyed.sk));
+        (pt, ct)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::AdditiveHomomorphicScheme;
+
+    #[test]
+    fn test_valid_params() {
+        let valid = valid_params();
+        assert_eq!(valid.len(), 8 * MODULI.len());
+        assert!(valid.contains(&(2048, 18014398492704769)));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_sum_decrypts((keyed, cts) in keyed_scheme().prop_flat_map(|keyed| {
+            let cts = proptest::collection::vec(encrypted(&keyed), 1..4);
+            (Just(keyed), cts)
+        })) {
+            let KeyedScheme { fv, pk, sk } = &keyed;
+            let mut sum = fv.encrypt_zero(pk);
+            let mut expected = vec![0u8; fv.n];
+            for (pt, ct) in cts.iter() {
+                fv.add_inplace(&mut sum, ct);
+                for (e, x) in expected.iter_mut().zip(pt.iter()) {
+                    *e = e.wrapping_add(*x);
+                }
+            }
+            fv.rerandomize(&mut sum, pk);
+            prop_assert_eq!(fv.decrypt(&sum, sk), expected);
+        }
+    }
+}
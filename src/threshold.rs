@@ -0,0 +1,357 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! n-out-of-n threshold decryption.
+//!
+//! The secret key s is split into n additive shares s_1 + ... + s_n = s, each uniform in Rq on
+//! its own, so that no server holding fewer than all shares learns anything about s. Each server
+//! computes a partial decryption d_i = c0 * s_i + f_i of a ciphertext, where the flooding noise
+//! f_i hides its share, and the combiner recovers the plaintext from c1 - (d_1 + ... + d_n).
+//!
+//! Since all but one holder may collude with the combiner, every f_i must hide the noise of the
+//! ciphertext on its own. [`FV::partial_decryption_stdev`] calibrates its standard deviation for
+//! a security level and a bound on the number of additions behind the ciphertext.
+use crate::flooding::FloodingUnsupported;
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{randutils, FiniteRingElt, RqPoly, NTT};
+use crate::traits::Serializable;
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, FVPlaintext, SecretKey, FV};
+use std::error::Error;
+use std::fmt;
+
+/// The share of a secret key held by one server.
+pub struct SecretKeyShare<T: ArithUtils<T>> {
+    index: usize,
+    count: usize,
+    poly: RqPoly<T>,
+}
+
+impl<T: ArithUtils<T>> SecretKeyShare<T> {
+    /// The index of this share, in [0, count).
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The number of shares the key was split into.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub(crate) fn poly_mut(&mut self) -> &mut RqPoly<T> {
+        &mut self.poly
+    }
+}
+
+impl<T: ArithUtils<T>> fmt::Debug for SecretKeyShare<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretKeyShare({}/{})", self.index, self.count)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: ArithUtils<T>> Drop for SecretKeyShare<T> {
+    fn drop(&mut self) {
+        crate::utils::wipe(&mut self.poly);
+    }
+}
+
+/// The partial decryption of a ciphertext by the holder of one share.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartialDecryption<T> {
+    index: usize,
+    count: usize,
+    poly: RqPoly<T>,
+}
+
+impl<T> Serializable for PartialDecryption<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The index and count as 4-byte little-endian integers, followed by the polynomial.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.index as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(self.count as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.poly.to_bytes());
+        bytes
+    }
+}
+
+/// The reasons for which partial decryptions cannot be combined.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ThresholdError {
+    /// Not every share index in [0, count) has exactly one partial decryption.
+    MissingShares { expected: usize, actual: usize },
+    /// A partial decryption comes from a split into a different number of shares.
+    CountMismatch { expected: usize, actual: usize },
+    /// Fewer share holders than the threshold took part in the decryption.
+    BelowThreshold { threshold: usize, actual: usize },
+    /// The partial decryptions were computed for different sets of share holders.
+    ParticipantMismatch,
+}
+
+impl fmt::Display for ThresholdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThresholdError::MissingShares { expected, actual } => write!(
+                f,
+                "expected partial decryptions for {} distinct shares, found {}",
+                expected, actual
+            ),
+            ThresholdError::CountMismatch { expected, actual } => write!(
+                f,
+                "partial decryption of a {}-share split, expected {}",
+                actual, expected
+            ),
+            ThresholdError::BelowThreshold { threshold, actual } => write!(
+                f,
+                "{} share holders took part, the threshold is {}",
+                actual, threshold
+            ),
+            ThresholdError::ParticipantMismatch => {
+                write!(f, "partial decryptions for different sets of share holders")
+            }
+        }
+    }
+}
+
+impl Error for ThresholdError {}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Split a secret key into `n` additive shares, all of which are needed for decryption.
+    pub fn split_secret_key(&self, sk: &SecretKey<T>, n: usize) -> Vec<SecretKeyShare<T>> {
+        assert!(n > 0, "a key is split into at least one share");
+        let mut last = sk.0.unpack(self.context.clone());
+        let mut shares = Vec::with_capacity(n);
+        for index in 0..n - 1 {
+            let poly = randutils::sample_uniform_poly(self.context.clone());
+            last.sub_inplace(&poly);
+            shares.push(self.new_share(index, n, poly));
+        }
+        shares.push(self.new_share(n - 1, n, last));
+        shares
+    }
+
+    pub(crate) fn new_share(
+        &self,
+        index: usize,
+        count: usize,
+        mut poly: RqPoly<T>,
+    ) -> SecretKeyShare<T> {
+        if self.context.is_ntt_enabled {
+            poly.forward_transform();
+        }
+        SecretKeyShare { index, count, poly }
+    }
+
+    /// Partially decrypt a ciphertext with one share. The result is flooded with noise of
+    /// standard deviation `self.flooding_stdev`, so that it can be sent to the combiner without
+    /// revealing the share.
+    pub fn partial_decrypt(
+        &self,
+        ct: &FVCiphertext<T>,
+        share: &SecretKeyShare<T>,
+    ) -> PartialDecryption<T> {
+        self.partial_decrypt_with_stdev(ct, share, self.flooding_stdev)
+    }
+
+    /// Partially decrypt a ciphertext with one share, flooded with noise of the given standard
+    /// deviation, e.g. as computed by `partial_decryption_stdev`.
+    pub fn partial_decrypt_with_stdev(
+        &self,
+        ct: &FVCiphertext<T>,
+        share: &SecretKeyShare<T>,
+        flooding_stdev: f64,
+    ) -> PartialDecryption<T> {
+        let mut poly = (self.poly_multiplier)(&ct.0, &share.poly);
+        poly.add_inplace(&randutils::sample_flooding_poly(
+            self.context.clone(),
+            flooding_stdev,
+        ));
+        PartialDecryption {
+            index: share.index,
+            count: share.count,
+            poly,
+        }
+    }
+
+    /// The standard deviation of the flooding noise each of `parties` holders must add to its
+    /// partial decryption of a ciphertext obtained by at most `ops_bound` additions of fresh
+    /// ciphertexts, so that the partial decryption hides the noise up to a statistical distance
+    /// of 2^-lambda_bits. Fails if the combined phase would not decode correctly.
+    pub fn partial_decryption_stdev(
+        &self,
+        lambda_bits: u32,
+        ops_bound: u64,
+        parties: usize,
+    ) -> Result<f64, FloodingUnsupported> {
+        let model = self.noise_model();
+        let input = model.sum(&model.fresh_pk(), ops_bound.saturating_add(1));
+        let (bound, flooding_stdev) = self.hiding_flooding(&input, lambda_bits);
+        let max_noise = model.max_noise();
+        if bound * parties as f64 >= max_noise {
+            return Err(FloodingUnsupported {
+                flooding_stdev,
+                noise: bound * parties as f64,
+                max_noise,
+            });
+        }
+        let output = model.combine_partials(&input, parties, flooding_stdev);
+        if !model.is_correct(&output) {
+            return Err(FloodingUnsupported {
+                flooding_stdev,
+                noise: output.worst_case,
+                max_noise,
+            });
+        }
+        Ok(flooding_stdev)
+    }
+
+    /// Combine the partial decryptions of a ciphertext by all the shares into its plaintext.
+    pub fn combine_partials(
+        &self,
+        ct: &FVCiphertext<T>,
+        partials: &[PartialDecryption<T>],
+    ) -> Result<FVPlaintext, ThresholdError> {
+        let count = partials.first().map_or(0, |p| p.count);
+        if let Some(p) = partials.iter().find(|p| p.count != count) {
+            return Err(ThresholdError::CountMismatch {
+                expected: count,
+                actual: p.count,
+            });
+        }
+        // The count is untrusted, so the bitmap is sized by the partials actually received.
+        let len = count.min(partials.len());
+        let mut seen = vec![false; len];
+        for p in partials.iter().filter(|p| p.index < len) {
+            seen[p.index] = true;
+        }
+        let distinct = seen.iter().filter(|x| **x).count();
+        if count == 0 || distinct != count || partials.len() != count {
+            return Err(ThresholdError::MissingShares {
+                expected: count,
+                actual: distinct,
+            });
+        }
+
+        let mut phase = ct.1.clone();
+        for p in partials.iter() {
+            phase.sub_inplace(&p.poly);
+        }
+        Ok(self.decode_phase(&phase))
+    }
+
+    /// Decode and validate a partial decryption.
+    pub fn partial_decryption_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<PartialDecryption<T>, ValidationError> {
+        let expected = 8 + self.poly_byte_len();
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let word = |i: usize| {
+            let mut w = [0u8; 4];
+            w.copy_from_slice(&bytes[i..i + 4]);
+            u32::from_le_bytes(w) as usize
+        };
+        let (index, count) = (word(0), word(4));
+        if index >= count {
+            return Err(ValidationError::InvalidShareIndex { index, count });
+        }
+        let poly = self.poly_from_bytes(&bytes[8..], 0)?;
+        self.validate_poly(&poly, 0, false)?;
+        Ok(PartialDecryption { index, count, poly })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_threshold_decryption() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let shares = fv.split_secret_key(&sk, 3);
+        let mut ct = fv.encrypt(&vec![1; fv.n], &pk);
+        fv.add_inplace(&mut ct, &fv.encrypt(&vec![2; fv.n], &pk));
+
+        let partials: Vec<_> = shares
+            .iter()
+            .map(|share| {
+                let bytes = fv.partial_decrypt(&ct, share).to_bytes();
+                fv.partial_decryption_from_bytes(&bytes).unwrap()
+            })
+            .collect();
+        assert_eq!(fv.combine_partials(&ct, &partials), Ok(vec![3; fv.n]));
+    }
+
+    #[test]
+    fn test_calibrated_partial_decryption() {
+        let fv = FV::<Scalar>::default_2048();
+        let (pk, sk) = fv.generate_keypair();
+        let shares = fv.split_secret_key(&sk, 3);
+        let mut ct = fv.encrypt(&vec![1; fv.n], &pk);
+        for _ in 0..4 {
+            fv.add_inplace(&mut ct, &fv.encrypt(&vec![1; fv.n], &pk));
+        }
+        let stdev = fv.partial_decryption_stdev(8, 4, 3).unwrap();
+        assert!(stdev > fv.partial_decryption_stdev(4, 4, 3).unwrap());
+        let partials: Vec<_> = shares
+            .iter()
+            .map(|share| fv.partial_decrypt_with_stdev(&ct, share, stdev))
+            .collect();
+        assert_eq!(fv.combine_partials(&ct, &partials), Ok(vec![5; fv.n]));
+
+        let err = fv.partial_decryption_stdev(8, 4, 1 << 20).unwrap_err();
+        assert!(err.noise >= err.max_noise);
+        assert!(fv.partial_decryption_stdev(128, 4, 3).is_err());
+    }
+
+    #[test]
+    fn test_missing_shares() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, sk) = fv.generate_keypair();
+        let shares = fv.split_secret_key(&sk, 3);
+        let ct = fv.encrypt(&vec![1; fv.n], &pk);
+        let p0 = fv.partial_decrypt(&ct, &shares[0]);
+        let p1 = fv.partial_decrypt(&ct, &shares[1]);
+        assert_eq!(
+            fv.combine_partials(&ct, &[p0.clone(), p1.clone()]),
+            Err(ThresholdError::MissingShares {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            fv.combine_partials(&ct, &[p0.clone(), p1, p0.clone()]),
+            Err(ThresholdError::MissingShares {
+                expected: 3,
+                actual: 2
+            })
+        );
+
+        // A forged count is rejected without allocating for it.
+        let mut bytes = p0.to_bytes();
+        bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        let forged = fv.partial_decryption_from_bytes(&bytes).unwrap();
+        assert_eq!(
+            fv.combine_partials(&ct, &[forged]),
+            Err(ThresholdError::MissingShares {
+                expected: u32::MAX as usize,
+                actual: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Statistical timing leakage tests in the style of dudect.
+//!
+//! An operation is timed on two classes of inputs, a fixed input and random inputs, drawn in a
+//! random interleaved order. Welch's t-test then compares the two timing distributions; a large
+//! |t| indicates that the running time depends on the input.
+//!
+//! With the `timing` feature, [`FV::timing_suite`] runs the tests of decryption and Gaussian
+//! sampling, so that constant-time claims can be checked on the hardware at hand. The results
+//! are sensitive to the load of the machine, and are only meaningful in a release build.
+use crate::integer_arith::ArithUtils;
+use crate::rng::with_rng;
+use crate::rqpoly::randutils::sample_gaussian_poly_with;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::traits::{PKEncryption, SKEncryption};
+use crate::FV;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::cell::Cell;
+use std::fmt;
+use std::hint::black_box;
+use std::time::Instant;
+
+/// The |t| above which an operation is considered to leak, as in dudect.
+pub const LEAKAGE_THRESHOLD: f64 = 4.5;
+
+/// The number of inputs of each class prepared in advance, and cycled through, by the suite. The
+/// operations only take references into the pools: timing operations on freshly cloned inputs
+/// favours the fixed class.
+const POOL: usize = 64;
+
+/// The size of a test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimingConfig {
+    /// Number of timing samples.
+    pub samples: usize,
+    /// Number of calls of the operation per timing sample.
+    pub batch: usize,
+}
+
+impl Default for TimingConfig {
+    /// 100000 samples of 16 calls.
+    fn default() -> Self {
+        TimingConfig {
+            samples: 100_000,
+            batch: 16,
+        }
+    }
+}
+
+/// The outcome of the test of one operation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LeakageReport {
+    /// The name of the operation.
+    pub operation: &'static str,
+    /// Welch's t-statistic of the fixed and random classes.
+    pub t: f64,
+}
+
+impl LeakageReport {
+    /// Whether |t| exceeds [`LEAKAGE_THRESHOLD`].
+    pub fn leaks(&self) -> bool {
+        self.t.abs() > LEAKAGE_THRESHOLD
+    }
+}
+
+impl fmt::Display for LeakageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: t = {:.2} ({})",
+            self.operation,
+            self.t,
+            if self.leaks() {
+                "leaks"
+            } else {
+                "no leakage detected"
+            }
+        )
+    }
+}
+
+/// Welch's t-statistic of two samples.
+pub fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+    let stats = |v: &[f64]| {
+        let n = v.len() as f64;
+        let mean = v.iter().sum::<f64>() / n;
+        let var = v.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1f64);
+        (n, mean, var)
+    };
+    let (na, ma, va) = stats(a);
+    let (nb, mb, vb) = stats(b);
+    (ma - mb) / (va / na + vb / nb).sqrt()
+}
+
+/// Time `op` on fixed and random inputs with the default configuration, and return the
+/// t-statistic of the two classes.
+pub fn fixed_vs_random_t<I, F, R, O, U>(fixed: F, random: R, op: O) -> f64
+where
+    F: Fn() -> I,
+    R: Fn() -> I,
+    O: Fn(I) -> U,
+{
+    fixed_vs_random_t_with(&TimingConfig::default(), fixed, random, op)
+}
+
+/// Time `op` on fixed and random inputs, and return the t-statistic of the two classes. The
+/// slowest 10% of the samples are discarded, as they are dominated by interrupts.
+///
+/// # Panics
+///
+/// Panics if `config.samples` is zero.
+pub fn fixed_vs_random_t_with<I, F, R, O, U>(
+    config: &TimingConfig,
+    fixed: F,
+    random: R,
+    op: O,
+) -> f64
+where
+    F: Fn() -> I,
+    R: Fn() -> I,
+    O: Fn(I) -> U,
+{
+    assert!(
+        config.samples > 0,
+        "a timing test needs at least one sample"
+    );
+    let mut timings: Vec<(bool, f64)> = Vec::with_capacity(config.samples);
+    for _ in 0..config.samples {
+        let class = with_rng(|rng| rng.next_u32() & 1 == 1);
+        // both classes are prepared identically, so that the state of the caches does not
+        // depend on the class.
+        let inputs: Vec<I> = (0..config.batch)
+            .map(|_| {
+                let (f, r) = (fixed(), random());
+                if class {
+                    r
+                } else {
+                    f
+                }
+            })
+            .collect();
+        let start = Instant::now();
+        for input in inputs {
+            black_box(op(black_box(input)));
+        }
+        timings.push((class, start.elapsed().as_nanos() as f64));
+    }
+
+    let mut sorted: Vec<f64> = timings.iter().map(|x| x.1).collect();
+    sorted.sort_by(f64::total_cmp);
+    let cutoff = sorted[config.samples * 9 / 10];
+    let class = |c: bool| -> Vec<f64> {
+        timings
+            .iter()
+            .filter(|x| x.0 == c && x.1 <= cutoff)
+            .map(|x| x.1)
+            .collect()
+    };
+    welch_t(&class(false), &class(true))
+}
+
+/// Cycle through a pool of inputs.
+fn cycle<'a, I>(pool: &'a [I]) -> impl Fn() -> &'a I + 'a {
+    let next = Cell::new(0);
+    move || {
+        let i = next.get();
+        next.set((i + 1) % pool.len());
+        &pool[i]
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Test decryption under a fresh key, on a fixed ciphertext against random ones.
+    pub fn decryption_leakage(&self, config: &TimingConfig) -> LeakageReport {
+        let (pk, sk) = self.generate_keypair();
+        let ct = self.encrypt(&vec![0; self.n], &pk);
+        // copies of the fixed ciphertext are allocated alternately with the random ones, so that
+        // neither class is laid out more favourably in memory.
+        let (mut fixed, mut random) = (vec![], vec![]);
+        for _ in 0..POOL {
+            fixed.push(ct.clone());
+            let pt: Vec<u8> = (0..self.n)
+                .map(|_| with_rng(|rng| rng.next_u32() as u8))
+                .collect();
+            random.push(self.encrypt(&pt, &pk));
+        }
+        let t = fixed_vs_random_t_with(config, cycle(&fixed), cycle(&random), |ct| {
+            self.decrypt(ct, &sk)
+        });
+        LeakageReport {
+            operation: "decryption",
+            t,
+        }
+    }
+
+    /// Test the sampling of the Gaussian error polynomials, from a generator with a fixed seed
+    /// against generators with random seeds.
+    pub fn gaussian_sampling_leakage(&self, config: &TimingConfig) -> LeakageReport {
+        let seeds: Vec<[u8; 32]> = (0..POOL)
+            .map(|_| {
+                let mut seed = [0u8; 32];
+                with_rng(|rng| rng.fill_bytes(&mut seed));
+                seed
+            })
+            .collect();
+        let fixed = [[0u8; 32]; POOL];
+        let t = fixed_vs_random_t_with(config, cycle(&fixed), cycle(&seeds), |seed| {
+            let mut rng = StdRng::from_seed(*seed);
+            sample_gaussian_poly_with(self.context.clone(), self.stdev, &mut rng)
+        });
+        LeakageReport {
+            operation: "gaussian_sampling",
+            t,
+        }
+    }
+
+    /// Run the tests of decryption and Gaussian sampling.
+    pub fn timing_suite(&self, config: &TimingConfig) -> Vec<LeakageReport> {
+        vec![
+            self.decryption_leakage(config),
+            self.gaussian_sampling_leakage(config),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_welch_t() {
+        let a = [1f64, 2f64, 3f64, 4f64];
+        assert_eq!(welch_t(&a, &a), 0f64);
+        let b = [11f64, 12f64, 13f64, 14f64];
+        assert!(welch_t(&a, &b) < -8f64);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_detects_leakage() {
+        // an operation whose running time obviously depends on its input.
+        let t = fixed_vs_random_t(
+            || 0u64,
+            || 1000u64,
+            |x| {
+                let mut acc = 0u64;
+                for i in 0..black_box(x) {
+                    acc = acc.wrapping_add(black_box(i));
+                }
+                acc
+            },
+        );
+        assert!(t.abs() > LEAKAGE_THRESHOLD);
+    }
+
+    #[test]
+    fn test_suite_reports() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let config = TimingConfig {
+            samples: 200,
+            batch: 2,
+        };
+        let reports = fv.timing_suite(&config);
+        let operations: Vec<_> = reports.iter().map(|r| r.operation).collect();
+        assert_eq!(operations, ["decryption", "gaussian_sampling"]);
+        assert!(reports.iter().all(|r| r.t.is_finite()));
+        let report = LeakageReport {
+            operation: "decryption",
+            t: -5.0,
+        };
+        assert!(report.leaks());
+        assert_eq!(report.to_string(), "decryption: t = -5.00 (leaks)");
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one sample")]
+    fn test_rejects_empty_config() {
+        let config = TimingConfig {
+            samples: 0,
+            batch: 1,
+        };
+        fixed_vs_random_t_with(&config, || 0u64, || 1u64, |x| x);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_gaussian_sampling_constant_time() {
+        let fv = FV::<Scalar>::default_2048();
+        let report = fv.gaussian_sampling_leakage(&TimingConfig::default());
+        assert!(!report.leaks(), "{}", report);
+    }
+}
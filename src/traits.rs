@@ -2,6 +2,8 @@
 //
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
+use alloc::vec::Vec;
+
 /// The trait for symmetric key encryption.
 pub trait SKEncryption<CT, PT, SK> {
     /// Generate a secret key
@@ -40,4 +42,41 @@ pub trait AdditiveHomomorphicScheme<CT, PT, SK>: SKEncryption<CT, PT, SK> {
     /// Rerandomize a ciphertext in-place. The resulting ciphertext will decrypt to the same
     /// plaintext, while being unlinkable to the input ciphertext.
     fn rerandomize(&self, ct: &mut CT, pk: &CT);
+
+    /// The sum of two ciphertexts.
+    fn add(&self, ct1: &CT, ct2: &CT) -> CT
+    where
+        CT: Clone,
+    {
+        let mut ct = ct1.clone();
+        self.add_inplace(&mut ct, ct2);
+        ct
+    }
+
+    /// The sum of a ciphertext and a plaintext.
+    fn add_plain(&self, ct: &CT, pt: &PT) -> CT
+    where
+        CT: Clone,
+    {
+        let mut sum = ct.clone();
+        self.add_plain_inplace(&mut sum, pt);
+        sum
+    }
+
+    /// A rerandomized copy of a ciphertext.
+    fn rerandomized(&self, ct: &CT, pk: &CT) -> CT
+    where
+        CT: Clone,
+    {
+        let mut fresh = ct.clone();
+        self.rerandomize(&mut fresh, pk);
+        fresh
+    }
+}
+
+/// The trait for types with a canonical byte encoding. Decoding needs the parameters of the
+/// scheme, and is provided by the scheme, e.g. `FV::ciphertext_from_bytes`.
+pub trait Serializable {
+    /// The canonical byte encoding.
+    fn to_bytes(&self) -> Vec<u8>;
 }
@@ -0,0 +1,176 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! A scheme without encryption, for testing protocol logic.
+//!
+//! [`TransparentScheme`] implements the encryption traits of [`crate::traits`] with the identity
+//! as encryption: a [`TransparentCiphertext`] holds its plaintext in the clear, additions wrap
+//! modulo 256 like those of FV, and rerandomization does nothing. Code written against the
+//! traits can thus be unit-tested without keys or the cost of the cryptography, and a failing
+//! test shows the plaintexts directly.
+//!
+//! It provides no security at all, and is only compiled with debug assertions, so that it cannot
+//! end up in a release build.
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption, Serializable};
+use crate::validation::ValidationError;
+use crate::FVPlaintext;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A scheme whose ciphertexts are their plaintexts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransparentScheme {
+    /// The number of plaintext slots.
+    pub n: usize,
+}
+
+/// A ciphertext of a [`TransparentScheme`], holding its n plaintext slots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransparentCiphertext(pub Vec<u8>);
+
+/// The key of a [`TransparentScheme`], which has none.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransparentKey;
+
+impl TransparentScheme {
+    /// A scheme with n slots.
+    pub fn new(n: usize) -> Self {
+        TransparentScheme { n }
+    }
+
+    /// A scheme with the 2048 slots of the default FV parameters.
+    pub fn default_2048() -> Self {
+        Self::new(2048)
+    }
+
+    /// Decode a ciphertext.
+    pub fn ciphertext_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<TransparentCiphertext, ValidationError> {
+        if bytes.len() != self.n {
+            return Err(ValidationError::WrongLength {
+                expected: self.n,
+                actual: bytes.len(),
+            });
+        }
+        Ok(TransparentCiphertext(bytes.to_vec()))
+    }
+
+    /// The first n coefficients of a plaintext, padded with zeros, as FV encrypts them.
+    fn slots(&self, pt: &FVPlaintext) -> Vec<u8> {
+        let mut slots: Vec<u8> = pt.iter().take(self.n).cloned().collect();
+        slots.resize(self.n, 0);
+        slots
+    }
+}
+
+impl Serializable for TransparentCiphertext {
+    /// The plaintext slots.
+    fn to_bytes(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+impl SKEncryption<TransparentCiphertext, FVPlaintext, TransparentKey> for TransparentScheme {
+    fn generate_key(&self) -> TransparentKey {
+        TransparentKey
+    }
+
+    fn encrypt_zero_sk(&self, _sk: &TransparentKey) -> TransparentCiphertext {
+        TransparentCiphertext(vec![0; self.n])
+    }
+
+    fn encrypt_sk(&self, pt: &FVPlaintext, _sk: &TransparentKey) -> TransparentCiphertext {
+        TransparentCiphertext(self.slots(pt))
+    }
+
+    fn decrypt(&self, ct: &TransparentCiphertext, _sk: &TransparentKey) -> FVPlaintext {
+        self.slots(&ct.0)
+    }
+}
+
+impl PKEncryption<TransparentCiphertext, FVPlaintext, TransparentKey> for TransparentScheme {
+    fn generate_keypair(&self) -> (TransparentCiphertext, TransparentKey) {
+        (TransparentCiphertext(vec![0; self.n]), TransparentKey)
+    }
+
+    fn encrypt_zero(&self, _pk: &TransparentCiphertext) -> TransparentCiphertext {
+        TransparentCiphertext(vec![0; self.n])
+    }
+
+    fn encrypt(&self, pt: &FVPlaintext, _pk: &TransparentCiphertext) -> TransparentCiphertext {
+        TransparentCiphertext(self.slots(pt))
+    }
+}
+
+impl AdditiveHomomorphicScheme<TransparentCiphertext, FVPlaintext, TransparentKey>
+    for TransparentScheme
+{
+    fn add_inplace(&self, ct1: &mut TransparentCiphertext, ct2: &TransparentCiphertext) {
+        for (x, y) in ct1.0.iter_mut().zip(ct2.0.iter()) {
+            *x = x.wrapping_add(*y);
+        }
+    }
+
+    fn add_plain_inplace(&self, ct1: &mut TransparentCiphertext, pt: &FVPlaintext) {
+        for (x, y) in ct1.0.iter_mut().zip(pt.iter()) {
+            *x = x.wrapping_add(*y);
+        }
+    }
+
+    fn rerandomize(&self, _ct: &mut TransparentCiphertext, _pk: &TransparentCiphertext) {}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::FV;
+
+    /// A protocol written against the traits: the sum of the votes of three clients.
+    fn tally<S, CT, SK>(scheme: &S, n: usize) -> FVPlaintext
+    where
+        S: PKEncryption<CT, FVPlaintext, SK> + AdditiveHomomorphicScheme<CT, FVPlaintext, SK>,
+    {
+        let (pk, sk) = scheme.generate_keypair();
+        let mut sum = scheme.encrypt_zero(&pk);
+        for vote in 0..3 {
+            let mut pt = vec![0; n];
+            pt[vote] = 1;
+            pt[n - 1] = 200;
+            scheme.add_inplace(&mut sum, &scheme.encrypt(&pt, &pk));
+        }
+        scheme.rerandomize(&mut sum, &pk);
+        scheme.decrypt(&sum, &sk)
+    }
+
+    #[test]
+    fn test_matches_fv() {
+        let fv = FV::<Scalar>::default_2048();
+        let transparent = TransparentScheme::default_2048();
+        let expected = tally(&fv, fv.n);
+        assert_eq!(tally(&transparent, transparent.n), expected);
+        assert_eq!(expected[..3], [1, 1, 1]);
+        assert_eq!(expected[fv.n - 1], 88);
+    }
+
+    #[test]
+    fn test_transparent_encoding() {
+        let scheme = TransparentScheme::new(4);
+        let (pk, sk) = scheme.generate_keypair();
+        let ct = scheme.encrypt(&vec![1, 2], &pk);
+        assert_eq!(ct.to_bytes(), vec![1, 2, 0, 0]);
+        let ct = scheme.add_plain(&ct, &vec![255, 0, 0, 0, 9]);
+        assert_eq!(scheme.decrypt(&ct, &sk), vec![0, 2, 0, 0]);
+        assert_eq!(scheme.ciphertext_from_bytes(&ct.to_bytes()), Ok(ct));
+        assert_eq!(
+            scheme.ciphertext_from_bytes(&[0; 5]),
+            Err(ValidationError::WrongLength {
+                expected: 4,
+                actual: 5
+            })
+        );
+    }
+}
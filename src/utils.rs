@@ -2,7 +2,7 @@
 //
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
-pub(crate) fn reverse_bits_perm<T>(input: &mut Vec<T>) {
+pub(crate) fn reverse_bits_perm<T>(input: &mut [T]) {
     let n = input.len();
     if !n.is_power_of_two() {
         panic!("n must be a power of 2");
@@ -29,11 +29,39 @@ pub(crate) fn bit_reverse(i: usize, n: usize) -> usize {
     j
 }
 
+/// Compare two byte strings in time independent of their contents.
+#[cfg(feature = "std")]
+pub(crate) fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    let diff = a
+        .iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    a.len() == b.len() && diff == 0
+}
+
+/// Wipe a secret value, with the `zeroize` feature.
+#[cfg(feature = "zeroize")]
+pub(crate) fn wipe<Z: zeroize::Zeroize + ?Sized>(secret: &mut Z) {
+    secret.zeroize();
+}
+
+/// Wipe a secret value, with the `zeroize` feature.
+#[cfg(not(feature = "zeroize"))]
+pub(crate) fn wipe<Z: ?Sized>(_secret: &mut Z) {}
+
 // tests
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_ct_eq() {
+        assert!(ct_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!ct_eq(&[1, 2, 3], &[1, 2]));
+        assert!(ct_eq(&[], &[]));
+    }
+
     #[test]
     fn test_bit_reverse() {
         assert_eq!(bit_reverse(0, 4), 0);
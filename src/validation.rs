@@ -0,0 +1,289 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Validity checks for ciphertexts and public keys received from untrusted parties.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::RqPoly;
+use crate::{FVCiphertext, FV};
+use alloc::sync::Arc;
+use core::error::Error;
+use core::fmt;
+
+/// The reasons for which a ciphertext or a public key can be rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// A component does not have n coefficients.
+    WrongDegree { expected: usize, actual: usize },
+    /// A coefficient of a component is not reduced modulo q.
+    CoefficientOutOfRange { component: usize, index: usize },
+    /// A component is not in the NTT domain expected for its type.
+    WrongNttForm { component: usize, expected: bool },
+    /// An encoding does not have the length expected for the parameters.
+    WrongLength { expected: usize, actual: usize },
+    /// The NTT flag of an encoded component is neither 0 nor 1.
+    InvalidNttFlag { component: usize },
+    /// The index of a share is not below the number of shares.
+    InvalidShareIndex { index: usize, count: usize },
+    /// A component belongs to a ring with another modulus or another NTT root of unity.
+    ParameterMismatch { component: usize },
+    /// An encoded secret key has a coefficient outside {-1, 0, 1}.
+    InvalidSecretKey,
+    /// An encoded message starts with an unknown tag.
+    UnknownMessageTag { tag: u8 },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::WrongDegree { expected, actual } => write!(
+                f,
+                "expected {} coefficients, found {}",
+                expected, actual
+            ),
+            ValidationError::CoefficientOutOfRange { component, index } => write!(
+                f,
+                "coefficient {} of component {} is not reduced modulo q",
+                index, component
+            ),
+            ValidationError::WrongNttForm {
+                component,
+                expected,
+            } => write!(
+                f,
+                "component {} should {}be in NTT form",
+                component,
+                if *expected { "" } else { "not " }
+            ),
+            ValidationError::WrongLength { expected, actual } => {
+                write!(f, "expected {} bytes, found {}", expected, actual)
+            }
+            ValidationError::InvalidNttFlag { component } => {
+                write!(f, "invalid NTT flag for component {}", component)
+            }
+            ValidationError::InvalidShareIndex { index, count } => {
+                write!(f, "share index {} out of {} shares", index, count)
+            }
+            ValidationError::ParameterMismatch { component } => write!(
+                f,
+                "component {} was created for other ring parameters",
+                component
+            ),
+            ValidationError::InvalidSecretKey => write!(f, "invalid secret key encoding"),
+            ValidationError::UnknownMessageTag { tag } => write!(f, "unknown message tag {}", tag),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+impl<T> FV<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// Check that a ciphertext is well-formed for this scheme: both components have n
+    /// coefficients in [0, q) and are in coefficient form. Ciphertexts deserialized from
+    /// untrusted sources should be validated before any homomorphic operation.
+    pub fn validate(&self, ct: &FVCiphertext<T>) -> Result<(), ValidationError> {
+        self.validate_poly(&ct.0, 0, false)?;
+        self.validate_poly(&ct.1, 1, false)
+    }
+
+    /// Check that a public key is well-formed for this scheme: both components have n
+    /// coefficients in [0, q), and are in NTT form if the parameters enable the NTT.
+    pub fn validate_public_key(&self, pk: &FVCiphertext<T>) -> Result<(), ValidationError> {
+        let ntt = self.context.is_ntt_enabled;
+        self.validate_poly(&pk.0, 0, ntt)?;
+        self.validate_poly(&pk.1, 1, ntt)
+    }
+
+    pub(crate) fn validate_poly(
+        &self,
+        poly: &RqPoly<T>,
+        component: usize,
+        ntt_form: bool,
+    ) -> Result<(), ValidationError> {
+        if poly.coeffs.len() != self.n {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                params_id = %self.params_id(),
+                expected = self.n,
+                actual = poly.coeffs.len(),
+                "rejected a polynomial of another degree"
+            );
+            return Err(ValidationError::WrongDegree {
+                expected: self.n,
+                actual: poly.coeffs.len(),
+            });
+        }
+        if !Arc::ptr_eq(poly.context(), &self.context) && **poly.context() != *self.context {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                params_id = %self.params_id(),
+                received_params_id = %crate::fingerprint::params_fingerprint(
+                    poly.context().n,
+                    T::to_u64(poly.context().q.clone())
+                ),
+                component,
+                "rejected a polynomial of other parameters"
+            );
+            return Err(ValidationError::ParameterMismatch { component });
+        }
+        let q = T::to_u64(self.q.clone());
+        if poly.is_ntt_form != ntt_form {
+            return Err(ValidationError::WrongNttForm {
+                component,
+                expected: ntt_form,
+            });
+        }
+        if let Some(index) = poly.coeffs.iter().position(|x| T::to_u64(x.clone()) >= q) {
+            return Err(ValidationError::CoefficientOutOfRange { component, index });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+    use crate::traits::*;
+
+    #[test]
+    fn test_validate_fresh_ciphertext() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let ct = fv.encrypt(&vec![1; fv.n], &pk);
+        assert_eq!(fv.validate(&ct), Ok(()));
+        assert_eq!(fv.validate_public_key(&pk), Ok(()));
+        assert_eq!(
+            fv.validate(&pk),
+            Err(ValidationError::WrongNttForm {
+                component: 0,
+                expected: false
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_malformed_ciphertext() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let (pk, _) = fv.generate_keypair();
+        let ct = fv.encrypt(&vec![1; fv.n], &pk);
+
+        let mut bad = ct.clone();
+        bad.1.coeffs.pop();
+        assert_eq!(
+            fv.validate(&bad),
+            Err(ValidationError::WrongDegree {
+                expected: 16,
+                actual: 15
+            })
+        );
+
+        let mut bad = ct;
+        bad.1.coeffs[3] = Scalar::from_u64_raw(65537);
+        assert_eq!(
+            fv.validate(&bad),
+            Err(ValidationError::CoefficientOutOfRange {
+                component: 1,
+                index: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_parameter_mismatch() {
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let other = FV::new(16, &Scalar::new_modulus(12289));
+        let (pk, _) = other.generate_keypair();
+        let ct = other.encrypt(&vec![1; other.n], &pk);
+        assert_eq!(
+            fv.validate(&ct),
+            Err(ValidationError::ParameterMismatch { component: 0 })
+        );
+
+        // Same degree and modulus, but another NTT root.
+        let q = Scalar::new_modulus(65537);
+        let psi = fv.context.root().unwrap();
+        let psi3 = Scalar::pow_mod(&psi, &Scalar::from_u64_raw(3), &q);
+        let twisted = FV::with_ntt_root(16, &q, &psi3).unwrap();
+        let (pk, _) = twisted.generate_keypair();
+        assert_eq!(
+            fv.validate_public_key(&pk),
+            Err(ValidationError::ParameterMismatch { component: 0 })
+        );
+        let (pk, _) = FV::new(16, &q).generate_keypair();
+        assert_eq!(fv.validate_public_key(&pk), Ok(()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_parameter_mismatch() {
+        use std::fmt::Debug;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Records the name and the fields of every span and event, as `name key=value ...`.
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        struct Line(String);
+
+        impl Visit for Line {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push_str(&format!(" {}={:?}", field.name(), value));
+            }
+        }
+
+        impl Recorder {
+            fn push(&self, name: &str, visit: impl FnOnce(&mut Line)) {
+                let mut line = Line(name.to_string());
+                visit(&mut line);
+                self.0.lock().unwrap().push(line.0);
+            }
+        }
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                self.push(span.metadata().name(), |line| span.record(line));
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                self.push(event.metadata().level().as_str(), |line| event.record(line));
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let fv = FV::new(16, &Scalar::new_modulus(65537));
+        let other = FV::new(16, &Scalar::new_modulus(12289));
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let (pk, _) = other.generate_keypair();
+            let ct = other.encrypt(&vec![1; other.n], &pk);
+            assert!(fv.validate(&ct).is_err());
+        });
+        let lines = recorder.0.lock().unwrap();
+        assert_eq!(
+            lines[0],
+            format!("generate_keypair params_id={}", other.params_id())
+        );
+        assert_eq!(
+            lines.last().unwrap(),
+            &format!(
+                "WARN message=rejected a polynomial of other parameters params_id={} \
+                 received_params_id={} component=0",
+                fv.params_id(),
+                other.params_id()
+            )
+        );
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! Encrypted vectors longer than one ciphertext.
+//!
+//! A ciphertext holds n plaintext slots. An [`EncryptedVector`] of len values spans
+//! ceil(len / n) ciphertexts, value i taking slot i mod n of ciphertext i / n, and the slots
+//! past the end of the last ciphertext holding zeros. It is encrypted, added, rerandomized,
+//! decrypted and serialized as a whole, so that an application does not split its vectors and
+//! track the chunks itself.
+//!
+//! A vector is encoded as its length as an 8-byte little-endian integer, followed by the
+//! canonical encodings of its ciphertexts.
+use crate::integer_arith::ArithUtils;
+use crate::rqpoly::{FiniteRingElt, RqPoly};
+use crate::scratch::NoiseScratch;
+use crate::traits::{AdditiveHomomorphicScheme, PKEncryption, SKEncryption, Serializable};
+use crate::validation::ValidationError;
+use crate::{FVCiphertext, SecretKey, FV};
+use std::convert::{TryFrom, TryInto};
+use std::error::Error;
+use std::fmt;
+
+/// The error returned when two operands have different lengths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LengthMismatch {
+    /// The length of the encrypted vector.
+    pub expected: usize,
+    /// The length of the other operand.
+    pub actual: usize,
+}
+
+impl fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "expected a vector of {} values, found {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl Error for LengthMismatch {}
+
+/// A vector of bytes encrypted in ceil(len / n) ciphertexts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncryptedVector<T> {
+    len: usize,
+    chunks: Vec<FVCiphertext<T>>,
+}
+
+impl<T> EncryptedVector<T> {
+    /// The number of values.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the vector has no values, and thus no ciphertexts.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The ciphertexts, the k-th one holding the values [k n, (k + 1) n).
+    pub fn chunks(&self) -> &[FVCiphertext<T>] {
+        &self.chunks
+    }
+
+    /// Take the ciphertexts.
+    pub fn into_chunks(self) -> Vec<FVCiphertext<T>> {
+        self.chunks
+    }
+
+    fn check_len(&self, actual: usize) -> Result<(), LengthMismatch> {
+        if actual != self.len {
+            return Err(LengthMismatch {
+                expected: self.len,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T> Serializable for EncryptedVector<T>
+where
+    T: ArithUtils<T> + Clone,
+{
+    /// The length as an 8-byte little-endian integer, followed by the ciphertexts.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.len as u64).to_le_bytes().to_vec();
+        for ct in self.chunks.iter() {
+            bytes.extend_from_slice(&ct.to_bytes());
+        }
+        bytes
+    }
+}
+
+impl<T> FV<T>
+where
+    RqPoly<T>: FiniteRingElt,
+    T: Clone + ArithUtils<T> + PartialEq,
+{
+    /// Encrypt a vector of any length under a public key.
+    pub fn encrypt_vector(&self, values: &[u8], pk: &FVCiphertext<T>) -> EncryptedVector<T> {
+        EncryptedVector {
+            len: values.len(),
+            chunks: values
+                .chunks(self.n)
+                .map(|chunk| self.encrypt(&chunk.to_vec(), pk))
+                .collect(),
+        }
+    }
+
+    /// Encrypt a vector of any length under a secret key.
+    pub fn encrypt_vector_sk(&self, values: &[u8], sk: &SecretKey<T>) -> EncryptedVector<T> {
+        EncryptedVector {
+            len: values.len(),
+            chunks: values
+                .chunks(self.n)
+                .map(|chunk| self.encrypt_sk(&chunk.to_vec(), sk))
+                .collect(),
+        }
+    }
+
+    /// Decrypt a vector.
+    pub fn decrypt_vector(&self, v: &EncryptedVector<T>, sk: &SecretKey<T>) -> Vec<u8> {
+        let mut values = Vec::with_capacity(v.chunks.len() * self.n);
+        for ct in v.chunks.iter() {
+            values.extend_from_slice(&self.decrypt(ct, sk));
+        }
+        values.truncate(v.len);
+        values
+    }
+
+    /// Add an encrypted vector of the same length into another.
+    pub fn add_vector_inplace(
+        &self,
+        v1: &mut EncryptedVector<T>,
+        v2: &EncryptedVector<T>,
+    ) -> Result<(), LengthMismatch> {
+        v1.check_len(v2.len)?;
+        for (ct1, ct2) in v1.chunks.iter_mut().zip(v2.chunks.iter()) {
+            self.add_inplace(ct1, ct2);
+        }
+        Ok(())
+    }
+
+    /// Add a plaintext vector of the same length into an encrypted vector.
+    pub fn add_plain_vector_inplace(
+        &self,
+        v: &mut EncryptedVector<T>,
+        values: &[u8],
+    ) -> Result<(), LengthMismatch> {
+        v.check_len(values.len())?;
+        for (ct, chunk) in v.chunks.iter_mut().zip(values.chunks(self.n)) {
+            self.add_plain_inplace(ct, &chunk.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Rerandomize every ciphertext of a vector, reusing the noise buffers across them.
+    pub fn rerandomize_vector(&self, v: &mut EncryptedVector<T>, pk: &FVCiphertext<T>) {
+        let mut scratch = NoiseScratch::new(self);
+        for ct in v.chunks.iter_mut() {
+            self.rerandomize_with_pool(ct, pk, &mut scratch);
+        }
+    }
+
+    /// Decode and validate an encrypted vector.
+    pub fn vector_from_bytes(&self, bytes: &[u8]) -> Result<EncryptedVector<T>, ValidationError> {
+        if bytes.len() < 8 {
+            return Err(ValidationError::WrongLength {
+                expected: 8,
+                actual: bytes.len(),
+            });
+        }
+        let len = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let ct_len = self.ciphertext_byte_len();
+        let expected = usize::try_from(len)
+            .ok()
+            .map(|len| len.div_ceil(self.n))
+            .and_then(|count| count.checked_mul(ct_len))
+            .and_then(|len| len.checked_add(8))
+            .unwrap_or(usize::MAX);
+        if bytes.len() != expected {
+            return Err(ValidationError::WrongLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let chunks = bytes[8..]
+            .chunks_exact(ct_len)
+            .map(|chunk| self.ciphertext_from_bytes(chunk))
+            .collect::<Result<_, _>>()?;
+        Ok(EncryptedVector {
+            len: len as usize,
+            chunks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer_arith::scalar::Scalar;
+
+    #[test]
+    fn test_vector_arithmetic() {
+        let fv = FV::new(16, &Scalar::new_modulus(18014398492704769));
+        let (pk, sk) = fv.generate_keypair();
+        let a: Vec<u8> = (0..40).collect();
+        let b: Vec<u8> = (0..40).map(|i| 250 - i).collect();
+        let mut v = fv.encrypt_vector(&a, &pk);
+        assert_eq!((v.len(), v.chunks().len()), (40, 3));
+
+        fv.add_vector_inplace(&mut v, &fv.encrypt_vector_sk(&b, &sk))
+            .unwrap();
+        fv.add_plain_vector_inplace(&mut v, &[10; 40]).unwrap();
+        fv.rerandomize_vector(&mut v, &pk);
+        assert_eq!(fv.decrypt_vector(&v, &sk), vec![4; 40]);
+
+        let short = fv.encrypt_vector(&[1; 32], &pk);
+        assert_eq!(
+            fv.add_vector_inplace(&mut v, &short),
+            Err(LengthMismatch {
+                expected: 40,
+                actual: 32
+            })
+        );
+        assert!(fv.add_plain_vector_inplace(&mut v, &[1; 41]).is_err());
+    }
+
+    #[test]
+    fn test_vector_encoding() {
+        let fv = FV::new(16, &Scalar::new_modulus(18014398492704769));
+        let (pk, sk) = fv.generate_keypair();
+        for len in [0, 1, 16, 17] {
+            let values = vec![3; len];
+            let v = fv.encrypt_vector(&values, &pk);
+            let bytes = v.to_bytes();
+            assert_eq!(bytes.len(), 8 + len.div_ceil(16) * fv.ciphertext_byte_len());
+            let decoded = fv.vector_from_bytes(&bytes).unwrap();
+            assert_eq!(decoded, v);
+            assert_eq!(fv.decrypt_vector(&decoded, &sk), values);
+        }
+
+        let mut bytes = fv.encrypt_vector(&[1; 17], &pk).to_bytes();
+        bytes[0] = 16;
+        assert!(matches!(
+            fv.vector_from_bytes(&bytes),
+            Err(ValidationError::WrongLength { .. })
+        ));
+        bytes[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(fv.vector_from_bytes(&bytes).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+//! JavaScript bindings, with the `wasm` feature.
+//!
+//! A web client creates a [`Scheme`] with the default parameters, and exchanges public keys and
+//! ciphertexts with a server in their canonical encoding, as `Uint8Array`s:
+//!
+//! ```js
+//! const scheme = new Scheme();
+//! const ct = scheme.encrypt(answers, serverPublicKey);
+//! ```
+//!
+//! A [`KeyPair`] keeps its secret key inside the module, and only exposes its public key. Errors
+//! are thrown as strings.
+use crate::error::CupcakeError;
+use crate::integer_arith::scalar::Scalar;
+use crate::traits::{PKEncryption, Serializable};
+use crate::{FVCiphertext, SecretKey, FV};
+use wasm_bindgen::prelude::*;
+
+/// The default scheme.
+#[wasm_bindgen]
+pub struct Scheme {
+    fv: FV<Scalar>,
+}
+
+/// A keypair of a [`Scheme`].
+#[wasm_bindgen]
+pub struct KeyPair {
+    pk: FVCiphertext<Scalar>,
+    sk: SecretKey<Scalar>,
+}
+
+fn to_js(e: CupcakeError) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+#[wasm_bindgen]
+impl KeyPair {
+    /// The encoding of the public key.
+    #[wasm_bindgen(js_name = publicKey)]
+    pub fn public_key(&self) -> Vec<u8> {
+        self.pk.to_bytes()
+    }
+}
+
+impl Default for Scheme {
+    fn default() -> Self {
+        Scheme {
+            fv: FV::<Scalar>::default_2048(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Scheme {
+    /// The scheme with the default parameters.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Scheme {
+        Scheme::default()
+    }
+
+    /// The number of bytes of a plaintext.
+    #[wasm_bindgen(js_name = plaintextLength)]
+    pub fn plaintext_len(&self) -> usize {
+        self.fv.n
+    }
+
+    /// Generate a keypair.
+    #[wasm_bindgen(js_name = generateKeypair)]
+    pub fn generate_keypair(&self) -> KeyPair {
+        let (pk, sk) = self.fv.generate_keypair();
+        KeyPair { pk, sk }
+    }
+
+    /// Encrypt a plaintext under an encoded public key, and return the encoded ciphertext.
+    pub fn encrypt(&self, pt: &[u8], public_key: &[u8]) -> Result<Vec<u8>, JsValue> {
+        self.try_encrypt(pt, public_key).map_err(to_js)
+    }
+
+    /// The encoding of the sum of two encoded ciphertexts.
+    pub fn add(&self, ct1: &[u8], ct2: &[u8]) -> Result<Vec<u8>, JsValue> {
+        self.try_add(ct1, ct2).map_err(to_js)
+    }
+
+    /// Decrypt an encoded ciphertext with the secret key of a keypair.
+    pub fn decrypt(&self, ct: &[u8], keypair: &KeyPair) -> Result<Vec<u8>, JsValue> {
+        self.try_decrypt(ct, keypair).map_err(to_js)
+    }
+}
+
+// The operations, with errors which can be inspected outside of a JavaScript runtime.
+impl Scheme {
+    fn try_encrypt(&self, pt: &[u8], public_key: &[u8]) -> Result<Vec<u8>, CupcakeError> {
+        let pk = self.fv.public_key_from_bytes(public_key)?;
+        Ok(self.fv.try_encrypt(&pt.to_vec(), &pk)?.to_bytes())
+    }
+
+    fn try_add(&self, ct1: &[u8], ct2: &[u8]) -> Result<Vec<u8>, CupcakeError> {
+        let mut sum = self.fv.ciphertext_from_bytes(ct1)?;
+        self.fv
+            .try_add_inplace(&mut sum, &self.fv.ciphertext_from_bytes(ct2)?)?;
+        Ok(sum.to_bytes())
+    }
+
+    fn try_decrypt(&self, ct: &[u8], keypair: &KeyPair) -> Result<Vec<u8>, CupcakeError> {
+        let ct = self.fv.ciphertext_from_bytes(ct)?;
+        self.fv.try_decrypt(&ct, &keypair.sk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::ValidationError;
+
+    #[test]
+    fn test_wasm_bindings() {
+        let scheme = Scheme::new();
+        let n = scheme.plaintext_len();
+        let keypair = scheme.generate_keypair();
+        let pk = keypair.public_key();
+        let a = scheme.encrypt(&vec![2; n], &pk).unwrap();
+        let b = scheme.encrypt(&vec![3; n], &pk).unwrap();
+        let sum = scheme.add(&a, &b).unwrap();
+        assert_eq!(scheme.decrypt(&sum, &keypair).unwrap(), vec![5; n]);
+
+        assert_eq!(
+            scheme.try_add(&a, &a[1..]).unwrap_err(),
+            CupcakeError::Invalid(ValidationError::WrongLength {
+                expected: a.len(),
+                actual: a.len() - 1
+            })
+        );
+    }
+}